@@ -0,0 +1,109 @@
+//! Measures time spent formatting and writing common instructions, and heap allocations made for
+//! each of them.
+//!
+//! Output is written to `std::io::sink()` so only `cargo-build` overhead is measured. With `stdout`
+//! argument output is written to the default `stdout` stream instead, timings go to `stderr`.
+//!
+//! ```sh
+//! cargo bench --bench directives
+//! cargo bench --bench directives -- stdout > /dev/null
+//! ```
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+/// Counts heap allocations, which unlike timings don't depend on the machine.
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+const ITERATIONS: u32 = 100_000;
+
+/// Fastest of several samples is reported, it is the least affected by other processes.
+const SAMPLES: u32 = 10;
+
+fn bench(name: &str, mut f: impl FnMut()) {
+    // Warm up
+    for _ in 0..ITERATIONS / 10 {
+        f();
+    }
+
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    f();
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+
+    let mut fastest = Duration::MAX;
+    for _ in 0..SAMPLES {
+        let start = Instant::now();
+        for _ in 0..ITERATIONS {
+            f();
+        }
+        fastest = fastest.min(start.elapsed());
+    }
+
+    eprintln!(
+        "{name:<28} {:>8.1} ns/iter {allocations:>4} allocs/iter",
+        fastest.as_nanos() as f64 / ITERATIONS as f64
+    );
+}
+
+fn main() {
+    if !std::env::args().any(|arg| arg == "stdout") {
+        cargo_build::build_out::set(std::io::sink());
+    }
+
+    bench("rerun_if_changed", || {
+        cargo_build::rerun_if_changed(black_box("src/main.c"));
+    });
+    bench("rerun_if_env_changed", || {
+        cargo_build::rerun_if_env_changed(black_box("CC"));
+    });
+    bench("rustc_link_arg", || {
+        cargo_build::rustc_link_arg(black_box("-Wl,--cref"));
+    });
+    bench("rustc_link_lib", || {
+        cargo_build::rustc_link_lib(black_box("static=ssl"));
+    });
+    bench("rustc_link_lib_static", || {
+        cargo_build::rustc_link_lib_static(["+whole-archive"], black_box("ssl"));
+    });
+    bench("rustc_link_search_native", || {
+        cargo_build::rustc_link_search_native(black_box("vendor/openssl/lib"));
+    });
+    bench("rustc_cfg", || {
+        cargo_build::rustc_cfg(black_box(("api_version", "1")));
+    });
+    bench("rustc_check_cfg", || {
+        cargo_build::rustc_check_cfg(black_box("api_version"), ["1", "2", "3"]);
+    });
+    bench("rustc_env", || {
+        cargo_build::rustc_env(black_box("GIT_HASH"), black_box("0123456789abcdef"));
+    });
+    bench("warning", || {
+        cargo_build::warning(black_box("Warning during build process"));
+    });
+    bench("metadata", || {
+        cargo_build::metadata(black_box("include"), black_box("/usr/include"));
+    });
+}
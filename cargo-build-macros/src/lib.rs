@@ -0,0 +1,61 @@
+//! Attribute macros for `cargo-build` crate.
+//!
+//! Enable `main` feature of `cargo-build` and use them as `#[cargo_build::main]`.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, ItemFn};
+
+/// Runs `main` function of the build script with `cargo_build::run`.
+///
+/// `main` may return `()` or `Result<(), E>` where `E: Display`. `Err` is emitted as `cargo::error`
+/// and the build script exits with code `1`. Panics are emitted as `cargo::error` too, and the output
+/// stream is flushed before the build script exits.
+///
+/// ```rust,ignore
+/// // build.rs
+/// #[cargo_build::main]
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let sdk = std::env::var("FOO_SDK")?;
+///
+///     cargo_build::rustc_link_search_native(format!("{sdk}/lib"));
+///     Ok(())
+/// }
+/// ```
+#[proc_macro_attribute]
+pub fn main(args: TokenStream, item: TokenStream) -> TokenStream {
+    if !args.is_empty() {
+        let args = proc_macro2::TokenStream::from(args);
+        return syn::Error::new_spanned(args, "`#[cargo_build::main]` takes no arguments")
+            .to_compile_error()
+            .into();
+    }
+
+    let ItemFn {
+        attrs,
+        vis,
+        sig,
+        block,
+    } = parse_macro_input!(item as ItemFn);
+
+    if sig.ident != "main" || !sig.inputs.is_empty() || sig.asyncness.is_some() {
+        return syn::Error::new_spanned(
+            sig,
+            "`#[cargo_build::main]` can only be used on `fn main()` without arguments",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let output = &sig.output;
+
+    quote! {
+        #(#attrs)*
+        #vis fn main() {
+            fn __cargo_build_main() #output #block
+
+            ::cargo_build::run(__cargo_build_main)
+        }
+    }
+    .into()
+}
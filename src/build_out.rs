@@ -1,14 +1,165 @@
-use std::cell::RefCell;
-use std::io::{stdout, Write};
+//! Output stream of `cargo-build` instructions.
+//!
+//! Every thread writes into its own output stream, set with [`set`], [`scoped`] and similar
+//! functions. Threads which didn't set one use the process-wide output stream set with
+//! [`set_global`], or `stdout` by default. Every instruction is written with single `write_all`
+//! call.
+//!
+//! Locks around output streams are recovered if a thread panics while holding them. Instructions are
+//! append-only text, so panic of one worker thread doesn't stop others from emitting instructions.
+//!
+//! Default `stdout` is buffered by this crate, so emitting thousands of instructions doesn't cost a
+//! syscall each. It is flushed by [`flush`](crate::flush) and [`finish`](crate::finish), when output
+//! stream is replaced with [`set`] or [`set_global`], and when the main thread exits. Instructions
+//! don't keep their place among lines printed with `println!`, call [`flush`](crate::flush) before
+//! printing if order matters.
+//!
+//! Output streams set with [`set`], [`set_global`] and [`DirectiveSink`] are flushed once when the main
+//! thread exits or the build script exits through this crate, e.g. with
+//! [`error_and_exit`](crate::error_and_exit). Use [`final_flush`] if the build script exits by other
+//! means, e.g. with [`std::process::exit`], or emits instructions only from threads other than main.
+//!
+//! [`DirectiveSink`] set with [`set_sink`] receives every instruction as [`Instruction`] value before
+//! it is written.
+
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::io::{stdout, BufWriter, Stderr, StderrLock, Stdout, StdoutLock, Write};
+use std::panic::Location;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Sender, SyncSender};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread::JoinHandle;
+
+use crate::Instruction;
+
+pub(crate) const ERR_MSG: &str = "Unable to write to CARGO_BUILD_OUT";
+
+thread_local! {
+    /// Only accessed through [`with_out`] and [`replace_out`].
+    static CARGO_BUILD_OUT: RefCell<Box<dyn Write>> = RefCell::new(Box::new(GlobalOut));
+
+    static SINK: RefCell<Option<Box<dyn DirectiveSink>>> = const { RefCell::new(None) };
+
+    /// `None` means that [`MIRROR_ENV_VAR`] decides.
+    static MIRROR_DIAGNOSTICS: Cell<Option<bool>> = const { Cell::new(None) };
+
+    static MIRROR_STDERR: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Output stream shared by all threads which didn't [`set`] their own. `None` means [`STDOUT`].
+static GLOBAL_OUT: Mutex<Option<Box<dyn Write + Send>>> = Mutex::new(None);
+
+/// Hook registered with [`on_emit`].
+type Hook = fn(&mut Instruction) -> HookAction;
+
+/// Hooks registered with [`on_emit`]. Replaced on registration so running them only clones `Arc`.
+static HOOKS: Mutex<Option<Arc<[Hook]>>> = Mutex::new(None);
+
+/// Whether [`HOOKS`] is non-empty. Checked before locking it for every instruction.
+static HAS_HOOKS: AtomicBool = AtomicBool::new(false);
+
+/// Buffered `stdout`. Created on first write.
+static STDOUT: Mutex<Option<BufWriter<Stdout>>> = Mutex::new(None);
+
+/// Set by [`final_flush`] and [`FinalFlush`], so output streams are flushed at most once at exit.
+static FINAL_FLUSHED: AtomicBool = AtomicBool::new(false);
 
 thread_local! {
-    pub(crate) static CARGO_BUILD_OUT: RefCell<Box<dyn Write>> = RefCell::new(Box::new(stdout()));
+    /// Flushes output streams when the main thread exits. Registered after [`SINK`] and
+    /// [`CARGO_BUILD_OUT`] are first used, so it is dropped before them.
+    static FINAL_FLUSH: FinalFlush = const { FinalFlush };
+}
+
+struct FinalFlush;
+
+impl Drop for FinalFlush {
+    fn drop(&mut self) {
+        if FINAL_FLUSHED.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        // Other thread locals may be already destroyed, errors can't be reported at this point.
+        let _ = SINK.try_with(|sink| {
+            sink.try_borrow_mut()
+                .map(|mut sink| sink.as_mut().map(|sink| sink.flush()))
+        });
+        let _ = CARGO_BUILD_OUT.try_with(|out| out.try_borrow_mut().map(|mut out| out.flush()));
+        let _ = GlobalOut::with(|out| out.flush());
+    }
+}
+
+/// Registers [`FinalFlush`] on the main thread.
+fn register_final_flush() {
+    if std::thread::current().name() == Some("main") {
+        let _ = FINAL_FLUSH.try_with(|_| ());
+    }
+}
+
+/// Flushes [`DirectiveSink`] and output streams at the end of the build script. Only the first call
+/// flushes, later calls do nothing.
+///
+/// Called automatically when the main thread exits and before the build script exits through this
+/// crate, e.g. with [`error_and_exit`](crate::error_and_exit). Call it before exiting by other means,
+/// which skips destructors and loses instructions buffered by the output stream.
+///
+/// ```rust,no_run
+/// let log = std::fs::File::create("target/build-script.log").unwrap();
+/// cargo_build::build_out::set_global(std::io::BufWriter::new(log));
+///
+/// cargo_build::rustc_cfg("has_foo");
+///
+/// cargo_build::build_out::final_flush().unwrap();
+/// std::process::exit(0);
+/// ```
+pub fn final_flush() -> std::io::Result<()> {
+    if FINAL_FLUSHED.swap(true, Ordering::AcqRel) {
+        return Ok(());
+    }
+    flush()
+}
+
+/// Runs `f` with [`STDOUT`].
+fn with_stdout<R>(f: impl FnOnce(&mut BufWriter<Stdout>) -> R) -> R {
+    register_final_flush();
+    let mut out = STDOUT.lock().unwrap_or_else(PoisonError::into_inner);
+    f(out.get_or_insert_with(|| BufWriter::new(stdout())))
+}
+
+fn flush_stdout() -> std::io::Result<()> {
+    match STDOUT
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_mut()
+    {
+        Some(out) => out.flush(),
+        None => Ok(()),
+    }
+}
+
+/// Runs `f` with output stream of the current thread. Every write goes through this function.
+fn with_out<R>(f: impl FnOnce(&mut Box<dyn Write>) -> R) -> R {
+    CARGO_BUILD_OUT.with_borrow_mut(f)
+}
+
+/// Replaces output stream of the current thread and returns the previous one.
+fn replace_out(wr: Box<dyn Write>) -> Box<dyn Write> {
+    CARGO_BUILD_OUT.replace(wr)
 }
 
+/// Env variable which enables [`mirror_diagnostics`] unless it was called explicitly.
+const MIRROR_ENV_VAR: &str = "MIRROR_BUILD_WARNINGS";
+
 /// Use this function to set custom output stream for `cargo-build` commands.
 ///
 /// Useful for debugging, logging and testing.
 ///
+/// Output stream is set for the current thread only. Use [`set_global`] to set output stream for
+/// all threads.
+///
 /// Use [`reset`] to reset output stream to `stdout`. This is the default and is necessary
 /// for `cargo-build` commands to work inside `build.rs`.
 ///
@@ -23,8 +174,21 @@ thread_local! {
 ///
 /// assert_eq!(out, "cargo::rerun-if-changed=README.md\n");
 /// ```
-pub fn set(wr: impl Write + 'static) {
-    CARGO_BUILD_OUT.set(Box::new(wr));
+///
+/// Returns previous output stream, which can be restored later or composed with the new one:
+///
+/// ```rust
+/// use cargo_build::build_out::{self, TeeWriter};
+///
+/// let log = std::fs::File::create("target/cargo_build_composed_log.txt").unwrap();
+///
+/// let previous = build_out::set(std::io::sink());
+/// build_out::set(TeeWriter::new(previous, log));
+/// ```
+pub fn set(wr: impl Write + 'static) -> Box<dyn Write> {
+    // Instructions buffered for `stdout` are written before ones emitted into the new stream
+    let _ = flush_stdout();
+    replace_out(Box::new(wr))
 }
 
 /// Use this function to reset output stream of `cargo-build` commands to `stdout`. This is necassery for
@@ -32,6 +196,1933 @@ pub fn set(wr: impl Write + 'static) {
 ///
 /// `stdout` is the default. There is no need to reset output stream of `cargo-build` commands if it wasn't
 /// previously changed by [`set`].
+///
+/// If output stream was set with [`set_global`], current thread uses it again.
 pub fn reset() {
-    CARGO_BUILD_OUT.set(Box::new(stdout()));
+    replace_out(Box::new(GlobalOut));
+}
+
+/// Discards all `cargo-build` instructions emitted on the current thread.
+///
+/// Useful for helpers shared between build scripts and regular binaries, which shouldn't print
+/// `cargo::` lines outside of `build.rs`. Use [`reset`] to enable output again.
+///
+/// ```rust
+/// fn generate_bindings() {
+///     cargo_build::rerun_if_changed(["include/api.h"]);
+///     // ...
+/// }
+///
+/// // Regular binary
+/// cargo_build::build_out::disable();
+/// generate_bindings();
+/// ```
+pub fn disable() {
+    set(std::io::sink());
+}
+
+/// Sets output stream for all threads of the process which didn't [`set`] their own.
+///
+/// Useful for multi-threaded build scripts, since [`set`] only affects the current thread. Writes
+/// are serialized with a lock and every instruction is written at once.
+///
+/// ```rust
+/// let file = std::fs::File::create("target/cargo_build_global_log.txt").unwrap();
+///
+/// cargo_build::build_out::set_global(file);
+///
+/// std::thread::spawn(|| cargo_build::rustc_cfg("from_worker")).join().unwrap();
+///
+/// cargo_build::build_out::reset_global();
+///
+/// let out = std::fs::read_to_string("target/cargo_build_global_log.txt").unwrap();
+///
+/// assert_eq!(out, "cargo::rustc-cfg=from_worker\n");
+/// ```
+pub fn set_global(wr: impl Write + Send + 'static) {
+    let mut global = GLOBAL_OUT.lock().unwrap_or_else(PoisonError::into_inner);
+    if global.is_none() {
+        let _ = flush_stdout();
+    }
+    let previous = global.replace(Box::new(wr));
+    drop(global);
+    drop(previous);
+}
+
+/// Resets output stream set with [`set_global`] to `stdout`.
+pub fn reset_global() {
+    let previous = GLOBAL_OUT
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .take();
+    drop(previous);
+}
+
+/// Default output stream of every thread: [`GLOBAL_OUT`] if set, [`STDOUT`] otherwise.
+struct GlobalOut;
+
+impl GlobalOut {
+    fn with<R>(f: impl FnOnce(&mut dyn Write) -> R) -> R {
+        let mut global = GLOBAL_OUT.lock().unwrap_or_else(PoisonError::into_inner);
+        match global.as_mut() {
+            Some(out) => f(out),
+            None => with_stdout(|out| f(out)),
+        }
+    }
+}
+
+impl Write for GlobalOut {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        GlobalOut::with(|out| out.write(buf))
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        GlobalOut::with(|out| out.write_all(buf))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        GlobalOut::with(|out| out.flush())
+    }
+}
+
+/// Sets output stream of the current thread to both `stdout` and `log`.
+///
+/// Instructions still reach Cargo, while `log` keeps a copy for post-mortem debugging of the build
+/// script. Output stream set with [`set_global`] is used instead of `stdout` if present.
+///
+/// ```rust,no_run
+/// let log = std::fs::OpenOptions::new()
+///     .create(true)
+///     .append(true)
+///     .open("target/build-script.log")
+///     .unwrap();
+///
+/// cargo_build::build_out::tee(log);
+///
+/// // Written to both `stdout` and `target/build-script.log`
+/// cargo_build::rerun_if_changed(["build.rs"]);
+/// ```
+pub fn tee(log: impl Write + 'static) {
+    set(TeeWriter::new(GlobalOut, log));
+}
+
+/// Records everything written to the output stream of the current thread into file at `path`.
+///
+/// Output stream is wrapped, so instructions still reach Cargo. Recorded instructions can be emitted
+/// again with [`replay`](crate::replay), which lets expensive build scripts skip the work on cache hit
+/// while telling Cargo the same link and rerun information.
+///
+/// ```rust
+/// let recording = std::path::Path::new("target/cargo_build_recording.txt");
+///
+/// if recording.exists() {
+///     cargo_build::replay(recording).unwrap();
+/// } else {
+///     cargo_build::build_out::record_to(recording).unwrap();
+///
+///     // Expensive work
+///     cargo_build::rustc_link_lib(["foo"]);
+///     cargo_build::flush();
+/// }
+/// ```
+pub fn record_to(path: impl AsRef<Path>) -> std::io::Result<()> {
+    let file = std::fs::File::create(path)?;
+    let previous = replace_out(Box::new(std::io::sink()));
+    drop(replace_out(Box::new(TeeWriter::new(previous, file))));
+    Ok(())
+}
+
+/// Sets output stream of the current thread to multiple writers at once.
+///
+/// Every instruction is written to each writer in order, e.g. to `stdout` for Cargo, to a log file and
+/// to an in-memory recorder. Writing continues if one of the writers fails, and the first error is
+/// reported afterwards.
+///
+/// ```rust
+/// use std::io::Write;
+///
+/// let log = std::fs::File::create("target/cargo_build_many_log.txt").unwrap();
+///
+/// cargo_build::build_out::set_many([
+///     Box::new(std::io::stdout()) as Box<dyn Write>,
+///     Box::new(log),
+/// ]);
+///
+/// cargo_build::rerun_if_changed(["build.rs"]);
+///
+/// cargo_build::build_out::reset();
+///
+/// let out = std::fs::read_to_string("target/cargo_build_many_log.txt").unwrap();
+///
+/// assert_eq!(out, "cargo::rerun-if-changed=build.rs\n");
+/// ```
+///
+/// See also [`tee`] and [`TeeWriter`] for two writers.
+pub fn set_many(writers: impl IntoIterator<Item = Box<dyn Write>>) {
+    set(FanOut(writers.into_iter().collect()));
+}
+
+struct FanOut(Vec<Box<dyn Write>>);
+
+impl Write for FanOut {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        self.0
+            .iter_mut()
+            .map(|out| out.write_all(buf))
+            .fold(Ok(()), Result::and)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0
+            .iter_mut()
+            .map(|out| out.flush())
+            .fold(Ok(()), Result::and)
+    }
+}
+
+/// Sets output stream of the current thread to send every instruction over the channel.
+///
+/// Lines are sent without trailing newline. This lets a dedicated thread own `stdout`, so
+/// multi-threaded build scripts don't lock it on every write.
+///
+/// ```rust
+/// let (tx, rx) = std::sync::mpsc::channel();
+///
+/// let printer = std::thread::spawn(move || {
+///     for line in rx {
+///         println!("{line}");
+///     }
+/// });
+///
+/// std::thread::scope(|s| {
+///     for module in ["net", "fs"] {
+///         let tx = tx.clone();
+///         s.spawn(move || {
+///             cargo_build::build_out::set_channel(tx);
+///             cargo_build::rerun_if_changed(format!("src/{module}.c"));
+///         });
+///     }
+/// });
+///
+/// drop(tx);
+/// printer.join().unwrap();
+/// ```
+pub fn set_channel(tx: Sender<String>) {
+    set(ChannelWriter::new(tx));
+}
+
+/// Writer which sends every complete line over the channel. See [`set_channel`].
+///
+/// Writing fails with [`std::io::ErrorKind::BrokenPipe`] if the receiver was dropped.
+#[derive(Debug)]
+pub struct ChannelWriter {
+    tx: Sender<String>,
+    /// Incomplete line.
+    pending: Vec<u8>,
+}
+
+impl ChannelWriter {
+    pub fn new(tx: Sender<String>) -> Self {
+        Self {
+            tx,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for_each_line(&mut self.pending, buf, |line| {
+            self.tx
+                .send(line.to_string())
+                .map_err(|err| std::io::Error::new(std::io::ErrorKind::BrokenPipe, err))
+        })?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Sets output stream for all threads of the process to `wr` owned by a dedicated writer thread.
+///
+/// Instructions are handed to the writer thread through a queue of `capacity` writes, so slow output
+/// streams (network, slow filesystems) don't stall compute threads until the queue is full.
+/// [`flush`](crate::flush) waits until the writer thread has written everything queued before it.
+/// See [`set_global`].
+///
+/// ```rust
+/// let file = std::fs::File::create("target/cargo_build_background_log.txt").unwrap();
+///
+/// cargo_build::build_out::set_background(file, 1024);
+///
+/// std::thread::scope(|s| {
+///     for module in ["net", "fs"] {
+///         s.spawn(move || cargo_build::rerun_if_changed(format!("src/{module}.c")));
+///     }
+/// });
+///
+/// cargo_build::flush();
+/// cargo_build::build_out::reset_global();
+///
+/// let out = std::fs::read_to_string("target/cargo_build_background_log.txt").unwrap();
+///
+/// assert_eq!(out.lines().count(), 2);
+/// ```
+pub fn set_background(wr: impl Write + Send + 'static, capacity: usize) {
+    set_global(BackgroundWriter::new(wr, capacity));
+}
+
+/// Writer which hands every write to a dedicated thread through a bounded queue. See
+/// [`set_background`].
+///
+/// [`Write::flush`] blocks until the writer thread has written and flushed everything queued before
+/// it, and returns the first error of the wrapped writer. Dropping `BackgroundWriter` joins the writer
+/// thread.
+#[derive(Debug)]
+pub struct BackgroundWriter {
+    tx: Option<SyncSender<Background>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+enum Background {
+    Write(Vec<u8>),
+    Flush(SyncSender<std::io::Result<()>>),
+}
+
+impl BackgroundWriter {
+    /// Spawns writer thread which owns `wr`. Writes block when `capacity` writes are queued.
+    pub fn new(mut wr: impl Write + Send + 'static, capacity: usize) -> Self {
+        let (tx, rx) = std::sync::mpsc::sync_channel(capacity);
+
+        let thread = std::thread::Builder::new()
+            .name("cargo-build-writer".to_string())
+            .spawn(move || {
+                let mut error = None;
+                for message in rx {
+                    match message {
+                        Background::Write(buf) => {
+                            if error.is_none() {
+                                error = wr.write_all(&buf).err();
+                            }
+                        }
+                        Background::Flush(ack) => {
+                            let result = match error.take() {
+                                Some(err) => Err(err),
+                                None => wr.flush(),
+                            };
+                            let _ = ack.send(result);
+                        }
+                    }
+                }
+                let _ = wr.flush();
+            })
+            .expect("Unable to spawn writer thread");
+
+        Self {
+            tx: Some(tx),
+            thread: Some(thread),
+        }
+    }
+
+    fn send(&self, message: Background) -> std::io::Result<()> {
+        self.tx
+            .as_ref()
+            .expect("Sender is only taken on drop")
+            .send(message)
+            .map_err(|_| {
+                std::io::Error::new(std::io::ErrorKind::BrokenPipe, "Writer thread exited")
+            })
+    }
+}
+
+impl Write for BackgroundWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.send(Background::Write(buf.to_vec()))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let (ack, result) = std::sync::mpsc::sync_channel(1);
+        self.send(Background::Flush(ack))?;
+        result.recv().map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::BrokenPipe, "Writer thread exited")
+        })?
+    }
+}
+
+impl Drop for BackgroundWriter {
+    fn drop(&mut self) {
+        drop(self.tx.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Wraps output stream of the current thread into [`Validating`] writer.
+///
+/// Every line is checked against the grammar of `cargo::` instructions before it is written, including
+/// lines written with `write!` to [`Stream`]. Instruction Cargo would reject or silently ignore fails
+/// with a descriptive message instead of failing the build later.
+///
+/// ```rust,should_panic
+/// use std::io::Write;
+///
+/// cargo_build::build_out::set(std::io::sink());
+/// cargo_build::build_out::validate();
+///
+/// cargo_build::rustc_cfg(("feature", "std"));
+///
+/// // Invalid instruction `cargo::rerun-if-chnaged=build.rs`: Unknown instruction `rerun-if-chnaged`
+/// writeln!(cargo_build::build_out::Stream, "cargo::rerun-if-chnaged=build.rs").unwrap();
+/// ```
+pub fn validate() {
+    let previous = replace_out(Box::new(std::io::sink()));
+    drop(replace_out(Box::new(Validating::new(previous))));
+}
+
+/// Writer which checks every complete line against the grammar of `cargo::` instructions before
+/// writing it to the inner writer. See [`validate`].
+///
+/// Lines which don't start with `cargo:` are not instructions and are passed through. When a write
+/// contains an invalid line, the lines before it are written and reported as a short write. A
+/// write starting with an invalid line fails with [`std::io::ErrorKind::InvalidData`] and writes
+/// nothing, the invalid line stays buffered so every following write fails too.
+#[derive(Debug)]
+pub struct Validating<W> {
+    inner: W,
+    /// Incomplete line.
+    pending: Vec<u8>,
+}
+
+impl<W: Write> Validating<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            pending: Vec::new(),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for Validating<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let Some(end) = buf.iter().rposition(|&b| b == b'\n') else {
+            self.pending.extend_from_slice(buf);
+            return Ok(buf.len());
+        };
+
+        // `pending` never contains a newline, so every complete line ends inside `buf`
+        let buffered = self.pending.len();
+        let mut lines = std::mem::take(&mut self.pending);
+        lines.extend_from_slice(&buf[..=end]);
+
+        let mut valid = 0;
+        for bytes in lines.split_inclusive(|&b| b == b'\n') {
+            let line = String::from_utf8_lossy(bytes);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if let Err(reason) = crate::grammar::check(line) {
+                if valid == 0 {
+                    let err = std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        format!("Invalid instruction `{line}`: {reason}"),
+                    );
+                    lines.truncate(buffered);
+                    self.pending = lines;
+                    return Err(err);
+                }
+                self.inner.write_all(&lines[..valid])?;
+                return Ok(valid - buffered);
+            }
+            valid += bytes.len();
+        }
+
+        self.inner.write_all(&lines)?;
+        self.pending.extend_from_slice(&buf[end + 1..]);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Sets output stream of the current thread to call `f` with every instruction.
+///
+/// Lines are passed without trailing newline. Instructions are not written anywhere else, call
+/// `println!` inside `f` to forward them to Cargo.
+///
+/// ```rust
+/// use std::cell::Cell;
+/// use std::rc::Rc;
+///
+/// let rerun_count = Rc::new(Cell::new(0));
+///
+/// let count = rerun_count.clone();
+/// cargo_build::build_out::set_callback(move |line| {
+///     if line.starts_with("cargo::rerun-if-changed=") {
+///         count.set(count.get() + 1);
+///     }
+///     println!("{line}");
+/// });
+///
+/// cargo_build::rerun_if_changed(["build.rs", "src/main.c"]);
+///
+/// assert_eq!(rerun_count.get(), 2);
+/// ```
+pub fn set_callback(f: impl FnMut(&str) + 'static) {
+    set(Callback {
+        f,
+        pending: Vec::new(),
+    });
+}
+
+struct Callback<F> {
+    f: F,
+    pending: Vec<u8>,
+}
+
+impl<F: FnMut(&str)> Write for Callback<F> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for_each_line(&mut self.pending, buf, |line| {
+            (self.f)(line);
+            Ok(())
+        })?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writer which counts instructions by kind and passes everything to the inner writer.
+///
+/// Useful for assertions in tests and for a final summary of the build script.
+///
+/// ```rust
+/// use cargo_build::build_out::{self, StatsSink};
+///
+/// let sink = StatsSink::new(std::io::stdout());
+/// let stats = sink.stats();
+///
+/// build_out::set(sink);
+///
+/// cargo_build::rerun_if_changed(["build.rs", "src/main.c"]);
+/// cargo_build::warning("libfoo not found");
+///
+/// build_out::reset();
+///
+/// assert_eq!(stats.rerun_if_changed(), 2);
+/// assert_eq!(stats.warnings(), 1);
+/// assert_eq!(stats.total(), 3);
+/// ```
+#[derive(Debug)]
+pub struct StatsSink<W> {
+    inner: W,
+    stats: Stats,
+    pending: Vec<u8>,
+}
+
+impl<W: Write> StatsSink<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            stats: Stats::default(),
+            pending: Vec::new(),
+        }
+    }
+
+    /// Returns handle to the statistics, which stays valid after the sink is passed to [`set`].
+    pub fn stats(&self) -> Stats {
+        self.stats.clone()
+    }
+}
+
+impl<W: Write> Write for StatsSink<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.inner.write_all(buf)?;
+
+        let stats = &self.stats;
+        for_each_line(&mut self.pending, buf, |line| {
+            stats.record(line);
+            Ok(())
+        })?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Number of instructions written to [`StatsSink`] by key, e.g. `warning` or `rustc-link-lib`.
+///
+/// Cloned handles share the same statistics.
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    counts: Arc<Mutex<BTreeMap<String, usize>>>,
+}
+
+impl Stats {
+    fn record(&self, line: &str) {
+        let Some(instruction) = line
+            .strip_prefix("cargo::")
+            .or_else(|| line.strip_prefix("cargo:"))
+        else {
+            return;
+        };
+        let key = instruction
+            .split_once('=')
+            .map_or(instruction, |(key, _)| key);
+
+        *Mutex::lock(&self.counts)
+            .unwrap_or_else(PoisonError::into_inner)
+            .entry(key.to_string())
+            .or_default() += 1;
+    }
+
+    /// Returns number of instructions with given key.
+    pub fn count(&self, key: &str) -> usize {
+        let counts = Mutex::lock(&self.counts).unwrap_or_else(PoisonError::into_inner);
+        counts.get(key).copied().unwrap_or(0)
+    }
+
+    /// Returns number of instructions by key.
+    pub fn by_key(&self) -> BTreeMap<String, usize> {
+        Mutex::lock(&self.counts)
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    pub fn total(&self) -> usize {
+        self.by_key().values().sum()
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.count("warning")
+    }
+
+    pub fn errors(&self) -> usize {
+        self.count("error")
+    }
+
+    pub fn rerun_if_changed(&self) -> usize {
+        self.count("rerun-if-changed")
+    }
+
+    /// Returns number of `rustc-link-lib`, `rustc-link-search` and `rustc-link-arg*` instructions.
+    pub fn link(&self) -> usize {
+        self.by_key()
+            .iter()
+            .filter(|(key, _)| key.starts_with("rustc-link-"))
+            .map(|(_, count)| count)
+            .sum()
+    }
+}
+
+/// Log file which prefixes every instruction with UTC timestamp and rotates once it grows too large.
+///
+/// When the log exceeds [`RotatingLog::max_size`], it is renamed to `PATH.1`, `PATH.1` to `PATH.2`
+/// and so on, keeping up to [`RotatingLog::max_files`] old logs. Useful to keep a persistent
+/// diagnostic trail of frequently re-run build scripts under `target/`.
+///
+/// ```rust
+/// use cargo_build::build_out::{self, RotatingLog, TeeWriter};
+///
+/// let log = RotatingLog::new("target/build-script.log")
+///     .max_size(64 * 1024)
+///     .max_files(2);
+///
+/// // 2026-10-16T08:12:45.117Z cargo::rerun-if-changed=build.rs
+/// build_out::set(TeeWriter::new(std::io::stdout(), log));
+///
+/// cargo_build::rerun_if_changed(["build.rs"]);
+///
+/// build_out::reset();
+/// ```
+#[derive(Debug)]
+pub struct RotatingLog {
+    path: PathBuf,
+    max_size: u64,
+    max_files: usize,
+    file: Option<std::fs::File>,
+    size: u64,
+    pending: Vec<u8>,
+}
+
+impl RotatingLog {
+    /// Creates log at `path` with 1 MiB size limit and 3 old logs. File is opened on first write.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_size: 1024 * 1024,
+            max_files: 3,
+            file: None,
+            size: 0,
+            pending: Vec::new(),
+        }
+    }
+
+    /// Sets size in bytes after which the log is rotated.
+    pub fn max_size(mut self, max_size: u64) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Sets number of old logs to keep. `0` means the log is truncated on rotation.
+    pub fn max_files(mut self, max_files: usize) -> Self {
+        self.max_files = max_files;
+        self
+    }
+
+    fn write_line(&mut self, line: &str) -> std::io::Result<()> {
+        let entry = format!(
+            "{} {line}\n",
+            format_timestamp(std::time::SystemTime::now())
+        );
+
+        if self.file.is_none() {
+            if let Some(parent) = self.path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let file = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            self.size = file.metadata()?.len();
+            self.file = Some(file);
+        }
+        if self.size > 0 && self.size + entry.len() as u64 > self.max_size {
+            self.rotate()?;
+        }
+
+        let file = self.file.as_mut().expect("Log file is open");
+        file.write_all(entry.as_bytes())?;
+        self.size += entry.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.file = None;
+
+        let rotated = |n: usize| {
+            let mut path = self.path.clone().into_os_string();
+            path.push(format!(".{n}"));
+            PathBuf::from(path)
+        };
+        if self.max_files > 0 {
+            for n in (1..self.max_files).rev() {
+                if rotated(n).exists() {
+                    std::fs::rename(rotated(n), rotated(n + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, rotated(1))?;
+        }
+
+        self.file = Some(std::fs::File::create(&self.path)?);
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingLog {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut pending = std::mem::take(&mut self.pending);
+        let result = for_each_line(&mut pending, buf, |line| self.write_line(line));
+        self.pending = pending;
+
+        result.map(|()| buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.file {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Formats time as RFC 3339 UTC timestamp with milliseconds, e.g. `2026-10-16T08:12:45.117Z`.
+pub(crate) fn format_timestamp(time: std::time::SystemTime) -> String {
+    let since_epoch = time
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+
+    // Civil date from days since 1970-01-01, see http://howardhinnant.github.io/date_algorithms.html
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+/// Appends `buf` to `pending` and calls `f` with every complete line, which is removed from `pending`.
+fn for_each_line(
+    pending: &mut Vec<u8>,
+    buf: &[u8],
+    mut f: impl FnMut(&str) -> std::io::Result<()>,
+) -> std::io::Result<()> {
+    pending.extend_from_slice(buf);
+
+    while let Some(end) = pending.iter().position(|&b| b == b'\n') {
+        let line: Vec<u8> = pending.drain(..=end).collect();
+        f(&String::from_utf8_lossy(&line[..end]))?;
+    }
+    Ok(())
+}
+
+/// Redirects output stream of all threads into memory for build scripts running async code.
+///
+/// Buffer is installed with [`set_global`], so instructions emitted from async tasks on any worker
+/// thread never block on `stdout`. Threads which [`set`] their own output stream are not affected.
+/// Collected instructions are written with [`AsyncBridge::flush_to`].
+///
+/// ```rust
+/// # tokio::runtime::Builder::new_current_thread().build().unwrap().block_on(async {
+/// let bridge = cargo_build::build_out::async_bridge();
+///
+/// // Inside async tasks
+/// cargo_build::rerun_if_env_changed("MIRROR_URL");
+///
+/// bridge.flush_to(&mut tokio::io::sink()).await.unwrap();
+/// # });
+/// ```
+#[cfg(feature = "async")]
+pub fn async_bridge() -> AsyncBridge {
+    let bridge = AsyncBridge::default();
+    set_global(SharedVec(bridge.buf.clone()));
+    bridge
+}
+
+/// Handle returned by [`async_bridge`].
+#[cfg(feature = "async")]
+#[derive(Debug, Clone, Default)]
+pub struct AsyncBridge {
+    buf: Arc<Mutex<Vec<u8>>>,
+}
+
+#[cfg(feature = "async")]
+impl AsyncBridge {
+    /// Writes instructions collected so far to `out` and flushes it.
+    pub async fn flush_to<W>(&self, out: &mut W) -> std::io::Result<()>
+    where
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        use tokio::io::AsyncWriteExt;
+
+        let buf =
+            std::mem::take(&mut *Mutex::lock(&self.buf).unwrap_or_else(PoisonError::into_inner));
+        out.write_all(&buf).await?;
+        out.flush().await
+    }
+}
+
+#[cfg(feature = "async")]
+struct SharedVec(Arc<Mutex<Vec<u8>>>);
+
+#[cfg(feature = "async")]
+impl Write for SharedVec {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        Mutex::lock(&self.0)
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writer which duplicates everything written to it into two writers.
+///
+/// See [`tee`].
+///
+/// ```rust
+/// use std::io::Write;
+///
+/// let mut tee = cargo_build::build_out::TeeWriter::new(Vec::new(), Vec::new());
+///
+/// tee.write_all(b"cargo::rustc-cfg=tee\n").unwrap();
+///
+/// let (first, second) = tee.into_inner();
+/// assert_eq!(first, second);
+/// ```
+#[derive(Debug)]
+pub struct TeeWriter<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A: Write, B: Write> TeeWriter<A, B> {
+    pub fn new(first: A, second: B) -> Self {
+        Self { first, second }
+    }
+
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+impl<A: Write, B: Write> Write for TeeWriter<A, B> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    /// Writes `buf` to both writers, even if the first one fails.
+    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        let first = self.first.write_all(buf);
+        let second = self.second.write_all(buf);
+        first.and(second)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let first = self.first.flush();
+        let second = self.second.flush();
+        first.and(second)
+    }
+}
+
+/// Output stream which is shared between threads and locked for every write.
+///
+/// Every instruction is written with single `write_all` call, so sink set with [`set_lockable`] is
+/// locked exactly once per instruction and lines emitted from different threads never interleave.
+///
+/// Implemented for [`Stdout`], [`Stderr`], [`Mutex<W>`] and [`Arc<T>`].
+pub trait LockableWrite {
+    type Locked<'a>: Write
+    where
+        Self: 'a;
+
+    fn lock(&self) -> Self::Locked<'_>;
+}
+
+impl LockableWrite for Stdout {
+    type Locked<'a> = StdoutLock<'static>;
+
+    fn lock(&self) -> Self::Locked<'_> {
+        Stdout::lock(self)
+    }
+}
+
+impl LockableWrite for Stderr {
+    type Locked<'a> = StderrLock<'static>;
+
+    fn lock(&self) -> Self::Locked<'_> {
+        Stderr::lock(self)
+    }
+}
+
+impl<W: Write> LockableWrite for Mutex<W> {
+    type Locked<'a>
+        = LockedWriter<'a, W>
+    where
+        W: 'a;
+
+    fn lock(&self) -> Self::Locked<'_> {
+        LockedWriter(Mutex::lock(self).unwrap_or_else(PoisonError::into_inner))
+    }
+}
+
+impl<T: LockableWrite + ?Sized> LockableWrite for Arc<T> {
+    type Locked<'a>
+        = T::Locked<'a>
+    where
+        T: 'a;
+
+    fn lock(&self) -> Self::Locked<'_> {
+        T::lock(self)
+    }
+}
+
+/// Locked [`Mutex<W>`] returned by [`LockableWrite::lock`].
+pub struct LockedWriter<'a, W>(MutexGuard<'a, W>);
+
+impl<W: Write> Write for LockedWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        self.0.write_all(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.flush()
+    }
+}
+
+/// Sets output stream of the current thread to sink which is locked once per instruction.
+///
+/// Useful when build script spawns threads: set the same sink in every thread and instructions
+/// will never be torn.
+///
+/// ```rust
+/// use std::sync::{Arc, Mutex};
+///
+/// let log = Arc::new(Mutex::new(Vec::new()));
+///
+/// std::thread::scope(|s| {
+///     for module in ["net", "fs"] {
+///         let log = log.clone();
+///         s.spawn(move || {
+///             cargo_build::build_out::set_lockable(log);
+///             cargo_build::rerun_if_changed(format!("src/{module}.c"));
+///         });
+///     }
+/// });
+///
+/// let log = log.lock().unwrap();
+/// assert_eq!(log.iter().filter(|&&b| b == b'\n').count(), 2);
+/// ```
+pub fn set_lockable(wr: impl LockableWrite + 'static) {
+    replace_out(Box::new(Locking(wr)));
+}
+
+/// Adapter which acquires the lock of [`LockableWrite`] for every call.
+struct Locking<L>(L);
+
+impl<L: LockableWrite> Write for Locking<L> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        self.0.lock().write_all(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.0.lock().flush()
+    }
+}
+
+/// Enables or disables duplicating every [`warning`](crate::warning) and [`error`](crate::error) to
+/// `stderr` on the current thread, prefixed with the name of the package.
+///
+/// Cargo only shows warnings of build scripts of the path dependencies. `stderr` of every build script
+/// is shown with `cargo build -vv` and when the build script fails, and is saved next to its output in
+/// `target/*/build/*/stderr`.
+///
+/// Instructions written to the output stream are unchanged.
+///
+/// Mirroring can also be enabled by setting `MIRROR_BUILD_WARNINGS` env variable to any value except `0`.
+///
+/// ```rust
+/// cargo_build::build_out::mirror_diagnostics(true);
+///
+/// // stdout: cargo::warning=libfoo not found, using bundled copy
+/// // stderr: [my-crate] warning: libfoo not found, using bundled copy
+/// cargo_build::warning("libfoo not found, using bundled copy");
+/// ```
+pub fn mirror_diagnostics(enabled: bool) {
+    MIRROR_DIAGNOSTICS.set(Some(enabled));
+}
+
+/// Writes diagnostic to `stderr` if [`mirror_diagnostics`] is enabled.
+pub(crate) fn mirror_diagnostic(kind: &str, msg: &str) {
+    let enabled = MIRROR_DIAGNOSTICS
+        .get()
+        .unwrap_or_else(|| std::env::var(MIRROR_ENV_VAR).is_ok_and(|value| value != "0"));
+    if enabled {
+        let package = std::env::var("CARGO_PKG_NAME").ok();
+        eprint!("{}", format_diagnostic(kind, package.as_deref(), msg));
+    }
+}
+
+/// Echoes every instruction emitted on the current thread to `stderr`, prefixed with the name of the
+/// package. Useful for debugging, because `stdout` of build scripts is consumed by Cargo while
+/// `stderr` is shown with `cargo build -vv`.
+///
+/// Instructions written to the output stream are unchanged.
+///
+/// ```rust
+/// cargo_build::build_out::mirror_stderr(true);
+///
+/// // stdout: cargo::rustc-cfg=has_foo
+/// // stderr: [my-crate] directive: cargo::rustc-cfg=has_foo
+/// cargo_build::rustc_cfg("has_foo");
+///
+/// cargo_build::build_out::mirror_stderr(false);
+/// ```
+pub fn mirror_stderr(enabled: bool) {
+    MIRROR_STDERR.set(enabled);
+}
+
+pub(crate) fn format_diagnostic(kind: &str, package: Option<&str>, msg: &str) -> String {
+    let prefix = match package {
+        Some(package) => format!("[{package}] {kind}: "),
+        None => format!("{kind}: "),
+    };
+    msg.lines()
+        .map(|line| format!("{prefix}{line}\n"))
+        .collect()
+}
+
+/// Handle to the output stream of the current thread.
+///
+/// Implements both [`std::io::Write`] and [`std::fmt::Write`], so it can be used with `write!`
+/// and any code generic over formatting targets. `write!` with [`std::fmt::Write`] formats on the
+/// stack and writes the result at once.
+///
+/// ```rust
+/// use std::fmt::Write;
+///
+/// cargo_build::build_out::set(std::io::sink());
+///
+/// let mut out = cargo_build::build_out::Stream;
+///
+/// let version = 3;
+/// writeln!(out, "cargo::rustc-env=API_VERSION={version}").unwrap();
+///
+/// cargo_build::build_out::reset();
+/// ```
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stream;
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        with_out(|out| out.write(buf))
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        with_out(|out| out.write_all(buf))
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        with_out(|out| out.flush())
+    }
+}
+
+impl fmt::Write for Stream {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        Write::write_all(self, s.as_bytes()).map_err(|_| fmt::Error)
+    }
+
+    /// Formats `args` into [`LineBuf`] and writes them with single `write_all` call, so `write!`
+    /// doesn't write every argument separately.
+    fn write_fmt(&mut self, args: fmt::Arguments<'_>) -> fmt::Result {
+        if let Some(s) = args.as_str() {
+            return self.write_str(s);
+        }
+        let mut line = LineBuf::new();
+        fmt::Write::write_fmt(&mut line, args)?;
+        self.write_str(line.as_str())
+    }
+}
+
+/// Receives every instruction emitted on the current thread as structured [`Instruction`] value.
+///
+/// Sinks can store, filter or re-serialize instructions without parsing text. [`Stream`] is the sink
+/// which writes instructions to the output stream, and sinks usually pass instructions to it.
+///
+/// ```rust
+/// use cargo_build::build_out::{self, DirectiveSink, Stream};
+/// use cargo_build::Instruction;
+///
+/// /// Drops all warnings.
+/// struct NoWarnings;
+///
+/// impl DirectiveSink for NoWarnings {
+///     fn emit(&mut self, instruction: &Instruction) -> std::io::Result<()> {
+///         if instruction.key() == "warning" {
+///             return Ok(());
+///         }
+///         Stream.emit(instruction)
+///     }
+/// }
+///
+/// build_out::set_sink(NoWarnings);
+///
+/// cargo_build::warning("Not shown");
+///
+/// build_out::reset_sink();
+/// ```
+pub trait DirectiveSink {
+    fn emit(&mut self, instruction: &Instruction) -> std::io::Result<()>;
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes instruction to the output stream of the current thread.
+impl DirectiveSink for Stream {
+    fn emit(&mut self, instruction: &Instruction) -> std::io::Result<()> {
+        Write::write_all(self, render_line(instruction)?.as_bytes())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Write::flush(self)
+    }
+}
+
+impl<S: DirectiveSink + ?Sized> DirectiveSink for Box<S> {
+    fn emit(&mut self, instruction: &Instruction) -> std::io::Result<()> {
+        (**self).emit(instruction)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        (**self).flush()
+    }
+}
+
+/// Sets [`DirectiveSink`] of the current thread and returns the previous one.
+///
+/// Instructions written with [`Stream`] as raw text don't pass through the sink. Instructions emitted
+/// from inside [`DirectiveSink::emit`] are written to the output stream directly.
+pub fn set_sink(sink: impl DirectiveSink + 'static) -> Option<Box<dyn DirectiveSink>> {
+    SINK.replace(Some(Box::new(sink)))
+}
+
+/// Removes [`DirectiveSink`] of the current thread and returns it.
+pub fn reset_sink() -> Option<Box<dyn DirectiveSink>> {
+    SINK.take()
+}
+
+/// Records every instruction emitted on the current thread instead of writing it, until returned
+/// guard is finished or dropped.
+///
+/// Useful in tests, and for build scripts which inspect or deduplicate their instructions before
+/// emitting them with [`emit`](crate::emit). Previous [`DirectiveSink`] is restored afterwards.
+///
+/// ```rust
+/// use cargo_build::Instruction;
+///
+/// let recording = cargo_build::build_out::recording();
+///
+/// cargo_build::rerun_if_changed(["build.rs"]);
+/// cargo_build::rustc_cfg("has_foo");
+///
+/// assert_eq!(
+///     recording.finish(),
+///     [
+///         Instruction::new("rerun-if-changed", "build.rs"),
+///         Instruction::new("rustc-cfg", "has_foo"),
+///     ]
+/// );
+/// ```
+pub fn recording() -> Recording {
+    let instructions = Rc::new(RefCell::new(Vec::new()));
+    let previous = SINK.replace(Some(Box::new(Recorder(instructions.clone()))));
+    Recording {
+        instructions,
+        previous: Some(previous),
+    }
+}
+
+/// Guard returned by [`recording`].
+#[must_use = "Recording stops as soon as the guard is dropped"]
+pub struct Recording {
+    instructions: Rc<RefCell<Vec<Instruction>>>,
+    previous: Option<Option<Box<dyn DirectiveSink>>>,
+}
+
+impl Recording {
+    /// Returns instructions recorded so far.
+    pub fn instructions(&self) -> Vec<Instruction> {
+        self.instructions.borrow().clone()
+    }
+
+    /// Stops recording and returns recorded instructions.
+    pub fn finish(mut self) -> Vec<Instruction> {
+        self.restore();
+        self.instructions.take()
+    }
+
+    fn restore(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            drop(SINK.replace(previous));
+        }
+    }
+}
+
+impl Drop for Recording {
+    fn drop(&mut self) {
+        self.restore();
+    }
+}
+
+struct Recorder(Rc<RefCell<Vec<Instruction>>>);
+
+impl DirectiveSink for Recorder {
+    fn emit(&mut self, instruction: &Instruction) -> std::io::Result<()> {
+        self.0.borrow_mut().push(instruction.clone());
+        Ok(())
+    }
+}
+
+/// What happens to the instruction after [`on_emit`] hook returns.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HookAction {
+    /// Instruction, possibly changed by the hook, is passed to the next hook and written.
+    Emit,
+    /// Instruction is dropped silently.
+    Skip,
+    /// Instruction is dropped, functions panic and `try_*` functions return [`Error::Io`] of
+    /// [`std::io::ErrorKind::InvalidInput`] kind with the reason.
+    ///
+    /// [`Error::Io`]: crate::Error::Io
+    Reject(String),
+}
+
+/// Registers `hook` called with every instruction emitted on any thread before it is written.
+///
+/// Hooks run in the order they were registered, before [`DirectiveSink`] of the thread. Useful for
+/// policies which apply to every call site, e.g. logging, rewriting paths or rejecting instructions.
+/// Instructions emitted by the hook itself run hooks again.
+///
+/// ```rust
+/// use cargo_build::build_out::HookAction;
+/// use cargo_build::{Instruction, LinkSearch};
+///
+/// fn check_search_paths(instruction: &mut Instruction) -> HookAction {
+///     let Instruction::RustcLinkSearch(LinkSearch { path, .. }) = instruction else {
+///         return HookAction::Emit;
+///     };
+///     if path.starts_with("/usr") {
+///         return HookAction::Reject("System library directories must come from pkg-config".into());
+///     }
+///     if let Ok(relative) = path.strip_prefix("vendor/") {
+///         *path = std::path::Path::new("third_party").join(relative);
+///     }
+///     HookAction::Emit
+/// }
+///
+/// cargo_build::build_out::on_emit(check_search_paths);
+///
+/// // cargo::rustc-link-search=third_party/libs
+/// cargo_build::rustc_link_search(["vendor/libs"]);
+///
+/// assert!(cargo_build::try_rustc_link_search(["/usr/lib"]).is_err());
+///
+/// cargo_build::build_out::clear_on_emit();
+/// ```
+pub fn on_emit(hook: fn(&mut Instruction) -> HookAction) {
+    let mut hooks = HOOKS.lock().unwrap_or_else(PoisonError::into_inner);
+    let registered = hooks.as_deref().unwrap_or_default();
+    *hooks = Some(registered.iter().copied().chain([hook]).collect());
+    HAS_HOOKS.store(true, Ordering::Release);
+}
+
+/// Removes all hooks registered with [`on_emit`].
+pub fn clear_on_emit() {
+    let mut hooks = HOOKS.lock().unwrap_or_else(PoisonError::into_inner);
+    *hooks = None;
+    HAS_HOOKS.store(false, Ordering::Release);
+}
+
+/// Calls hooks registered with [`on_emit`] and returns instruction to write, `None` if it was
+/// skipped. Instruction is only cloned if there are hooks. Lock is not held while hooks run.
+fn run_hooks(instruction: &Instruction) -> std::io::Result<Option<Cow<'_, Instruction>>> {
+    if !HAS_HOOKS.load(Ordering::Acquire) {
+        return Ok(Some(Cow::Borrowed(instruction)));
+    }
+    let hooks = HOOKS.lock().unwrap_or_else(PoisonError::into_inner).clone();
+
+    let mut instruction = instruction.clone();
+    for hook in hooks.iter().flat_map(|hooks| hooks.iter()) {
+        match hook(&mut instruction) {
+            HookAction::Emit => {}
+            HookAction::Skip => return Ok(None),
+            HookAction::Reject(reason) => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    format!("Instruction `{instruction}` is rejected: {reason}"),
+                ))
+            }
+        }
+    }
+    Ok(Some(Cow::Owned(instruction)))
+}
+
+/// Drops exact duplicate instructions emitted on the current thread.
+///
+/// Common when [`rerun_if_changed`](crate::rerun_if_changed) is called from loops or from multiple
+/// modules. Fewer instructions mean less noise in Cargo's fingerprint input. Same as
+/// `set_sink(Dedup::new(Stream))`.
+///
+/// ```rust
+/// cargo_build::build_out::dedup();
+///
+/// for _ in 0..3 {
+///     cargo_build::rerun_if_changed(["proto/api.proto"]); // Emitted once
+/// }
+///
+/// cargo_build::build_out::reset_sink();
+/// ```
+pub fn dedup() {
+    set_sink(Dedup::new(Stream));
+}
+
+/// [`DirectiveSink`] which passes every distinct instruction to `inner` once. See [`dedup`].
+#[derive(Debug, Default)]
+pub struct Dedup<S> {
+    inner: S,
+    seen: HashSet<Instruction>,
+}
+
+impl<S: DirectiveSink> Dedup<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            seen: HashSet::new(),
+        }
+    }
+}
+
+impl<S: DirectiveSink> DirectiveSink for Dedup<S> {
+    fn emit(&mut self, instruction: &Instruction) -> std::io::Result<()> {
+        if self.seen.contains(instruction) {
+            return Ok(());
+        }
+        self.inner.emit(instruction)?;
+        self.seen.insert(instruction.clone());
+        Ok(())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Emits instructions of the current thread as JSON Lines into `wr` instead of the output stream.
+///
+/// Every instruction is written as a JSON object on its own line, e.g.
+/// `{"kind":"rerun-if-changed","value":"src/main.c"}`, for external tooling which analyzes behavior of
+/// build scripts. Cargo doesn't receive these instructions, use [`reset_sink`] to switch back to
+/// `cargo::` output. Same as `set_sink(JsonLines::new(wr))`.
+///
+/// ```rust
+/// let log = std::fs::File::create("target/cargo_build_json_lines.jsonl").unwrap();
+///
+/// cargo_build::build_out::set_json_lines(log);
+/// cargo_build::rerun_if_changed(["src/main.c"]);
+/// cargo_build::build_out::reset_sink();
+///
+/// let out = std::fs::read_to_string("target/cargo_build_json_lines.jsonl").unwrap();
+///
+/// assert_eq!(out, "{\"kind\":\"rerun-if-changed\",\"value\":\"src/main.c\"}\n");
+/// ```
+pub fn set_json_lines(wr: impl Write + 'static) {
+    set_sink(JsonLines::new(wr));
+}
+
+/// [`DirectiveSink`] which writes every instruction as JSON object on its own line. See
+/// [`set_json_lines`].
+#[derive(Debug)]
+pub struct JsonLines<W> {
+    wr: W,
+}
+
+impl<W: Write> JsonLines<W> {
+    pub fn new(wr: W) -> Self {
+        Self { wr }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.wr
+    }
+}
+
+impl<W: Write> DirectiveSink for JsonLines<W> {
+    fn emit(&mut self, instruction: &Instruction) -> std::io::Result<()> {
+        let mut line = String::from("{\"kind\":");
+        push_json_string(&mut line, instruction.key());
+        line.push_str(",\"value\":");
+        push_json_string(&mut line, &instruction.value());
+        line.push_str("}\n");
+        self.wr.write_all(line.as_bytes())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.wr.flush()
+    }
+}
+
+/// Appends `s` as JSON string literal.
+fn push_json_string(out: &mut String, s: &str) {
+    use std::fmt::Write;
+
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32).expect("Writing to String"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Runs `f` with [`DirectiveSink`] of the current thread. Returns `None` if there is none.
+///
+/// Sink is taken out while `f` runs, so instructions emitted by the sink itself are written directly.
+fn with_sink<R>(f: impl FnOnce(&mut dyn DirectiveSink) -> R) -> Option<R> {
+    let mut sink = SINK.take()?;
+    let result = f(&mut *sink);
+
+    SINK.with_borrow_mut(|slot| {
+        if slot.is_none() {
+            *slot = Some(sink);
+        }
+    });
+    Some(result)
+}
+
+/// Writes `cargo::KEY=VALUE` instruction to the output stream.
+///
+/// Instruction is formatted into [`LineBuf`] first and written with single `write_all` call, so
+/// writers shared between threads never receive torn lines. Every function, macro and
+/// `directive_fmt` use the same path.
+pub(crate) fn write_instruction(instruction: &Instruction) -> std::io::Result<()> {
+    let Some(instruction) = run_hooks(instruction)? else {
+        return Ok(());
+    };
+    let instruction = &*instruction;
+
+    if MIRROR_STDERR.get() {
+        let package = std::env::var("CARGO_PKG_NAME").ok();
+        let line = instruction.to_string();
+        eprint!(
+            "{}",
+            format_diagnostic("directive", package.as_deref(), &line)
+        );
+    }
+
+    let sink_result = with_sink(|sink| sink.emit(instruction));
+    register_final_flush();
+    if let Some(result) = sink_result {
+        return result;
+    }
+
+    Stream.emit(instruction)
+}
+
+/// Renders instruction in [`syntax::get`](crate::syntax::get) syntax, followed by newline.
+fn render_line(instruction: &Instruction) -> std::io::Result<LineBuf> {
+    let mut line = LineBuf::new();
+    let rendered = crate::syntax::get().display(instruction);
+    fmt::Write::write_fmt(&mut line, format_args!("{rendered}\n"))
+        .map_err(|_| std::io::Error::other("Unable to format instruction"))?;
+    Ok(line)
+}
+
+/// Flushes [`DirectiveSink`] and output stream of the current thread and output stream set with
+/// [`set_global`].
+pub(crate) fn flush() -> std::io::Result<()> {
+    if let Some(result) = with_sink(|sink| sink.flush()) {
+        result?;
+    }
+    with_out(|out| out.flush())?;
+    GlobalOut::with(|out| out.flush())
+}
+
+/// Sets output stream of the current thread until returned guard is dropped.
+///
+/// Previous output stream is restored on drop, even if the thread panics. Useful in tests, which
+/// otherwise leak output stream to other tests running on the same thread.
+///
+/// ```rust
+/// let file = std::fs::File::create("target/cargo_build_scoped_log.txt").unwrap();
+///
+/// {
+///     let _out = cargo_build::build_out::scoped(file);
+///     cargo_build::rustc_cfg("scoped");
+/// }
+///
+/// // Output stream is `stdout` again
+/// cargo_build::rustc_cfg("unscoped");
+///
+/// let out = std::fs::read_to_string("target/cargo_build_scoped_log.txt").unwrap();
+///
+/// assert_eq!(out, "cargo::rustc-cfg=scoped\n");
+/// ```
+pub fn scoped(wr: impl Write + 'static) -> ScopedOut {
+    ScopedOut {
+        previous: Some(replace_out(Box::new(wr))),
+    }
+}
+
+/// Guard returned by [`scoped`] which restores previous output stream on drop.
+#[must_use = "Output stream is restored as soon as the guard is dropped"]
+pub struct ScopedOut {
+    previous: Option<Box<dyn Write>>,
+}
+
+impl Drop for ScopedOut {
+    fn drop(&mut self) {
+        if let Some(previous) = self.previous.take() {
+            drop(replace_out(previous));
+        }
+    }
+}
+
+/// Buffers all instructions of the current thread in memory until returned guard is flushed or dropped.
+///
+/// Buffered instructions are written to the previous output stream with single `write_all` call. This
+/// saves syscalls for build scripts which emit thousands of instructions, and Cargo sees either none
+/// or all of them.
+///
+/// ```rust
+/// let out = cargo_build::build_out::buffered();
+///
+/// for i in 0..1000 {
+///     cargo_build::rerun_if_changed(format!("assets/{i}.png"));
+/// }
+///
+/// // Writes all instructions to `stdout` at once
+/// out.flush().unwrap();
+/// ```
+pub fn buffered() -> Buffered {
+    let buf = Rc::new(RefCell::new(Vec::new()));
+    Buffered {
+        out: scoped(SharedBuf(buf.clone())),
+        buf,
+        sorted: false,
+    }
+}
+
+/// Guard returned by [`buffered`].
+#[must_use = "Buffered instructions are written as soon as the guard is dropped"]
+pub struct Buffered {
+    buf: Rc<RefCell<Vec<u8>>>,
+    out: ScopedOut,
+    sorted: bool,
+}
+
+impl Buffered {
+    /// Sorts buffered instructions by kind and value when they are written, so output is byte-for-byte
+    /// reproducible regardless of iteration order of `HashMap`s, `read_dir` and threads.
+    ///
+    /// Only instructions whose order doesn't matter (`rerun-if-*`, `rustc-cfg` and `rustc-check-cfg`)
+    /// are sorted by value. Others, e.g. `rustc-link-search` where order decides which library the
+    /// linker picks, or `rustc-env` where the last value wins, are sorted by kind only and keep the
+    /// order they were emitted in.
+    ///
+    /// ```rust
+    /// let out = cargo_build::build_out::buffered().sorted();
+    ///
+    /// cargo_build::rerun_if_changed(["b.png"]);
+    /// cargo_build::rustc_cfg("foo");
+    /// cargo_build::rerun_if_changed(["a.png"]);
+    ///
+    /// // cargo::rerun-if-changed=a.png
+    /// // cargo::rerun-if-changed=b.png
+    /// // cargo::rustc-cfg=foo
+    /// out.flush().unwrap();
+    /// ```
+    pub fn sorted(mut self) -> Self {
+        self.sorted = true;
+        self
+    }
+
+    /// Writes buffered instructions to the previous output stream and restores it.
+    ///
+    /// Dropping the guard writes them too, but write errors are ignored there.
+    pub fn flush(mut self) -> std::io::Result<()> {
+        self.write_out()
+    }
+
+    fn write_out(&mut self) -> std::io::Result<()> {
+        let mut buf = self.buf.take();
+        if self.sorted {
+            buf = sort_lines(&buf);
+        }
+        match self.out.previous.as_mut() {
+            Some(previous) if !buf.is_empty() => {
+                previous.write_all(&buf)?;
+                previous.flush()
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Drop for Buffered {
+    fn drop(&mut self) {
+        // Nothing is left if guard was flushed. Panicking here would abort during unwinding.
+        let _ = self.write_out();
+    }
+}
+
+/// Sorts instruction lines by kind, and by value if their order is insignificant.
+fn sort_lines(buf: &[u8]) -> Vec<u8> {
+    const UNORDERED: &[&str] = &[
+        "rerun-if-changed",
+        "rerun-if-env-changed",
+        "rustc-cfg",
+        "rustc-check-cfg",
+    ];
+
+    let mut lines: Vec<(&[u8], &[u8], &[u8])> = buf
+        .split_inclusive(|&b| b == b'\n')
+        .map(|line| {
+            let eq = line.iter().position(|&b| b == b'=').unwrap_or(line.len());
+            let (kind, value) = line.split_at(eq);
+            let name = kind.rsplit(|&b| b == b':').next().unwrap_or(kind);
+            let unordered = UNORDERED.iter().any(|k| name == k.as_bytes());
+            (kind, if unordered { value } else { &[][..] }, line)
+        })
+        .collect();
+    lines.sort_by(|a, b| (a.0, a.1).cmp(&(b.0, b.1)));
+
+    let mut sorted: Vec<u8> = Vec::with_capacity(buf.len());
+    for (_, _, line) in lines {
+        sorted.extend_from_slice(line);
+        if !line.ends_with(b"\n") {
+            sorted.push(b'\n');
+        }
+    }
+    sorted
+}
+
+/// Runs `f` with output stream of current thread redirected into buffer and returns its contents.
+pub(crate) fn capture<R>(f: impl FnOnce() -> R) -> (R, Vec<u8>) {
+    let buf = Rc::new(RefCell::new(Vec::new()));
+
+    let out = scoped(SharedBuf(buf.clone()));
+    let result = f();
+    drop(out);
+
+    (result, buf.take())
+}
+
+/// Instruction or raw output of a task run with [`capture_instructions`].
+pub(crate) enum Captured {
+    /// Instructions of a single call and its caller, before they are recorded in the session or
+    /// written.
+    Instructions(Vec<Instruction>, &'static Location<'static>),
+    /// Bytes written to the output stream directly.
+    Raw(Vec<u8>),
+}
+
+thread_local! {
+    /// Set while [`capture_instructions`] runs.
+    static CAPTURED: RefCell<Option<Rc<RefCell<Vec<Captured>>>>> = const { RefCell::new(None) };
+}
+
+/// Runs `f` and returns instructions it emitted instead of emitting them, so they can be emitted
+/// by another thread with its sink, session and policies.
+pub(crate) fn capture_instructions<R>(f: impl FnOnce() -> R) -> (R, Vec<Captured>) {
+    let captured = Rc::new(RefCell::new(Vec::new()));
+
+    let out = scoped(CapturedRaw(captured.clone()));
+    let previous = CAPTURED.replace(Some(captured.clone()));
+    let result = f();
+    CAPTURED.set(previous);
+    drop(out);
+
+    (result, captured.take())
+}
+
+/// Keeps `instruction` if [`capture_instructions`] runs on the current thread, returns it otherwise.
+pub(crate) fn capture_instruction(
+    instruction: Instruction,
+    caller: &'static Location<'static>,
+) -> Option<Instruction> {
+    CAPTURED.with_borrow(|captured| match captured {
+        Some(captured) => {
+            let instructions = Captured::Instructions(vec![instruction], caller);
+            captured.borrow_mut().push(instructions);
+            None
+        }
+        None => Some(instruction),
+    })
+}
+
+/// [`capture_instruction`] for instructions of a single call, which are kept together.
+pub(crate) fn capture_instructions_at(
+    instructions: Vec<Instruction>,
+    caller: &'static Location<'static>,
+) -> Option<Vec<Instruction>> {
+    CAPTURED.with_borrow(|captured| match captured {
+        Some(captured) => {
+            let instructions = Captured::Instructions(instructions, caller);
+            captured.borrow_mut().push(instructions);
+            None
+        }
+        None => Some(instructions),
+    })
+}
+
+struct CapturedRaw(Rc<RefCell<Vec<Captured>>>);
+
+impl Write for CapturedRaw {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut captured = self.0.borrow_mut();
+        match captured.last_mut() {
+            Some(Captured::Raw(raw)) => raw.extend_from_slice(buf),
+            _ => captured.push(Captured::Raw(buf.to_vec())),
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Writes already formatted instructions to the output stream.
+pub(crate) fn write_raw(bytes: &[u8]) {
+    with_out(|out| out.write_all(bytes).expect(ERR_MSG));
+}
+
+struct SharedBuf(Rc<RefCell<Vec<u8>>>);
+
+impl Write for SharedBuf {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Most instructions are well under this size and never touch the heap.
+const INLINE_CAPACITY: usize = 256;
+
+/// Line buffer which lives on the stack and falls back to the heap for lines longer than
+/// [`INLINE_CAPACITY`].
+pub(crate) struct LineBuf {
+    inline: [u8; INLINE_CAPACITY],
+    len: usize,
+    heap: Vec<u8>,
+}
+
+impl LineBuf {
+    pub(crate) fn new() -> Self {
+        Self {
+            inline: [0; INLINE_CAPACITY],
+            len: 0,
+            heap: Vec::new(),
+        }
+    }
+
+    pub(crate) fn as_bytes(&self) -> &[u8] {
+        if self.is_spilled() {
+            &self.heap
+        } else {
+            &self.inline[..self.len]
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> &str {
+        std::str::from_utf8(self.as_bytes()).expect("Only `str`s are written into LineBuf")
+    }
+
+    pub(crate) fn is_spilled(&self) -> bool {
+        !self.heap.is_empty()
+    }
+}
+
+impl fmt::Write for LineBuf {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.is_spilled() {
+            self.heap.extend_from_slice(s.as_bytes());
+        } else if self.len + s.len() <= INLINE_CAPACITY {
+            self.inline[self.len..self.len + s.len()].copy_from_slice(s.as_bytes());
+            self.len += s.len();
+        } else {
+            self.heap.reserve(self.len + s.len());
+            self.heap.extend_from_slice(&self.inline[..self.len]);
+            self.heap.extend_from_slice(s.as_bytes());
+        }
+        Ok(())
+    }
+}
+
+/// Per-thread buffers for build scripts which emit instructions from many threads.
+///
+/// Each thread writes into its own buffer without any locking. Buffer is handed over to
+/// [`ThreadBuffers`] once the thread finishes its work, and [`ThreadBuffers::flush`] writes all
+/// collected buffers to the output stream of the calling thread. Instructions emitted by the
+/// same [`ThreadBuffers::run`] call stay together.
+///
+/// ```rust
+/// let buffers = cargo_build::build_out::ThreadBuffers::new();
+///
+/// std::thread::scope(|s| {
+///     for module in ["net", "fs", "ui"] {
+///         let buffers = &buffers;
+///         s.spawn(move || {
+///             buffers.run(|| {
+///                 cargo_build::rerun_if_changed(format!("src/{module}.c"));
+///                 cargo_build::rustc_cfg(format!("has_{module}"));
+///             })
+///         });
+///     }
+/// });
+///
+/// // Writes all instructions to `stdout`
+/// buffers.flush();
+/// ```
+#[derive(Clone, Default)]
+pub struct ThreadBuffers {
+    finished: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+impl ThreadBuffers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Runs `f` with output stream of current thread redirected into private buffer.
+    ///
+    /// Previous output stream is restored afterwards.
+    pub fn run<R>(&self, f: impl FnOnce() -> R) -> R {
+        let (result, buf) = capture(f);
+
+        if !buf.is_empty() {
+            self.finished
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .push(buf);
+        }
+        result
+    }
+
+    /// Writes all buffers collected so far to the output stream of current thread.
+    pub fn flush(&self) {
+        let finished =
+            std::mem::take(&mut *self.finished.lock().unwrap_or_else(PoisonError::into_inner));
+
+        with_out(|out| {
+            for buf in finished {
+                out.write_all(&buf).expect(ERR_MSG);
+            }
+        });
+    }
 }
@@ -0,0 +1,1113 @@
+use std::cell::RefCell;
+use std::fmt::Write;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex, RwLock};
+
+use crate as cargo_build;
+use crate::build_out::LineBuf;
+
+#[test]
+fn line_buf_inline_test() {
+    let mut line = LineBuf::new();
+
+    let path = "src/main.c";
+    writeln!(line, "cargo::rerun-if-changed={path}").unwrap();
+
+    assert!(!line.is_spilled());
+    assert_eq!(line.as_bytes(), b"cargo::rerun-if-changed=src/main.c\n");
+}
+
+#[test]
+fn line_buf_spilled_test() {
+    let mut line = LineBuf::new();
+    let long = "a".repeat(300);
+
+    write!(line, "cargo::warning=").unwrap();
+    assert!(!line.is_spilled());
+
+    writeln!(line, "{long}").unwrap();
+    assert!(line.is_spilled());
+
+    assert_eq!(
+        line.as_bytes(),
+        format!("cargo::warning={long}\n").as_bytes()
+    );
+}
+
+#[test]
+fn thread_buffers_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    let buffers = cargo_build::build_out::ThreadBuffers::new();
+
+    std::thread::scope(|s| {
+        for thread in 0..8 {
+            let buffers = &buffers;
+            s.spawn(move || {
+                buffers.run(|| {
+                    for i in 0..100 {
+                        cargo_build::rerun_if_env_changed(format!("THREAD_{thread}_{i}"));
+                    }
+                })
+            });
+        }
+    });
+
+    assert!(vec_out.0.read().unwrap().is_empty());
+
+    buffers.flush();
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    let lines: Vec<&str> = out.lines().collect();
+    assert_eq!(lines.len(), 800);
+
+    // Instructions emitted by one thread stay together
+    for chunk in lines.chunks(100) {
+        let (thread, _) = chunk[0]
+            .strip_prefix("cargo::rerun-if-env-changed=THREAD_")
+            .unwrap()
+            .split_once('_')
+            .unwrap();
+        for (i, line) in chunk.iter().enumerate() {
+            assert_eq!(
+                *line,
+                format!("cargo::rerun-if-env-changed=THREAD_{thread}_{i}")
+            );
+        }
+    }
+}
+
+#[test]
+fn thread_buffers_restore_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    let buffers = cargo_build::build_out::ThreadBuffers::new();
+
+    buffers.run(|| cargo_build::warning("buffered"));
+    cargo_build::warning("direct");
+    buffers.flush();
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(out, "cargo::warning=direct\ncargo::warning=buffered\n");
+}
+
+#[test]
+fn stream_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    let version = 3;
+    writeln!(
+        cargo_build::build_out::Stream,
+        "cargo::rustc-env=API_VERSION={version}"
+    )
+    .unwrap();
+    std::io::Write::write_all(
+        &mut cargo_build::build_out::Stream,
+        b"cargo::rustc-cfg=io\n",
+    )
+    .unwrap();
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(out, "cargo::rustc-env=API_VERSION=3\ncargo::rustc-cfg=io\n");
+}
+
+#[test]
+fn stream_write_fmt_test() {
+    /// Keeps every `write` call separately.
+    struct Writes(Rc<RefCell<Vec<Vec<u8>>>>);
+
+    impl std::io::Write for Writes {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.borrow_mut().push(buf.to_vec());
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let writes = Rc::new(RefCell::new(Vec::new()));
+    let _out = cargo_build::build_out::scoped(Writes(writes.clone()));
+
+    let (key, version) = ("API_VERSION", 3);
+    writeln!(
+        cargo_build::build_out::Stream,
+        "cargo::rustc-env={key}={version}"
+    )
+    .unwrap();
+
+    assert_eq!(
+        *writes.borrow(),
+        [b"cargo::rustc-env=API_VERSION=3\n".to_vec()]
+    );
+}
+
+#[test]
+fn format_diagnostic_test() {
+    use crate::build_out::format_diagnostic;
+
+    assert_eq!(
+        format_diagnostic(
+            "warning",
+            Some("my-crate"),
+            "libfoo not found\nusing bundled copy"
+        ),
+        "[my-crate] warning: libfoo not found\n[my-crate] warning: using bundled copy\n"
+    );
+    assert_eq!(
+        format_diagnostic("error", None, "libfoo not found"),
+        "error: libfoo not found\n"
+    );
+}
+
+#[test]
+fn mirror_diagnostics_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    cargo_build::build_out::mirror_diagnostics(true);
+    cargo_build::warning("mirrored");
+    cargo_build::build_out::mirror_diagnostics(false);
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    // Directive stream is unchanged
+    assert_eq!(out, "cargo::warning=mirrored\n");
+}
+
+#[test]
+fn mirror_stderr_test() {
+    let vec_out = TestWriteVecHandle::new();
+    let _out = cargo_build::build_out::scoped(vec_out.clone());
+
+    cargo_build::build_out::mirror_stderr(true);
+    cargo_build::rustc_cfg("mirrored");
+    cargo_build::build_out::mirror_stderr(false);
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    // Directive stream is unchanged
+    assert_eq!(out, "cargo::rustc-cfg=mirrored\n");
+}
+
+#[test]
+fn line_atomic_test() {
+    let calls = Arc::new(RwLock::new(Vec::new()));
+    cargo_build::build_out::set(WriteCallsHandle(calls.clone()));
+
+    let long_value = "x".repeat(1000);
+
+    cargo_build::rerun_if_changed(["src/a.c", "src/b.c"]);
+    cargo_build::rustc_cfg(("feature", "std"));
+    cargo_build::rustc_env("LONG", &long_value);
+    cargo_build::warning("first line\nsecond line");
+
+    cargo_build::build_out::reset();
+
+    // Every directive reaches the writer with single `write` call
+    assert_eq!(
+        *calls.read().expect("Unable to aquire Read lock"),
+        [
+            "cargo::rerun-if-changed=src/a.c\n".to_string(),
+            "cargo::rerun-if-changed=src/b.c\n".to_string(),
+            "cargo::rustc-cfg=feature=\"std\"\n".to_string(),
+            format!("cargo::rustc-env=LONG={long_value}\n"),
+            "cargo::warning=first line\n".to_string(),
+            "cargo::warning=second line\n".to_string(),
+        ]
+    );
+}
+
+#[test]
+fn set_lockable_test() {
+    use std::sync::Mutex;
+
+    let out = Arc::new(Mutex::new(Vec::new()));
+
+    std::thread::scope(|s| {
+        for thread in 0..4 {
+            let out = out.clone();
+            s.spawn(move || {
+                cargo_build::build_out::set_lockable(out);
+                for i in 0..50 {
+                    cargo_build::rustc_cfg(format!("thread_{thread}_cfg_{i}"));
+                }
+            });
+        }
+    });
+
+    let out = out.lock().expect("Unable to aquire Lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(out.lines().count(), 200);
+    assert!(out
+        .lines()
+        .all(|line| line.starts_with("cargo::rustc-cfg=thread_") && line.contains("_cfg_")));
+}
+
+#[test]
+fn set_global_test() {
+    let vec_out = TestWriteVecHandle::new();
+    let local_out = TestWriteVecHandle::new();
+
+    cargo_build::build_out::set_global(vec_out.clone());
+
+    std::thread::scope(|s| {
+        s.spawn(|| cargo_build::rustc_cfg("set_global_test_worker"));
+        s.spawn(|| {
+            // Thread-local output stream takes precedence
+            cargo_build::build_out::set(local_out.clone());
+            cargo_build::rustc_cfg("set_global_test_local");
+        });
+    });
+
+    cargo_build::build_out::reset_global();
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    // Other tests without their own output stream may write here too
+    assert!(out.contains("cargo::rustc-cfg=set_global_test_worker\n"));
+    assert!(!out.contains("set_global_test_local"));
+
+    let local = local_out.0.read().expect("Unable to aquire Read lock");
+    assert_eq!(&local[..], b"cargo::rustc-cfg=set_global_test_local\n");
+}
+
+#[test]
+fn scoped_test() {
+    let outer = TestWriteVecHandle::new();
+    let inner = TestWriteVecHandle::new();
+
+    {
+        let _outer = cargo_build::build_out::scoped(outer.clone());
+        cargo_build::rustc_cfg("outer");
+        {
+            let _inner = cargo_build::build_out::scoped(inner.clone());
+            cargo_build::rustc_cfg("inner");
+        }
+        cargo_build::rustc_cfg("outer_again");
+    }
+
+    let panicked = std::panic::catch_unwind(|| {
+        let _inner = cargo_build::build_out::scoped(inner.clone());
+        panic!("restored anyway");
+    });
+    assert!(panicked.is_err());
+
+    let _outer = cargo_build::build_out::scoped(outer.clone());
+    cargo_build::rustc_cfg("last");
+
+    let outer = outer.0.read().expect("Unable to aquire Read lock");
+    let inner = inner.0.read().expect("Unable to aquire Read lock");
+
+    assert_eq!(
+        std::str::from_utf8(&outer).unwrap(),
+        "cargo::rustc-cfg=outer\ncargo::rustc-cfg=outer_again\ncargo::rustc-cfg=last\n"
+    );
+    assert_eq!(
+        std::str::from_utf8(&inner).unwrap(),
+        "cargo::rustc-cfg=inner\n"
+    );
+}
+
+#[test]
+fn tee_writer_test() {
+    let cargo = TestWriteVecHandle::new();
+    let log = TestWriteVecHandle::new();
+
+    let _out = cargo_build::build_out::scoped(cargo_build::build_out::TeeWriter::new(
+        cargo.clone(),
+        log.clone(),
+    ));
+
+    cargo_build::rerun_if_changed(["build.rs"]);
+    cargo_build::warning("teed");
+
+    let cargo = cargo.0.read().expect("Unable to aquire Read lock");
+    let log = log.0.read().expect("Unable to aquire Read lock");
+
+    assert_eq!(
+        std::str::from_utf8(&cargo).unwrap(),
+        "cargo::rerun-if-changed=build.rs\ncargo::warning=teed\n"
+    );
+    assert_eq!(cargo[..], log[..]);
+}
+
+#[test]
+fn buffered_test() {
+    let calls = Arc::new(RwLock::new(Vec::new()));
+    let _out = cargo_build::build_out::scoped(WriteCallsHandle(calls.clone()));
+
+    let buffered = cargo_build::build_out::buffered();
+    cargo_build::rerun_if_changed(["a.png", "b.png"]);
+    cargo_build::rustc_cfg("buffered");
+
+    assert!(calls.read().expect("Unable to aquire Read lock").is_empty());
+
+    buffered.flush().unwrap();
+    cargo_build::rustc_cfg("unbuffered");
+
+    assert_eq!(
+        *calls.read().expect("Unable to aquire Read lock"),
+        [
+            "cargo::rerun-if-changed=a.png\ncargo::rerun-if-changed=b.png\ncargo::rustc-cfg=buffered\n",
+            "cargo::rustc-cfg=unbuffered\n",
+        ]
+    );
+}
+
+#[test]
+fn buffered_error_test() {
+    let (tx, rx) = std::sync::mpsc::channel();
+    drop(rx);
+    let _out = cargo_build::build_out::scoped(cargo_build::build_out::ChannelWriter::new(tx));
+
+    let buffered = cargo_build::build_out::buffered();
+    cargo_build::rustc_cfg("lost");
+
+    let err = buffered.flush().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::BrokenPipe);
+
+    // Dropping the guard doesn't panic
+    let buffered = cargo_build::build_out::buffered();
+    cargo_build::rustc_cfg("lost");
+    drop(buffered);
+}
+
+#[test]
+fn set_many_test() {
+    let first = TestWriteVecHandle::new();
+    let second = TestWriteVecHandle::new();
+
+    cargo_build::build_out::set_many([
+        Box::new(first.clone()) as Box<dyn std::io::Write>,
+        Box::new(std::io::sink()),
+        Box::new(second.clone()),
+    ]);
+
+    cargo_build::rustc_cfg("many");
+    cargo_build::warning("fan\nout");
+
+    cargo_build::build_out::reset();
+
+    let first = first.0.read().expect("Unable to aquire Read lock");
+    let second = second.0.read().expect("Unable to aquire Read lock");
+
+    assert_eq!(
+        std::str::from_utf8(&first).unwrap(),
+        "cargo::rustc-cfg=many\ncargo::warning=fan\ncargo::warning=out\n"
+    );
+    assert_eq!(first[..], second[..]);
+}
+
+#[test]
+fn set_returns_previous_test() {
+    let first = TestWriteVecHandle::new();
+    let second = TestWriteVecHandle::new();
+
+    cargo_build::build_out::set(first.clone());
+    let previous = cargo_build::build_out::set(second.clone());
+
+    cargo_build::rustc_cfg("second");
+    cargo_build::build_out::set(previous);
+    cargo_build::rustc_cfg("first");
+
+    let first = first.0.read().expect("Unable to aquire Read lock");
+    let second = second.0.read().expect("Unable to aquire Read lock");
+
+    assert_eq!(&first[..], b"cargo::rustc-cfg=first\n");
+    assert_eq!(&second[..], b"cargo::rustc-cfg=second\n");
+}
+
+#[test]
+fn flush_test() {
+    let vec_out = TestWriteVecHandle::new();
+    let writer = std::io::BufWriter::new(vec_out.clone());
+
+    let _out = cargo_build::build_out::scoped(cargo_build::build_out::TeeWriter::new(
+        writer,
+        std::io::sink(),
+    ));
+
+    cargo_build::rustc_cfg("flushed");
+    assert!(vec_out
+        .0
+        .read()
+        .expect("Unable to aquire Read lock")
+        .is_empty());
+
+    cargo_build::flush();
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    assert_eq!(&out[..], b"cargo::rustc-cfg=flushed\n");
+}
+
+#[test]
+fn channel_writer_test() {
+    use std::io::Write;
+
+    let (tx, rx) = std::sync::mpsc::channel();
+
+    std::thread::scope(|s| {
+        for thread in 0..2 {
+            let tx = tx.clone();
+            s.spawn(move || {
+                cargo_build::build_out::set_channel(tx);
+                cargo_build::rustc_cfg(format!("thread_{thread}"));
+            });
+        }
+    });
+
+    let mut writer = cargo_build::build_out::ChannelWriter::new(tx);
+    writer.write_all(b"cargo::rustc-cfg=par").unwrap();
+    writer
+        .write_all(b"tial\ncargo::rustc-cfg=a\ncargo::rustc-cfg=b")
+        .unwrap();
+    drop(writer);
+
+    let mut lines: Vec<String> = rx.iter().collect();
+    lines[..2].sort();
+
+    assert_eq!(
+        lines,
+        [
+            "cargo::rustc-cfg=thread_0",
+            "cargo::rustc-cfg=thread_1",
+            "cargo::rustc-cfg=partial",
+            "cargo::rustc-cfg=a",
+        ]
+    );
+
+    // Receiver is gone
+    let (tx, rx) = std::sync::mpsc::channel();
+    drop(rx);
+    let err = cargo_build::build_out::ChannelWriter::new(tx)
+        .write_all(b"cargo::rustc-cfg=lost\n")
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::BrokenPipe);
+}
+
+#[test]
+fn background_writer_test() {
+    use std::io::Write;
+
+    let vec_out = TestWriteVecHandle::new();
+    let mut writer = cargo_build::build_out::BackgroundWriter::new(vec_out.clone(), 1);
+
+    for i in 0..100 {
+        writeln!(writer, "cargo::rustc-cfg=cfg_{i}").unwrap();
+    }
+    writer.flush().unwrap();
+
+    {
+        let out = vec_out.0.read().expect("Unable to aquire Read lock");
+        let out: &str = std::str::from_utf8(&out).unwrap();
+        assert_eq!(out.lines().count(), 100);
+        assert!(out.ends_with("cargo::rustc-cfg=cfg_99\n"));
+    }
+
+    writer.write_all(b"cargo::rustc-cfg=last\n").unwrap();
+    drop(writer);
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+    assert!(out.ends_with("cargo::rustc-cfg=last\n"));
+}
+
+#[test]
+fn set_callback_test() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    let lines = Rc::new(RefCell::new(Vec::new()));
+
+    let collected = lines.clone();
+    cargo_build::build_out::set_callback(move |line| collected.borrow_mut().push(line.to_string()));
+
+    cargo_build::rustc_cfg("callback");
+    cargo_build::warning("multi\nline");
+
+    cargo_build::build_out::reset();
+
+    assert_eq!(
+        *lines.borrow(),
+        [
+            "cargo::rustc-cfg=callback",
+            "cargo::warning=multi",
+            "cargo::warning=line"
+        ]
+    );
+}
+
+#[test]
+#[cfg(feature = "async")]
+fn async_bridge_test() {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .build()
+        .unwrap();
+
+    let out = runtime.block_on(async {
+        let bridge = cargo_build::build_out::async_bridge();
+
+        std::thread::spawn(|| cargo_build::rustc_cfg("async_bridge_test"))
+            .join()
+            .unwrap();
+
+        let mut out = Vec::new();
+        bridge.flush_to(&mut out).await.unwrap();
+        cargo_build::build_out::reset_global();
+        out
+    });
+
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    // Other tests without their own output stream may write here too
+    assert!(out.contains("cargo::rustc-cfg=async_bridge_test\n"));
+}
+
+#[test]
+fn disable_test() {
+    let vec_out = TestWriteVecHandle::new();
+    let previous = cargo_build::build_out::set(vec_out.clone());
+
+    cargo_build::build_out::disable();
+    cargo_build::rustc_cfg("disabled");
+    cargo_build::warning("disabled");
+
+    cargo_build::build_out::set(previous);
+
+    assert!(vec_out
+        .0
+        .read()
+        .expect("Unable to aquire Read lock")
+        .is_empty());
+}
+
+#[test]
+fn stats_sink_test() {
+    use cargo_build::build_out::StatsSink;
+
+    let vec_out = TestWriteVecHandle::new();
+    let sink = StatsSink::new(vec_out.clone());
+    let stats = sink.stats();
+
+    let _out = cargo_build::build_out::scoped(sink);
+
+    cargo_build::rerun_if_changed(["build.rs", "src/main.c"]);
+    cargo_build::rustc_link_lib(["z", "ssl"]);
+    cargo_build::rustc_link_search(["libs"]);
+    cargo_build::rustc_link_arg("-Wl,--as-needed");
+    cargo_build::warning("first\nsecond");
+    cargo_build::error("fatal");
+    cargo_build::rustc_cfg("stats");
+
+    assert_eq!(stats.rerun_if_changed(), 2);
+    assert_eq!(stats.link(), 4);
+    assert_eq!(stats.warnings(), 2);
+    assert_eq!(stats.errors(), 1);
+    assert_eq!(stats.count("rustc-cfg"), 1);
+    assert_eq!(stats.count("rustc-env"), 0);
+    assert_eq!(stats.total(), 10);
+
+    // Everything is passed through
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    assert_eq!(std::str::from_utf8(&out).unwrap().lines().count(), 10);
+}
+
+#[test]
+fn format_timestamp_test() {
+    use crate::build_out::format_timestamp;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    assert_eq!(format_timestamp(UNIX_EPOCH), "1970-01-01T00:00:00.000Z");
+    assert_eq!(
+        format_timestamp(UNIX_EPOCH + Duration::from_millis(951_782_400_250)),
+        "2000-02-29T00:00:00.250Z"
+    );
+    assert_eq!(
+        format_timestamp(UNIX_EPOCH + Duration::from_secs(1_792_141_965)),
+        "2026-10-16T09:12:45.000Z"
+    );
+}
+
+#[test]
+fn rotating_log_test() {
+    use cargo_build::build_out::RotatingLog;
+
+    let dir = std::env::temp_dir().join("cargo_build_rotating_log_test");
+    let _ = std::fs::remove_dir_all(&dir);
+    let path = dir.join("build.log");
+
+    // Every entry is `TIMESTAMP cargo::rustc-cfg=N\n`, 44 bytes
+    let log = RotatingLog::new(&path).max_size(100).max_files(2);
+    let _out = cargo_build::build_out::scoped(log);
+
+    for i in 0..7 {
+        cargo_build::rustc_cfg(format!("cfg_{i}"));
+    }
+    cargo_build::flush();
+
+    let read = |name: &str| {
+        let log = std::fs::read_to_string(dir.join(name)).unwrap();
+        log.lines()
+            .map(|line| line.split_once(' ').unwrap().1.to_string())
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(read("build.log"), ["cargo::rustc-cfg=cfg_6"]);
+    assert_eq!(
+        read("build.log.1"),
+        ["cargo::rustc-cfg=cfg_4", "cargo::rustc-cfg=cfg_5"]
+    );
+    assert_eq!(
+        read("build.log.2"),
+        ["cargo::rustc-cfg=cfg_2", "cargo::rustc-cfg=cfg_3"]
+    );
+    assert!(!dir.join("build.log.3").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn directive_sink_test() {
+    use cargo_build::build_out::{DirectiveSink, Stream};
+    use cargo_build::Instruction;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    struct Collect(Rc<RefCell<Vec<Instruction>>>);
+
+    impl DirectiveSink for Collect {
+        fn emit(&mut self, instruction: &Instruction) -> std::io::Result<()> {
+            self.0.borrow_mut().push(instruction.clone());
+            if instruction.key() == "warning" {
+                // Emitted from inside the sink, written directly
+                cargo_build::rustc_cfg("from_sink");
+                return Ok(());
+            }
+            Stream.emit(instruction)
+        }
+    }
+
+    let vec_out = TestWriteVecHandle::new();
+    let _out = cargo_build::build_out::scoped(vec_out.clone());
+
+    let collected = Rc::new(RefCell::new(Vec::new()));
+    cargo_build::build_out::set_sink(Collect(collected.clone()));
+
+    cargo_build::rerun_if_changed(["build.rs"]);
+    cargo_build::warning("dropped");
+    cargo_build::rustc_link_lib("static=z");
+
+    assert!(cargo_build::build_out::reset_sink().is_some());
+    cargo_build::rustc_cfg("no_sink");
+
+    assert_eq!(
+        *collected.borrow(),
+        [
+            Instruction::new("rerun-if-changed", "build.rs"),
+            Instruction::new("warning", "dropped"),
+            Instruction::new("rustc-link-lib", "static=z"),
+        ]
+    );
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(
+        out,
+        "\
+cargo::rerun-if-changed=build.rs
+cargo::rustc-cfg=from_sink
+cargo::rustc-link-lib=static=z
+cargo::rustc-cfg=no_sink
+"
+    );
+}
+
+#[test]
+fn dedup_test() {
+    let vec_out = TestWriteVecHandle::new();
+    let _out = cargo_build::build_out::scoped(vec_out.clone());
+
+    cargo_build::build_out::dedup();
+
+    for _ in 0..3 {
+        cargo_build::rerun_if_changed(["proto/api.proto", "build.rs"]);
+        cargo_build::rustc_cfg(("feature", "std"));
+    }
+    cargo_build::rustc_cfg(("feature", "alloc"));
+
+    cargo_build::build_out::reset_sink();
+    cargo_build::rerun_if_changed(["build.rs"]);
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(
+        out,
+        "\
+cargo::rerun-if-changed=proto/api.proto
+cargo::rerun-if-changed=build.rs
+cargo::rustc-cfg=feature=\"std\"
+cargo::rustc-cfg=feature=\"alloc\"
+cargo::rerun-if-changed=build.rs
+"
+    );
+}
+
+#[test]
+fn buffered_sorted_test() {
+    let vec_out = TestWriteVecHandle::new();
+    let _out = cargo_build::build_out::scoped(vec_out.clone());
+
+    let buffered = cargo_build::build_out::buffered().sorted();
+    cargo_build::rustc_link_lib(["z"]);
+    cargo_build::rerun_if_changed(["b.png"]);
+    cargo_build::rustc_cfg("foo");
+    cargo_build::rustc_link_lib(["a"]);
+    cargo_build::rerun_if_changed(["a.png"]);
+    cargo_build::rerun_if_env_changed("CC");
+    cargo_build::rustc_link_search(["native=libs/vendored", "native=libs/system"]);
+    cargo_build::rustc_env("API_VERSION", "3");
+    cargo_build::rustc_env("API_VERSION", "2");
+    buffered.flush().unwrap();
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(
+        out,
+        "\
+cargo::rerun-if-changed=a.png
+cargo::rerun-if-changed=b.png
+cargo::rerun-if-env-changed=CC
+cargo::rustc-cfg=foo
+cargo::rustc-env=API_VERSION=3
+cargo::rustc-env=API_VERSION=2
+cargo::rustc-link-lib=z
+cargo::rustc-link-lib=a
+cargo::rustc-link-search=native=libs/vendored
+cargo::rustc-link-search=native=libs/system
+"
+    );
+}
+
+#[test]
+fn poisoned_lock_test() {
+    let log = Arc::new(Mutex::new(Vec::new()));
+
+    let poisoner = log.clone();
+    let result = std::thread::spawn(move || {
+        let _guard = poisoner.lock().unwrap();
+        panic!("worker panicked while holding the lock");
+    })
+    .join();
+    assert!(result.is_err());
+    assert!(log.is_poisoned());
+
+    cargo_build::build_out::set_lockable(log.clone());
+    cargo_build::rustc_cfg("after_panic");
+    cargo_build::build_out::reset();
+
+    let out = log
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    assert_eq!(
+        std::str::from_utf8(&out).unwrap(),
+        "cargo::rustc-cfg=after_panic\n"
+    );
+}
+
+#[test]
+fn validating_test() {
+    use std::io::Write;
+
+    let vec_out = TestWriteVecHandle::new();
+    let mut writer = cargo_build::build_out::Validating::new(vec_out.clone());
+
+    writer.write_all(b"cargo::rustc-cfg=has_").unwrap();
+    writer.write_all(b"foo\ncargo::rustc-env=A=1\n").unwrap();
+
+    // Valid prefix is reported as a short write, the invalid line is not consumed
+    let invalid = b"cargo::rustc-cfg=ok\ncargo::rustc-link-lib=shared=foo\n";
+    assert_eq!(
+        writer.write(invalid).unwrap(),
+        "cargo::rustc-cfg=ok\n".len()
+    );
+    let err = writer
+        .write(b"cargo::rustc-link-lib=shared=foo\n")
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+
+    let err = writer
+        .write_all(
+            b"cargo::rustc-cfg=ok\ncargo::rustc-link-lib=shared=foo\ncargo::rustc-cfg=after\n",
+        )
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert_eq!(
+        err.to_string(),
+        "Invalid instruction `cargo::rustc-link-lib=shared=foo`: \
+            Unknown kind `shared` of `rustc-link-lib`, expected one of `dylib`, `static`, `framework`"
+    );
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    // Lines before invalid line are written, `write_all` fails on the invalid line
+    assert_eq!(
+        out,
+        "cargo::rustc-cfg=has_foo\ncargo::rustc-env=A=1\ncargo::rustc-cfg=ok\ncargo::rustc-cfg=ok\n"
+    );
+}
+
+#[test]
+fn validate_test() {
+    let vec_out = TestWriteVecHandle::new();
+    let _out = cargo_build::build_out::scoped(vec_out.clone());
+
+    cargo_build::build_out::validate();
+
+    cargo_build::rerun_if_changed(["build.rs"]);
+    cargo_build::rustc_link_lib_static([], ["foo"]);
+    cargo_build::rustc_check_cfgs(["has_foo"]);
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(out.lines().count(), 3);
+}
+
+#[test]
+fn json_lines_test() {
+    let vec_out = TestWriteVecHandle::new();
+
+    cargo_build::build_out::set_json_lines(vec_out.clone());
+    cargo_build::rerun_if_changed(["src/main.c"]);
+    cargo_build::rustc_cfg(("feature", "std"));
+    cargo_build::warning("tab\tand \\ and \u{1}");
+    cargo_build::build_out::reset_sink();
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(
+        out,
+        r#"{"kind":"rerun-if-changed","value":"src/main.c"}
+{"kind":"rustc-cfg","value":"feature=\"std\""}
+{"kind":"warning","value":"tab\tand \\ and \u0001"}
+"#
+    );
+}
+
+#[test]
+fn on_emit_test() {
+    use cargo_build::build_out::HookAction;
+    use cargo_build::Instruction;
+
+    thread_local! {
+        static SEEN: std::cell::RefCell<Vec<String>> = const { std::cell::RefCell::new(Vec::new()) };
+    }
+
+    fn record(instruction: &mut Instruction) -> HookAction {
+        SEEN.with_borrow_mut(|seen| seen.push(instruction.to_string()));
+        HookAction::Emit
+    }
+
+    let vec_out = TestWriteVecHandle::new();
+    let _out = cargo_build::build_out::scoped(vec_out.clone());
+
+    // Hooks apply to every thread, so only instructions of this test are changed
+    fn rewrite(instruction: &mut Instruction) -> HookAction {
+        let Instruction::RustcCfg { name, .. } = instruction else {
+            return HookAction::Emit;
+        };
+        match name.as_str() {
+            "hook_old" => *name = "hook_new".to_string(),
+            "hook_skipped" => return HookAction::Skip,
+            "hook_rejected" => return HookAction::Reject("not allowed".to_string()),
+            _ => {}
+        }
+        HookAction::Emit
+    }
+
+    cargo_build::build_out::on_emit(record);
+    cargo_build::build_out::on_emit(rewrite);
+    cargo_build::rerun_if_changed(["build.rs"]);
+    cargo_build::rustc_cfg("hook_old");
+    cargo_build::rustc_cfg("hook_skipped");
+    let err = cargo_build::try_rustc_cfg("hook_rejected").unwrap_err();
+    cargo_build::build_out::clear_on_emit();
+    cargo_build::rustc_cfg("hook_old");
+
+    assert_eq!(
+        err.to_string(),
+        cargo_build::Error::Io(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "Instruction `cargo::rustc-cfg=hook_rejected` is rejected: not allowed"
+        ))
+        .to_string()
+    );
+    assert_eq!(
+        SEEN.take(),
+        [
+            "cargo::rerun-if-changed=build.rs",
+            "cargo::rustc-cfg=hook_old",
+            "cargo::rustc-cfg=hook_skipped",
+            "cargo::rustc-cfg=hook_rejected",
+        ]
+    );
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(
+        out,
+        "cargo::rerun-if-changed=build.rs\ncargo::rustc-cfg=hook_new\ncargo::rustc-cfg=hook_old\n"
+    );
+}
+
+#[test]
+fn recording_test() {
+    use cargo_build::Instruction;
+
+    let vec_out = TestWriteVecHandle::new();
+    let _out = cargo_build::build_out::scoped(vec_out.clone());
+
+    cargo_build::build_out::dedup();
+
+    let recording = cargo_build::build_out::recording();
+    cargo_build::rerun_if_changed(["build.rs"]);
+    cargo_build::rustc_link_lib(["foo"]);
+    assert_eq!(recording.instructions().len(), 2);
+    cargo_build::rerun_if_changed(["build.rs"]);
+    let recorded = recording.finish();
+
+    assert_eq!(
+        recorded,
+        [
+            Instruction::new("rerun-if-changed", "build.rs"),
+            Instruction::new("rustc-link-lib", "foo"),
+            Instruction::new("rerun-if-changed", "build.rs"),
+        ]
+    );
+
+    // Previous sink is restored
+    for instruction in recorded {
+        cargo_build::emit(instruction);
+    }
+    cargo_build::build_out::reset_sink();
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(
+        out,
+        "cargo::rerun-if-changed=build.rs\ncargo::rustc-link-lib=foo\n"
+    );
+}
+
+#[test]
+fn final_flush_test() {
+    let vec_out = TestWriteVecHandle::new();
+    let _out = cargo_build::build_out::scoped(std::io::BufWriter::new(vec_out.clone()));
+
+    cargo_build::rustc_cfg("has_foo");
+    assert!(vec_out.0.read().unwrap().is_empty());
+
+    cargo_build::build_out::final_flush().unwrap();
+    assert_eq!(&*vec_out.0.read().unwrap(), b"cargo::rustc-cfg=has_foo\n");
+
+    // Only the first call flushes
+    cargo_build::rustc_cfg("has_bar");
+    cargo_build::build_out::final_flush().unwrap();
+    assert_eq!(&*vec_out.0.read().unwrap(), b"cargo::rustc-cfg=has_foo\n");
+}
+
+/// Spawned by [`default_stdout_test`], emits into default `stdout` and exits without destructors.
+#[test]
+#[ignore = "spawned by default_stdout_test"]
+fn default_stdout_child() {
+    cargo_build::build_out::reset();
+
+    cargo_build::rustc_cfg("first");
+    cargo_build::rustc_cfg("second");
+    cargo_build::finish();
+
+    cargo_build::rustc_cfg("lost");
+    std::process::exit(0);
+}
+
+#[test]
+fn default_stdout_test() {
+    let output = std::process::Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "build_out_test::default_stdout_child"])
+        .args(["--ignored", "--nocapture", "--test-threads=1"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    // Buffered until `finish`, instructions emitted afterwards are lost on `process::exit`
+    assert!(
+        stdout.contains("cargo::rustc-cfg=first\ncargo::rustc-cfg=second\n"),
+        "{stdout}"
+    );
+    assert!(!stdout.contains("cargo::rustc-cfg=lost"), "{stdout}");
+}
+
+struct WriteCallsHandle(Arc<RwLock<Vec<String>>>);
+
+impl std::io::Write for WriteCallsHandle {
+    fn write(&mut self, buf: &[u8]) -> std::result::Result<usize, std::io::Error> {
+        self.0
+            .write()
+            .expect("Unable to aquire Write lock")
+            .push(String::from_utf8(buf.to_vec()).unwrap());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::result::Result<(), std::io::Error> {
+        Ok(())
+    }
+}
+
+struct TestWriteVecHandle(Arc<RwLock<Vec<u8>>>);
+
+impl TestWriteVecHandle {
+    fn new() -> Self {
+        Self(Arc::new(RwLock::new(Vec::new())))
+    }
+}
+
+impl Clone for TestWriteVecHandle {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl std::io::Write for TestWriteVecHandle {
+    fn write(&mut self, buf: &[u8]) -> std::result::Result<usize, std::io::Error> {
+        self.0
+            .write()
+            .expect("Unable to aquire Write lock")
+            .write(buf)
+    }
+
+    fn flush(&mut self) -> std::result::Result<(), std::io::Error> {
+        Ok(())
+    }
+}
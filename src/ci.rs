@@ -0,0 +1,126 @@
+//! Detection of CI providers for embedding build provenance into binaries.
+
+use std::fmt::{self, Display};
+
+use crate::{rerun_if_env_changed, rustc_env};
+
+/// CI provider recognized by [`detect`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CiProvider {
+    GitHubActions,
+    GitLab,
+    Buildkite,
+}
+
+impl Display for CiProvider {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            CiProvider::GitHubActions => "github-actions",
+            CiProvider::GitLab => "gitlab",
+            CiProvider::Buildkite => "buildkite",
+        })
+    }
+}
+
+/// Information about CI run returned by [`detect`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CiInfo {
+    pub provider: CiProvider,
+    pub run_id: Option<String>,
+    /// Branch or tag name.
+    pub commit_ref: Option<String>,
+    pub commit_sha: Option<String>,
+}
+
+/// Env variables of each provider: marker, run id, ref, sha.
+const PROVIDERS: [(CiProvider, [&str; 4]); 3] = [
+    (
+        CiProvider::GitHubActions,
+        [
+            "GITHUB_ACTIONS",
+            "GITHUB_RUN_ID",
+            "GITHUB_REF_NAME",
+            "GITHUB_SHA",
+        ],
+    ),
+    (
+        CiProvider::GitLab,
+        [
+            "GITLAB_CI",
+            "CI_PIPELINE_ID",
+            "CI_COMMIT_REF_NAME",
+            "CI_COMMIT_SHA",
+        ],
+    ),
+    (
+        CiProvider::Buildkite,
+        [
+            "BUILDKITE",
+            "BUILDKITE_BUILD_ID",
+            "BUILDKITE_BRANCH",
+            "BUILDKITE_COMMIT",
+        ],
+    ),
+];
+
+/// Detects GitHub Actions, GitLab CI or Buildkite from their env variables.
+///
+/// ```rust
+/// if let Some(ci) = cargo_build::ci::detect() {
+///     println!("Building on {}", ci.provider);
+/// }
+/// ```
+pub fn detect() -> Option<CiInfo> {
+    detect_with(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+}
+
+pub(crate) fn detect_with(var: impl Fn(&str) -> Option<String>) -> Option<CiInfo> {
+    PROVIDERS
+        .into_iter()
+        .find(|(_, [marker, ..])| var(marker).is_some())
+        .map(|(provider, [_, run_id, commit_ref, commit_sha])| CiInfo {
+            provider,
+            run_id: var(run_id),
+            commit_ref: var(commit_ref),
+            commit_sha: var(commit_sha),
+        })
+}
+
+/// Sets `CI_PROVIDER`, `CI_RUN_ID`, `CI_COMMIT_REF` and `CI_COMMIT_SHA` compile-time env variables
+/// with [`rustc_env`] for the package being built.
+///
+/// Env variables of every supported provider are tracked with [`rerun_if_env_changed`]. Variables are
+/// only set when CI is detected and the value is known, so use `option_env!` to read them.
+///
+/// ```rust
+/// // build.rs
+/// cargo_build::ci::emit_metadata();
+/// ```
+/// ```rust
+/// // main.rs
+/// let provider = option_env!("CI_PROVIDER").unwrap_or("local");
+/// ```
+pub fn emit_metadata() -> Option<CiInfo> {
+    for (_, vars) in PROVIDERS {
+        rerun_if_env_changed(vars);
+    }
+
+    let info = detect()?;
+    emit_info(&info);
+    Some(info)
+}
+
+pub(crate) fn emit_info(info: &CiInfo) {
+    rustc_env("CI_PROVIDER", &info.provider.to_string());
+
+    let values = [
+        ("CI_RUN_ID", &info.run_id),
+        ("CI_COMMIT_REF", &info.commit_ref),
+        ("CI_COMMIT_SHA", &info.commit_sha),
+    ];
+    for (name, value) in values {
+        if let Some(value) = value {
+            rustc_env(name, value);
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+use crate as cargo_build;
+use crate::ci::{detect_with, emit_info, CiInfo, CiProvider};
+
+#[test]
+fn detect_test() {
+    let vars = HashMap::from([
+        ("GITHUB_ACTIONS", "true"),
+        ("GITHUB_RUN_ID", "1658821493"),
+        ("GITHUB_REF_NAME", "main"),
+        ("GITHUB_SHA", "ffac537e6cbbf934b08745a378932722df287a53"),
+    ]);
+
+    assert_eq!(
+        detect_with(|name| vars.get(name).map(|value| value.to_string())),
+        Some(CiInfo {
+            provider: CiProvider::GitHubActions,
+            run_id: Some("1658821493".to_string()),
+            commit_ref: Some("main".to_string()),
+            commit_sha: Some("ffac537e6cbbf934b08745a378932722df287a53".to_string()),
+        })
+    );
+
+    let vars = HashMap::from([("BUILDKITE", "true"), ("BUILDKITE_BRANCH", "release")]);
+
+    assert_eq!(
+        detect_with(|name| vars.get(name).map(|value| value.to_string())),
+        Some(CiInfo {
+            provider: CiProvider::Buildkite,
+            run_id: None,
+            commit_ref: Some("release".to_string()),
+            commit_sha: None,
+        })
+    );
+
+    assert_eq!(detect_with(|_| None), None);
+}
+
+#[test]
+fn emit_info_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    emit_info(&CiInfo {
+        provider: CiProvider::GitLab,
+        run_id: Some("42".to_string()),
+        commit_ref: None,
+        commit_sha: Some("ffac537e".to_string()),
+    });
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(
+        out,
+        "\
+cargo::rustc-env=CI_PROVIDER=gitlab
+cargo::rustc-env=CI_RUN_ID=42
+cargo::rustc-env=CI_COMMIT_SHA=ffac537e
+"
+    );
+}
+
+struct TestWriteVecHandle(Arc<RwLock<Vec<u8>>>);
+
+impl TestWriteVecHandle {
+    fn new() -> Self {
+        Self(Arc::new(RwLock::new(Vec::new())))
+    }
+}
+
+impl Clone for TestWriteVecHandle {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl std::io::Write for TestWriteVecHandle {
+    fn write(&mut self, buf: &[u8]) -> std::result::Result<usize, std::io::Error> {
+        self.0
+            .write()
+            .expect("Unable to aquire Write lock")
+            .write(buf)
+    }
+
+    fn flush(&mut self) -> std::result::Result<(), std::io::Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+use std::fmt::Display;
+
+use crate::functions::emit_error;
+use crate::session;
+
+/// Gathers errors during the build script and emits all of them at once.
+///
+/// Instead of failing at the first problem, build script can check everything (missing tools, bad env
+/// values, failed probes) and report every issue in one build iteration. Each error is emitted as a
+/// separate [`error`](crate::error) when collector is finished or dropped.
+///
+/// ```rust
+/// let mut errors = cargo_build::ErrorCollector::new();
+///
+/// if std::env::var("FOO_SDK").is_err() {
+///     errors.push("FOO_SDK is not set");
+/// }
+/// let jobs: Option<u32> = errors.check("NUM_JOBS is not a number", "8".parse());
+///
+/// // Emits `cargo::error` for each collected error
+/// let ok = errors.finish();
+/// ```
+///
+/// If [`ErrorPolicy::Exit`](crate::ErrorPolicy::Exit) is set for active [`Session`](crate::Session),
+/// build script exits after all collected errors were emitted.
+#[derive(Debug, Default)]
+pub struct ErrorCollector {
+    errors: Vec<String>,
+}
+
+impl ErrorCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds error to the collector.
+    pub fn push(&mut self, msg: impl Into<String>) {
+        self.errors.push(msg.into());
+    }
+
+    /// Returns `Ok` value, or adds `context: error` to the collector and returns `None`.
+    pub fn check<T, E: Display>(&mut self, context: &str, result: Result<T, E>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(err) => {
+                self.push(format!("{context}: {err}"));
+                None
+            }
+        }
+    }
+
+    /// Returns errors collected so far.
+    pub fn errors(&self) -> &[String] {
+        &self.errors
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Emits every collected error. Returns `true` if there were no errors.
+    ///
+    /// Same as dropping the collector.
+    pub fn finish(mut self) -> bool {
+        self.emit()
+    }
+
+    fn emit(&mut self) -> bool {
+        let errors = std::mem::take(&mut self.errors);
+        for msg in &errors {
+            emit_error(msg);
+        }
+
+        if !errors.is_empty() {
+            if let Some(code) = session::error_exit_code() {
+                session::exit(code);
+            }
+        }
+        errors.is_empty()
+    }
+}
+
+impl Drop for ErrorCollector {
+    fn drop(&mut self) {
+        if !std::thread::panicking() {
+            self.emit();
+        }
+    }
+}
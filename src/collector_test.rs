@@ -0,0 +1,67 @@
+use std::sync::{Arc, RwLock};
+
+use crate as cargo_build;
+
+#[test]
+fn error_collector_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    let mut errors = cargo_build::ErrorCollector::new();
+
+    errors.push("FOO_SDK is not set");
+    let jobs: Option<u32> = errors.check("NUM_JOBS is not a number", "eight".parse::<u32>());
+    let level: Option<u32> = errors.check("OPT_LEVEL is not a number", "3".parse::<u32>());
+
+    assert_eq!(jobs, None);
+    assert_eq!(level, Some(3));
+    assert_eq!(errors.errors().len(), 2);
+
+    // Nothing is emitted until collector is finished
+    assert!(vec_out
+        .0
+        .read()
+        .expect("Unable to aquire Read lock")
+        .is_empty());
+
+    assert!(!errors.finish());
+    assert!(cargo_build::ErrorCollector::new().finish());
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(
+        out,
+        "\
+cargo::error=FOO_SDK is not set
+cargo::error=NUM_JOBS is not a number: invalid digit found in string
+"
+    );
+}
+
+struct TestWriteVecHandle(Arc<RwLock<Vec<u8>>>);
+
+impl TestWriteVecHandle {
+    fn new() -> Self {
+        Self(Arc::new(RwLock::new(Vec::new())))
+    }
+}
+
+impl Clone for TestWriteVecHandle {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl std::io::Write for TestWriteVecHandle {
+    fn write(&mut self, buf: &[u8]) -> std::result::Result<usize, std::io::Error> {
+        self.0
+            .write()
+            .expect("Unable to aquire Write lock")
+            .write(buf)
+    }
+
+    fn flush(&mut self) -> std::result::Result<(), std::io::Error> {
+        Ok(())
+    }
+}
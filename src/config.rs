@@ -0,0 +1,125 @@
+//! Convenience wrapper over per-thread setters such as [`set_newline_policy`](crate::set_newline_policy),
+//! applied at the top of the build script.
+//!
+//! Settings other than syntax only affect the thread which called [`Config::apply`] and tasks of
+//! [`parallel::run`](crate::parallel::run) it starts. Other threads call [`Config::apply`] themselves.
+//!
+//! ```rust
+//! use cargo_build::config::Config;
+//! use cargo_build::{MissingPathPolicy, NewlinePolicy};
+//!
+//! Config::new()
+//!     .newline_policy(NewlinePolicy::Strip)
+//!     .missing_path_policy(MissingPathPolicy::Warn)
+//!     .strict(true)
+//!     .dedup(true)
+//!     .apply();
+//!
+//! cargo_build::rerun_if_changed("build.rs");
+//! ```
+
+use crate::syntax::{self, Syntax};
+use crate::{build_out, paths};
+use crate::{MissingPathPolicy, NewlinePolicy, UnsupportedPolicy};
+
+/// Settings applied with [`Config::apply`]. [`Default`] matches the behavior of the crate without
+/// configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Config {
+    /// `None` keeps current syntax, see [`syntax::get`].
+    pub syntax: Option<Syntax>,
+    pub newline_policy: NewlinePolicy,
+    pub missing_path_policy: MissingPathPolicy,
+    pub unsupported_policy: UnsupportedPolicy,
+    /// See [`strict_metadata_keys`](crate::strict_metadata_keys) and
+    /// [`strict_rerun_paths`](crate::strict_rerun_paths).
+    pub strict: bool,
+    /// See [`build_out::dedup`].
+    pub dedup: bool,
+    /// Normalization of `rerun-if-changed` and `rustc-link-search` paths, e.g. stripping `\\?\`
+    /// prefix on Windows.
+    pub normalize_paths: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            syntax: None,
+            newline_policy: NewlinePolicy::default(),
+            missing_path_policy: MissingPathPolicy::default(),
+            unsupported_policy: UnsupportedPolicy::default(),
+            strict: false,
+            dedup: false,
+            normalize_paths: true,
+        }
+    }
+}
+
+impl Config {
+    /// Creates default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets syntax of instructions, see [`syntax::set`].
+    pub fn syntax(mut self, syntax: Syntax) -> Self {
+        self.syntax = Some(syntax);
+        self
+    }
+
+    /// Sets [`NewlinePolicy`], see [`set_newline_policy`](crate::set_newline_policy).
+    pub fn newline_policy(mut self, policy: NewlinePolicy) -> Self {
+        self.newline_policy = policy;
+        self
+    }
+
+    /// Sets [`MissingPathPolicy`], see [`set_missing_path_policy`](crate::set_missing_path_policy).
+    pub fn missing_path_policy(mut self, policy: MissingPathPolicy) -> Self {
+        self.missing_path_policy = policy;
+        self
+    }
+
+    /// Sets [`UnsupportedPolicy`], see [`set_unsupported_policy`](crate::set_unsupported_policy).
+    pub fn unsupported_policy(mut self, policy: UnsupportedPolicy) -> Self {
+        self.unsupported_policy = policy;
+        self
+    }
+
+    /// Enables strict checks of metadata keys and `rerun-if-changed` paths.
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Drops exact duplicate instructions.
+    pub fn dedup(mut self, dedup: bool) -> Self {
+        self.dedup = dedup;
+        self
+    }
+
+    /// Enables or disables normalization of paths. Enabled by default.
+    pub fn normalize_paths(mut self, normalize: bool) -> Self {
+        self.normalize_paths = normalize;
+        self
+    }
+
+    /// Calls setter of every setting for the current thread.
+    ///
+    /// Syntax is set for all threads. [`DirectiveSink`](build_out::DirectiveSink) of the current thread
+    /// is replaced if `dedup` is enabled and kept otherwise, so disabling `dedup` doesn't remove
+    /// deduplication installed before, call [`build_out::reset_sink`] for that.
+    pub fn apply(self) {
+        if let Some(syntax) = self.syntax {
+            syntax::set(syntax);
+        }
+        crate::set_newline_policy(self.newline_policy);
+        crate::set_missing_path_policy(self.missing_path_policy);
+        crate::set_unsupported_policy(self.unsupported_policy);
+        crate::strict_metadata_keys(self.strict);
+        crate::strict_rerun_paths(self.strict);
+        if self.dedup {
+            build_out::dedup();
+        }
+        paths::set_normalize(self.normalize_paths);
+    }
+}
@@ -0,0 +1,64 @@
+use std::sync::{Arc, RwLock};
+
+use crate as cargo_build;
+use crate::config::Config;
+use crate::{MissingPathPolicy, NewlinePolicy};
+
+#[test]
+fn config_apply_test() {
+    let vec_out = TestWriteVecHandle::new();
+    let _out = cargo_build::build_out::scoped(vec_out.clone());
+
+    Config::new()
+        .newline_policy(NewlinePolicy::Strip)
+        .missing_path_policy(MissingPathPolicy::Warn)
+        .strict(true)
+        .dedup(true)
+        .apply();
+
+    cargo_build::rustc_env("FOO_VERSION", "1.2.3\n");
+    cargo_build::rerun_if_changed(["Crago.toml", "Crago.toml"]);
+    assert!(cargo_build::try_rerun_if_changed("target/generated.rs").is_err());
+
+    Config::default().apply();
+    cargo_build::build_out::reset_sink();
+
+    assert!(cargo_build::try_rustc_env("FOO_VERSION", "1.2.3\n").is_err());
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    assert_eq!(
+        std::str::from_utf8(&out).unwrap(),
+        "\
+cargo::rustc-env=FOO_VERSION=1.2.3
+cargo::warning=`rerun-if-changed` path `Crago.toml` doesn't exist
+cargo::rerun-if-changed=Crago.toml
+"
+    );
+}
+
+struct TestWriteVecHandle(Arc<RwLock<Vec<u8>>>);
+
+impl TestWriteVecHandle {
+    fn new() -> Self {
+        Self(Arc::new(RwLock::new(Vec::new())))
+    }
+}
+
+impl Clone for TestWriteVecHandle {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl std::io::Write for TestWriteVecHandle {
+    fn write(&mut self, buf: &[u8]) -> std::result::Result<usize, std::io::Error> {
+        self.0
+            .write()
+            .expect("Unable to aquire Write lock")
+            .write(buf)
+    }
+
+    fn flush(&mut self) -> std::result::Result<(), std::io::Error> {
+        Ok(())
+    }
+}
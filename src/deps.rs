@@ -0,0 +1,63 @@
+//! Metadata of dependencies emitted with [`metadata`](crate::metadata) by their build scripts.
+//!
+//! Cargo passes metadata of dependencies with `links` key to build scripts of packages which depend
+//! on them directly, as `DEP_<LINKS>_<KEY>` env variables.
+//!
+//! <https://doc.rust-lang.org/cargo/reference/build-scripts.html#the-links-manifest-key>
+
+use std::collections::BTreeMap;
+
+use crate::functions::sanitize_metadata_key;
+
+/// Returns metadata `key` emitted by the dependency with `links = "<links>"` in `Cargo.toml`.
+///
+/// Reads `DEP_<LINKS>_<KEY>`. Returns `None` if the dependency didn't emit the key or the package
+/// doesn't depend on it directly.
+///
+/// ```rust
+/// // build.rs of the package which depends on `openssl-sys`
+/// if let Some(include) = cargo_build::deps::metadata("openssl", "include") {
+///     println!("OpenSSL headers: {include}");
+/// }
+/// ```
+pub fn metadata(links: &str, key: &str) -> Option<String> {
+    std::env::var(metadata_var(links, key)).ok()
+}
+
+/// Returns all metadata emitted by the dependency with `links = "<links>"` in `Cargo.toml`.
+///
+/// Keys are lowercase, since Cargo uppercases them, e.g. `include` for `DEP_OPENSSL_INCLUDE`.
+pub fn all(links: &str) -> BTreeMap<String, String> {
+    all_in(
+        links,
+        std::env::vars_os().filter_map(|(name, value)| {
+            Some((name.into_string().ok()?, value.into_string().ok()?))
+        }),
+    )
+}
+
+/// `DEP_<LINKS>_<KEY>` with both parts mangled like Cargo does.
+pub(crate) fn metadata_var(links: &str, key: &str) -> String {
+    format!(
+        "{}{}",
+        prefix(links),
+        sanitize_metadata_key(key).to_uppercase()
+    )
+}
+
+pub(crate) fn all_in(
+    links: &str,
+    vars: impl IntoIterator<Item = (String, String)>,
+) -> BTreeMap<String, String> {
+    let prefix = prefix(links);
+    vars.into_iter()
+        .filter_map(|(name, value)| {
+            let key = name.strip_prefix(&prefix)?;
+            (!key.is_empty()).then(|| (key.to_lowercase(), value))
+        })
+        .collect()
+}
+
+fn prefix(links: &str) -> String {
+    format!("DEP_{}_", links.to_uppercase().replace('-', "_"))
+}
@@ -0,0 +1,32 @@
+use crate::deps::{all_in, metadata_var};
+
+#[test]
+fn metadata_var_test() {
+    assert_eq!(metadata_var("openssl", "include"), "DEP_OPENSSL_INCLUDE");
+    assert_eq!(
+        metadata_var("foo-sys", "include-dir"),
+        "DEP_FOO_SYS_INCLUDE_DIR"
+    );
+}
+
+#[test]
+fn all_in_test() {
+    let vars = [
+        ("DEP_OPENSSL_INCLUDE", "/usr/include"),
+        ("DEP_OPENSSL_VERSION_NUMBER", "30000000"),
+        ("DEP_OPENSSL_", "empty key"),
+        ("DEP_Z_INCLUDE", "/opt/zlib/include"),
+        ("OPENSSL_DIR", "/usr"),
+    ]
+    .map(|(name, value)| (name.to_string(), value.to_string()));
+
+    let metadata = all_in("openssl", vars);
+
+    assert_eq!(
+        metadata.into_iter().collect::<Vec<_>>(),
+        [
+            ("include".to_string(), "/usr/include".to_string()),
+            ("version_number".to_string(), "30000000".to_string()),
+        ]
+    );
+}
@@ -0,0 +1,111 @@
+use std::fmt::{self, Display};
+
+use crate::Instruction;
+
+/// Difference between two sets of instructions returned by [`diff`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DirectiveDiff {
+    /// Instructions present only in `new`.
+    pub added: Vec<Instruction>,
+    /// Instructions present only in `old`.
+    pub removed: Vec<Instruction>,
+    /// Instructions which set the same thing to different value, as `(old, new)` pairs, e.g.
+    /// `rustc-env` with the same variable or `rustc-link-lib` with the same library.
+    pub changed: Vec<(Instruction, Instruction)>,
+}
+
+impl DirectiveDiff {
+    /// Returns `true` if both sets of instructions tell Cargo the same.
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Renders one line per difference: `+ ADDED`, `- REMOVED` and `~ OLD -> NEW`.
+impl Display for DirectiveDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for instruction in &self.removed {
+            writeln!(f, "- {instruction}")?;
+        }
+        for instruction in &self.added {
+            writeln!(f, "+ {instruction}")?;
+        }
+        for (old, new) in &self.changed {
+            writeln!(f, "~ {old} -> {new}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares two sets of instructions, e.g. recorded with
+/// [`build_out::recording`](crate::build_out::recording) before and after refactoring of the build
+/// script.
+///
+/// Order of instructions is ignored, repeated instructions are counted.
+///
+/// ```rust
+/// use cargo_build::Instruction;
+///
+/// let old = [
+///     Instruction::new("rerun-if-changed", "build.rs"),
+///     Instruction::new("rustc-link-lib", "foo"),
+///     Instruction::new("rustc-env", "API_VERSION=2"),
+/// ];
+/// let new = [
+///     Instruction::new("rustc-env", "API_VERSION=3"),
+///     Instruction::new("rustc-link-lib", "foo"),
+///     Instruction::new("rustc-cfg", "has_bar"),
+/// ];
+///
+/// let diff = cargo_build::diff(&old, &new);
+///
+/// assert_eq!(
+///     diff.to_string(),
+///     "\
+/// - cargo::rerun-if-changed=build.rs
+/// + cargo::rustc-cfg=has_bar
+/// ~ cargo::rustc-env=API_VERSION=2 -> cargo::rustc-env=API_VERSION=3
+/// "
+/// );
+/// ```
+pub fn diff(old: &[Instruction], new: &[Instruction]) -> DirectiveDiff {
+    let mut added: Vec<Option<&Instruction>> = new.iter().map(Some).collect();
+    let mut removed = Vec::new();
+
+    for instruction in old {
+        match added.iter_mut().find(|added| *added == &Some(instruction)) {
+            Some(same) => *same = None,
+            None => removed.push(instruction),
+        }
+    }
+
+    let mut diff = DirectiveDiff::default();
+
+    for old in removed {
+        let target = identity(old).and_then(|id| {
+            added
+                .iter_mut()
+                .find(|new| new.is_some_and(|new| identity(new) == Some(id)))
+        });
+        match target.and_then(Option::take) {
+            Some(new) => diff.changed.push((old.clone(), new.clone())),
+            None => diff.removed.push(old.clone()),
+        }
+    }
+    diff.added = added.into_iter().flatten().cloned().collect();
+
+    diff
+}
+
+/// Returns what the instruction sets, if at most one instruction of its kind can set it.
+fn identity(instruction: &Instruction) -> Option<(&str, &str)> {
+    let id = match instruction {
+        Instruction::RustcEnv { var, .. } => var,
+        Instruction::Metadata { key, .. } => key,
+        Instruction::RustcCheckCfg { name, .. } => name,
+        Instruction::RustcLinkLib(lib) => &lib.name,
+        Instruction::RustcLinkSearch(search) => search.path.to_str()?,
+        _ => return None,
+    };
+    Some((instruction.key(), id))
+}
@@ -0,0 +1,48 @@
+use crate::{diff, DirectiveDiff, Instruction};
+
+#[test]
+fn diff_same_test() {
+    let old = [
+        Instruction::new("rerun-if-changed", "build.rs"),
+        Instruction::new("rustc-cfg", "has_foo"),
+    ];
+    let new = [
+        Instruction::new("rustc-cfg", "has_foo"),
+        Instruction::new("rerun-if-changed", "build.rs"),
+    ];
+
+    assert!(diff(&old, &new).is_empty());
+    assert_eq!(diff(&old, &new).to_string(), "");
+}
+
+#[test]
+fn diff_test() {
+    let old = [
+        Instruction::new("rustc-link-lib", "dylib=foo"),
+        Instruction::new("rustc-link-search", "native=libs"),
+        Instruction::new("rustc-cfg", "feature=\"std\""),
+        Instruction::new("warning", "repeated"),
+        Instruction::new("warning", "repeated"),
+    ];
+    let new = [
+        Instruction::new("rustc-link-lib", "static=foo"),
+        Instruction::new("rustc-link-search", "native=libs"),
+        Instruction::new("rustc-cfg", "feature=\"alloc\""),
+        Instruction::new("warning", "repeated"),
+    ];
+
+    assert_eq!(
+        diff(&old, &new),
+        DirectiveDiff {
+            added: vec![Instruction::new("rustc-cfg", "feature=\"alloc\"")],
+            removed: vec![
+                Instruction::new("rustc-cfg", "feature=\"std\""),
+                Instruction::new("warning", "repeated"),
+            ],
+            changed: vec![(
+                Instruction::new("rustc-link-lib", "dylib=foo"),
+                Instruction::new("rustc-link-lib", "static=foo"),
+            )],
+        }
+    );
+}
@@ -0,0 +1,658 @@
+//! Typed accessors for env variables set by Cargo for build scripts.
+//!
+//! <https://doc.rust-lang.org/cargo/reference/environment-variables.html#environment-variables-cargo-sets-for-build-scripts>
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Mutex, PoisonError};
+
+use crate::Triple;
+
+/// Env variables passed to [`rerun_if_env_changed`](crate::rerun_if_env_changed).
+pub(crate) static TRACKED: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+pub(crate) fn track(name: &str) {
+    TRACKED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(name.to_string());
+}
+
+/// Checks that the target supports atomic operations of given width in bits.
+///
+/// Reads `CARGO_CFG_TARGET_HAS_ATOMIC`, e.g. `8,16,32,64,ptr`. Returns `false` outside of build scripts.
+///
+/// ```rust
+/// // build.rs
+/// if !cargo_build::env::has_atomic(64) {
+///     cargo_build::rustc_cfg("portable_atomic_fallback");
+/// }
+/// ```
+pub fn has_atomic(width: u32) -> bool {
+    list_contains(&var("CARGO_CFG_TARGET_HAS_ATOMIC"), &width.to_string())
+}
+
+/// Checks that the target supports pointer-sized atomic operations.
+///
+/// Reads `CARGO_CFG_TARGET_HAS_ATOMIC`. Returns `false` outside of build scripts.
+pub fn has_atomic_ptr() -> bool {
+    list_contains(&var("CARGO_CFG_TARGET_HAS_ATOMIC"), "ptr")
+}
+
+/// Returns target features enabled for the target, e.g. `["fxsr", "sse", "sse2"]`.
+///
+/// Reads `CARGO_CFG_TARGET_FEATURE`, which reflects `-C target-feature` and `-C target-cpu` flags.
+/// Returns empty `Vec` outside of build scripts.
+pub fn target_features() -> Vec<String> {
+    parse_list(&var("CARGO_CFG_TARGET_FEATURE"))
+}
+
+/// Returns parsed `CARGO_CFG_TARGET_FEATURE` list. Same as [`target_features`], named after the variable.
+///
+/// See also [`emit_cfg_for_feature`](crate::emit_cfg_for_feature).
+pub fn cfg_target_features() -> Vec<String> {
+    target_features()
+}
+
+/// Checks that target feature is enabled for the target.
+///
+/// Reads `CARGO_CFG_TARGET_FEATURE`. Returns `false` outside of build scripts.
+///
+/// ```rust
+/// // build.rs
+/// if cargo_build::env::has_target_feature("neon") {
+///     cargo_build::rerun_if_changed("src/simd/neon.c");
+/// }
+/// ```
+pub fn has_target_feature(feature: &str) -> bool {
+    list_contains(&var("CARGO_CFG_TARGET_FEATURE"), feature)
+}
+
+/// Returns operating system of the target, e.g. `linux`, `windows`, `macos` or `none`.
+///
+/// Reads `CARGO_CFG_TARGET_OS`. Returns `None` outside of build scripts.
+///
+/// ```rust
+/// // build.rs
+/// match cargo_build::env::target_os().as_deref() {
+///     Some("windows") => cargo_build::rustc_link_lib("ws2_32"),
+///     Some("macos" | "ios") => cargo_build::rustc_link_lib_framework([], "Security"),
+///     _ => {}
+/// }
+/// ```
+pub fn target_os() -> Option<String> {
+    non_empty_var("CARGO_CFG_TARGET_OS")
+}
+
+/// Returns architecture of the target, e.g. `x86_64`, `aarch64` or `wasm32`.
+///
+/// Reads `CARGO_CFG_TARGET_ARCH`. Returns `None` outside of build scripts.
+pub fn target_arch() -> Option<String> {
+    non_empty_var("CARGO_CFG_TARGET_ARCH")
+}
+
+/// Returns ABI disambiguator of the target, e.g. `gnu`, `musl` or `msvc`.
+///
+/// Reads `CARGO_CFG_TARGET_ENV`. Returns `None` outside of build scripts and for targets without it,
+/// e.g. macOS.
+pub fn target_env() -> Option<String> {
+    non_empty_var("CARGO_CFG_TARGET_ENV")
+}
+
+/// Returns families of the target, e.g. `["unix"]` or `["unix", "wasm"]`.
+///
+/// Reads `CARGO_CFG_TARGET_FAMILY`. Returns empty `Vec` outside of build scripts and for bare-metal
+/// targets.
+pub fn target_family() -> Vec<String> {
+    parse_list(&var("CARGO_CFG_TARGET_FAMILY"))
+}
+
+/// Returns pointer width of the target in bits, e.g. `64`.
+///
+/// Reads `CARGO_CFG_TARGET_POINTER_WIDTH`. Returns `None` outside of build scripts.
+pub fn target_pointer_width() -> Option<u32> {
+    var("CARGO_CFG_TARGET_POINTER_WIDTH").parse().ok()
+}
+
+/// Byte order of the target returned by [`target_endian`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Endian {
+    Little,
+    Big,
+}
+
+/// Returns byte order of the target.
+///
+/// Reads `CARGO_CFG_TARGET_ENDIAN`. Returns `None` outside of build scripts.
+pub fn target_endian() -> Option<Endian> {
+    parse_endian(&var("CARGO_CFG_TARGET_ENDIAN"))
+}
+
+pub(crate) fn parse_endian(endian: &str) -> Option<Endian> {
+    match endian {
+        "little" => Some(Endian::Little),
+        "big" => Some(Endian::Big),
+        _ => None,
+    }
+}
+
+/// Returns triple of the host, e.g. `x86_64-unknown-linux-gnu`.
+///
+/// Reads `HOST`. Returns `None` outside of build scripts.
+pub fn host() -> Option<String> {
+    std::env::var("HOST").ok()
+}
+
+/// Returns triple of the target the package is compiled for, e.g. `aarch64-linux-android`.
+///
+/// Reads `TARGET`. Returns `None` outside of build scripts.
+pub fn target() -> Option<String> {
+    std::env::var("TARGET").ok()
+}
+
+/// Returns parsed triple of the target, see [`target`].
+///
+/// ```rust
+/// // build.rs
+/// if cargo_build::env::target_triple().is_some_and(|triple| triple.is_windows_msvc()) {
+///     cargo_build::rustc_link_lib("advapi32");
+/// }
+/// ```
+pub fn target_triple() -> Option<Triple> {
+    Triple::parse(&target()?)
+}
+
+/// Returns parsed triple of the host, see [`host`].
+pub fn host_triple() -> Option<Triple> {
+    Triple::parse(&host()?)
+}
+
+/// Checks that the package is compiled for the host.
+///
+/// This is the case for proc-macros and build dependencies, which are always compiled for the host,
+/// and for every package when `--target` is not used. During cross-compilation `false` means that
+/// target-only instructions, e.g. linking libraries built for the target, are appropriate.
+///
+/// Cargo doesn't tell build scripts the target of the whole build, so build dependency compiled with
+/// `--target` equal to the host is indistinguishable from the target build.
+///
+/// ```rust
+/// // build.rs
+/// if !cargo_build::env::is_host_build() {
+///     cargo_build::rustc_link_lib("target_only_lib");
+/// }
+/// ```
+pub fn is_host_build() -> bool {
+    is_host(host().as_deref(), target().as_deref())
+}
+
+/// Checks that `TARGET` differs from `HOST`. Opposite of [`is_host_build`].
+pub fn is_cross_compiling() -> bool {
+    !is_host_build()
+}
+
+pub(crate) fn is_host(host: Option<&str>, target: Option<&str>) -> bool {
+    host == target
+}
+
+/// Returns runner for target binaries, e.g. `["qemu-aarch64", "-L", "/usr/aarch64-linux-gnu"]`.
+///
+/// Reads `CARGO_TARGET_<TRIPLE>_RUNNER` and splits it on whitespace like Cargo does. Build scripts which
+/// execute probe binaries compiled for the target should prepend the runner to the command.
+/// Emits `rerun-if-env-changed` for the variable.
+///
+/// Runner configured in `.cargo/config.toml` is not visible to build scripts, only the env variable is.
+///
+/// ```rust
+/// // build.rs
+/// let probe = "target/probe";
+///
+/// let command = match cargo_build::env::target_runner() {
+///     Some(runner) => {
+///         let mut command = std::process::Command::new(&runner[0]);
+///         command.args(&runner[1..]).arg(probe);
+///         command
+///     }
+///     None => std::process::Command::new(probe),
+/// };
+/// ```
+pub fn target_runner() -> Option<Vec<String>> {
+    let name = target_runner_var(&target()?);
+    crate::rerun_if_env_changed(name.as_str());
+
+    let runner = parse_runner(&std::env::var(&name).ok()?);
+    (!runner.is_empty()).then_some(runner)
+}
+
+/// `CARGO_TARGET_<TRIPLE>_RUNNER` with triple uppercased and `-`, `.` replaced by `_`.
+pub(crate) fn target_runner_var(triple: &str) -> String {
+    let triple = triple.to_uppercase().replace(['-', '.'], "_");
+    format!("CARGO_TARGET_{triple}_RUNNER")
+}
+
+pub(crate) fn parse_runner(runner: &str) -> Vec<String> {
+    runner.split_whitespace().map(str::to_string).collect()
+}
+
+/// Profile the package is compiled with, returned by [`profile`].
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Profile {
+    /// `dev` profile, used by `cargo build` and `cargo test`.
+    Debug,
+    /// `release` profile, used by `cargo build --release`.
+    Release,
+    /// Custom profile, e.g. `dist` used by `cargo build --profile dist`.
+    Custom(String),
+}
+
+impl Profile {
+    /// Returns name of the profile as used with `--profile`, e.g. `dev` or `release`.
+    pub fn name(&self) -> &str {
+        match self {
+            Self::Debug => "dev",
+            Self::Release => "release",
+            Self::Custom(name) => name,
+        }
+    }
+}
+
+/// Returns profile the package is compiled with.
+///
+/// `PROFILE` env variable is `release` for custom profiles inheriting from `release` and `debug` for
+/// all others, so the name of the profile is taken from `OUT_DIR`, which is inside
+/// `target/<PROFILE>/build`. Use [`is_optimized`] and [`has_debug_info`] to check settings of the
+/// profile. Returns [`Profile::Debug`] outside of build scripts.
+///
+/// ```rust
+/// use cargo_build::env::Profile;
+///
+/// // build.rs
+/// if cargo_build::env::profile() == Profile::Custom("dist".to_string()) {
+///     cargo_build::rustc_cfg("dist");
+/// }
+/// ```
+pub fn profile() -> Profile {
+    profile_with(
+        non_empty_var("PROFILE").as_deref(),
+        std::env::var_os("OUT_DIR").as_deref().map(Path::new),
+    )
+}
+
+pub(crate) fn profile_with(profile: Option<&str>, out_dir: Option<&Path>) -> Profile {
+    // OUT_DIR is `target/[TRIPLE/]PROFILE/build/PACKAGE-HASH/out`
+    let dir = out_dir
+        .filter(|out_dir| out_dir.ends_with("out"))
+        .and_then(|out_dir| out_dir.ancestors().nth(2))
+        .filter(|build| build.ends_with("build"))
+        .and_then(Path::parent)
+        .and_then(Path::file_name)
+        .and_then(|name| name.to_str());
+
+    match (dir, profile) {
+        (Some("debug"), _) => Profile::Debug,
+        (Some("release"), _) => Profile::Release,
+        (Some(name), _) => Profile::Custom(name.to_string()),
+        (None, Some("release")) => Profile::Release,
+        (None, _) => Profile::Debug,
+    }
+}
+
+/// Checks that the package is compiled with optimizations.
+///
+/// Reads `OPT_LEVEL`, e.g. `0`, `3` or `s`. Returns `false` outside of build scripts.
+pub fn is_optimized() -> bool {
+    non_empty_var("OPT_LEVEL").is_some_and(|level| level != "0")
+}
+
+/// Checks that the package is compiled with debug info.
+///
+/// Reads `DEBUG`, e.g. `true`, `false`, `line-tables-only` or `2`. Returns `false` outside of build
+/// scripts.
+pub fn has_debug_info() -> bool {
+    non_empty_var("DEBUG").is_some_and(|debug| !matches!(debug.as_str(), "false" | "0" | "none"))
+}
+
+/// Returns compiler used by Cargo, e.g. `/home/user/.rustup/toolchains/stable-x86_64-unknown-linux-gnu/bin/rustc`.
+///
+/// Reads `RUSTC`. Returns `rustc` outside of build scripts.
+pub fn rustc() -> PathBuf {
+    rustc_with(non_empty_var).into()
+}
+
+/// Returns [`Command`] which runs [`rustc`] like Cargo does, for build scripts probing the compiler.
+///
+/// - Compiler is run through `RUSTC_WRAPPER` and `RUSTC_WORKSPACE_WRAPPER` if set.
+/// - `--target` is set to `TARGET`.
+/// - Flags from `CARGO_ENCODED_RUSTFLAGS` are added.
+///
+/// ```rust,no_run
+/// // build.rs
+/// let out_dir = cargo_build::env::out_dir();
+/// std::fs::write(out_dir.join("probe.rs"), "fn main() { let _ = std::hint::black_box(1); }").unwrap();
+///
+/// let status = cargo_build::env::rustc_command()
+///     .args(["--crate-type=bin", "--emit=metadata", "--out-dir"])
+///     .arg(&out_dir)
+///     .arg(out_dir.join("probe.rs"))
+///     .status()
+///     .unwrap();
+///
+/// if status.success() {
+///     cargo_build::rustc_cfg("has_black_box");
+/// }
+/// ```
+pub fn rustc_command() -> Command {
+    rustc_command_with(non_empty_var)
+}
+
+fn rustc_with(var: impl Fn(&str) -> Option<String>) -> String {
+    var("RUSTC").unwrap_or_else(|| "rustc".to_string())
+}
+
+pub(crate) fn rustc_command_with(var: impl Fn(&str) -> Option<String>) -> Command {
+    // Cargo runs `RUSTC_WRAPPER RUSTC_WORKSPACE_WRAPPER RUSTC` if both wrappers are set
+    let mut programs = ["RUSTC_WRAPPER", "RUSTC_WORKSPACE_WRAPPER"]
+        .into_iter()
+        .filter_map(&var)
+        .chain([rustc_with(&var)]);
+
+    let mut command = Command::new(programs.next().expect("rustc is always present"));
+    command.args(programs);
+    if let Some(target) = var("TARGET") {
+        command.args(["--target", &target]);
+    }
+    if let Some(flags) = var("CARGO_ENCODED_RUSTFLAGS") {
+        command.args(parse_encoded_rustflags(&flags));
+    }
+    command
+}
+
+/// Returns flags passed by Cargo to the compiler, e.g. `["-Ctarget-cpu=native", "--cfg", "foo"]`.
+///
+/// Reads `CARGO_ENCODED_RUSTFLAGS`, which contains flags from `RUSTFLAGS`, `build.rustflags` and
+/// similar settings separated by `0x1f` byte. Returns empty `Vec` outside of build scripts.
+pub fn rustflags() -> Vec<String> {
+    parse_encoded_rustflags(&var("CARGO_ENCODED_RUSTFLAGS"))
+}
+
+/// Checks that [`rustflags`] contain `flag`.
+///
+/// `-C KEY=VALUE`, `--codegen KEY=VALUE` and `-CKEY=VALUE` forms are equivalent, and features of
+/// `-Ctarget-feature` are checked one by one.
+///
+/// ```rust
+/// // build.rs
+/// if cargo_build::env::has_rustflag("-Ctarget-feature=+crt-static") {
+///     cargo_build::rustc_link_lib_static([], "stdc++");
+/// }
+/// ```
+pub fn has_rustflag(flag: &str) -> bool {
+    rustflags_contain(&rustflags(), flag)
+}
+
+pub(crate) fn parse_encoded_rustflags(flags: &str) -> Vec<String> {
+    flags
+        .split('\x1f')
+        .filter(|flag| !flag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+pub(crate) fn rustflags_contain(flags: &[String], flag: &str) -> bool {
+    let flags = normalize_rustflags(flags.iter().map(String::as_str));
+    let mut query = normalize_rustflags(flag.split_whitespace());
+    match query.len() {
+        0 => false,
+        1 => flags.contains(&query.remove(0)),
+        _ => flags.windows(query.len()).any(|window| window == query),
+    }
+}
+
+/// Joins `-C KEY=VALUE` and `--codegen KEY=VALUE` into `-CKEY=VALUE` and splits `-Ctarget-feature`
+/// into one flag per feature.
+fn normalize_rustflags<'a>(flags: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut normalized = Vec::new();
+    let mut flags = flags.into_iter();
+    while let Some(flag) = flags.next() {
+        let codegen = if flag == "-C" || flag == "--codegen" {
+            flags.next().unwrap_or_default()
+        } else if let Some(codegen) = flag
+            .strip_prefix("--codegen=")
+            .or_else(|| flag.strip_prefix("-C"))
+        {
+            codegen
+        } else {
+            normalized.push(flag.to_string());
+            continue;
+        };
+        match codegen.strip_prefix("target-feature=") {
+            Some(features) => normalized.extend(
+                features
+                    .split(',')
+                    .map(|feature| format!("-Ctarget-feature={feature}")),
+            ),
+            None => normalized.push(format!("-C{codegen}")),
+        }
+    }
+    normalized
+}
+
+/// Returns `OUT_DIR`, the directory where build script should place its output.
+///
+/// Panics outside of build scripts.
+pub fn out_dir() -> PathBuf {
+    std::env::var_os("OUT_DIR")
+        .expect("OUT_DIR is not set. It is only available inside the build scripts")
+        .into()
+}
+
+/// [`out_dir`] alternative which returns [`camino::Utf8PathBuf`].
+///
+/// Panics outside of build scripts and if `OUT_DIR` is not valid UTF-8.
+///
+/// ```rust,no_run
+/// // build.rs
+/// let out_dir = cargo_build::env::out_dir_utf8();
+///
+/// cargo_build::rustc_link_search_native([out_dir.join("lib")]);
+/// ```
+#[cfg(feature = "camino")]
+pub fn out_dir_utf8() -> camino::Utf8PathBuf {
+    camino::Utf8PathBuf::from_path_buf(out_dir()).expect("OUT_DIR is not valid UTF-8")
+}
+
+fn var(name: &str) -> String {
+    std::env::var(name).unwrap_or_default()
+}
+
+/// Returns value of the env variable, `None` if it is unset, empty or not valid UTF-8.
+pub(crate) fn non_empty_var(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+/// Splits comma-separated list used by `CARGO_CFG_*` variables with multiple values.
+pub(crate) fn parse_list(list: &str) -> Vec<String> {
+    list.split(',')
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+pub(crate) fn list_contains(list: &str, item: &str) -> bool {
+    list.split(',').any(|i| i == item)
+}
+
+/// Name of the snapshot file inside `OUT_DIR`.
+const SNAPSHOT_FILE: &str = "cargo-build-env-snapshot";
+
+/// Env variables which change on every run without affecting the build.
+const VOLATILE: [&str; 1] = ["CARGO_MAKEFLAGS"];
+
+/// Change of the env variable between build script runs reported by [`snapshot`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EnvChange {
+    pub name: String,
+    /// `None` if variable was not set during previous run.
+    pub old: Option<String>,
+    /// `None` if variable is not set now.
+    pub new: Option<String>,
+}
+
+/// Records build script inputs into `OUT_DIR` and reports which of them changed since the previous run.
+///
+/// Snapshot contains `CARGO_*` variables, `TARGET`, `HOST`, `PROFILE`, `OPT_LEVEL`, `DEBUG`, `RUSTC*`,
+/// `NUM_JOBS` and every variable passed to [`rerun_if_env_changed`](crate::rerun_if_env_changed)
+/// before this call. Each change is reported as [`warning`](crate::warning) and returned.
+///
+/// Call it at the end of the build script to answer "why did my build script rerun?".
+///
+/// ```rust,no_run
+/// // build.rs
+/// cargo_build::rerun_if_env_changed("MYLIB_DIR");
+///
+/// // ...
+///
+/// cargo_build::env::snapshot();
+/// ```
+pub fn snapshot() -> Vec<EnvChange> {
+    let out_dir = std::env::var_os("OUT_DIR")
+        .expect("OUT_DIR is not set. `env::snapshot` can only be used inside the build scripts");
+
+    let tracked = TRACKED
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone();
+
+    let current: BTreeMap<String, String> = std::env::vars()
+        .filter(|(name, _)| is_snapshot_var(name) || tracked.contains(name))
+        .collect();
+
+    snapshot_in(Path::new(&out_dir), &tracked, current)
+}
+
+pub(crate) fn snapshot_in(
+    out_dir: &Path,
+    tracked: &BTreeSet<String>,
+    current: BTreeMap<String, String>,
+) -> Vec<EnvChange> {
+    let path = out_dir.join(SNAPSHOT_FILE);
+
+    // Tracked variables which are unset are recorded too, so setting them later is reported
+    let current: BTreeMap<String, Option<String>> = tracked
+        .iter()
+        .map(|name| (name.clone(), None))
+        .chain(current.into_iter().map(|(name, value)| (name, Some(value))))
+        .collect();
+
+    let changes = match std::fs::read_to_string(&path) {
+        Ok(previous) => diff_snapshots(&parse_snapshot(&previous), &current),
+        Err(_) => Vec::new(),
+    };
+
+    for change in &changes {
+        crate::warning(&describe_change(change));
+    }
+
+    if let Err(err) = std::fs::write(&path, render_snapshot(&current)) {
+        crate::warning(&format!(
+            "Unable to write env snapshot `{}`: {err}",
+            path.display()
+        ));
+    }
+    changes
+}
+
+fn is_snapshot_var(name: &str) -> bool {
+    let build_var = name.starts_with("CARGO_")
+        || name.starts_with("RUSTC")
+        || [
+            "TARGET",
+            "HOST",
+            "PROFILE",
+            "OPT_LEVEL",
+            "DEBUG",
+            "NUM_JOBS",
+        ]
+        .contains(&name);
+
+    build_var && !VOLATILE.contains(&name)
+}
+
+pub(crate) fn describe_change(change: &EnvChange) -> String {
+    let EnvChange { name, old, new } = change;
+    match (old, new) {
+        (Some(old), Some(new)) => {
+            format!("`{name}` changed since the last run: `{old}` -> `{new}`")
+        }
+        (None, Some(new)) => format!("`{name}` was set since the last run: `{new}`"),
+        (Some(old), None) => format!("`{name}` was unset since the last run, it was `{old}`"),
+        (None, None) => format!("`{name}` is unchanged"),
+    }
+}
+
+pub(crate) fn diff_snapshots(
+    old: &BTreeMap<String, Option<String>>,
+    new: &BTreeMap<String, Option<String>>,
+) -> Vec<EnvChange> {
+    let names: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let old = old.get(name).cloned().flatten();
+            let new = new.get(name).cloned().flatten();
+
+            (old != new).then(|| EnvChange {
+                name: name.clone(),
+                old,
+                new,
+            })
+        })
+        .collect()
+}
+
+/// Renders one variable per line as `NAME=VALUE`, or `NAME` if variable is unset.
+///
+/// `\` and newlines in values are escaped.
+pub(crate) fn render_snapshot(vars: &BTreeMap<String, Option<String>>) -> String {
+    let mut out = String::new();
+    for (name, value) in vars {
+        out += name;
+        if let Some(value) = value {
+            out.push('=');
+            out += &value.replace('\\', "\\\\").replace('\n', "\\n");
+        }
+        out.push('\n');
+    }
+    out
+}
+
+pub(crate) fn parse_snapshot(snapshot: &str) -> BTreeMap<String, Option<String>> {
+    snapshot
+        .lines()
+        .map(|line| match line.split_once('=') {
+            Some((name, value)) => (name.to_string(), Some(unescape(value))),
+            None => (line.to_string(), None),
+        })
+        .collect()
+}
+
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => {
+                out.push('\n');
+                chars.next();
+            }
+            ('\\', Some('\\')) => {
+                out.push('\\');
+                chars.next();
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
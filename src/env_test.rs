@@ -0,0 +1,275 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use crate as cargo_build;
+use crate::env::{
+    describe_change, diff_snapshots, is_host, list_contains, parse_encoded_rustflags, parse_endian,
+    parse_list, parse_runner, parse_snapshot, profile_with, render_snapshot, rustc_command_with,
+    rustflags_contain, snapshot_in, target_runner_var, track, Endian, EnvChange, Profile, TRACKED,
+};
+
+#[test]
+fn parse_list_test() {
+    assert_eq!(parse_list("fxsr,sse,sse2"), vec!["fxsr", "sse", "sse2"]);
+    assert_eq!(parse_list("neon"), vec!["neon"]);
+    assert!(parse_list("").is_empty());
+}
+
+#[test]
+fn list_contains_test() {
+    let atomics = "8,16,32,64,ptr";
+
+    assert!(list_contains(atomics, "64"));
+    assert!(list_contains(atomics, "ptr"));
+    assert!(!list_contains(atomics, "128"));
+    assert!(!list_contains(atomics, "6"));
+    assert!(!list_contains("", "64"));
+}
+
+#[test]
+fn parse_endian_test() {
+    assert_eq!(parse_endian("little"), Some(Endian::Little));
+    assert_eq!(parse_endian("big"), Some(Endian::Big));
+    assert_eq!(parse_endian(""), None);
+}
+
+#[test]
+fn profile_test() {
+    let profile = |profile, out_dir: &str| profile_with(profile, Some(Path::new(out_dir)));
+
+    assert_eq!(
+        profile(Some("debug"), "/work/target/debug/build/foo-1234/out"),
+        Profile::Debug
+    );
+    assert_eq!(
+        profile(
+            Some("release"),
+            "/work/target/x86_64-unknown-linux-gnu/release/build/foo-1234/out"
+        ),
+        Profile::Release
+    );
+    // Custom profile inheriting from `release`
+    assert_eq!(
+        profile(Some("release"), "/work/target/dist/build/foo-1234/out"),
+        Profile::Custom("dist".to_string())
+    );
+    assert_eq!(Profile::Custom("dist".to_string()).name(), "dist");
+    assert_eq!(Profile::Debug.name(), "dev");
+
+    // Unknown `OUT_DIR` layout
+    assert_eq!(profile(Some("release"), "/tmp/out-dir"), Profile::Release);
+    assert_eq!(profile_with(None, None), Profile::Debug);
+}
+
+#[test]
+fn parse_encoded_rustflags_test() {
+    assert_eq!(
+        parse_encoded_rustflags("-C\x1ftarget-cpu=native\x1f--cfg\x1ffoo"),
+        ["-C", "target-cpu=native", "--cfg", "foo"]
+    );
+    assert!(parse_encoded_rustflags("").is_empty());
+}
+
+#[test]
+fn rustflags_contain_test() {
+    let flags = parse_encoded_rustflags(
+        "-C\x1ftarget-feature=+avx2,+crt-static\x1f--codegen=opt-level=3\x1f--cfg\x1ffoo",
+    );
+
+    assert!(rustflags_contain(&flags, "-Ctarget-feature=+crt-static"));
+    assert!(rustflags_contain(&flags, "-C target-feature=+avx2"));
+    assert!(rustflags_contain(&flags, "-Copt-level=3"));
+    assert!(rustflags_contain(&flags, "--cfg foo"));
+    assert!(!rustflags_contain(&flags, "--cfg bar"));
+    assert!(!rustflags_contain(&flags, "-Ctarget-feature=+sse4.2"));
+    assert!(!rustflags_contain(&flags, ""));
+}
+
+#[test]
+fn rustc_command_test() {
+    let args = |command: &std::process::Command| {
+        let mut args = vec![command.get_program().to_str().unwrap().to_string()];
+        args.extend(
+            command
+                .get_args()
+                .map(|arg| arg.to_str().unwrap().to_string()),
+        );
+        args
+    };
+
+    let vars = BTreeMap::from([
+        ("RUSTC", "/toolchain/bin/rustc"),
+        ("RUSTC_WRAPPER", "sccache"),
+        ("TARGET", "aarch64-unknown-linux-gnu"),
+        (
+            "CARGO_ENCODED_RUSTFLAGS",
+            "-Ctarget-cpu=native\x1f--cfg=foo",
+        ),
+    ]);
+    let command = rustc_command_with(|name| vars.get(name).map(|value| value.to_string()));
+    assert_eq!(
+        args(&command),
+        [
+            "sccache",
+            "/toolchain/bin/rustc",
+            "--target",
+            "aarch64-unknown-linux-gnu",
+            "-Ctarget-cpu=native",
+            "--cfg=foo",
+        ]
+    );
+
+    let command = rustc_command_with(|_| None);
+    assert_eq!(args(&command), ["rustc"]);
+}
+
+#[test]
+fn is_host_test() {
+    let host = Some("x86_64-unknown-linux-gnu");
+
+    assert!(is_host(host, host));
+    assert!(!is_host(host, Some("aarch64-linux-android")));
+    assert!(is_host(None, None));
+}
+
+#[test]
+fn target_runner_test() {
+    assert_eq!(
+        target_runner_var("aarch64-unknown-linux-gnu"),
+        "CARGO_TARGET_AARCH64_UNKNOWN_LINUX_GNU_RUNNER"
+    );
+    assert_eq!(
+        target_runner_var("thumbv8m.main-none-eabi"),
+        "CARGO_TARGET_THUMBV8M_MAIN_NONE_EABI_RUNNER"
+    );
+
+    assert_eq!(
+        parse_runner("qemu-aarch64  -L /usr/aarch64-linux-gnu"),
+        vec!["qemu-aarch64", "-L", "/usr/aarch64-linux-gnu"]
+    );
+    assert!(parse_runner(" ").is_empty());
+}
+
+#[test]
+fn snapshot_format_test() {
+    let vars = BTreeMap::from([
+        ("MYLIB_DIR".to_string(), None),
+        ("PROFILE".to_string(), Some("debug".to_string())),
+        ("WEIRD".to_string(), Some("a\\nb\nc".to_string())),
+    ]);
+
+    let rendered = render_snapshot(&vars);
+
+    assert_eq!(rendered, "MYLIB_DIR\nPROFILE=debug\nWEIRD=a\\\\nb\\nc\n");
+    assert_eq!(parse_snapshot(&rendered), vars);
+}
+
+#[test]
+fn diff_snapshots_test() {
+    let old = BTreeMap::from([
+        ("PROFILE".to_string(), Some("debug".to_string())),
+        ("OPT_LEVEL".to_string(), Some("0".to_string())),
+        ("MYLIB_DIR".to_string(), None),
+    ]);
+    let new = BTreeMap::from([
+        ("PROFILE".to_string(), Some("release".to_string())),
+        ("MYLIB_DIR".to_string(), Some("/opt/mylib".to_string())),
+    ]);
+
+    let changes = diff_snapshots(&old, &new);
+
+    assert_eq!(
+        changes,
+        vec![
+            EnvChange {
+                name: "MYLIB_DIR".to_string(),
+                old: None,
+                new: Some("/opt/mylib".to_string()),
+            },
+            EnvChange {
+                name: "OPT_LEVEL".to_string(),
+                old: Some("0".to_string()),
+                new: None,
+            },
+            EnvChange {
+                name: "PROFILE".to_string(),
+                old: Some("debug".to_string()),
+                new: Some("release".to_string()),
+            },
+        ]
+    );
+    assert_eq!(
+        describe_change(&changes[2]),
+        "`PROFILE` changed since the last run: `debug` -> `release`"
+    );
+}
+
+#[test]
+fn snapshot_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    let out_dir = std::env::temp_dir().join("cargo_build_snapshot_test");
+    let _ = std::fs::remove_dir_all(&out_dir);
+    std::fs::create_dir_all(&out_dir).unwrap();
+
+    let tracked = BTreeSet::from(["MYLIB_DIR".to_string()]);
+    let vars = |profile: &str| BTreeMap::from([("PROFILE".to_string(), profile.to_string())]);
+
+    assert!(snapshot_in(&out_dir, &tracked, vars("debug")).is_empty());
+    assert!(snapshot_in(&out_dir, &tracked, vars("debug")).is_empty());
+    assert_eq!(snapshot_in(&out_dir, &tracked, vars("release")).len(), 1);
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(
+        out,
+        "cargo::warning=`PROFILE` changed since the last run: `debug` -> `release`\n"
+    );
+}
+
+#[test]
+fn track_after_poison_test() {
+    let _ = std::thread::spawn(|| {
+        let _tracked = TRACKED.lock();
+        panic!("poison `TRACKED`");
+    })
+    .join();
+    assert!(TRACKED.is_poisoned());
+
+    track("TRACKED_AFTER_POISON");
+
+    let tracked = TRACKED
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner);
+    assert!(tracked.contains("TRACKED_AFTER_POISON"));
+}
+
+struct TestWriteVecHandle(Arc<RwLock<Vec<u8>>>);
+
+impl TestWriteVecHandle {
+    fn new() -> Self {
+        Self(Arc::new(RwLock::new(Vec::new())))
+    }
+}
+
+impl Clone for TestWriteVecHandle {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl std::io::Write for TestWriteVecHandle {
+    fn write(&mut self, buf: &[u8]) -> std::result::Result<usize, std::io::Error> {
+        self.0
+            .write()
+            .expect("Unable to aquire Write lock")
+            .write(buf)
+    }
+
+    fn flush(&mut self) -> std::result::Result<(), std::io::Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,93 @@
+use std::fmt::{self, Display};
+use std::io;
+use std::path::PathBuf;
+
+use crate::ParseInstructionError;
+
+/// Error returned by fallible functions of this crate, e.g.
+/// [`try_rustc_link_lib`](crate::try_rustc_link_lib).
+///
+/// ```rust
+/// let err = cargo_build::try_rustc_env("VERSION", "1.0\n2.0").unwrap_err();
+///
+/// assert!(matches!(err, cargo_build::Error::InvalidInput(_)));
+/// assert_eq!(
+///     err.to_string(),
+///     "Env variable values containing newlines cannot be used in the build scripts"
+/// );
+/// ```
+///
+/// [`ParseInstructionError`] converts into `Error`, so build scripts can use `?` for both:
+///
+/// ```rust
+/// fn forward(line: &str) -> Result<(), cargo_build::Error> {
+///     let instruction: cargo_build::Instruction = line.parse()?;
+///     cargo_build::try_emit(instruction)
+/// }
+///
+/// assert!(forward("cargo::rustc-cfg=has_foo").is_ok());
+/// assert!(matches!(
+///     forward("rustc-cfg=has_foo"),
+///     Err(cargo_build::Error::Parse(cargo_build::ParseInstructionError::MissingPrefix))
+/// ));
+/// ```
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// Argument can't be passed to Cargo, e.g. it contains newline. Nothing was emitted.
+    InvalidInput(String),
+    /// Path can't be passed to Cargo because it is not valid UTF-8. Nothing was emitted.
+    NonUtf8Path(PathBuf),
+    /// Instruction is not supported by Cargo which runs the build script.
+    Unsupported {
+        /// `KEY` of the instruction, e.g. `error`.
+        key: String,
+        /// First Cargo version which supports the instruction, e.g. `1.84`.
+        since: &'static str,
+    },
+    /// Line is not a valid instruction.
+    Parse(ParseInstructionError),
+    /// Writing to the output stream failed.
+    Io(io::Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidInput(msg) => f.write_str(msg),
+            Self::NonUtf8Path(path) => write!(
+                f,
+                "Path `{}` is not valid UTF-8 and cannot be used in the build scripts",
+                path.display()
+            ),
+            Self::Unsupported { key, since } => write!(
+                f,
+                "Instruction `cargo::{key}` requires Cargo {since} or newer"
+            ),
+            Self::Parse(err) => write!(f, "{err}"),
+            Self::Io(err) => write!(f, "{}: {err}", crate::build_out::ERR_MSG),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Parse(err) => Some(err),
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<ParseInstructionError> for Error {
+    fn from(err: ParseInstructionError) -> Self {
+        Self::Parse(err)
+    }
+}
@@ -0,0 +1,39 @@
+use std::error::Error as _;
+use std::path::PathBuf;
+
+use crate::{Error, ParseInstructionError};
+
+#[test]
+fn error_display_test() {
+    assert_eq!(
+        Error::NonUtf8Path(PathBuf::from("libs")).to_string(),
+        "Path `libs` is not valid UTF-8 and cannot be used in the build scripts"
+    );
+    assert_eq!(
+        Error::Unsupported {
+            key: "error".to_string(),
+            since: "1.84",
+        }
+        .to_string(),
+        "Instruction `cargo::error` requires Cargo 1.84 or newer"
+    );
+    assert_eq!(
+        Error::from(ParseInstructionError::MissingEquals).to_string(),
+        "Instruction must have `KEY=VALUE` form"
+    );
+}
+
+#[test]
+fn error_source_test() {
+    let err = Error::from(std::io::Error::other("closed"));
+    assert_eq!(
+        err.source().map(|err| err.to_string()),
+        Some("closed".to_string())
+    );
+
+    let err = Error::from(ParseInstructionError::MissingPrefix);
+    assert!(err.source().is_some());
+
+    let err = Error::InvalidInput("bad".to_string());
+    assert!(err.source().is_none());
+}
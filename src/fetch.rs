@@ -0,0 +1,194 @@
+//! Downloading vendored sources and binaries into `OUT_DIR`.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use crate::sha256;
+use crate::{error, rerun_if_env_changed};
+
+/// Number of download attempts before giving up.
+const ATTEMPTS: u32 = 3;
+
+/// Downloads `url` into `OUT_DIR/out_name` and verifies its SHA-256 checksum.
+///
+/// - Download is skipped if the file already exists and matches the checksum.
+/// - Download is retried up to 3 times. `curl` must be available in `PATH`, which is the case on
+///   Windows 10+, macOS and most Linux distributions.
+/// - If `CARGO_NET_OFFLINE` is `true` nothing is downloaded.
+///
+/// Emits [`error`] with the reason and returns `None` if the file can't be downloaded or the checksum
+/// doesn't match. Partially downloaded or corrupted files are never left at `OUT_DIR/out_name`.
+///
+/// ```rust,no_run
+/// // build.rs
+/// let tarball = cargo_build::fetch::download(
+///     "https://github.com/madler/zlib/releases/download/v1.3.1/zlib-1.3.1.tar.gz",
+///     "9a93b2b7dfdac77ceba5a558a580e74667dd6fede4585b91eefb60f03b72df23",
+///     "zlib-1.3.1.tar.gz",
+/// );
+///
+/// let Some(tarball) = tarball else {
+///     return;
+/// };
+/// ```
+pub fn download(url: &str, sha256: &str, out_name: &str) -> Option<PathBuf> {
+    let out_dir = std::env::var_os("OUT_DIR")
+        .expect("OUT_DIR is not set. `fetch::download` can only be used inside the build scripts");
+
+    download_to(Path::new(&out_dir), url, sha256, out_name)
+}
+
+pub(crate) fn download_to(
+    out_dir: &Path,
+    url: &str,
+    sha256: &str,
+    out_name: &str,
+) -> Option<PathBuf> {
+    let sha256 = normalize_sha256(sha256);
+
+    let path = out_dir.join(out_name);
+    if matches!(checksum(&path, &sha256), Ok(Checksum::Matches)) {
+        return Some(path);
+    }
+
+    rerun_if_env_changed("CARGO_NET_OFFLINE");
+    if is_offline(std::env::var("CARGO_NET_OFFLINE").ok().as_deref()) {
+        error(&format!(
+            "Unable to download `{url}` because Cargo is in offline mode (CARGO_NET_OFFLINE). \
+             Download it manually to `{}` or disable offline mode",
+            path.display()
+        ));
+        return None;
+    }
+
+    let partial = out_dir.join(format!("{out_name}.part"));
+
+    let mut last_error = String::new();
+    for attempt in 1..=ATTEMPTS {
+        match curl(url, &partial) {
+            Ok(()) => {
+                last_error.clear();
+                break;
+            }
+            Err(err) => last_error = err,
+        }
+        if attempt != ATTEMPTS {
+            std::thread::sleep(Duration::from_secs(attempt.into()));
+        }
+    }
+    if !last_error.is_empty() {
+        let _ = std::fs::remove_file(&partial);
+        error(&format!(
+            "Unable to download `{url}` after {ATTEMPTS} attempts: {last_error}"
+        ));
+        return None;
+    }
+
+    match checksum(&partial, &sha256) {
+        Ok(Checksum::Matches) => {}
+        Ok(Checksum::Mismatch(actual)) => {
+            let _ = std::fs::remove_file(&partial);
+            error(&format!(
+                "Checksum mismatch for `{url}`: expected sha256 {sha256}, got {actual}"
+            ));
+            return None;
+        }
+        Err(err) => {
+            error(&format!("Unable to read `{}`: {err}", partial.display()));
+            return None;
+        }
+    }
+
+    if let Err(err) = std::fs::rename(&partial, &path) {
+        error(&format!("Unable to write `{}`: {err}", path.display()));
+        return None;
+    }
+    Some(path)
+}
+
+/// Verifies SHA-256 checksum of the file, e.g. vendored tarball or prebuilt binary.
+///
+/// Emits [`error`] with the file path, expected and actual checksums and returns `false` on mismatch.
+/// [`download`] performs the same verification for downloaded files.
+///
+/// ```rust,no_run
+/// // build.rs
+/// let verified = cargo_build::fetch::verify_sha256(
+///     "vendor/zlib-1.3.1.tar.gz",
+///     "9a93b2b7dfdac77ceba5a558a580e74667dd6fede4585b91eefb60f03b72df23",
+/// );
+///
+/// if !verified {
+///     return;
+/// }
+/// ```
+pub fn verify_sha256(path: impl AsRef<Path>, expected: &str) -> bool {
+    let path = path.as_ref();
+    let expected = normalize_sha256(expected);
+
+    match checksum(path, &expected) {
+        Ok(Checksum::Matches) => true,
+        Ok(Checksum::Mismatch(actual)) => {
+            error(&format!(
+                "Checksum mismatch for `{}`: expected sha256 {expected}, got {actual}",
+                path.display()
+            ));
+            false
+        }
+        Err(err) => {
+            error(&format!("Unable to read `{}`: {err}", path.display()));
+            false
+        }
+    }
+}
+
+enum Checksum {
+    Matches,
+    /// Contains actual checksum.
+    Mismatch(String),
+}
+
+fn checksum(path: &Path, expected: &str) -> std::io::Result<Checksum> {
+    let actual = sha256::file_hex_digest(path)?;
+
+    Ok(if actual == expected {
+        Checksum::Matches
+    } else {
+        Checksum::Mismatch(actual)
+    })
+}
+
+fn normalize_sha256(sha256: &str) -> String {
+    assert!(
+        sha256.len() == 64 && sha256.chars().all(|c| c.is_ascii_hexdigit()),
+        "SHA-256 checksum must be 64 hex digits"
+    );
+    sha256.to_ascii_lowercase()
+}
+
+/// Checks `CARGO_NET_OFFLINE` value.
+pub(crate) fn is_offline(value: Option<&str>) -> bool {
+    matches!(value, Some("true" | "1"))
+}
+
+fn curl(url: &str, out: &Path) -> Result<(), String> {
+    let output = Command::new("curl")
+        .args([
+            "--fail",
+            "--location",
+            "--silent",
+            "--show-error",
+            "--output",
+        ])
+        .arg(out)
+        .arg(url)
+        .output()
+        .map_err(|err| format!("unable to run `curl`: {err}"))?;
+
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
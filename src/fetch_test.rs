@@ -0,0 +1,138 @@
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use crate as cargo_build;
+use crate::fetch::{download_to, is_offline, verify_sha256};
+use crate::sha256::hex_digest;
+
+fn out_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+#[test]
+fn is_offline_test() {
+    assert!(is_offline(Some("true")));
+    assert!(is_offline(Some("1")));
+    assert!(!is_offline(Some("false")));
+    assert!(!is_offline(None));
+}
+
+#[test]
+fn download_existing_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    let out_dir = out_dir("cargo_build_download_existing_test");
+    std::fs::write(out_dir.join("blob.bin"), "blob").unwrap();
+
+    // Verified file is reused without touching the network
+    let path = download_to(
+        &out_dir,
+        "https://invalid.invalid/blob.bin",
+        &hex_digest(b"blob"),
+        "blob.bin",
+    );
+
+    assert_eq!(path, Some(out_dir.join("blob.bin")));
+    assert!(vec_out.0.read().unwrap().is_empty());
+}
+
+#[test]
+#[cfg(unix)]
+fn download_file_url_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    let out_dir = out_dir("cargo_build_download_file_url_test");
+    let source = out_dir.join("source.txt");
+    std::fs::write(&source, "vendored").unwrap();
+    let url = format!("file://{}", source.display());
+
+    let path = download_to(&out_dir, &url, &hex_digest(b"vendored"), "vendored.txt");
+
+    assert_eq!(path, Some(out_dir.join("vendored.txt")));
+    assert_eq!(
+        std::fs::read_to_string(out_dir.join("vendored.txt")).unwrap(),
+        "vendored"
+    );
+
+    let path = download_to(&out_dir, &url, &hex_digest(b"other"), "other.txt");
+
+    assert_eq!(path, None);
+    assert!(!out_dir.join("other.txt").exists());
+    assert!(!out_dir.join("other.txt.part").exists());
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert!(out.contains(&format!(
+        "cargo::error=Checksum mismatch for `{url}`: expected sha256 {}, got {}\n",
+        hex_digest(b"other"),
+        hex_digest(b"vendored")
+    )));
+}
+
+#[test]
+fn verify_sha256_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    let dir = out_dir("cargo_build_verify_sha256_test");
+    let blob = dir.join("blob.bin");
+    std::fs::write(&blob, "blob").unwrap();
+
+    assert!(verify_sha256(&blob, &hex_digest(b"blob")));
+    assert!(verify_sha256(&blob, &hex_digest(b"blob").to_uppercase()));
+    assert!(vec_out.0.read().unwrap().is_empty());
+
+    assert!(!verify_sha256(&blob, &hex_digest(b"other")));
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(
+        out,
+        format!(
+            "cargo::error=Checksum mismatch for `{}`: expected sha256 {}, got {}\n",
+            blob.display(),
+            hex_digest(b"other"),
+            hex_digest(b"blob")
+        )
+    );
+}
+
+#[test]
+#[should_panic]
+fn download_invalid_checksum_test() {
+    download_to(&std::env::temp_dir(), "https://invalid.invalid", "abc", "x");
+}
+
+struct TestWriteVecHandle(Arc<RwLock<Vec<u8>>>);
+
+impl TestWriteVecHandle {
+    fn new() -> Self {
+        Self(Arc::new(RwLock::new(Vec::new())))
+    }
+}
+
+impl Clone for TestWriteVecHandle {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl std::io::Write for TestWriteVecHandle {
+    fn write(&mut self, buf: &[u8]) -> std::result::Result<usize, std::io::Error> {
+        self.0
+            .write()
+            .expect("Unable to aquire Write lock")
+            .write(buf)
+    }
+
+    fn flush(&mut self) -> std::result::Result<(), std::io::Error> {
+        Ok(())
+    }
+}
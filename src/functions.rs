@@ -1,9 +1,19 @@
-use std::io::Write;
+use std::borrow::Cow;
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::panic::Location;
 use std::path::{Path, PathBuf};
-
-use super::build_out::CARGO_BUILD_OUT;
-
-const ERR_MSG: &str = "Unable to write to CARGO_BUILD_OUT";
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Once;
+
+use super::build_out::{self, write_instruction, LineBuf};
+use super::error::Error;
+use super::instruction::Instruction;
+use super::link::{LinkKind, LinkLib, LinkSearch, SearchKind};
+use super::parse::{self, Line};
+use super::paths;
+use super::session;
+use super::toolchain::{self, Version};
 
 /// Tells Cargo to re-run the build script **ONLY** if file or directory with given name changes.
 ///
@@ -40,6 +50,14 @@ const ERR_MSG: &str = "Unable to write to CARGO_BUILD_OUT";
 /// the script will be re-run after it has been recompiled. Otherwise, specifying build.rs is redundant
 /// and unnecessary.
 ///
+/// On Windows `\\?\` and `\\?\UNC\` prefixes are stripped when the path means the same thing without
+/// them. Paths exceeding 260 characters emit a [`warning`].
+///
+/// On macOS paths are converted to Unicode NFC form when `nfc` feature is enabled. HFS+ and APFS may
+/// report NFD names, which causes spurious rebuilds or missed changes.
+///
+/// Panics if path is not valid UTF-8: Cargo ignores such lines of build script output.
+///
 /// <https://doc.rust-lang.org/cargo/reference/build-scripts.html#rerun-if-changed>
 #[allow(private_bounds)]
 pub fn rerun_if_changed<I>(file_paths: impl Into<VarArg<I>>)
@@ -47,20 +65,27 @@ where
     I: IntoIterator,
     I::Item: AsRef<Path>,
 {
+    or_panic(try_rerun_if_changed(file_paths));
+}
+
+/// Fallible version of [`rerun_if_changed`], returns [`Error`] instead of panicking.
+#[allow(private_bounds)]
+pub fn try_rerun_if_changed<I>(file_paths: impl Into<VarArg<I>>) -> Result<(), Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    let mut instructions = Vec::new();
     for file_path in file_paths.into() {
         let path = file_path.as_ref();
 
-        if let Some(path) = path.to_str() {
-            assert!(
-                !path.contains('\n'),
-                "Paths containing newlines cannot be used in the build scripts"
-            )
-        }
-        let path = path.display();
-
-        CARGO_BUILD_OUT
-            .with_borrow_mut(|out| writeln!(out, "cargo::rerun-if-changed={path}").expect(ERR_MSG));
+        let path = check_path(
+            path,
+            "Paths containing newlines cannot be used in the build scripts",
+        )?;
+        instructions.push(Instruction::RerunIfChanged(path.into_owned()));
     }
+    try_emit_all(instructions)
 }
 
 /// Tells Cargo to re-run the build script if environment variable with the given name has changed.
@@ -90,6 +115,8 @@ where
 /// and trigger rebuilds. `rerun-if-env-changed` is no longer needed for variables already
 /// referenced by these macros.
 ///
+/// Panics if variable name is empty or contains `=` or NUL characters, which no env variable can have.
+///
 /// <https://doc.rust-lang.org/cargo/reference/build-scripts.html#rerun-if-env-changed>
 #[allow(private_bounds)]
 pub fn rerun_if_env_changed<I>(env_vars: impl Into<VarArg<I>>)
@@ -97,18 +124,34 @@ where
     I: IntoIterator,
     I::Item: AsRef<str>,
 {
+    or_panic(try_rerun_if_env_changed(env_vars));
+}
+
+/// Fallible version of [`rerun_if_env_changed`], returns [`Error`] instead of panicking.
+#[allow(private_bounds)]
+pub fn try_rerun_if_env_changed<I>(env_vars: impl Into<VarArg<I>>) -> Result<(), Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut vars = Vec::new();
     for env_var in env_vars.into() {
         let env_var: &str = env_var.as_ref();
 
-        assert!(
-            !env_var.contains('\n'),
-            "Env var names containing newlines cannot be used in the build scripts"
-        );
+        let env_var = check_newlines(
+            env_var,
+            "Env var names containing newlines cannot be used in the build scripts",
+        )?;
+        check_env_var_name(&env_var)?;
 
-        CARGO_BUILD_OUT.with_borrow_mut(|out| {
-            writeln!(out, "cargo::rerun-if-env-changed={env_var}").expect(ERR_MSG)
-        });
+        vars.push(env_var.to_string());
     }
+
+    for env_var in vars {
+        crate::env::track(&env_var);
+        try_emit_instruction(Instruction::RerunIfEnvChanged(env_var))?;
+    }
+    Ok(())
 }
 
 /// Passes custom flags to a linker for benchmarks, binaries, `cdylib` crates, examples, and tests.
@@ -141,24 +184,35 @@ where
 /// It is useful to set the shared library version or linker script.
 ///
 /// <https://doc.rust-lang.org/cargo/reference/build-scripts.html#rustc-link-arg>
+#[track_caller]
 #[allow(private_bounds)]
 pub fn rustc_link_arg<I>(linker_flags: impl Into<VarArg<I>>)
 where
     I: IntoIterator,
     I::Item: AsRef<str>,
 {
-    for flag in linker_flags.into() {
-        let flag = flag.as_ref();
-
-        assert!(
-            !flag.contains('\n'),
-            "Compiler flags containing newlines cannot be used in the build scripts"
-        );
+    or_panic(try_rustc_link_arg(linker_flags));
+}
 
-        CARGO_BUILD_OUT.with_borrow_mut(|out| {
-            writeln!(out, "cargo::rustc-link-arg={flag}").expect(ERR_MSG);
-        });
+/// Fallible version of [`rustc_link_arg`], returns [`Error`] instead of panicking.
+#[track_caller]
+#[allow(private_bounds)]
+pub fn try_rustc_link_arg<I>(linker_flags: impl Into<VarArg<I>>) -> Result<(), Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut instructions = Vec::new();
+    for flag in linker_flags.into() {
+        let flags = split_newlines(
+            flag.as_ref(),
+            "Compiler flags containing newlines cannot be used in the build scripts",
+        )?;
+        for flag in flags {
+            instructions.push(Instruction::RustcLinkArg(flag.into_owned()));
+        }
     }
+    try_emit_all(instructions)
 }
 
 /// Passes custom flags to a linker for `cdylib` crates.
@@ -182,24 +236,35 @@ where
 /// It is useful to set the shared library version or linker script.
 ///
 /// <https://doc.rust-lang.org/cargo/reference/build-scripts.html#rustc-cdylib-link-arg>
+#[track_caller]
 #[allow(private_bounds)]
 pub fn rustc_link_arg_cdylib<I>(linker_flags: impl Into<VarArg<I>>)
 where
     I: IntoIterator,
     I::Item: AsRef<str>,
 {
-    for flag in linker_flags.into() {
-        let flag = flag.as_ref();
-
-        assert!(
-            !flag.contains('\n'),
-            "Compiler flags containing newlines cannot be used in the build scripts"
-        );
+    or_panic(try_rustc_link_arg_cdylib(linker_flags));
+}
 
-        CARGO_BUILD_OUT.with_borrow_mut(|out| {
-            writeln!(out, "cargo::rustc-link-arg-cdylib={flag}").expect(ERR_MSG)
-        });
+/// Fallible version of [`rustc_link_arg_cdylib`], returns [`Error`] instead of panicking.
+#[track_caller]
+#[allow(private_bounds)]
+pub fn try_rustc_link_arg_cdylib<I>(linker_flags: impl Into<VarArg<I>>) -> Result<(), Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut instructions = Vec::new();
+    for flag in linker_flags.into() {
+        let flags = split_newlines(
+            flag.as_ref(),
+            "Compiler flags containing newlines cannot be used in the build scripts",
+        )?;
+        for flag in flags {
+            instructions.push(Instruction::RustcLinkArgCdylib(flag.into_owned()));
+        }
     }
+    try_emit_all(instructions)
 }
 
 /// Passes custom flags to a linker for specific binary name.
@@ -226,28 +291,43 @@ where
 /// specific. It is useful to set the shared library version or linker script.
 ///
 /// <https://doc.rust-lang.org/cargo/reference/build-scripts.html#rustc-bin-link-arg>
+#[track_caller]
 #[allow(private_bounds)]
 pub fn rustc_link_arg_bin<I>(bin: &str, linker_flags: impl Into<VarArg<I>>)
 where
     I: IntoIterator,
     I::Item: AsRef<str>,
 {
+    or_panic(try_rustc_link_arg_bin(bin, linker_flags));
+}
+
+/// Fallible version of [`rustc_link_arg_bin`], returns [`Error`] instead of panicking.
+#[track_caller]
+#[allow(private_bounds)]
+pub fn try_rustc_link_arg_bin<I>(bin: &str, linker_flags: impl Into<VarArg<I>>) -> Result<(), Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let bin = check_newlines(
+        bin,
+        "Binary names containing newlines cannot be used in the build scripts",
+    )?;
+
+    let mut instructions = Vec::new();
     for flag in linker_flags.into() {
-        CARGO_BUILD_OUT.with_borrow_mut(|out| {
-            let flag = flag.as_ref();
-
-            assert!(
-                !bin.contains('\n'),
-                "Binary names containing newlines cannot be used in the build scripts"
-            );
-            assert!(
-                !flag.contains('\n'),
-                "Compiler flags containing newlines cannot be used in the build scripts"
-            );
-
-            writeln!(out, "cargo::rustc-link-arg-bin={bin}={flag}").expect(ERR_MSG)
-        });
+        let flags = split_newlines(
+            flag.as_ref(),
+            "Compiler flags containing newlines cannot be used in the build scripts",
+        )?;
+        for flag in flags {
+            instructions.push(Instruction::RustcLinkArgBin {
+                bin: bin.to_string(),
+                flag: flag.into_owned(),
+            });
+        }
     }
+    try_emit_all(instructions)
 }
 
 /// Passes custom flags to a linker for binaries.
@@ -272,24 +352,35 @@ where
 /// specific. It is useful to set the shared library version or linker script.
 ///
 /// <https://doc.rust-lang.org/cargo/reference/build-scripts.html#rustc-link-arg-bins>
+#[track_caller]
 #[allow(private_bounds)]
 pub fn rustc_link_arg_bins<I>(linker_flags: impl Into<VarArg<I>>)
 where
     I: IntoIterator,
     I::Item: AsRef<str>,
 {
-    for flag in linker_flags.into() {
-        let flag = flag.as_ref();
-
-        assert!(
-            !flag.contains('\n'),
-            "Compiler flags containing newlines cannot be used in the build scripts"
-        );
+    or_panic(try_rustc_link_arg_bins(linker_flags));
+}
 
-        CARGO_BUILD_OUT.with_borrow_mut(|out| {
-            writeln!(out, "cargo::rustc-link-arg-bins={flag}").expect(ERR_MSG)
-        });
+/// Fallible version of [`rustc_link_arg_bins`], returns [`Error`] instead of panicking.
+#[track_caller]
+#[allow(private_bounds)]
+pub fn try_rustc_link_arg_bins<I>(linker_flags: impl Into<VarArg<I>>) -> Result<(), Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut instructions = Vec::new();
+    for flag in linker_flags.into() {
+        let flags = split_newlines(
+            flag.as_ref(),
+            "Compiler flags containing newlines cannot be used in the build scripts",
+        )?;
+        for flag in flags {
+            instructions.push(Instruction::RustcLinkArgBins(flag.into_owned()));
+        }
     }
+    try_emit_all(instructions)
 }
 
 /// Passes custom flags to a linker for tests.
@@ -313,24 +404,35 @@ where
 /// specific. It is useful to set the shared library version or linker script.
 ///
 /// <https://doc.rust-lang.org/cargo/reference/build-scripts.html#rustc-link-arg-tests>
+#[track_caller]
 #[allow(private_bounds)]
 pub fn rustc_link_arg_tests<I>(linker_flags: impl Into<VarArg<I>>)
 where
     I: IntoIterator,
     I::Item: AsRef<str>,
 {
-    for flag in linker_flags.into() {
-        let flag = flag.as_ref();
-
-        assert!(
-            !flag.contains('\n'),
-            "Compiler flags containing newlines cannot be used in the build scripts"
-        );
+    or_panic(try_rustc_link_arg_tests(linker_flags));
+}
 
-        CARGO_BUILD_OUT.with_borrow_mut(|out| {
-            writeln!(out, "cargo::rustc-link-arg-tests={flag}").expect(ERR_MSG)
-        });
+/// Fallible version of [`rustc_link_arg_tests`], returns [`Error`] instead of panicking.
+#[track_caller]
+#[allow(private_bounds)]
+pub fn try_rustc_link_arg_tests<I>(linker_flags: impl Into<VarArg<I>>) -> Result<(), Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut instructions = Vec::new();
+    for flag in linker_flags.into() {
+        let flags = split_newlines(
+            flag.as_ref(),
+            "Compiler flags containing newlines cannot be used in the build scripts",
+        )?;
+        for flag in flags {
+            instructions.push(Instruction::RustcLinkArgTests(flag.into_owned()));
+        }
     }
+    try_emit_all(instructions)
 }
 
 /// Passes custom flags to a linker for examples.
@@ -354,24 +456,35 @@ where
 /// specific. It is useful to set the shared library version or linker script.
 ///
 /// <https://doc.rust-lang.org/cargo/reference/build-scripts.html#rustc-link-arg-examples>
+#[track_caller]
 #[allow(private_bounds)]
 pub fn rustc_link_arg_examples<I>(linker_flags: impl Into<VarArg<I>>)
 where
     I: IntoIterator,
     I::Item: AsRef<str>,
 {
-    for flag in linker_flags.into() {
-        let flag = flag.as_ref();
-
-        assert!(
-            !flag.contains('\n'),
-            "Compiler flags containing newlines cannot be used in the build scripts"
-        );
+    or_panic(try_rustc_link_arg_examples(linker_flags));
+}
 
-        CARGO_BUILD_OUT.with_borrow_mut(|out| {
-            writeln!(out, "cargo::rustc-link-arg-examples={flag}").expect(ERR_MSG)
-        });
+/// Fallible version of [`rustc_link_arg_examples`], returns [`Error`] instead of panicking.
+#[track_caller]
+#[allow(private_bounds)]
+pub fn try_rustc_link_arg_examples<I>(linker_flags: impl Into<VarArg<I>>) -> Result<(), Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut instructions = Vec::new();
+    for flag in linker_flags.into() {
+        let flags = split_newlines(
+            flag.as_ref(),
+            "Compiler flags containing newlines cannot be used in the build scripts",
+        )?;
+        for flag in flags {
+            instructions.push(Instruction::RustcLinkArgExamples(flag.into_owned()));
+        }
     }
+    try_emit_all(instructions)
 }
 
 /// Passes custom flags to a linker for benches.
@@ -395,24 +508,35 @@ where
 /// specific. It is useful to set the shared library version or linker script.
 ///
 /// <https://doc.rust-lang.org/cargo/reference/build-scripts.html#rustc-link-arg-benches>
+#[track_caller]
 #[allow(private_bounds)]
 pub fn rustc_link_arg_benches<I>(linker_flags: impl Into<VarArg<I>>)
 where
     I: IntoIterator,
     I::Item: AsRef<str>,
 {
-    for flag in linker_flags.into() {
-        let flag = flag.as_ref();
-
-        assert!(
-            !flag.contains('\n'),
-            "Compiler flags containing newlines cannot be used in the build scripts"
-        );
+    or_panic(try_rustc_link_arg_benches(linker_flags));
+}
 
-        CARGO_BUILD_OUT.with_borrow_mut(|out| {
-            writeln!(out, "cargo::rustc-link-arg-benches={flag}").expect(ERR_MSG)
-        });
+/// Fallible version of [`rustc_link_arg_benches`], returns [`Error`] instead of panicking.
+#[track_caller]
+#[allow(private_bounds)]
+pub fn try_rustc_link_arg_benches<I>(linker_flags: impl Into<VarArg<I>>) -> Result<(), Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut instructions = Vec::new();
+    for flag in linker_flags.into() {
+        let flags = split_newlines(
+            flag.as_ref(),
+            "Compiler flags containing newlines cannot be used in the build scripts",
+        )?;
+        for flag in flags {
+            instructions.push(Instruction::RustcLinkArgBenches(flag.into_owned()));
+        }
     }
+    try_emit_all(instructions)
 }
 
 /// Adds a library to link.
@@ -456,17 +580,28 @@ where
     I: IntoIterator,
     I::Item: AsRef<str>,
 {
+    or_panic(try_rustc_link_lib(lib_names));
+}
+
+/// Fallible version of [`rustc_link_lib`], returns [`Error`] instead of panicking.
+#[allow(private_bounds)]
+pub fn try_rustc_link_lib<I>(lib_names: impl Into<VarArg<I>>) -> Result<(), Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut instructions = Vec::new();
     for lib in lib_names.into() {
         let lib = lib.as_ref();
 
-        assert!(
-            !lib.contains('\n'),
-            "Library names containing newlines cannot be used in the build scripts"
-        );
+        let lib = check_newlines(
+            lib,
+            "Library names containing newlines cannot be used in the build scripts",
+        )?;
 
-        CARGO_BUILD_OUT
-            .with_borrow_mut(|out| writeln!(out, "cargo::rustc-link-lib={lib}").expect(ERR_MSG));
+        instructions.push(Instruction::new("rustc-link-lib", lib));
     }
+    try_emit_all(instructions)
 }
 
 /// [`rustc_link_lib`] alternative that automatically passes `dylib=`.
@@ -491,35 +626,49 @@ where
     I::Item: AsRef<str>,
     M: IntoIterator<Item = I::Item>,
 {
-    let modifiers: String = modifiers
+    or_panic(try_rustc_link_lib_dylib(modifiers, lib_names));
+}
+
+/// Fallible version of [`rustc_link_lib_dylib`], returns [`Error`] instead of panicking.
+#[allow(private_bounds)]
+pub fn try_rustc_link_lib_dylib<M, I>(
+    modifiers: impl Into<VarArg<M>>,
+    lib_names: impl Into<VarArg<I>>,
+) -> Result<(), Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+    M: IntoIterator<Item = I::Item>,
+{
+    let modifiers = modifiers
         .into()
         .into_iter()
-        .map(|e| e.as_ref().to_string())
-        .inspect(|e| {
-            assert!(
-                !e.contains('\n'),
-                "Link modifiers containing newlines cannot be used in build scripts"
-            );
+        .map(|modifier| {
+            check_newlines(
+                modifier.as_ref(),
+                "Link modifiers containing newlines cannot be used in build scripts",
+            )
+            .map(Cow::into_owned)
         })
-        .collect::<Vec<_>>()
-        .join(",");
+        .collect::<Result<Vec<_>, _>>()?;
 
+    let mut instructions = Vec::new();
     for lib in lib_names.into() {
         let lib = lib.as_ref();
 
-        assert!(
-            !lib.contains('\n'),
-            "Library names containing newlines cannot be used in the build scripts"
-        );
-
-        CARGO_BUILD_OUT.with_borrow_mut(|out| {
-            if !modifiers.is_empty() {
-                writeln!(out, "cargo::rustc-link-lib=dylib:{modifiers}={lib}").expect(ERR_MSG)
-            } else {
-                writeln!(out, "cargo::rustc-link-lib=dylib={lib}").expect(ERR_MSG)
-            }
-        });
+        let lib = check_newlines(
+            lib,
+            "Library names containing newlines cannot be used in the build scripts",
+        )?;
+
+        instructions.push(Instruction::RustcLinkLib(LinkLib {
+            kind: Some(LinkKind::Dylib),
+            modifiers: modifiers.clone(),
+            name: lib.to_string(),
+            rename: None,
+        }));
     }
+    try_emit_all(instructions)
 }
 
 /// [`rustc_link_lib`] alternative that automatically passes `static=`.
@@ -544,35 +693,49 @@ where
     I::Item: AsRef<str>,
     M: IntoIterator<Item = I::Item>,
 {
-    let modifiers: String = modifiers
+    or_panic(try_rustc_link_lib_static(modifiers, lib_names));
+}
+
+/// Fallible version of [`rustc_link_lib_static`], returns [`Error`] instead of panicking.
+#[allow(private_bounds)]
+pub fn try_rustc_link_lib_static<M, I>(
+    modifiers: impl Into<VarArg<M>>,
+    lib_names: impl Into<VarArg<I>>,
+) -> Result<(), Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+    M: IntoIterator<Item = I::Item>,
+{
+    let modifiers = modifiers
         .into()
         .into_iter()
-        .map(|e| e.as_ref().to_string())
-        .inspect(|e| {
-            assert!(
-                !e.contains('\n'),
-                "Link modifiers containing newlines cannot be used in build scripts"
-            );
+        .map(|modifier| {
+            check_newlines(
+                modifier.as_ref(),
+                "Link modifiers containing newlines cannot be used in build scripts",
+            )
+            .map(Cow::into_owned)
         })
-        .collect::<Vec<_>>()
-        .join(",");
+        .collect::<Result<Vec<_>, _>>()?;
 
+    let mut instructions = Vec::new();
     for lib in lib_names.into() {
         let lib = lib.as_ref();
 
-        assert!(
-            !lib.contains('\n'),
-            "Library names containing newlines cannot be used in the build scripts"
-        );
-
-        CARGO_BUILD_OUT.with_borrow_mut(|out| {
-            if !modifiers.is_empty() {
-                writeln!(out, "cargo::rustc-link-lib=static:{modifiers}={lib}").expect(ERR_MSG)
-            } else {
-                writeln!(out, "cargo::rustc-link-lib=static={lib}").expect(ERR_MSG)
-            }
-        });
+        let lib = check_newlines(
+            lib,
+            "Library names containing newlines cannot be used in the build scripts",
+        )?;
+
+        instructions.push(Instruction::RustcLinkLib(LinkLib {
+            kind: Some(LinkKind::Static),
+            modifiers: modifiers.clone(),
+            name: lib.to_string(),
+            rename: None,
+        }));
     }
+    try_emit_all(instructions)
 }
 
 /// [`rustc_link_lib`] alternative that automatically passes `framework=`.
@@ -599,35 +762,49 @@ pub fn rustc_link_lib_framework<M, I>(
     I::Item: AsRef<str>,
     M: IntoIterator<Item = I::Item>,
 {
-    let modifiers: String = modifiers
+    or_panic(try_rustc_link_lib_framework(modifiers, lib_names));
+}
+
+/// Fallible version of [`rustc_link_lib_framework`], returns [`Error`] instead of panicking.
+#[allow(private_bounds)]
+pub fn try_rustc_link_lib_framework<M, I>(
+    modifiers: impl Into<VarArg<M>>,
+    lib_names: impl Into<VarArg<I>>,
+) -> Result<(), Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+    M: IntoIterator<Item = I::Item>,
+{
+    let modifiers = modifiers
         .into()
         .into_iter()
-        .map(|e| e.as_ref().to_string())
-        .inspect(|e| {
-            assert!(
-                !e.contains('\n'),
-                "Link modifiers containing newlines cannot be used in build scripts"
-            );
+        .map(|modifier| {
+            check_newlines(
+                modifier.as_ref(),
+                "Link modifiers containing newlines cannot be used in build scripts",
+            )
+            .map(Cow::into_owned)
         })
-        .collect::<Vec<_>>()
-        .join(",");
+        .collect::<Result<Vec<_>, _>>()?;
 
+    let mut instructions = Vec::new();
     for lib in lib_names.into() {
         let lib = lib.as_ref();
 
-        assert!(
-            !lib.contains('\n'),
-            "Library names containing newlines cannot be used in the build scripts"
-        );
-
-        CARGO_BUILD_OUT.with_borrow_mut(|out| {
-            if !modifiers.is_empty() {
-                writeln!(out, "cargo::rustc-link-lib=framework:{modifiers}={lib}").expect(ERR_MSG)
-            } else {
-                writeln!(out, "cargo::rustc-link-lib=framework={lib}").expect(ERR_MSG)
-            }
-        });
+        let lib = check_newlines(
+            lib,
+            "Library names containing newlines cannot be used in the build scripts",
+        )?;
+
+        instructions.push(Instruction::RustcLinkLib(LinkLib {
+            kind: Some(LinkKind::Framework),
+            modifiers: modifiers.clone(),
+            name: lib.to_string(),
+            rename: None,
+        }));
     }
+    try_emit_all(instructions)
 }
 
 /// Adds a directory to the library search path.
@@ -657,6 +834,14 @@ pub fn rustc_link_lib_framework<M, I>(
 /// See more specific [`rustc_link_search_dependency`], [`rustc_link_search_crate`], [`rustc_link_search_native`],
 /// [`rustc_link_search_framework`], [`rustc_link_search_all`].
 ///
+/// On Windows `\\?\` and `\\?\UNC\` prefixes are stripped when the path means the same thing without
+/// them. Paths exceeding 260 characters emit a [`warning`].
+///
+/// On macOS paths are converted to Unicode NFC form when `nfc` feature is enabled. HFS+ and APFS may
+/// report NFD names, which causes spurious rebuilds or missed changes.
+///
+/// Panics if path is not valid UTF-8: Cargo ignores such lines of build script output.
+///
 /// <https://doc.rust-lang.org/cargo/reference/build-scripts.html#rustc-link-search>
 #[allow(private_bounds)]
 pub fn rustc_link_search<I>(lib_paths: impl Into<VarArg<I>>)
@@ -664,21 +849,31 @@ where
     I: IntoIterator,
     I::Item: AsRef<Path>,
 {
+    or_panic(try_rustc_link_search(lib_paths));
+}
+
+/// Fallible version of [`rustc_link_search`], returns [`Error`] instead of panicking.
+#[allow(private_bounds)]
+pub fn try_rustc_link_search<I>(lib_paths: impl Into<VarArg<I>>) -> Result<(), Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    let mut instructions = Vec::new();
     for path in lib_paths.into() {
         let path = path.as_ref();
 
-        if let Some(path) = path.to_str() {
-            assert!(
-                !path.contains('\n'),
-                "Library paths containing newlines cannot be used in the build scripts"
-            )
-        }
-        let path = path.display();
+        let path = check_path(
+            path,
+            "Library paths containing newlines cannot be used in the build scripts",
+        )?;
 
-        CARGO_BUILD_OUT.with_borrow_mut(|out| {
-            writeln!(out, "cargo::rustc-link-search={}", path).expect(ERR_MSG);
-        });
+        instructions.push(Instruction::new(
+            "rustc-link-search",
+            path.display().to_string(),
+        ));
     }
+    try_emit_all(instructions)
 }
 
 /// [`rustc_link_search`] alternative that automatically passes `native=`.
@@ -697,21 +892,30 @@ where
     I: IntoIterator,
     I::Item: AsRef<Path>,
 {
+    or_panic(try_rustc_link_search_native(lib_paths));
+}
+
+/// Fallible version of [`rustc_link_search_native`], returns [`Error`] instead of panicking.
+#[allow(private_bounds)]
+pub fn try_rustc_link_search_native<I>(lib_paths: impl Into<VarArg<I>>) -> Result<(), Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    let mut instructions = Vec::new();
     for path in lib_paths.into() {
         let path = path.as_ref();
 
-        if let Some(path) = path.to_str() {
-            assert!(
-                !path.contains('\n'),
-                "Library paths containing newlines cannot be used in the build scripts"
-            )
-        }
-        let path = path.display();
+        let path = check_path(
+            path,
+            "Library paths containing newlines cannot be used in the build scripts",
+        )?;
 
-        CARGO_BUILD_OUT.with_borrow_mut(|out| {
-            writeln!(out, "cargo::rustc-link-search=native={path}").expect(ERR_MSG);
-        });
+        instructions.push(Instruction::RustcLinkSearch(
+            LinkSearch::new(path.into_owned()).kind(SearchKind::Native),
+        ));
     }
+    try_emit_all(instructions)
 }
 
 /// [`rustc_link_search`] alternative that automatically passes `dependency=`.
@@ -730,21 +934,30 @@ where
     I: IntoIterator,
     I::Item: AsRef<Path>,
 {
+    or_panic(try_rustc_link_search_dependency(lib_paths));
+}
+
+/// Fallible version of [`rustc_link_search_dependency`], returns [`Error`] instead of panicking.
+#[allow(private_bounds)]
+pub fn try_rustc_link_search_dependency<I>(lib_paths: impl Into<VarArg<I>>) -> Result<(), Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    let mut instructions = Vec::new();
     for path in lib_paths.into() {
         let path = path.as_ref();
 
-        if let Some(path) = path.to_str() {
-            assert!(
-                !path.contains('\n'),
-                "Library paths containing newlines cannot be used in the build scripts"
-            )
-        }
-        let path = path.display();
+        let path = check_path(
+            path,
+            "Library paths containing newlines cannot be used in the build scripts",
+        )?;
 
-        CARGO_BUILD_OUT.with_borrow_mut(|out| {
-            writeln!(out, "cargo::rustc-link-search=dependency={path}").expect(ERR_MSG);
-        });
+        instructions.push(Instruction::RustcLinkSearch(
+            LinkSearch::new(path.into_owned()).kind(SearchKind::Dependency),
+        ));
     }
+    try_emit_all(instructions)
 }
 
 /// [`rustc_link_search`] alternative that automatically passes `crate=`.
@@ -763,21 +976,30 @@ where
     I: IntoIterator,
     I::Item: AsRef<Path>,
 {
+    or_panic(try_rustc_link_search_crate(lib_paths));
+}
+
+/// Fallible version of [`rustc_link_search_crate`], returns [`Error`] instead of panicking.
+#[allow(private_bounds)]
+pub fn try_rustc_link_search_crate<I>(lib_paths: impl Into<VarArg<I>>) -> Result<(), Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    let mut instructions = Vec::new();
     for path in lib_paths.into() {
         let path = path.as_ref();
 
-        if let Some(path) = path.to_str() {
-            assert!(
-                !path.contains('\n'),
-                "Library paths containing newlines cannot be used in the build scripts"
-            )
-        }
-        let path = path.display();
+        let path = check_path(
+            path,
+            "Library paths containing newlines cannot be used in the build scripts",
+        )?;
 
-        CARGO_BUILD_OUT.with_borrow_mut(|out| {
-            writeln!(out, "cargo::rustc-link-search=crate={path}").expect(ERR_MSG);
-        });
+        instructions.push(Instruction::RustcLinkSearch(
+            LinkSearch::new(path.into_owned()).kind(SearchKind::Crate),
+        ));
     }
+    try_emit_all(instructions)
 }
 
 /// [`rustc_link_search`] alternative that automatically passes `framework=`.
@@ -796,21 +1018,30 @@ where
     I: IntoIterator,
     I::Item: AsRef<Path>,
 {
+    or_panic(try_rustc_link_search_framework(lib_paths));
+}
+
+/// Fallible version of [`rustc_link_search_framework`], returns [`Error`] instead of panicking.
+#[allow(private_bounds)]
+pub fn try_rustc_link_search_framework<I>(lib_paths: impl Into<VarArg<I>>) -> Result<(), Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    let mut instructions = Vec::new();
     for path in lib_paths.into() {
         let path = path.as_ref();
 
-        if let Some(path) = path.to_str() {
-            assert!(
-                !path.contains('\n'),
-                "Library paths containing newlines cannot be used in the build scripts"
-            )
-        }
-        let path = path.display();
+        let path = check_path(
+            path,
+            "Library paths containing newlines cannot be used in the build scripts",
+        )?;
 
-        CARGO_BUILD_OUT.with_borrow_mut(|out| {
-            writeln!(out, "cargo::rustc-link-search=framework={path}").expect(ERR_MSG);
-        });
+        instructions.push(Instruction::RustcLinkSearch(
+            LinkSearch::new(path.into_owned()).kind(SearchKind::Framework),
+        ));
     }
+    try_emit_all(instructions)
 }
 
 /// [`rustc_link_search`] alternative that automatically passes `all=`.
@@ -829,21 +1060,30 @@ where
     I: IntoIterator,
     I::Item: AsRef<Path>,
 {
+    or_panic(try_rustc_link_search_all(lib_paths));
+}
+
+/// Fallible version of [`rustc_link_search_all`], returns [`Error`] instead of panicking.
+#[allow(private_bounds)]
+pub fn try_rustc_link_search_all<I>(lib_paths: impl Into<VarArg<I>>) -> Result<(), Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<Path>,
+{
+    let mut instructions = Vec::new();
     for path in lib_paths.into() {
         let path = path.as_ref();
 
-        if let Some(path) = path.to_str() {
-            assert!(
-                !path.contains('\n'),
-                "Library paths containing newlines cannot be used in the build scripts"
-            )
-        }
-        let path = path.display();
+        let path = check_path(
+            path,
+            "Library paths containing newlines cannot be used in the build scripts",
+        )?;
 
-        CARGO_BUILD_OUT.with_borrow_mut(|out| {
-            writeln!(out, "cargo::rustc-link-search=all={path}").expect(ERR_MSG)
-        });
+        instructions.push(Instruction::RustcLinkSearch(
+            LinkSearch::new(path.into_owned()).kind(SearchKind::All),
+        ));
     }
+    try_emit_all(instructions)
 }
 
 /// Passes certain flags to the compiler.
@@ -867,6 +1107,18 @@ where
 /// [`rustc_link_lib!` macro](`crate::rustc_link_lib!`) with compile-time checked
 /// formatting, variable number of arguments and improved syntax.
 ///
+/// Flags are validated before emission. Each flag should be `-l`/`-L` followed by its value, either
+/// attached (`-lz`) or separated by whitespace (`-l z`). Values may specify `KIND=` just like
+/// [`rustc_link_lib`] and [`rustc_link_search`] do:
+///
+/// ```rust
+/// cargo_build::rustc_flags("-l static=z -L native=libs");
+/// ```
+/// ```rust,should_panic
+/// // Panics: Only `-l` and `-L` flags are allowed
+/// cargo_build::rustc_flags("-O");
+/// ```
+///
 /// <https://doc.rust-lang.org/cargo/reference/build-scripts.html#rustc-flags>
 #[allow(private_bounds)]
 pub fn rustc_flags<I>(flags: impl Into<VarArg<I>>)
@@ -874,18 +1126,128 @@ where
     I: IntoIterator,
     I::Item: AsRef<str>,
 {
+    or_panic(try_rustc_flags(flags));
+}
+
+/// Fallible version of [`rustc_flags`], returns [`Error`] instead of panicking.
+#[allow(private_bounds)]
+pub fn try_rustc_flags<I>(flags: impl Into<VarArg<I>>) -> Result<(), Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut instructions = Vec::new();
     for flag in flags.into() {
-        let flag = flag.as_ref();
+        let flags = split_newlines(
+            flag.as_ref(),
+            "Rustc flags containing newlines cannot be used in the build scripts",
+        )?;
+        for flag in flags {
+            if let Err(err) = parse_rustc_flags(&flag) {
+                ensure(false, format_args!("Invalid rustc flags `{flag}`: {err}"))?;
+            }
+            instructions.push(Instruction::RustcFlags(flag.into_owned()));
+        }
+    }
+    try_emit_all(instructions)
+}
+
+/// [`rustc_flags`] alternative that renders `-l`/`-L` flags from typed [`LinkLib`] and [`LinkSearch`] values.
+///
+/// #### Prefer [`rustc_link_lib`] and [`rustc_link_search`] unless `rustc-flags` instruction is specifically needed.
+///
+/// ```rust
+/// use cargo_build::{LinkKind, LinkLib, LinkSearch, SearchKind};
+///
+/// cargo_build::rustc_flags_from(
+///     &[LinkLib::new("z").kind(LinkKind::Static), LinkLib::new("ffi")],
+///     &[LinkSearch::new("libs").kind(SearchKind::Native)],
+/// );
+/// // cargo::rustc-flags=-L native=libs -l static=z -l ffi
+/// ```
+///
+/// All flags are emitted as single instruction. Search paths are rendered before libraries.
+/// Nothing is emitted when both slices are empty.
+///
+/// <https://doc.rust-lang.org/cargo/reference/build-scripts.html#rustc-flags>
+pub fn rustc_flags_from(libs: &[LinkLib], paths: &[LinkSearch]) {
+    or_panic(try_rustc_flags_from(libs, paths));
+}
 
-        assert!(
-            !flag.contains('\n'),
-            "Rustc flags containing newlines cannot be used in the build scripts"
-        );
+/// Fallible version of [`rustc_flags_from`], returns [`Error`] instead of panicking.
+pub fn try_rustc_flags_from(libs: &[LinkLib], paths: &[LinkSearch]) -> Result<(), Error> {
+    if libs.is_empty() && paths.is_empty() {
+        return Ok(());
+    }
 
-        CARGO_BUILD_OUT.with_borrow_mut(|out| {
-            writeln!(out, "cargo::rustc-flags={flag}").expect(ERR_MSG);
-        });
+    const MSG: &str = "Rustc flags containing whitespace cannot be used in the build scripts";
+    let has_whitespace = |s: &str| s.contains(char::is_whitespace);
+
+    for lib in libs {
+        check_link_lib(lib)?;
+        ensure(
+            !has_whitespace(&lib.name)
+                && !lib.rename.as_deref().is_some_and(has_whitespace)
+                && !lib.modifiers.iter().any(|m| has_whitespace(m)),
+            MSG,
+        )?;
+    }
+    for path in paths {
+        ensure(!has_whitespace(ensure_utf8(&path.path)?), MSG)?;
+    }
+
+    let paths = paths.iter().map(|path| format!("-L {path}"));
+    let libs = libs.iter().map(|lib| format!("-l {lib}"));
+
+    try_rustc_flags(paths.chain(libs).collect::<Vec<_>>().join(" "))
+}
+
+/// Rejects linking modifiers of [`LinkLib`] without `KIND`, which can't be rendered.
+fn check_link_lib(lib: &LinkLib) -> Result<(), Error> {
+    ensure(
+        lib.kind.is_some() || lib.modifiers.is_empty(),
+        format_args!(
+            "Linking modifiers of library `{}` require library KIND",
+            lib.name
+        ),
+    )
+}
+
+/// Splits `rustc-flags` value the same way Cargo does and validates every flag.
+pub(crate) fn parse_rustc_flags(flags: &str) -> Result<(), String> {
+    let mut tokens = flags.split_whitespace();
+
+    while let Some(token) = tokens.next() {
+        let (flag, value) = match token.get(..2) {
+            Some(flag @ ("-l" | "-L")) if token.len() > 2 => (flag, &token[2..]),
+            Some(flag @ ("-l" | "-L")) => match tokens.next() {
+                Some(value) => (flag, value),
+                None => return Err(format!("Flag `{flag}` is missing a value")),
+            },
+            _ => {
+                return Err(format!(
+                    "Only `-l` and `-L` flags are allowed, found `{token}`"
+                ))
+            }
+        };
+
+        let Some((kind, _)) = value.split_once('=') else {
+            continue;
+        };
+        if flag == "-l" {
+            let kind = kind.split_once(':').map_or(kind, |(kind, _)| kind);
+            if !matches!(kind, "dylib" | "static" | "framework") {
+                return Err(format!(
+                    "Unknown library kind `{kind}`, expected one of `dylib`, `static`, `framework`"
+                ));
+            }
+        } else if SearchKind::from_name(kind).is_none() {
+            return Err(format!(
+                "Unknown search path kind `{kind}`, expected one of `dependency`, `crate`, `native`, `framework`, `all`"
+            ));
+        }
     }
+    Ok(())
 }
 
 /// Enables custom compile-time `cfg` settings.
@@ -954,23 +1316,28 @@ where
 /// <https://doc.rust-lang.org/cargo/reference/build-scripts.html#rustc-cfg>
 #[allow(private_bounds)]
 pub fn rustc_cfg(cfg: impl Into<RustcCfg>) {
-    let RustcCfg { name, value } = cfg.into();
+    or_panic(try_rustc_cfg(cfg));
+}
 
-    assert!(
-        !name.contains('\n'),
-        "Cfg names containing newlines cannot be used in the build scripts"
-    );
+/// Fallible version of [`rustc_cfg`], returns [`Error`] instead of panicking.
+#[allow(private_bounds)]
+pub fn try_rustc_cfg(cfg: impl Into<RustcCfg>) -> Result<(), Error> {
+    let RustcCfg { name, value } = cfg.into();
 
-    CARGO_BUILD_OUT.with_borrow_mut(|out| match value {
-        None => writeln!(out, "cargo::rustc-cfg={name}").expect(ERR_MSG),
-        Some(value) => {
-            assert!(
-                !value.contains('\n'),
-                "Cfg values containing newlines cannot be used in the build scripts"
-            );
-            writeln!(out, "cargo::rustc-cfg={name}=\"{value}\"").expect(ERR_MSG);
-        }
-    });
+    let name = check_newlines_owned(
+        name,
+        "Cfg names containing newlines cannot be used in the build scripts",
+    )?;
+    let value = value
+        .map(|value| {
+            check_newlines_owned(
+                value,
+                "Cfg values containing newlines cannot be used in the build scripts",
+            )
+        })
+        .transpose()?;
+
+    try_emit_instruction(Instruction::RustcCfg { name, value })
 }
 
 /// Helper struct for [`rustc_cfg`] argument.
@@ -1116,32 +1483,37 @@ where
     I: IntoIterator,
     I::Item: AsRef<str>,
 {
-    assert!(
-        !name.contains('\n'),
-        "Cfg names containing newlines cannot be used in the build scripts"
-    );
+    or_panic(try_rustc_check_cfg(name, values));
+}
 
-    let values: String = values
+/// Fallible version of [`rustc_check_cfg`], returns [`Error`] instead of panicking.
+#[allow(private_bounds)]
+pub fn try_rustc_check_cfg<I>(name: &str, values: impl Into<VarArg<I>>) -> Result<(), Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let name = check_newlines(
+        name,
+        "Cfg names containing newlines cannot be used in the build scripts",
+    )?;
+
+    let values = values
         .into()
         .into_iter()
         .map(|value| {
-            let value = value.as_ref();
-            assert!(
-                !value.contains('\n'),
-                "Cfg values containing newlines cannot be used in the build scripts"
-            );
-            format!("\"{}\"", value)
+            check_newlines(
+                value.as_ref(),
+                "Cfg values containing newlines cannot be used in the build scripts",
+            )
+            .map(Cow::into_owned)
         })
-        .collect::<Vec<String>>()
-        .join(", ");
-
-    CARGO_BUILD_OUT.with_borrow_mut(|out| {
-        if values.is_empty() {
-            writeln!(out, "cargo::rustc-check-cfg=cfg({name})").expect(ERR_MSG);
-        } else {
-            writeln!(out, "cargo::rustc-check-cfg=cfg({name}, values({values}))").expect(ERR_MSG);
-        }
-    });
+        .collect::<Result<Vec<_>, _>>()?;
+
+    try_emit_instruction(Instruction::RustcCheckCfg {
+        name: name.to_string(),
+        values,
+    })
 }
 
 /// Define expected config names. Those names are used when checking the *reachable* cfg expressions
@@ -1162,18 +1534,153 @@ where
     I: IntoIterator,
     I::Item: AsRef<str>,
 {
+    or_panic(try_rustc_check_cfgs(cfg_names));
+}
+
+/// Fallible version of [`rustc_check_cfgs`], returns [`Error`] instead of panicking.
+#[allow(private_bounds)]
+pub fn try_rustc_check_cfgs<I>(cfg_names: impl Into<VarArg<I>>) -> Result<(), Error>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut instructions = Vec::new();
     for name in cfg_names.into() {
         let name = name.as_ref();
 
-        assert!(
-            !name.contains('\n'),
-            "Cfg names containing newlines cannot be used in the build scripts"
-        );
+        let name = check_newlines(
+            name,
+            "Cfg names containing newlines cannot be used in the build scripts",
+        )?;
 
-        CARGO_BUILD_OUT.with_borrow_mut(|out| {
-            writeln!(out, "cargo::rustc-check-cfg=cfg({name})").expect(ERR_MSG);
+        instructions.push(Instruction::RustcCheckCfg {
+            name: name.to_string(),
+            values: Vec::new(),
         });
     }
+    try_emit_all(instructions)
+}
+
+/// Emits `rustc-cfg=feature="NAME"` together with its `rustc-check-cfg` registration.
+///
+/// ```rust
+/// // build.rs
+/// if std::env::var("PROTOC").is_ok() {
+///     cargo_build::feature_cfg("extra-codegen");
+/// }
+/// ```
+/// ```rust,ignore
+/// // lib.rs
+/// #[cfg(feature = "extra-codegen")]
+/// mod generated;
+/// ```
+///
+/// #### This does NOT enable real Cargo feature.
+///
+/// The cfg only affects code of the current package. Optional dependencies and features of dependencies
+/// listed for `extra-codegen` in `Cargo.toml` stay disabled, and dependent crates never see it.
+/// Use a separate cfg name with [`rustc_cfg`] unless code must be shared with a real feature.
+///
+/// If `NAME` is an enabled Cargo feature (`CARGO_FEATURE_NAME` env variable is set) this function emits a
+/// [`warning`], because the cfg is already set by Cargo.
+///
+/// <https://doc.rust-lang.org/cargo/reference/build-scripts.html#rustc-cfg>
+pub fn feature_cfg(name: &str) {
+    or_panic(try_feature_cfg(name));
+}
+
+/// Fallible version of [`feature_cfg`], returns [`Error`] instead of panicking.
+pub fn try_feature_cfg(name: &str) -> Result<(), Error> {
+    ensure(
+        !name.contains('\n') && !name.contains('"'),
+        "Feature names containing newlines or quotes cannot be used in the build scripts",
+    )?;
+
+    let env_var = format!("CARGO_FEATURE_{}", name.to_uppercase().replace('-', "_"));
+    if std::env::var_os(&env_var).is_some() {
+        try_warning(&format!(
+            "`feature_cfg(\"{name}\")` is redundant: `{name}` is a real Cargo feature which is already enabled"
+        ))?;
+    }
+
+    try_rustc_check_cfg("feature", name)?;
+    try_rustc_cfg(("feature", name))
+}
+
+/// Registers custom cfg with `rustc-check-cfg` and sets it if target feature is enabled for the target.
+///
+/// Target features come from `CARGO_CFG_TARGET_FEATURE`, which reflects `-C target-feature` and
+/// `-C target-cpu` flags. Returns whether the cfg was set.
+///
+/// ```rust
+/// // build.rs
+/// cargo_build::emit_cfg_for_feature("avx2", "has_avx2");
+/// ```
+/// ```rust,ignore
+/// // lib.rs
+/// #[cfg(has_avx2)]
+/// mod avx2;
+/// ```
+pub fn emit_cfg_for_feature(target_feature: &str, cfg: &str) -> bool {
+    or_panic(try_emit_cfg_for_feature(target_feature, cfg))
+}
+
+/// Fallible version of [`emit_cfg_for_feature`], returns [`Error`] instead of panicking.
+pub fn try_emit_cfg_for_feature(target_feature: &str, cfg: &str) -> Result<bool, Error> {
+    try_rustc_check_cfgs(cfg)?;
+
+    let enabled = crate::env::has_target_feature(target_feature);
+    if enabled {
+        try_rustc_cfg(cfg)?;
+    }
+    Ok(enabled)
+}
+
+/// Registers every cfg of the table with `rustc-check-cfg` and sets the ones whose list of operating
+/// systems contains `CARGO_CFG_TARGET_OS`. Returns names of the cfgs which were set.
+///
+/// ```rust
+/// // build.rs
+/// cargo_build::emit_target_cfg_table(&[
+///     ("unix_sockets", &["linux", "macos", "freebsd"]),
+///     ("win_pipes", &["windows"]),
+/// ]);
+/// ```
+/// ```rust,ignore
+/// // lib.rs
+/// #[cfg(unix_sockets)]
+/// mod unix_sockets;
+/// ```
+///
+/// `CARGO_CFG_TARGET_OS` is only set for build scripts. Host operating system is used elsewhere.
+pub fn emit_target_cfg_table<'a>(table: &[(&'a str, &[&str])]) -> Vec<&'a str> {
+    or_panic(try_emit_target_cfg_table(table))
+}
+
+/// Fallible version of [`emit_target_cfg_table`], returns [`Error`] instead of panicking.
+pub fn try_emit_target_cfg_table<'a>(table: &[(&'a str, &[&str])]) -> Result<Vec<&'a str>, Error> {
+    let target_os =
+        std::env::var("CARGO_CFG_TARGET_OS").unwrap_or_else(|_| std::env::consts::OS.to_string());
+
+    try_emit_target_cfg_table_for(table, &target_os)
+}
+
+pub(crate) fn try_emit_target_cfg_table_for<'a>(
+    table: &[(&'a str, &[&str])],
+    target_os: &str,
+) -> Result<Vec<&'a str>, Error> {
+    try_rustc_check_cfgs(table.iter().map(|(cfg, _)| *cfg))?;
+
+    let enabled: Vec<&str> = table
+        .iter()
+        .filter(|(_, oses)| oses.contains(&target_os))
+        .map(|(cfg, _)| *cfg)
+        .collect();
+
+    for cfg in &enabled {
+        try_rustc_cfg(*cfg)?;
+    }
+    Ok(enabled)
 }
 
 /// Sets an environment variable.
@@ -1207,20 +1714,30 @@ where
 /// execution environment. Normally, these environment variables should only be checked at
 /// compile-time with the `env!` macro.
 ///
+/// Panics if `var` is empty or contains `=` or NUL characters: Cargo splits the instruction
+/// at the first `=`, so such names would set a different variable.
+///
 /// <https://doc.rust-lang.org/cargo/reference/build-scripts.html#rustc-env>
 pub fn rustc_env(var: &str, value: &str) {
-    assert!(
-        !var.contains('\n'),
-        "Env variables containing newlines cannot be used in the build scripts"
-    );
-    assert!(
-        !value.contains('\n'),
-        "Env variable values containing newlines cannot be used in the build scripts"
-    );
+    or_panic(try_rustc_env(var, value));
+}
 
-    CARGO_BUILD_OUT.with_borrow_mut(|out| {
-        writeln!(out, "cargo::rustc-env={var}={value}").expect(ERR_MSG);
-    });
+/// Fallible version of [`rustc_env`], returns [`Error`] instead of panicking.
+pub fn try_rustc_env(var: &str, value: &str) -> Result<(), Error> {
+    let var = check_newlines(
+        var,
+        "Env variables containing newlines cannot be used in the build scripts",
+    )?;
+    check_env_var_name(&var)?;
+    let value = check_newlines(
+        value,
+        "Env variable values containing newlines cannot be used in the build scripts",
+    )?;
+
+    try_emit_instruction(Instruction::RustcEnv {
+        var: var.to_string(),
+        value: value.to_string(),
+    })
 }
 
 /// Displays an error on the terminal.
@@ -1243,13 +1760,140 @@ pub fn rustc_env(var: &str, value: &str) {
 /// It may be better to return a `Result`, and allow the caller to decide if the error is fatal or not. The caller can then
 /// decide whether or not to display the `Err` variant using `cargo::error`.
 ///
+/// During active [`Session`](crate::Session) with [`ErrorPolicy::Exit`](crate::ErrorPolicy::Exit) the
+/// build script exits right after the error is emitted. See [`error_and_continue`] and [`error_and_exit`].
+///
 /// <https://doc.rust-lang.org/cargo/reference/build-scripts.html#cargo-error>
 pub fn error(msg: &str) {
-    CARGO_BUILD_OUT.with_borrow_mut(|out| {
-        for line in msg.lines() {
-            writeln!(out, "cargo::error={line}").expect(ERR_MSG);
+    emit_error(msg);
+
+    if let Some(code) = session::error_exit_code() {
+        session::exit(code);
+    }
+}
+
+/// Displays an error on the terminal and lets the build script continue regardless of
+/// [`ErrorPolicy`](crate::ErrorPolicy).
+///
+/// The build still fails once the build script has finished running.
+///
+/// ```rust
+/// cargo_build::error_and_continue("libfoo not found");
+/// ```
+pub fn error_and_continue(msg: &str) {
+    emit_error(msg);
+}
+
+/// Displays an error on the terminal and exits the build script with given exit code.
+///
+/// Active [`Session`](crate::Session) is finished and the output stream is flushed before exiting,
+/// so the error isn't masked by panics in the remaining steps.
+///
+/// ```rust,no_run
+/// let Ok(_sdk) = std::env::var("FOO_SDK") else {
+///     cargo_build::error_and_exit("FOO_SDK is not set", 1);
+/// };
+/// ```
+pub fn error_and_exit(msg: &str, code: i32) -> ! {
+    emit_error(msg);
+    session::exit(code)
+}
+
+pub(crate) fn emit_error(msg: &str) {
+    for line in msg.lines() {
+        emit_instruction(Instruction::Error(line.to_string()));
+    }
+    build_out::mirror_diagnostic("error", msg);
+}
+
+/// Set once any `cargo::error` instruction is emitted.
+static HAS_ERRORS: AtomicBool = AtomicBool::new(false);
+
+/// Returns `true` if [`error`] or any other function emitted `cargo::error` instruction, on any thread.
+///
+/// Cargo fails the build once the build script finishes, so remaining steps can often be skipped.
+///
+/// ```rust
+/// if std::env::var("FOO_SDK").is_err() {
+///     cargo_build::error_and_continue("FOO_SDK is not set");
+/// }
+///
+/// if !cargo_build::has_errors() {
+///     // build libfoo
+/// }
+/// ```
+pub fn has_errors() -> bool {
+    HAS_ERRORS.load(Ordering::Relaxed)
+}
+
+/// Flushes output stream and exits with code `1` if any `cargo::error` instruction was emitted.
+///
+/// Active [`Session`](crate::Session) is finished before exiting. Call at the end of the build script
+/// or after a step which may emit errors.
+///
+/// ```rust,no_run
+/// cargo_build::error_and_continue("libfoo not found");
+///
+/// cargo_build::finish(); // exits
+/// ```
+pub fn finish() {
+    if has_errors() {
+        session::exit(1);
+    }
+    flush();
+}
+
+/// Flushes output stream of `cargo-build` commands.
+///
+/// Output stream of the current thread and output stream set with
+/// [`build_out::set_global`](crate::build_out::set_global) are flushed, so file-backed streams are
+/// guaranteed to persist before the build script exits. Instructions held by
+/// [`build_out::buffered`](crate::build_out::buffered) are written when its guard is flushed or dropped.
+///
+/// ```rust
+/// let file = std::fs::File::create("target/cargo_build_flush_log.txt").unwrap();
+///
+/// cargo_build::build_out::set(std::io::BufWriter::new(file));
+///
+/// cargo_build::rustc_cfg("flushed");
+/// cargo_build::flush();
+///
+/// let out = std::fs::read_to_string("target/cargo_build_flush_log.txt").unwrap();
+///
+/// assert_eq!(out, "cargo::rustc-cfg=flushed\n");
+/// ```
+pub fn flush() {
+    or_panic(try_flush());
+}
+
+/// Fallible version of [`flush`], returns [`Error`] instead of panicking.
+pub fn try_flush() -> Result<(), Error> {
+    Ok(build_out::flush()?)
+}
+
+/// Emits instructions recorded with [`build_out::record_to`](crate::build_out::record_to) again.
+///
+/// Every instruction of the recording, in `cargo::` or legacy `cargo:` syntax, is emitted as if its
+/// function was called, so it goes through [`DirectiveSink`](crate::build_out::DirectiveSink), hooks
+/// and active [`Session`](crate::Session) and is written in current [`syntax`](crate::syntax). Other
+/// lines are written verbatim.
+///
+/// ```rust
+/// std::fs::write("target/cargo_build_replay.txt", "cargo::rustc-link-lib=foo\n").unwrap();
+///
+/// // cargo::rustc-link-lib=foo
+/// cargo_build::replay("target/cargo_build_replay.txt").unwrap();
+/// ```
+pub fn replay(path: impl AsRef<Path>) -> std::io::Result<()> {
+    let recording = std::fs::read_to_string(path)?;
+
+    for line in recording.lines() {
+        match parse::line(line) {
+            Line::Instruction(instruction) => emit_instruction(instruction),
+            _ => build_out::write_raw(format!("{line}\n").as_bytes()),
         }
-    });
+    }
+    Ok(())
 }
 
 /// Displays a warning on the terminal.
@@ -1271,11 +1915,16 @@ pub fn error(msg: &str) {
 ///
 /// <https://doc.rust-lang.org/cargo/reference/build-scripts.html#cargo-warning>
 pub fn warning(msg: &str) {
-    CARGO_BUILD_OUT.with_borrow_mut(|out| {
-        for line in msg.lines() {
-            writeln!(out, "cargo::warning={line}").expect(ERR_MSG);
-        }
-    });
+    or_panic(try_warning(msg));
+}
+
+/// Fallible version of [`warning`], returns [`Error`] instead of panicking.
+pub fn try_warning(msg: &str) -> Result<(), Error> {
+    for line in msg.lines() {
+        try_emit_instruction(Instruction::Warning(line.to_string()))?;
+    }
+    build_out::mirror_diagnostic("warning", msg);
+    Ok(())
 }
 
 /// Metadata, used by links scripts.
@@ -1317,22 +1966,704 @@ pub fn warning(msg: &str) {
 /// [Using another `sys` crate](https://doc.rust-lang.org/cargo/reference/build-script-examples.html#using-another-sys-crate)
 /// for an example of how this can be used.
 ///
-/// Note that metadata is only passed to immediate dependents, not transitive dependents.
+/// Note that metadata is only passed to immediate dependents, not transitive dependents. They can read it with
+/// [`deps::metadata`](crate::deps::metadata).
+///
+/// Keys become `DEP_<LINKS>_<KEY>` env variables of dependent packages. Characters other than ASCII letters,
+/// digits and `_` are replaced with `_`, so `include-dir` and `include.dir` are emitted as `include_dir`
+/// and dependent packages read `DEP_<LINKS>_INCLUDE_DIR`. Use [`strict_metadata_keys`] to reject keys which
+/// collide after this mangling. Panics if the key has non-ASCII characters or no letters and digits at all.
+///
+/// Metadata of the package without `links` key silently goes nowhere. When called from the build script,
+/// this function checks `CARGO_MANIFEST_LINKS` env variable set by Cargo and emits a [`warning`] once
+/// if the key is missing.
 ///
 /// <https://doc.rust-lang.org/cargo/reference/build-scripts.html#the-links-manifest-key>
 pub fn metadata(key: &str, value: &str) {
-    assert!(
-        !key.contains('\n'),
-        "Metadata keys containing newlines cannot be used in the build scripts"
-    );
-    assert!(
-        !value.contains('\n'),
-        "Metadata values containing newlines cannot be used in the build scripts"
-    );
+    or_panic(try_metadata(key, value));
+}
 
-    CARGO_BUILD_OUT.with_borrow_mut(|out| {
-        writeln!(out, "cargo::metadata={key}={value}").expect(ERR_MSG);
+/// Fallible version of [`metadata`], returns [`Error`] instead of panicking.
+pub fn try_metadata(key: &str, value: &str) -> Result<(), Error> {
+    let key = check_newlines(
+        key,
+        "Metadata keys containing newlines cannot be used in the build scripts",
+    )?;
+    let value = check_newlines(
+        value,
+        "Metadata values containing newlines cannot be used in the build scripts",
+    )?;
+
+    ensure(
+        key.is_ascii() && key.contains(|c: char| c.is_ascii_alphanumeric()),
+        format_args!(
+            "Metadata key `{key}` cannot be used in the build scripts: keys become `DEP_<LINKS>_<KEY>` env \
+            variables, so they should consist of ASCII letters, digits and `_`"
+        ),
+    )?;
+
+    let original = key;
+    let key = sanitize_metadata_key(&original);
+
+    STRICT_METADATA_KEYS.with_borrow_mut(|seen| {
+        let Some(seen) = seen else {
+            return Ok(());
+        };
+        let env_key = key.to_uppercase();
+        match seen.get(&env_key) {
+            Some(previous) => ensure(
+                previous == &*original,
+                format_args!("Metadata key `{original}` collides with `{previous}`: both become `DEP_<LINKS>_{env_key}`"),
+            ),
+            None => {
+                seen.insert(env_key, original.to_string());
+                Ok(())
+            }
+        }
+    })?;
+
+    static LINKS_CHECK: Once = Once::new();
+    LINKS_CHECK.call_once(|| {
+        if is_missing_links_key(|var| std::env::var_os(var).is_some()) {
+            warning("`cargo::metadata` is ignored because the package doesn't declare `links` key in Cargo.toml");
+        }
     });
+
+    try_emit_instruction(Instruction::Metadata {
+        key: key.to_string(),
+        value: value.to_string(),
+    })
+}
+
+/// What [`rerun_if_changed`] and `rustc_link_search*` functions do with paths which don't exist.
+///
+/// Set with [`set_missing_path_policy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MissingPathPolicy {
+    /// Paths are not checked. Default.
+    #[default]
+    Ignore,
+    /// [`warning`] is emitted for each missing path.
+    Warn,
+    /// Functions panic, `try_*` functions return [`Error::InvalidInput`].
+    Reject,
+}
+
+thread_local! {
+    static MISSING_PATH_POLICY: Cell<MissingPathPolicy> = const { Cell::new(MissingPathPolicy::Ignore) };
+}
+
+/// Sets [`MissingPathPolicy`] of the current thread.
+///
+/// Relative paths are resolved against `CARGO_MANIFEST_DIR`, like Cargo does. Catches typos, which
+/// otherwise make Cargo rerun the build script on every build or fail at link time.
+///
+/// ```rust
+/// use cargo_build::MissingPathPolicy;
+///
+/// cargo_build::set_missing_path_policy(MissingPathPolicy::Reject);
+///
+/// assert!(cargo_build::try_rerun_if_changed("Cargo.toml").is_ok());
+/// assert!(cargo_build::try_rerun_if_changed("Crago.toml").is_err());
+/// ```
+pub fn set_missing_path_policy(policy: MissingPathPolicy) {
+    MISSING_PATH_POLICY.set(policy);
+}
+
+/// Applies [`MissingPathPolicy`] to path of `key` instruction.
+fn check_exists(key: &str, path: &Path) -> Result<(), Error> {
+    let policy = MISSING_PATH_POLICY.get();
+    if policy == MissingPathPolicy::Ignore {
+        return Ok(());
+    }
+
+    let exists = match std::env::var_os("CARGO_MANIFEST_DIR") {
+        Some(manifest_dir) => Path::new(&manifest_dir).join(path).exists(),
+        None => path.exists(),
+    };
+    if exists {
+        return Ok(());
+    }
+
+    let msg = format!("`{key}` path `{}` doesn't exist", path.display());
+    match policy {
+        MissingPathPolicy::Warn => try_warning(&msg),
+        _ => Err(Error::InvalidInput(msg)),
+    }
+}
+
+/// What functions do with instructions which Cargo running the build script doesn't understand, e.g.
+/// `error` before Cargo 1.84. See [`Instruction::min_cargo_version`].
+///
+/// Set with [`set_unsupported_policy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum UnsupportedPolicy {
+    /// `error` is emitted as [`warning`], other instructions are skipped.
+    Downgrade,
+    /// Instructions are skipped.
+    Skip,
+    /// Instructions are emitted without checking Cargo version. Default.
+    #[default]
+    Emit,
+    /// Functions panic, `try_*` functions return [`Error::Unsupported`].
+    Reject,
+}
+
+thread_local! {
+    static UNSUPPORTED_POLICY: Cell<UnsupportedPolicy> = const { Cell::new(UnsupportedPolicy::Emit) };
+}
+
+/// Sets [`UnsupportedPolicy`] of the current thread.
+///
+/// Cargo version is only checked with policies other than [`UnsupportedPolicy::Emit`]. It is taken
+/// from `rust-version` of the package if it is new enough, otherwise it is detected with
+/// [`toolchain::cargo_version`](crate::toolchain::cargo_version). Instructions are assumed to be
+/// supported if Cargo version is unknown, e.g. outside of Cargo or if `$CARGO -V` fails.
+///
+/// ```rust
+/// use cargo_build::UnsupportedPolicy;
+///
+/// cargo_build::set_unsupported_policy(UnsupportedPolicy::Reject);
+///
+/// // Panics with Cargo older than 1.84
+/// cargo_build::error("libfoo not found");
+/// ```
+pub fn set_unsupported_policy(policy: UnsupportedPolicy) {
+    UNSUPPORTED_POLICY.set(policy);
+}
+
+/// Checks that Cargo running the build script is at least `since`.
+fn cargo_supports(since: Version) -> bool {
+    toolchain::rust_version().is_some_and(|version| version >= since)
+        || toolchain::cargo_version().is_none_or(|version| version >= since)
+}
+
+/// Applies [`UnsupportedPolicy`] to `instruction`. Returns `None` if it should be skipped.
+pub(crate) fn check_supported(
+    instruction: Instruction,
+    policy: UnsupportedPolicy,
+    supports: impl FnOnce(Version) -> bool,
+) -> Result<Option<Instruction>, Error> {
+    let Some(since) = instruction.min_cargo_version() else {
+        return Ok(Some(instruction));
+    };
+    if policy == UnsupportedPolicy::Emit
+        || supports(Version::parse(since).expect("Invalid version"))
+    {
+        return Ok(Some(instruction));
+    }
+    match (policy, instruction) {
+        (UnsupportedPolicy::Downgrade, Instruction::Error(msg)) => {
+            Ok(Some(Instruction::Warning(msg)))
+        }
+        (UnsupportedPolicy::Reject, instruction) => Err(Error::Unsupported {
+            key: instruction.key().to_string(),
+            since,
+        }),
+        _ => Ok(None),
+    }
+}
+
+thread_local! {
+    static STRICT_RERUN_PATHS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Enables or disables strict mode for [`rerun_if_changed`] paths on the current thread.
+///
+/// Paths inside `OUT_DIR`, `CARGO_TARGET_DIR` or `target` directory of the package change on every build,
+/// so Cargo reruns the build script on every build. [`rerun_if_changed`] emits a [`warning`] for such paths,
+/// in strict mode it panics instead.
+///
+/// ```rust
+/// cargo_build::strict_rerun_paths(true);
+///
+/// assert!(cargo_build::try_rerun_if_changed("target/debug/generated.rs").is_err());
+/// ```
+pub fn strict_rerun_paths(strict: bool) {
+    STRICT_RERUN_PATHS.set(strict);
+}
+
+/// Reports `rerun-if-changed` path inside one of [`BuildDirs`](paths::BuildDirs).
+fn check_build_dir(path: &Path) -> Result<(), Error> {
+    let build_dirs = paths::BuildDirs::from_env(|var| std::env::var_os(var));
+    let Some(dir) = build_dirs.find(path) else {
+        return Ok(());
+    };
+
+    let msg = format!(
+        "`rerun-if-changed` path `{}` is inside `{}` which changes on every build, so the build script reruns on every build",
+        path.display(),
+        dir.display()
+    );
+    if STRICT_RERUN_PATHS.get() {
+        Err(Error::InvalidInput(msg))
+    } else {
+        try_warning(&msg)
+    }
+}
+
+thread_local! {
+    /// Keys emitted by [`metadata`] by their `DEP_<LINKS>_<KEY>` form. `None` unless strict mode is enabled.
+    static STRICT_METADATA_KEYS: RefCell<Option<HashMap<String, String>>> = const { RefCell::new(None) };
+}
+
+/// Enables or disables strict mode for [`metadata`] keys on the current thread.
+///
+/// In strict mode [`metadata`] panics if the key collides with previously emitted key after mangling
+/// into `DEP_<LINKS>_<KEY>` env variable, e.g. `include-dir` and `INCLUDE_DIR`. Dependent packages would
+/// only see one of the values.
+///
+/// ```rust,should_panic
+/// cargo_build::strict_metadata_keys(true);
+///
+/// cargo_build::metadata("include-dir", "/opt/foo/include");
+/// cargo_build::metadata("INCLUDE_DIR", "/usr/include"); // panics
+/// ```
+pub fn strict_metadata_keys(strict: bool) {
+    STRICT_METADATA_KEYS.set(strict.then(HashMap::new));
+}
+
+/// Replaces characters other than ASCII letters, digits and `_` with `_`.
+pub(crate) fn sanitize_metadata_key(key: &str) -> Cow<'_, str> {
+    if key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return Cow::Borrowed(key);
+    }
+    Cow::Owned(
+        key.chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+            .collect(),
+    )
+}
+
+/// Checks that we are inside the build script (`OUT_DIR` and `TARGET` are set by Cargo) of the package
+/// without `links` key.
+pub(crate) fn is_missing_links_key(is_set: impl Fn(&str) -> bool) -> bool {
+    is_set("OUT_DIR") && is_set("TARGET") && !is_set("CARGO_MANIFEST_LINKS")
+}
+
+/// Emits arbitrary `cargo::KEY=VALUE` instruction.
+///
+/// #### Prefer specific functions such as [`rerun_if_changed`] or [`rustc_link_lib`] when they exist.
+///
+/// This function is an escape hatch for instructions which are not (yet) supported by this crate, e.g.
+/// instructions introduced by newer Cargo versions.
+///
+/// ```rust
+/// cargo_build::directive("rustc-link-arg", "-Wl,--cref");
+///
+/// let kind = "new-thing";
+/// cargo_build::directive(&format!("rustc-{kind}"), "value");
+/// ```
+///
+/// See also [`directive!` macro](`crate::directive!`) with compile-time checked formatting.
+///
+/// `KEY` may only contain ASCII letters, digits, `-` and `_`. Neither `KEY` nor `VALUE` may contain newlines.
+///
+/// <https://doc.rust-lang.org/cargo/reference/build-scripts.html#outputs-of-the-build-script>
+pub fn directive(key: &str, value: &str) {
+    or_panic(try_directive(key, value));
+}
+
+/// Fallible version of [`directive`], returns [`Error`] instead of panicking.
+pub fn try_directive(key: &str, value: &str) -> Result<(), Error> {
+    ensure(
+        !key.is_empty(),
+        "Empty instruction keys cannot be used in the build scripts",
+    )?;
+    ensure(
+        key.chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+        "Instruction keys may only contain ASCII letters, digits, `-` and `_`",
+    )?;
+    let value = check_newlines(
+        value,
+        "Instruction values containing newlines cannot be used in the build scripts",
+    )?;
+
+    try_emit_instruction(Instruction::new(key, value))
+}
+
+/// Emits [`Instruction`]. Every function of this crate builds [`Instruction`] and emits it the same way.
+///
+/// Useful to emit instructions stored as data, or built from typed values such as [`LinkLib`] which
+/// have no dedicated function.
+///
+/// ```rust
+/// use cargo_build::{Instruction, LinkKind, LinkLib};
+///
+/// cargo_build::emit(LinkLib::new("foo").kind(LinkKind::Static).rename("foo_v2"));
+///
+/// cargo_build::emit(Instruction::RustcEnv {
+///     var: "API_VERSION".to_string(),
+///     value: "3".to_string(),
+/// });
+/// ```
+///
+/// Instructions may not contain newlines. Keys of [`Instruction::Other`] may only contain ASCII letters,
+/// digits, `-` and `_`. Paths are normalized and checked the same way [`rerun_if_changed`] and
+/// [`rustc_link_search`] do.
+#[track_caller]
+pub fn emit(instruction: impl Into<Instruction>) {
+    or_panic(try_emit(instruction));
+}
+
+/// Fallible version of [`emit`], returns [`Error`] instead of panicking.
+#[track_caller]
+pub fn try_emit(instruction: impl Into<Instruction>) -> Result<(), Error> {
+    let instruction = instruction.into();
+
+    if let Instruction::Other { key, .. } = &instruction {
+        ensure(
+            !key.is_empty()
+                && key
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+            "Instruction keys may only contain ASCII letters, digits, `-` and `_`",
+        )?;
+    }
+    if let Instruction::RerunIfChanged(path)
+    | Instruction::RustcLinkSearch(LinkSearch { path, .. }) = &instruction
+    {
+        ensure_utf8(path)?;
+    }
+    if let Instruction::RustcLinkLib(lib) = &instruction {
+        check_link_lib(lib)?;
+    }
+    let instruction = match contains_newline(&instruction) {
+        true => {
+            let value = instruction.value();
+            let value = check_newlines(
+                &value,
+                "Instructions containing newlines cannot be used in the build scripts",
+            )?;
+            Instruction::new(instruction.key(), value)
+        }
+        false => instruction,
+    };
+
+    try_emit_all(vec![instruction])
+}
+
+/// Checks fields of the instruction for newlines without formatting it.
+fn contains_newline(instruction: &Instruction) -> bool {
+    let has = |s: &str| s.contains('\n');
+    match instruction {
+        Instruction::RerunIfChanged(path)
+        | Instruction::RustcLinkSearch(LinkSearch { path, .. }) => {
+            path.as_os_str().as_encoded_bytes().contains(&b'\n')
+        }
+        Instruction::RerunIfEnvChanged(value)
+        | Instruction::RustcLinkArg(value)
+        | Instruction::RustcLinkArgCdylib(value)
+        | Instruction::RustcLinkArgBins(value)
+        | Instruction::RustcLinkArgTests(value)
+        | Instruction::RustcLinkArgExamples(value)
+        | Instruction::RustcLinkArgBenches(value)
+        | Instruction::RustcFlags(value)
+        | Instruction::Warning(value)
+        | Instruction::Error(value) => has(value),
+        Instruction::RustcLinkArgBin { bin, flag } => has(bin) || has(flag),
+        Instruction::RustcLinkLib(lib) => {
+            has(&lib.name)
+                || lib.rename.as_deref().is_some_and(has)
+                || lib.modifiers.iter().any(|modifier| has(modifier))
+        }
+        Instruction::RustcCfg { name, value } => has(name) || value.as_deref().is_some_and(has),
+        Instruction::RustcCheckCfg { name, values } => {
+            has(name) || values.iter().any(|value| has(value))
+        }
+        Instruction::RustcEnv { var: key, value }
+        | Instruction::Metadata { key, value }
+        | Instruction::Other { key, value } => has(key) || has(value),
+    }
+}
+
+/// [`directive`] alternative which accepts [`format_args!`].
+///
+/// Value is formatted on the stack, values longer than 256 bytes fall back to the heap. It is still
+/// copied into [`Instruction`], which is validated and passed to sinks and hooks before it is written.
+///
+/// ```rust
+/// let version = 3;
+/// cargo_build::directive_fmt("rustc-env", format_args!("API_VERSION={version}"));
+/// ```
+///
+/// [`directive!` macro](`crate::directive!`) uses this function.
+pub fn directive_fmt(key: &str, value: std::fmt::Arguments<'_>) {
+    or_panic(try_directive_fmt(key, value));
+}
+
+/// Fallible version of [`directive_fmt`], returns [`Error`] instead of panicking, including when
+/// `Display` implementation of an argument fails.
+pub fn try_directive_fmt(key: &str, value: std::fmt::Arguments<'_>) -> Result<(), Error> {
+    if let Some(value) = value.as_str() {
+        return try_directive(key, value);
+    }
+    let mut line = LineBuf::new();
+    std::fmt::Write::write_fmt(&mut line, value)
+        .map_err(|_| Error::InvalidInput("Unable to format directive value".to_string()))?;
+
+    try_directive(key, line.as_str())
+}
+
+/// What functions do with arguments containing newlines, which would otherwise end the instruction
+/// early and turn the rest of the argument into a separate line of build script output.
+///
+/// Set with [`set_newline_policy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum NewlinePolicy {
+    /// Functions panic, `try_*` functions return [`Error::InvalidInput`]. Default.
+    #[default]
+    Reject,
+    /// Trailing newlines are removed, other newlines are replaced with spaces.
+    Strip,
+    /// Newlines are replaced with `\n` and carriage returns with `\r`.
+    Escape,
+    /// Linker flags and `rustc-flags` are split into an instruction per non-empty line, like
+    /// [`warning`] and [`error`] do. Other arguments are rejected.
+    Split,
+}
+
+thread_local! {
+    static NEWLINE_POLICY: Cell<NewlinePolicy> = const { Cell::new(NewlinePolicy::Reject) };
+}
+
+/// Sets [`NewlinePolicy`] of the current thread.
+///
+/// Useful when arguments come from env variables or output of external tools, where a stray trailing
+/// newline shouldn't fail the build.
+///
+/// ```rust
+/// use cargo_build::NewlinePolicy;
+///
+/// let version = "1.2.3\n"; // e.g. output of `pkg-config --modversion`
+///
+/// cargo_build::set_newline_policy(NewlinePolicy::Strip);
+/// cargo_build::rustc_env("FOO_VERSION", version); // cargo::rustc-env=FOO_VERSION=1.2.3
+///
+/// cargo_build::set_newline_policy(NewlinePolicy::Split);
+/// cargo_build::rustc_link_arg("-Wl,--as-needed\n-Wl,-z,now"); // emits 2 instructions
+///
+/// cargo_build::set_newline_policy(NewlinePolicy::Reject);
+/// assert!(cargo_build::try_rustc_env("FOO_VERSION", version).is_err());
+/// ```
+pub fn set_newline_policy(policy: NewlinePolicy) {
+    NEWLINE_POLICY.set(policy);
+}
+
+/// Applies [`NewlinePolicy`] to argument. [`NewlinePolicy::Split`] rejects it.
+fn check_newlines<'a>(value: &'a str, msg: &str) -> Result<Cow<'a, str>, Error> {
+    if !value.contains(['\n', '\r']) {
+        return Ok(Cow::Borrowed(value));
+    }
+    match NEWLINE_POLICY.get() {
+        NewlinePolicy::Strip => Ok(Cow::Owned(
+            value
+                .trim_end_matches(['\n', '\r'])
+                .lines()
+                .collect::<Vec<_>>()
+                .join(" "),
+        )),
+        NewlinePolicy::Escape => Ok(Cow::Owned(value.replace('\r', "\\r").replace('\n', "\\n"))),
+        NewlinePolicy::Reject | NewlinePolicy::Split if !value.contains('\n') => {
+            Ok(Cow::Borrowed(value))
+        }
+        NewlinePolicy::Reject | NewlinePolicy::Split => Err(Error::InvalidInput(msg.to_string())),
+    }
+}
+
+/// [`check_newlines`] for owned argument, which is only copied if newline policy changed it.
+fn check_newlines_owned(value: String, msg: &str) -> Result<String, Error> {
+    let checked = match check_newlines(&value, msg)? {
+        Cow::Owned(checked) => Some(checked),
+        Cow::Borrowed(_) => None,
+    };
+    Ok(checked.unwrap_or(value))
+}
+
+/// Applies [`NewlinePolicy`] to argument which can be split into an instruction per line.
+fn split_newlines<'a>(value: &'a str, msg: &str) -> Result<Vec<Cow<'a, str>>, Error> {
+    match NEWLINE_POLICY.get() {
+        NewlinePolicy::Split => Ok(value
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(Cow::Borrowed)
+            .collect()),
+        _ => Ok(vec![check_newlines(value, msg)?]),
+    }
+}
+
+/// Applies [`NewlinePolicy`] to path. Returns [`Error::NonUtf8Path`] if path is not valid UTF-8:
+/// Cargo skips lines of build script output which are not valid UTF-8, so such instruction would be
+/// silently ignored.
+fn check_path<'a>(path: &'a Path, msg: &str) -> Result<Cow<'a, Path>, Error> {
+    let str = ensure_utf8(path)?;
+    Ok(match check_newlines(str, msg)? {
+        Cow::Borrowed(_) => Cow::Borrowed(path),
+        Cow::Owned(str) => Cow::Owned(PathBuf::from(str)),
+    })
+}
+
+fn ensure_utf8(path: &Path) -> Result<&str, Error> {
+    path.to_str()
+        .ok_or_else(|| Error::NonUtf8Path(path.to_path_buf()))
+}
+
+/// Rejects env variable names which make `rustc-env=VAR=VALUE` ambiguous and can't be set by the OS.
+fn check_env_var_name(var: &str) -> Result<(), Error> {
+    ensure(
+        !var.is_empty(),
+        "Empty env variable names cannot be used in the build scripts",
+    )?;
+    ensure(
+        !var.contains(['=', '\0']),
+        format_args!("Env variable name `{var}` cannot contain `=` or NUL characters"),
+    )
+}
+
+/// Returns [`Error::InvalidInput`] with `msg` unless `condition` holds.
+fn ensure(condition: bool, msg: impl std::fmt::Display) -> Result<(), Error> {
+    match condition {
+        true => Ok(()),
+        false => Err(Error::InvalidInput(msg.to_string())),
+    }
+}
+
+/// Panics with the error message, used by panicking versions of `try_*` functions.
+#[track_caller]
+fn or_panic<T>(result: Result<T, Error>) -> T {
+    match result {
+        Ok(value) => value,
+        Err(err) => panic!("{err}"),
+    }
+}
+
+/// Normalizes paths and emits instructions once all of them were validated. Paths of
+/// [`rerun_if_changed`], `rustc_link_search*` and [`emit`] go through here.
+#[track_caller]
+fn try_emit_all(mut instructions: Vec<Instruction>) -> Result<(), Error> {
+    for instruction in &mut instructions {
+        match instruction {
+            Instruction::RerunIfChanged(path) => {
+                if let Cow::Owned(normalized) = paths::normalize(path) {
+                    *path = normalized;
+                }
+                check_build_dir(path)?;
+            }
+            Instruction::RustcLinkSearch(LinkSearch { path, .. }) => {
+                if let Cow::Owned(normalized) = paths::normalize_search(path) {
+                    *path = normalized;
+                }
+            }
+            _ => continue,
+        }
+        if let Instruction::RerunIfChanged(path)
+        | Instruction::RustcLinkSearch(LinkSearch { path, .. }) = &*instruction
+        {
+            check_exists(instruction.key(), path)?;
+        }
+    }
+    try_emit_instructions_at(instructions, Location::caller())
+}
+
+#[track_caller]
+pub(crate) fn emit_instruction(instruction: Instruction) {
+    or_panic(try_emit_instruction(instruction));
+}
+
+pub(crate) fn emit_instructions_at(
+    instructions: Vec<Instruction>,
+    caller: &'static Location<'static>,
+) {
+    or_panic(try_emit_instructions_at(instructions, caller));
+}
+
+/// Policies of the current thread, copied to threads of [`parallel::run`](crate::parallel::run).
+#[derive(Debug, Clone)]
+pub(crate) struct ThreadPolicies {
+    newline: NewlinePolicy,
+    missing_path: MissingPathPolicy,
+    unsupported: UnsupportedPolicy,
+    strict_rerun_paths: bool,
+    metadata_keys: Option<HashMap<String, String>>,
+    normalize_paths: bool,
+}
+
+impl ThreadPolicies {
+    pub(crate) fn current() -> Self {
+        Self {
+            newline: NEWLINE_POLICY.get(),
+            missing_path: MISSING_PATH_POLICY.get(),
+            unsupported: UNSUPPORTED_POLICY.get(),
+            strict_rerun_paths: STRICT_RERUN_PATHS.get(),
+            metadata_keys: STRICT_METADATA_KEYS.with_borrow(Clone::clone),
+            normalize_paths: paths::is_normalize(),
+        }
+    }
+
+    pub(crate) fn set(self) {
+        NEWLINE_POLICY.set(self.newline);
+        MISSING_PATH_POLICY.set(self.missing_path);
+        UNSUPPORTED_POLICY.set(self.unsupported);
+        STRICT_RERUN_PATHS.set(self.strict_rerun_paths);
+        STRICT_METADATA_KEYS.set(self.metadata_keys);
+        paths::set_normalize(self.normalize_paths);
+    }
+}
+
+/// Records instruction in active [`Session`](crate::Session) and writes it. `rustc-link-arg*`
+/// instructions are skipped if they were already emitted during the session.
+#[track_caller]
+pub(crate) fn try_emit_instruction(instruction: Instruction) -> Result<(), Error> {
+    let caller = Location::caller();
+    let Some(instruction) = build_out::capture_instruction(instruction, caller) else {
+        return Ok(());
+    };
+    if !session::record_link_args(std::slice::from_ref(&instruction), caller) {
+        return Ok(());
+    }
+    try_write_recorded(instruction)
+}
+
+/// [`try_emit_instruction`] for instructions of a single call with explicit caller, used to emit
+/// instructions captured on other threads. `rustc-link-arg*` instructions of the call are
+/// deduplicated as one sequence, so flags like `-framework Foo` stay intact.
+pub(crate) fn try_emit_instructions_at(
+    instructions: Vec<Instruction>,
+    caller: &'static Location<'static>,
+) -> Result<(), Error> {
+    let Some(instructions) = build_out::capture_instructions_at(instructions, caller) else {
+        return Ok(());
+    };
+    if !session::record_link_args(&instructions, caller) {
+        return Ok(());
+    }
+    for instruction in instructions {
+        try_write_recorded(instruction)?;
+    }
+    Ok(())
+}
+
+/// Writes instruction which passed session deduplication.
+fn try_write_recorded(instruction: Instruction) -> Result<(), Error> {
+    match &instruction {
+        Instruction::RerunIfChanged(path) => {
+            session::record_rerun("rerun-if-changed", format_args!("{}", path.display()));
+        }
+        Instruction::RerunIfEnvChanged(var) => {
+            session::record_rerun("rerun-if-env-changed", format_args!("{var}"));
+        }
+        _ => {}
+    }
+    let Some(instruction) = check_supported(instruction, UNSUPPORTED_POLICY.get(), cargo_supports)?
+    else {
+        return Ok(());
+    };
+    // Errors downgraded to warnings or skipped by `UnsupportedPolicy` don't fail the build
+    if let Instruction::Error(_) = instruction {
+        HAS_ERRORS.store(true, Ordering::Relaxed);
+    }
+    Ok(write_instruction(&instruction)?)
 }
 
 /// Helper struct for generic `one or many` iterator.
@@ -1370,6 +2701,13 @@ impl From<PathBuf> for VarArg<std::iter::Once<PathBuf>> {
     }
 }
 
+#[cfg(feature = "camino")]
+impl From<camino::Utf8PathBuf> for VarArg<std::iter::Once<camino::Utf8PathBuf>> {
+    fn from(value: camino::Utf8PathBuf) -> Self {
+        Self(std::iter::once(value))
+    }
+}
+
 impl<I: IntoIterator> From<I> for VarArg<I> {
     fn from(into_iter: I) -> Self {
         Self(into_iter)
@@ -14,7 +14,7 @@ fn change_output_test() {
 
     {
         let out = vec_out.0.read().expect("Unable to aquire Read lock");
-        let out: &str = str::from_utf8(&out).unwrap();
+        let out: &str = std::str::from_utf8(&out).unwrap();
 
         assert_eq!(
             out,
@@ -49,7 +49,7 @@ fn rerun_if_changed_test() {
     cargo_build::rerun_if_changed(["LICENSE.md", "README.md"]);
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -103,7 +103,7 @@ fn rerun_if_env_changed_test() {
     cargo_build::rerun_if_env_changed(["VAR1", "VAR2"]);
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -143,7 +143,7 @@ fn rustc_link_arg_test() {
     cargo_build::rustc_link_arg(["-mlongcalls", "-ffunction-sections", "-Wl,--cref"]);
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -163,7 +163,7 @@ fn rustc_link_arg_cdylib_test() {
     cargo_build::rustc_link_arg_cdylib(["-mlongcalls", "-ffunction-sections", "-Wl,--cref"]);
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -188,7 +188,7 @@ fn rustc_link_arg_bin_test() {
     );
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -209,7 +209,7 @@ fn rustc_link_arg_bins_test() {
     cargo_build::rustc_link_arg_bins(["-mlongcalls", "-ffunction-sections", "-Wl,--cref"]);
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -229,7 +229,7 @@ fn rustc_link_arg_tests_test() {
     cargo_build::rustc_link_arg_tests(["-mlongcalls", "-ffunction-sections", "-Wl,--cref"]);
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -249,7 +249,7 @@ fn rustc_link_arg_examples_test() {
     cargo_build::rustc_link_arg_examples(["-mlongcalls", "-ffunction-sections", "-Wl,--cref"]);
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -269,7 +269,7 @@ fn rustc_link_arg_benches_test() {
     cargo_build::rustc_link_arg_benches(["-mlongcalls", "-ffunction-sections", "-Wl,--cref"]);
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -289,7 +289,7 @@ fn rustc_link_lib_test() {
     cargo_build::rustc_link_lib(["nghttp2", "libssl", "libcrypto"]);
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -314,7 +314,7 @@ fn rustc_link_lib_static_test() {
     );
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -342,7 +342,7 @@ fn rustc_link_lib_dylib_test() {
     );
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -370,7 +370,7 @@ fn rustc_link_lib_framework_test() {
     );
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -395,7 +395,7 @@ fn rustc_link_search_test() {
     cargo_build::rustc_link_search(["native=libs", "framework=mac_os_libs"]);
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -426,7 +426,7 @@ fn rustc_flags_test() {
     cargo_build::rustc_flags(["-l ffi", "-l ncursesw", "-l stdc++", "-l z"]);
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -440,6 +440,112 @@ fn rustc_flags_test() {
     );
 }
 
+#[test]
+fn rustc_flags_syntax_test() {
+    let vec_out = TestWriteVecHandle::new();
+
+    cargo_build::build_out::set(vec_out.clone());
+
+    cargo_build::rustc_flags("-lz -Llibs");
+    cargo_build::rustc_flags("-l static=z -L native=libs");
+    cargo_build::rustc_flags("  -l   static:+whole-archive=z  ");
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(
+        out,
+        "\
+                cargo::rustc-flags=-lz -Llibs\n\
+                cargo::rustc-flags=-l static=z -L native=libs\n\
+                cargo::rustc-flags=  -l   static:+whole-archive=z  \n"
+    );
+}
+
+#[test]
+#[should_panic]
+fn rustc_flags_invalid_flag_test() {
+    cargo_build::rustc_flags("-l z -O");
+}
+
+#[test]
+#[should_panic]
+fn rustc_flags_missing_value_test() {
+    cargo_build::rustc_flags("-l z -L");
+}
+
+#[test]
+#[should_panic]
+fn rustc_flags_invalid_kind_test() {
+    cargo_build::rustc_flags("-l shared=z");
+}
+
+#[test]
+#[should_panic]
+fn rustc_flags_invalid_search_kind_test() {
+    cargo_build::rustc_flags("-L natve=/x");
+}
+
+#[test]
+fn rustc_flags_from_test() {
+    use cargo_build::{LinkKind, LinkLib, LinkSearch, SearchKind};
+
+    let vec_out = TestWriteVecHandle::new();
+
+    cargo_build::build_out::set(vec_out.clone());
+
+    cargo_build::rustc_flags_from(&[], &[]);
+    cargo_build::rustc_flags_from(
+        &[
+            LinkLib::new("z").kind(LinkKind::Static),
+            LinkLib::new("ffi"),
+        ],
+        &[
+            LinkSearch::new("libs").kind(SearchKind::Native),
+            LinkSearch::new("common_libs"),
+        ],
+    );
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(
+        out,
+        "cargo::rustc-flags=-L native=libs -L common_libs -l static=z -l ffi\n"
+    );
+}
+
+#[test]
+fn rustc_flags_from_invalid_test() {
+    use cargo_build::{LinkKind, LinkLib, LinkSearch};
+
+    let vec_out = TestWriteVecHandle::new();
+    let _out = cargo_build::build_out::scoped(vec_out.clone());
+
+    assert!(cargo_build::try_rustc_flags_from(&[], &[LinkSearch::new("my libs")]).is_err());
+    assert!(cargo_build::try_rustc_flags_from(&[LinkLib::new("my lib")], &[]).is_err());
+    assert!(cargo_build::try_rustc_flags_from(
+        &[LinkLib::new("z").kind(LinkKind::Static).rename("my z")],
+        &[]
+    )
+    .is_err());
+
+    // Modifiers can't be rendered without KIND
+    let verbatim = LinkLib::new("z").modifier("+verbatim");
+    assert!(cargo_build::try_rustc_flags_from(std::slice::from_ref(&verbatim), &[]).is_err());
+    assert!(cargo_build::try_emit(verbatim).is_err());
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+
+        let path = std::ffi::OsStr::from_bytes(b"libs\xff");
+        assert!(cargo_build::try_rustc_flags_from(&[], &[LinkSearch::new(path)]).is_err());
+    }
+
+    assert!(vec_out.0.read().unwrap().is_empty());
+}
+
 #[test]
 fn rustc_cfg_test_no_value() {
     let vec_out = TestWriteVecHandle::new();
@@ -449,7 +555,7 @@ fn rustc_cfg_test_no_value() {
     cargo_build::rustc_cfg("api_v1");
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(out, "cargo::rustc-cfg=api_v1\n");
 }
@@ -463,7 +569,7 @@ fn rustc_cfg_test_value() {
     cargo_build::rustc_cfg(("api_version", "1"));
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(out, "cargo::rustc-cfg=api_version=\"1\"\n");
 }
@@ -477,7 +583,7 @@ fn rustc_cfg_test_value_cfg() {
     cargo_build::rustc_cfg(("api_version", "1"));
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(out, "cargo::rustc-cfg=api_version=\"1\"\n");
 }
@@ -491,7 +597,7 @@ fn rustc_check_cfg_test_no_values() {
     cargo_build::rustc_check_cfg("api_version", std::iter::empty::<&str>());
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(out, "cargo::rustc-check-cfg=cfg(api_version)\n");
 }
@@ -505,7 +611,7 @@ fn rustc_check_cfg_test_single_value() {
     cargo_build::rustc_check_cfg("api_version", ["1"]);
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -522,7 +628,7 @@ fn rustc_check_cfg_test_many_values() {
     cargo_build::rustc_check_cfg("api_version", ["1", "2", "3"]);
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -539,7 +645,7 @@ fn rustc_env_test() {
     cargo_build::rustc_env("GIT_HASH", "1234");
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(out, "cargo::rustc-env=GIT_HASH=1234\n");
 }
@@ -552,7 +658,7 @@ fn rustc_warning_test() {
     cargo_build::warning("Warning during build process");
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(out, "cargo::warning=Warning during build process\n");
 }
@@ -570,7 +676,7 @@ Warning 3 during build process",
     );
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -590,7 +696,7 @@ fn rustc_error_test() {
     cargo_build::error("Fatal error during build process");
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(out, "cargo::error=Fatal error during build process\n");
 }
@@ -608,7 +714,7 @@ Fatal error 3 during build process",
     );
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -628,11 +734,716 @@ fn metadata_test() {
     cargo_build::metadata("META", "DATA");
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(out, "cargo::metadata=META=DATA\n");
 }
 
+#[test]
+fn metadata_sanitize_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    cargo_build::metadata("include-dir", "/opt/foo/include");
+    cargo_build::metadata("lib.dir", "/opt/foo/lib");
+    cargo_build::metadata("version_2", "2.0");
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(
+        out,
+        "\
+cargo::metadata=include_dir=/opt/foo/include
+cargo::metadata=lib_dir=/opt/foo/lib
+cargo::metadata=version_2=2.0
+"
+    );
+}
+
+#[test]
+fn metadata_invalid_key_test() {
+    let _out = cargo_build::build_out::scoped(std::io::sink());
+
+    let err = cargo_build::try_metadata("größe", "3").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Metadata key `größe` cannot be used in the build scripts: keys become `DEP_<LINKS>_<KEY>` env \
+        variables, so they should consist of ASCII letters, digits and `_`"
+    );
+    assert!(cargo_build::try_metadata("", "3").is_err());
+    assert!(cargo_build::try_metadata("--", "3").is_err());
+    assert!(cargo_build::try_metadata("_2", "3").is_ok());
+}
+
+#[test]
+fn strict_metadata_keys_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    cargo_build::strict_metadata_keys(true);
+
+    cargo_build::metadata("include-dir", "/opt/foo/include");
+    cargo_build::metadata("include-dir", "/opt/foo/include");
+    cargo_build::metadata("lib_dir", "/opt/foo/lib");
+
+    for key in ["INCLUDE_DIR", "include_dir", "include.dir"] {
+        let err = cargo_build::try_metadata(key, "/usr/include").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            cargo_build::Error::InvalidInput(format!(
+                "Metadata key `{key}` collides with `include-dir`: both become `DEP_<LINKS>_INCLUDE_DIR`"
+            ))
+            .to_string()
+        );
+    }
+
+    cargo_build::strict_metadata_keys(false);
+    cargo_build::metadata("INCLUDE_DIR", "/usr/include");
+}
+
+#[test]
+fn directive_fmt_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    let version = 3;
+    cargo_build::directive_fmt("rustc-env", format_args!("API_VERSION={version}"));
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(out, "cargo::rustc-env=API_VERSION=3\n");
+}
+
+#[test]
+fn try_directive_fmt_error_test() {
+    struct Failing;
+
+    impl std::fmt::Display for Failing {
+        fn fmt(&self, _: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            Err(std::fmt::Error)
+        }
+    }
+
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    let err = cargo_build::try_directive_fmt("rustc-env", format_args!("X={}", Failing));
+    assert_eq!(
+        err.unwrap_err().to_string(),
+        cargo_build::Error::InvalidInput("Unable to format directive value".to_string())
+            .to_string()
+    );
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    assert!(out.is_empty());
+}
+
+#[test]
+#[should_panic]
+fn directive_fmt_newline_test() {
+    let value = "a\nb";
+    cargo_build::directive_fmt("rustc-env", format_args!("X={value}"));
+}
+
+#[test]
+fn emit_cfg_for_feature_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    // `CARGO_CFG_TARGET_FEATURE` is only set for build scripts
+    let enabled = cargo_build::emit_cfg_for_feature("no-such-feature", "has_no_such_feature");
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert!(!enabled);
+    assert_eq!(out, "cargo::rustc-check-cfg=cfg(has_no_such_feature)\n");
+}
+
+#[test]
+fn emit_target_cfg_table_test() {
+    use crate::functions::try_emit_target_cfg_table_for;
+
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    let table: [(&str, &[&str]); 3] = [
+        ("unix_sockets", &["linux", "macos"]),
+        ("win_pipes", &["windows"]),
+        ("epoll", &["linux", "android"]),
+    ];
+    let enabled = try_emit_target_cfg_table_for(&table, "linux").unwrap();
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(enabled, ["unix_sockets", "epoll"]);
+    assert_eq!(
+        out,
+        "\
+cargo::rustc-check-cfg=cfg(unix_sockets)
+cargo::rustc-check-cfg=cfg(win_pipes)
+cargo::rustc-check-cfg=cfg(epoll)
+cargo::rustc-cfg=unix_sockets
+cargo::rustc-cfg=epoll
+"
+    );
+}
+
+#[test]
+#[cfg(feature = "camino")]
+fn camino_test() {
+    use camino::{Utf8Path, Utf8PathBuf};
+
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    cargo_build::rerun_if_changed(Utf8PathBuf::from("src/main.c"));
+    cargo_build::rerun_if_changed([Utf8Path::new("a.c"), Utf8Path::new("b.c")]);
+    cargo_build::rustc_link_search_native(Utf8PathBuf::from("libs"));
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(
+        out,
+        "\
+cargo::rerun-if-changed=src/main.c
+cargo::rerun-if-changed=a.c
+cargo::rerun-if-changed=b.c
+cargo::rustc-link-search=native=libs
+"
+    );
+}
+
+#[test]
+fn feature_cfg_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    cargo_build::feature_cfg("extra-codegen");
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(
+        out,
+        "\
+cargo::rustc-check-cfg=cfg(feature, values(\"extra-codegen\"))
+cargo::rustc-cfg=feature=\"extra-codegen\"
+"
+    );
+}
+
+#[test]
+#[should_panic]
+fn feature_cfg_quote_test() {
+    cargo_build::feature_cfg("extra\"codegen");
+}
+
+#[test]
+fn try_feature_cfg_error_test() {
+    let vec_out = TestWriteVecHandle::new();
+    let _out = cargo_build::build_out::scoped(vec_out.clone());
+
+    let err = cargo_build::try_feature_cfg("extra\ncodegen").unwrap_err();
+    assert!(matches!(err, cargo_build::Error::InvalidInput(_)));
+
+    let err = cargo_build::try_emit_cfg_for_feature("avx2", "has\navx2").unwrap_err();
+    assert!(matches!(err, cargo_build::Error::InvalidInput(_)));
+
+    let err = cargo_build::try_emit_target_cfg_table(&[("unix\nsockets", &["linux"])]).unwrap_err();
+    assert!(matches!(err, cargo_build::Error::InvalidInput(_)));
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    assert!(out.is_empty());
+}
+
+#[test]
+fn missing_links_key_test() {
+    use crate::functions::is_missing_links_key;
+
+    let build_script = |var: &str| ["OUT_DIR", "TARGET"].contains(&var);
+    let build_script_with_links =
+        |var: &str| ["OUT_DIR", "TARGET", "CARGO_MANIFEST_LINKS"].contains(&var);
+    let not_build_script = |_: &str| false;
+
+    assert!(is_missing_links_key(build_script));
+    assert!(!is_missing_links_key(build_script_with_links));
+    assert!(!is_missing_links_key(not_build_script));
+}
+
+#[test]
+fn directive_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    cargo_build::directive("rustc-new-thing", "value");
+    cargo_build::directive("rustc_new-thing2", "");
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(
+        out,
+        "\
+                cargo::rustc-new-thing=value\n\
+                cargo::rustc_new-thing2=\n"
+    );
+}
+
+#[test]
+#[should_panic]
+fn directive_invalid_key_test() {
+    cargo_build::directive("rustc-link-arg=bin", "value");
+}
+
+#[test]
+fn record_replay_test() {
+    let path = std::env::temp_dir().join("cargo_build_record_replay_test.txt");
+
+    let recorded = TestWriteVecHandle::new();
+    {
+        let _out = cargo_build::build_out::scoped(recorded.clone());
+        cargo_build::build_out::record_to(&path).unwrap();
+
+        cargo_build::rerun_if_changed(["build.rs"]);
+        cargo_build::rustc_link_lib_static([], ["foo"]);
+        cargo_build::build_out::write_raw(b"not an instruction\n");
+    }
+
+    let replayed = TestWriteVecHandle::new();
+    {
+        let _out = cargo_build::build_out::scoped(replayed.clone());
+        cargo_build::replay(&path).unwrap();
+    }
+
+    let recorded = recorded.0.read().expect("Unable to aquire Read lock");
+    let replayed = replayed.0.read().expect("Unable to aquire Read lock");
+
+    assert_eq!(
+        std::str::from_utf8(&replayed).unwrap(),
+        "\
+cargo::rerun-if-changed=build.rs
+cargo::rustc-link-lib=static=foo
+not an instruction
+"
+    );
+    assert_eq!(*recorded, *replayed);
+
+    // Legacy syntax
+    let legacy_path = std::env::temp_dir().join("cargo_build_record_replay_legacy_test.txt");
+    std::fs::write(
+        &legacy_path,
+        "cargo:rustc-link-lib=foo\ncargo:rustc-cdylib-link-arg=-Wl,-z,defs\ncargo:include=/usr/include\n",
+    )
+    .unwrap();
+
+    let replayed = TestWriteVecHandle::new();
+    {
+        let _out = cargo_build::build_out::scoped(replayed.clone());
+        cargo_build::replay(&legacy_path).unwrap();
+    }
+    let replayed = replayed.0.read().expect("Unable to aquire Read lock");
+    assert_eq!(
+        std::str::from_utf8(&replayed).unwrap(),
+        "\
+cargo::rustc-link-lib=foo
+cargo::rustc-link-arg-cdylib=-Wl,-z,defs
+cargo::metadata=include=/usr/include
+"
+    );
+
+    assert!(
+        cargo_build::replay(std::env::temp_dir().join("cargo_build_missing_recording")).is_err()
+    );
+}
+
+#[test]
+fn emit_test() {
+    use cargo_build::{Instruction, LinkKind, LinkLib, LinkSearch, SearchKind};
+
+    let vec_out = TestWriteVecHandle::new();
+    let _out = cargo_build::build_out::scoped(vec_out.clone());
+
+    cargo_build::emit(LinkLib::new("foo").kind(LinkKind::Static).rename("foo_v2"));
+    cargo_build::emit(LinkSearch::new("libs").kind(SearchKind::Native));
+    cargo_build::emit(Instruction::new("new-thing", "value"));
+    cargo_build::emit(Instruction::RustcEnv {
+        var: "API_VERSION".to_string(),
+        value: "3".to_string(),
+    });
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(
+        out,
+        "\
+cargo::rustc-link-lib=static=foo:foo_v2
+cargo::rustc-link-search=native=libs
+cargo::new-thing=value
+cargo::rustc-env=API_VERSION=3
+"
+    );
+}
+
+#[test]
+#[should_panic(expected = "Instructions containing newlines cannot be used in the build scripts")]
+fn emit_newline_test() {
+    let _out = cargo_build::build_out::scoped(std::io::sink());
+    cargo_build::emit(cargo_build::Instruction::Warning(
+        "first\nsecond".to_string(),
+    ));
+}
+
+#[test]
+fn emit_same_checks_test() {
+    use cargo_build::{Instruction, MissingPathPolicy};
+
+    let vec_out = TestWriteVecHandle::new();
+    let _out = cargo_build::build_out::scoped(vec_out.clone());
+
+    // Newlines in fields of typed variants are caught too
+    let lib = cargo_build::LinkLib::new("foo").rename("bar\nbaz");
+    assert!(cargo_build::try_emit(lib).is_err());
+
+    cargo_build::strict_rerun_paths(true);
+    let err = cargo_build::try_emit(Instruction::RerunIfChanged("target/generated.rs".into()));
+    assert!(matches!(err, Err(cargo_build::Error::InvalidInput(_))));
+    cargo_build::strict_rerun_paths(false);
+
+    cargo_build::set_missing_path_policy(MissingPathPolicy::Reject);
+    let err = cargo_build::try_emit(Instruction::new("rustc-link-search", "native=Crago"));
+    assert!(matches!(err, Err(cargo_build::Error::InvalidInput(_))));
+    cargo_build::set_missing_path_policy(MissingPathPolicy::Ignore);
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    assert!(out.is_empty());
+}
+
+#[test]
+#[should_panic(expected = "Instruction keys may only contain ASCII letters, digits, `-` and `_`")]
+fn emit_invalid_key_test() {
+    let _out = cargo_build::build_out::scoped(std::io::sink());
+    cargo_build::emit(cargo_build::Instruction::new("new thing", "value"));
+}
+
+#[test]
+fn try_invalid_input_test() {
+    let vec_out = TestWriteVecHandle::new();
+    let _out = cargo_build::build_out::scoped(vec_out.clone());
+
+    let err = cargo_build::try_rustc_link_lib(["foo", "bar\nbaz"]).unwrap_err();
+
+    assert!(matches!(err, cargo_build::Error::InvalidInput(_)));
+    assert_eq!(
+        err.to_string(),
+        "Library names containing newlines cannot be used in the build scripts"
+    );
+
+    let err = cargo_build::try_rustc_flags("-l foo -C opt-level=3").unwrap_err();
+
+    assert_eq!(
+        err.to_string(),
+        "Invalid rustc flags `-l foo -C opt-level=3`: Only `-l` and `-L` flags are allowed, found `-C`"
+    );
+
+    cargo_build::try_rustc_link_lib(["foo", "bar"]).unwrap();
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    assert_eq!(
+        std::str::from_utf8(&out).unwrap(),
+        "cargo::rustc-link-lib=foo\ncargo::rustc-link-lib=bar\n"
+    );
+}
+
+#[test]
+fn try_io_error_test() {
+    struct FailingWriter;
+
+    impl Write for FailingWriter {
+        fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::new(
+                std::io::ErrorKind::BrokenPipe,
+                "closed",
+            ))
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let _out = cargo_build::build_out::scoped(FailingWriter);
+
+    let err = cargo_build::try_rustc_cfg("has_foo").unwrap_err();
+
+    match &err {
+        cargo_build::Error::Io(err) => assert_eq!(err.kind(), std::io::ErrorKind::BrokenPipe),
+        err => panic!("Unexpected error {err:?}"),
+    }
+    assert_eq!(
+        err.to_string(),
+        "Unable to write to CARGO_BUILD_OUT: closed"
+    );
+}
+
+#[test]
+fn newline_policy_test() {
+    use cargo_build::NewlinePolicy;
+
+    let vec_out = TestWriteVecHandle::new();
+    let _out = cargo_build::build_out::scoped(vec_out.clone());
+
+    cargo_build::set_newline_policy(NewlinePolicy::Strip);
+    cargo_build::rustc_env("VERSION", "1.2.3\r\n");
+    cargo_build::rustc_cfg(("api", "v1\nv2"));
+    cargo_build::rerun_if_changed(PathBuf::from("include\n"));
+
+    cargo_build::set_newline_policy(NewlinePolicy::Escape);
+    cargo_build::metadata("banner", "line 1\nline 2");
+    cargo_build::emit(cargo_build::Instruction::new("rustc-env", "MOTD=hi\nthere"));
+
+    cargo_build::set_newline_policy(NewlinePolicy::Split);
+    cargo_build::rustc_link_arg("-Wl,--as-needed\n\n-Wl,-z,now\n");
+    cargo_build::rustc_flags("-l foo\n-L libs");
+    assert!(cargo_build::try_rustc_env("VERSION", "1.2.3\n").is_err());
+
+    cargo_build::set_newline_policy(NewlinePolicy::Reject);
+    assert!(cargo_build::try_rustc_link_arg("-Wl,--as-needed\n-Wl,-z,now").is_err());
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    assert_eq!(
+        std::str::from_utf8(&out).unwrap(),
+        "\
+cargo::rustc-env=VERSION=1.2.3
+cargo::rustc-cfg=api=\"v1 v2\"
+cargo::rerun-if-changed=include
+cargo::metadata=banner=line 1\\nline 2
+cargo::rustc-env=MOTD=hi\\nthere
+cargo::rustc-link-arg=-Wl,--as-needed
+cargo::rustc-link-arg=-Wl,-z,now
+cargo::rustc-flags=-l foo
+cargo::rustc-flags=-L libs
+"
+    );
+}
+
+#[test]
+#[cfg(unix)]
+fn non_utf8_path_test() {
+    use std::ffi::OsStr;
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::Path;
+
+    let vec_out = TestWriteVecHandle::new();
+    let _out = cargo_build::build_out::scoped(vec_out.clone());
+
+    let path = Path::new(OsStr::from_bytes(b"libs/\xFF"));
+
+    let err = cargo_build::try_rerun_if_changed(["build.rs".as_ref(), path]).unwrap_err();
+    assert!(matches!(&err, cargo_build::Error::NonUtf8Path(p) if p == path));
+    assert_eq!(
+        err.to_string(),
+        "Path `libs/\u{FFFD}` is not valid UTF-8 and cannot be used in the build scripts"
+    );
+
+    assert!(cargo_build::try_rustc_link_search_native(path).is_err());
+    assert!(cargo_build::try_emit(cargo_build::LinkSearch::new(path)).is_err());
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    assert!(out.is_empty());
+}
+
+#[test]
+#[cfg(unix)]
+#[should_panic(expected = "is not valid UTF-8")]
+fn non_utf8_path_panic_test() {
+    use std::ffi::OsString;
+    use std::os::unix::ffi::OsStringExt;
+
+    let _out = cargo_build::build_out::scoped(std::io::sink());
+
+    cargo_build::rerun_if_changed(PathBuf::from(OsString::from_vec(b"\xFF".to_vec())));
+}
+
+#[test]
+fn env_var_name_test() {
+    let vec_out = TestWriteVecHandle::new();
+    let _out = cargo_build::build_out::scoped(vec_out.clone());
+
+    let err = cargo_build::try_rustc_env("API=VERSION", "3").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Env variable name `API=VERSION` cannot contain `=` or NUL characters"
+    );
+    assert!(cargo_build::try_rustc_env("", "3").is_err());
+    assert!(cargo_build::try_rerun_if_env_changed(["FOO_DIR", "FOO\0DIR"]).is_err());
+
+    cargo_build::try_rustc_env("API_VERSION", "a=b c").unwrap();
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    assert_eq!(
+        std::str::from_utf8(&out).unwrap(),
+        "cargo::rustc-env=API_VERSION=a=b c\n"
+    );
+}
+
+#[test]
+fn missing_path_policy_test() {
+    use cargo_build::MissingPathPolicy;
+
+    let vec_out = TestWriteVecHandle::new();
+    let _out = cargo_build::build_out::scoped(vec_out.clone());
+
+    cargo_build::set_missing_path_policy(MissingPathPolicy::Warn);
+    cargo_build::rerun_if_changed(["Cargo.toml", "Crago.toml"]);
+    cargo_build::rustc_link_search("native=src");
+
+    cargo_build::set_missing_path_policy(MissingPathPolicy::Reject);
+    let err = cargo_build::try_rustc_link_search_native(["src", "libz"]).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "`rustc-link-search` path `libz` doesn't exist"
+    );
+
+    cargo_build::set_missing_path_policy(MissingPathPolicy::Ignore);
+    cargo_build::rerun_if_changed("always");
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    assert_eq!(
+        std::str::from_utf8(&out).unwrap(),
+        "\
+cargo::warning=`rerun-if-changed` path `Crago.toml` doesn't exist
+cargo::rerun-if-changed=Cargo.toml
+cargo::rerun-if-changed=Crago.toml
+cargo::rustc-link-search=native=src
+cargo::rerun-if-changed=always
+"
+    );
+}
+
+#[test]
+fn has_errors_test() {
+    let _out = cargo_build::build_out::scoped(std::io::sink());
+
+    cargo_build::error_and_continue("libfoo not found");
+
+    assert!(cargo_build::has_errors());
+}
+
+/// Spawned by [`downgraded_error_test`] with Cargo 1.70, which doesn't understand `cargo::error`.
+#[test]
+#[ignore = "spawned by downgraded_error_test"]
+fn downgraded_error_child() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    cargo_build::set_unsupported_policy(cargo_build::UnsupportedPolicy::Downgrade);
+    cargo_build::error_and_continue("libfoo not found");
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    assert_eq!(*out, b"cargo::warning=libfoo not found\n");
+    assert!(!cargo_build::has_errors());
+}
+
+#[cfg(unix)]
+#[test]
+fn downgraded_error_test() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let cargo = std::env::temp_dir().join("cargo_build_downgraded_error_test_cargo");
+    std::fs::write(
+        &cargo,
+        "#!/bin/sh\necho 'cargo 1.70.0 (ec8a8a0ca 2023-04-25)'\n",
+    )
+    .unwrap();
+    std::fs::set_permissions(&cargo, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    let output = std::process::Command::new(std::env::current_exe().unwrap())
+        .args(["--exact", "functions_test::downgraded_error_child"])
+        .args(["--ignored", "--test-threads=1"])
+        .env("CARGO", &cargo)
+        .env_remove("CARGO_PKG_RUST_VERSION")
+        .output()
+        .unwrap();
+
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stdout)
+    );
+}
+
+#[test]
+fn rerun_inside_build_dir_test() {
+    let vec_out = TestWriteVecHandle::new();
+    let _out = cargo_build::build_out::scoped(vec_out.clone());
+
+    cargo_build::rerun_if_changed("target/generated.rs");
+
+    cargo_build::strict_rerun_paths(true);
+    let err = cargo_build::try_rerun_if_changed(["build.rs", "target/generated.rs"]).unwrap_err();
+    assert!(matches!(err, cargo_build::Error::InvalidInput(_)));
+    cargo_build::strict_rerun_paths(false);
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out = std::str::from_utf8(&out).unwrap();
+    assert!(
+        out.starts_with("cargo::warning=`rerun-if-changed` path `target/generated.rs` is inside `")
+    );
+    assert!(out.ends_with(
+        "which changes on every build, so the build script reruns on every build\ncargo::rerun-if-changed=target/generated.rs\n"
+    ));
+}
+
+#[test]
+fn unsupported_policy_test() {
+    use crate::functions::check_supported;
+    use cargo_build::{Instruction, UnsupportedPolicy};
+
+    let error = || Instruction::new("error", "libfoo not found");
+    let old_cargo = |_| false;
+
+    assert_eq!(
+        check_supported(error(), UnsupportedPolicy::Downgrade, old_cargo).unwrap(),
+        Some(Instruction::new("warning", "libfoo not found"))
+    );
+    assert_eq!(
+        check_supported(error(), UnsupportedPolicy::Skip, old_cargo).unwrap(),
+        None
+    );
+    assert_eq!(
+        check_supported(error(), UnsupportedPolicy::Emit, old_cargo).unwrap(),
+        Some(error())
+    );
+    assert_eq!(
+        check_supported(error(), UnsupportedPolicy::Downgrade, |_| true).unwrap(),
+        Some(error())
+    );
+
+    let check_cfg = Instruction::new("rustc-check-cfg", "cfg(has_foo)");
+    assert_eq!(
+        check_supported(check_cfg.clone(), UnsupportedPolicy::Downgrade, old_cargo).unwrap(),
+        None
+    );
+    let err = check_supported(check_cfg, UnsupportedPolicy::Reject, old_cargo).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Instruction `cargo::rustc-check-cfg` requires Cargo 1.80 or newer"
+    );
+
+    // Instructions supported by every Cargo are not checked
+    let cfg = Instruction::new("rustc-cfg", "has_foo");
+    assert_eq!(
+        check_supported(cfg.clone(), UnsupportedPolicy::Reject, |_| unreachable!()).unwrap(),
+        Some(cfg)
+    );
+}
+
 struct TestWriteVecHandle(Arc<RwLock<Vec<u8>>>);
 
 impl TestWriteVecHandle {
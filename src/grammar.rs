@@ -0,0 +1,23 @@
+//! Grammar of `cargo::` instructions as documented in
+//! <https://doc.rust-lang.org/cargo/reference/build-scripts.html#outputs-of-the-build-script>.
+
+use crate::Instruction;
+
+/// Checks that `line` is an instruction Cargo accepts. Lines which don't start with `cargo:` are not
+/// instructions and are accepted.
+///
+/// Lines are parsed with [`Instruction`]'s `FromStr`, then checked for what the typed variants don't
+/// cover: unknown keys are rejected after `cargo::` and `rustc-flags` may only contain `-l` and `-L`.
+pub(crate) fn check(line: &str) -> Result<(), String> {
+    if !line.starts_with("cargo:") {
+        return Ok(());
+    }
+
+    match line.parse::<Instruction>().map_err(|err| err.to_string())? {
+        Instruction::RustcFlags(flags) => crate::functions::parse_rustc_flags(&flags),
+        // Forms of `rustc-check-cfg` without typed representation
+        Instruction::Other { key, .. } if key == "rustc-check-cfg" => Ok(()),
+        Instruction::Other { key, .. } => Err(format!("Unknown instruction `{key}`")),
+        _ => Ok(()),
+    }
+}
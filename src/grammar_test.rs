@@ -0,0 +1,83 @@
+use crate::grammar::check;
+
+#[test]
+fn check_valid_test() {
+    for line in [
+        "cargo::rerun-if-changed=build.rs",
+        "cargo::rerun-if-env-changed=CC",
+        "cargo::rustc-link-lib=foo",
+        "cargo::rustc-link-lib=static:+whole-archive=foo",
+        "cargo::rustc-link-search=native=libs",
+        "cargo::rustc-link-search=libs",
+        "cargo::rustc-link-arg-bin=app=-Wl,-z,now",
+        "cargo::rustc-flags=-l foo -Llibs",
+        "cargo::rustc-cfg=has_foo",
+        "cargo::rustc-cfg=feature=\"std\"",
+        "cargo::rustc-check-cfg=cfg(has_foo)",
+        "cargo::rustc-check-cfg=cfg(feature, values(\"std\", \"alloc\"))",
+        "cargo::rustc-check-cfg=cfg(api, values(\"a,b\"))",
+        "cargo::rustc-check-cfg=cfg(foo, values(none(), \"bar\"))",
+        "cargo::rustc-check-cfg=cfg(foo, values(any()))",
+        "cargo::rustc-check-cfg=cfg(has_foo, has_bar)",
+        "cargo::rustc-env=API_VERSION=3",
+        "cargo::metadata=include=/usr/include",
+        "cargo::warning=",
+        "cargo:custom_key=legacy metadata",
+        "not an instruction",
+    ] {
+        assert_eq!(check(line), Ok(()), "{line}");
+    }
+}
+
+#[test]
+fn check_invalid_test() {
+    for (line, reason) in [
+        (
+            "cargo::rerun-if-chnaged=build.rs",
+            "Unknown instruction `rerun-if-chnaged`",
+        ),
+        (
+            "cargo::rerun-if-changed",
+            "Instruction must have `KEY=VALUE` form",
+        ),
+        (
+            "cargo::rerun-if-changed=",
+            "Invalid value `` of `rerun-if-changed`, expected PATH",
+        ),
+        (
+            "cargo::rustc-link-lib=shared=foo",
+            "Unknown kind `shared` of `rustc-link-lib`, expected one of `dylib`, `static`, `framework`",
+        ),
+        (
+            "cargo::rustc-flags=-Wl,--as-needed",
+            "Only `-l` and `-L` flags are allowed, found `-Wl,--as-needed`",
+        ),
+        ("cargo::rustc-flags=-l", "Flag `-l` is missing a value"),
+        (
+            "cargo::rustc-flags=-l shared=foo",
+            "Unknown library kind `shared`, expected one of `dylib`, `static`, `framework`",
+        ),
+        (
+            "cargo::rustc-cfg=has-foo",
+            "Invalid value `has-foo` of `rustc-cfg`, expected NAME or NAME=\"VALUE\"",
+        ),
+        (
+            "cargo::rustc-cfg=feature=std",
+            "Invalid value `feature=std` of `rustc-cfg`, expected NAME or NAME=\"VALUE\"",
+        ),
+        (
+            "cargo::rustc-check-cfg=has_foo",
+            "Invalid value `has_foo` of `rustc-check-cfg`, expected cfg(NAME) or cfg(NAME, values(\"VALUE\", ...))",
+        ),
+        (
+            "cargo::rustc-env=API_VERSION",
+            "Invalid value `API_VERSION` of `rustc-env`, expected VAR=VALUE",
+        ),
+        (
+            "cargo::metadata=include",
+            "Invalid value `include` of `metadata`, expected KEY=VALUE",
+        ),
+    ] {
+        assert_eq!(check(line), Err(reason.to_string()), "{line}");
+    }
+}
@@ -0,0 +1,561 @@
+use std::borrow::Cow;
+use std::fmt::{self, Display, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use crate::{LinkKind, LinkLib, LinkSearch, SearchKind};
+
+/// Cargo instruction `cargo::KEY=VALUE` emitted by the build script.
+///
+/// Every function of this crate builds `Instruction` and emits it, so instructions can also be
+/// constructed, stored and inspected as data. Instructions are passed to
+/// [`DirectiveSink`](crate::build_out::DirectiveSink) before they are written to the output stream.
+/// [`Display`] renders the exact line without trailing newline.
+///
+/// ```rust
+/// use cargo_build::Instruction;
+///
+/// let instruction = Instruction::RustcCfg {
+///     name: "feature".to_string(),
+///     value: Some("std".to_string()),
+/// };
+///
+/// assert_eq!(instruction.key(), "rustc-cfg");
+/// assert_eq!(instruction.value(), "feature=\"std\"");
+/// assert_eq!(instruction.to_string(), "cargo::rustc-cfg=feature=\"std\"");
+///
+/// assert_eq!(Instruction::new("rustc-cfg", "feature=\"std\""), instruction);
+/// ```
+///
+/// With `serde` feature enabled, instructions serialize as `{"kind": KEY, "value": VALUE}`, where `VALUE`
+/// is an object for variants with multiple fields.
+///
+/// <https://doc.rust-lang.org/cargo/reference/build-scripts.html#outputs-of-the-build-script>
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "kind", content = "value", rename_all = "kebab-case")
+)]
+#[non_exhaustive]
+pub enum Instruction {
+    /// `rerun-if-changed=PATH`
+    RerunIfChanged(PathBuf),
+    /// `rerun-if-env-changed=VAR`
+    RerunIfEnvChanged(String),
+    /// `rustc-link-arg=FLAG`
+    RustcLinkArg(String),
+    /// `rustc-link-arg-cdylib=FLAG`
+    RustcLinkArgCdylib(String),
+    /// `rustc-link-arg-bin=BIN=FLAG`
+    RustcLinkArgBin { bin: String, flag: String },
+    /// `rustc-link-arg-bins=FLAG`
+    RustcLinkArgBins(String),
+    /// `rustc-link-arg-tests=FLAG`
+    RustcLinkArgTests(String),
+    /// `rustc-link-arg-examples=FLAG`
+    RustcLinkArgExamples(String),
+    /// `rustc-link-arg-benches=FLAG`
+    RustcLinkArgBenches(String),
+    /// `rustc-link-lib=[KIND[:MODIFIERS]=]NAME[:RENAME]`
+    RustcLinkLib(LinkLib),
+    /// `rustc-link-search=[KIND=]PATH`
+    RustcLinkSearch(LinkSearch),
+    /// `rustc-flags=FLAGS`
+    RustcFlags(String),
+    /// `rustc-cfg=NAME` or `rustc-cfg=NAME="VALUE"`
+    RustcCfg { name: String, value: Option<String> },
+    /// `rustc-check-cfg=cfg(NAME)` or `rustc-check-cfg=cfg(NAME, values("VALUE", ...))`
+    ///
+    /// Parsed lines with several names, `none()`, `any()` or empty `values()` are kept as
+    /// [`Other`](Self::Other).
+    RustcCheckCfg { name: String, values: Vec<String> },
+    /// `rustc-env=VAR=VALUE`
+    RustcEnv { var: String, value: String },
+    /// `metadata=KEY=VALUE`
+    Metadata { key: String, value: String },
+    /// `warning=MESSAGE`
+    Warning(String),
+    /// `error=MESSAGE`
+    Error(String),
+    /// Instruction without typed variant, e.g. emitted with [`directive`](crate::directive).
+    Other { key: String, value: String },
+}
+
+impl Instruction {
+    /// Creates instruction from `KEY` and `VALUE`.
+    ///
+    /// Known keys produce typed variants. Unknown keys and values which don't fit typed variant of
+    /// their key produce [`Instruction::Other`].
+    pub fn new(key: impl Into<String>, value: impl Into<String>) -> Self {
+        let (key, value) = (key.into(), value.into());
+        match Self::typed(&key, &value) {
+            Ok(Some(instruction)) => instruction,
+            _ => Self::Other { key, value },
+        }
+    }
+
+    /// Parses `VALUE` into typed variant of `KEY`. Returns `None` for unknown keys.
+    fn typed(key: &str, value: &str) -> Result<Option<Self>, ParseInstructionError> {
+        let invalid = |expected| ParseInstructionError::InvalidValue {
+            key: key.to_string(),
+            value: value.to_string(),
+            expected,
+        };
+        let string = |expected| match value.is_empty() {
+            true => Err(invalid(expected)),
+            false => Ok(value.to_string()),
+        };
+        let pair = |expected| match value.split_once('=') {
+            Some((k, v)) if !k.is_empty() => Ok((k.to_string(), v.to_string())),
+            _ => Err(invalid(expected)),
+        };
+
+        Ok(Some(match key {
+            "rerun-if-changed" => Self::RerunIfChanged(PathBuf::from(string("PATH")?)),
+            "rerun-if-env-changed" => Self::RerunIfEnvChanged(string("VAR")?),
+            "rustc-link-arg" => Self::RustcLinkArg(string("FLAG")?),
+            "rustc-link-arg-cdylib" => Self::RustcLinkArgCdylib(string("FLAG")?),
+            "rustc-link-arg-bin" => {
+                let (bin, flag) = pair("BIN=FLAG")?;
+                Self::RustcLinkArgBin { bin, flag }
+            }
+            "rustc-link-arg-bins" => Self::RustcLinkArgBins(string("FLAG")?),
+            "rustc-link-arg-tests" => Self::RustcLinkArgTests(string("FLAG")?),
+            "rustc-link-arg-examples" => Self::RustcLinkArgExamples(string("FLAG")?),
+            "rustc-link-arg-benches" => Self::RustcLinkArgBenches(string("FLAG")?),
+            "rustc-link-lib" => Self::RustcLinkLib(parse_link_lib(value)?),
+            "rustc-link-search" => {
+                string("[KIND=]PATH")?;
+                Self::RustcLinkSearch(parse_link_search(value))
+            }
+            "rustc-flags" => Self::RustcFlags(string("FLAGS")?),
+            "rustc-cfg" => {
+                const EXPECTED: &str = "NAME or NAME=\"VALUE\"";
+                let (name, value) = match value.split_once('=') {
+                    Some((name, quoted)) => {
+                        let value = parse_string(quoted).ok_or_else(|| invalid(EXPECTED))?;
+                        (name, Some(value))
+                    }
+                    None => (value, None),
+                };
+                if !is_identifier(name) {
+                    return Err(invalid(EXPECTED));
+                }
+                Self::RustcCfg {
+                    name: name.to_string(),
+                    value,
+                }
+            }
+            "rustc-check-cfg" => parse_check_cfg(value)
+                .ok_or_else(|| invalid("cfg(NAME) or cfg(NAME, values(\"VALUE\", ...))"))?,
+            "rustc-env" => {
+                let (var, value) = pair("VAR=VALUE")?;
+                Self::RustcEnv { var, value }
+            }
+            "metadata" => {
+                let (key, value) = pair("KEY=VALUE")?;
+                Self::Metadata { key, value }
+            }
+            "warning" => Self::Warning(value.to_string()),
+            "error" => Self::Error(value.to_string()),
+            _ => return Ok(None),
+        }))
+    }
+
+    /// Returns `KEY` of the instruction, e.g. `rerun-if-changed`.
+    pub fn key(&self) -> &str {
+        match self {
+            Self::RerunIfChanged(_) => "rerun-if-changed",
+            Self::RerunIfEnvChanged(_) => "rerun-if-env-changed",
+            Self::RustcLinkArg(_) => "rustc-link-arg",
+            Self::RustcLinkArgCdylib(_) => "rustc-link-arg-cdylib",
+            Self::RustcLinkArgBin { .. } => "rustc-link-arg-bin",
+            Self::RustcLinkArgBins(_) => "rustc-link-arg-bins",
+            Self::RustcLinkArgTests(_) => "rustc-link-arg-tests",
+            Self::RustcLinkArgExamples(_) => "rustc-link-arg-examples",
+            Self::RustcLinkArgBenches(_) => "rustc-link-arg-benches",
+            Self::RustcLinkLib(_) => "rustc-link-lib",
+            Self::RustcLinkSearch(_) => "rustc-link-search",
+            Self::RustcFlags(_) => "rustc-flags",
+            Self::RustcCfg { .. } => "rustc-cfg",
+            Self::RustcCheckCfg { .. } => "rustc-check-cfg",
+            Self::RustcEnv { .. } => "rustc-env",
+            Self::Metadata { .. } => "metadata",
+            Self::Warning(_) => "warning",
+            Self::Error(_) => "error",
+            Self::Other { key, .. } => key,
+        }
+    }
+
+    /// Returns first Cargo version which understands the instruction, e.g. `1.84` for `error`.
+    /// `None` if every Cargo version does.
+    pub fn min_cargo_version(&self) -> Option<&'static str> {
+        match self {
+            Self::RustcCheckCfg { .. } => Some("1.80"),
+            Self::Other { key, .. } if key == "rustc-check-cfg" => Some("1.80"),
+            Self::Error(_) => Some("1.84"),
+            _ => None,
+        }
+    }
+
+    /// Returns `VALUE` of the instruction, e.g. `src/main.c`.
+    pub fn value(&self) -> Cow<'_, str> {
+        match self {
+            Self::RerunIfEnvChanged(value)
+            | Self::RustcLinkArg(value)
+            | Self::RustcLinkArgCdylib(value)
+            | Self::RustcLinkArgBins(value)
+            | Self::RustcLinkArgTests(value)
+            | Self::RustcLinkArgExamples(value)
+            | Self::RustcLinkArgBenches(value)
+            | Self::RustcFlags(value)
+            | Self::Warning(value)
+            | Self::Error(value)
+            | Self::Other { value, .. } => Cow::Borrowed(value),
+            Self::RustcCfg { name, value: None } => Cow::Borrowed(name),
+            _ => Cow::Owned(Value(self).to_string()),
+        }
+    }
+}
+
+impl From<LinkLib> for Instruction {
+    fn from(lib: LinkLib) -> Self {
+        Self::RustcLinkLib(lib)
+    }
+}
+
+impl From<LinkSearch> for Instruction {
+    fn from(search: LinkSearch) -> Self {
+        Self::RustcLinkSearch(search)
+    }
+}
+
+/// Formats `VALUE` of the instruction without allocating.
+pub(crate) struct Value<'a>(pub(crate) &'a Instruction);
+
+impl Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Instruction::RerunIfChanged(path) => write!(f, "{}", path.display()),
+            Instruction::RustcLinkArgBin { bin, flag } => write!(f, "{bin}={flag}"),
+            Instruction::RustcLinkLib(lib) => write!(f, "{lib}"),
+            Instruction::RustcLinkSearch(search) => write!(f, "{search}"),
+            Instruction::RustcCfg {
+                name,
+                value: Some(value),
+            } => {
+                write!(f, "{name}=")?;
+                write_string(f, value)
+            }
+            Instruction::RustcCheckCfg { name, values } if values.is_empty() => {
+                write!(f, "cfg({name})")
+            }
+            Instruction::RustcCheckCfg { name, values } => {
+                write!(f, "cfg({name}, values(")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i != 0 {
+                        f.write_str(", ")?;
+                    }
+                    write_string(f, value)?;
+                }
+                f.write_str("))")
+            }
+            Instruction::RustcEnv { var: key, value } | Instruction::Metadata { key, value } => {
+                write!(f, "{key}={value}")
+            }
+            instruction => f.write_str(&instruction.value()),
+        }
+    }
+}
+
+/// Writes `"VALUE"` with `"` and `\` escaped, which [`parse_string`] reads back.
+fn write_string(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in value.chars() {
+        if matches!(c, '"' | '\\') {
+            f.write_char('\\')?;
+        }
+        f.write_char(c)?;
+    }
+    f.write_char('"')
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cargo::{}={}", self.key(), Value(self))
+    }
+}
+
+/// Parses single line of build script output, with or without trailing newline.
+///
+/// Both `cargo::KEY=VALUE` and legacy `cargo:KEY=VALUE` syntax are accepted. Unknown keys produce
+/// [`Instruction::Other`], or [`Instruction::Metadata`] in legacy syntax, where unknown keys are
+/// metadata.
+///
+/// ```rust
+/// use cargo_build::{Instruction, LinkKind, LinkLib, ParseInstructionError};
+///
+/// let instruction: Instruction = "cargo::rustc-link-lib=static:+whole-archive=foo".parse().unwrap();
+///
+/// assert_eq!(
+///     instruction,
+///     Instruction::RustcLinkLib(LinkLib::new("foo").kind(LinkKind::Static).modifier("+whole-archive"))
+/// );
+///
+/// let err = "cargo::rustc-link-lib=shared=foo".parse::<Instruction>().unwrap_err();
+///
+/// assert_eq!(
+///     err.to_string(),
+///     "Unknown kind `shared` of `rustc-link-lib`, expected one of `dylib`, `static`, `framework`"
+/// );
+/// ```
+impl FromStr for Instruction {
+    type Err = ParseInstructionError;
+
+    fn from_str(line: &str) -> Result<Self, Self::Err> {
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+
+        let (rest, legacy) = match line.strip_prefix("cargo::") {
+            Some(rest) => (rest, false),
+            None => match line.strip_prefix("cargo:") {
+                Some(rest) => (rest, true),
+                None => return Err(ParseInstructionError::MissingPrefix),
+            },
+        };
+        let (key, value) = rest
+            .split_once('=')
+            .ok_or(ParseInstructionError::MissingEquals)?;
+
+        if key.is_empty()
+            || !key
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(ParseInstructionError::InvalidKey(key.to_string()));
+        }
+
+        // Old name of `rustc-link-arg-cdylib`, still accepted by Cargo
+        let key = match key {
+            "rustc-cdylib-link-arg" => "rustc-link-arg-cdylib",
+            key => key,
+        };
+
+        Ok(match Self::typed(key, value)? {
+            Some(instruction) => instruction,
+            None if legacy => Self::Metadata {
+                key: key.to_string(),
+                value: value.to_string(),
+            },
+            None => Self::Other {
+                key: key.to_string(),
+                value: value.to_string(),
+            },
+        })
+    }
+}
+
+/// Error returned when parsing [`Instruction`] fails.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseInstructionError {
+    /// Line doesn't start with `cargo::` or `cargo:`.
+    MissingPrefix,
+    /// Line has no `=` between `KEY` and `VALUE`.
+    MissingEquals,
+    /// `KEY` is empty or contains characters other than ASCII letters, digits, `-` and `_`.
+    InvalidKey(String),
+    /// `VALUE` doesn't have the form required by `KEY`.
+    InvalidValue {
+        key: String,
+        value: String,
+        expected: &'static str,
+    },
+    /// `KIND` of `rustc-link-lib` is not `dylib`, `static` or `framework`.
+    UnknownLinkKind(String),
+}
+
+impl Display for ParseInstructionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingPrefix => f.write_str("Instruction must start with `cargo::` or `cargo:`"),
+            Self::MissingEquals => f.write_str("Instruction must have `KEY=VALUE` form"),
+            Self::InvalidKey(key) => write!(
+                f,
+                "Invalid key `{key}`, keys may only contain ASCII letters, digits, `-` and `_`"
+            ),
+            Self::InvalidValue {
+                key,
+                value,
+                expected,
+            } => write!(f, "Invalid value `{value}` of `{key}`, expected {expected}"),
+            Self::UnknownLinkKind(kind) => write!(
+                f,
+                "Unknown kind `{kind}` of `rustc-link-lib`, expected one of `dylib`, `static`, `framework`"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ParseInstructionError {}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c == '_' || c.is_alphabetic())
+        && chars.all(|c| c == '_' || c.is_alphanumeric())
+}
+
+fn unquote(s: &str) -> Option<&str> {
+    s.strip_prefix('"')?.strip_suffix('"')
+}
+
+fn parse_link_lib(value: &str) -> Result<LinkLib, ParseInstructionError> {
+    let (kind, modifiers, lib) = match value.split_once('=') {
+        Some((kind, lib)) => {
+            let (kind, modifiers) = match kind.split_once(':') {
+                Some((kind, modifiers)) => (kind, modifiers.split(',').map(String::from).collect()),
+                None => (kind, Vec::new()),
+            };
+            let kind = match kind {
+                "dylib" => LinkKind::Dylib,
+                "static" => LinkKind::Static,
+                "framework" => LinkKind::Framework,
+                _ => return Err(ParseInstructionError::UnknownLinkKind(kind.to_string())),
+            };
+            (Some(kind), modifiers, lib)
+        }
+        None => (None, Vec::new(), value),
+    };
+    let (name, rename) = match lib.split_once(':') {
+        Some((name, rename)) => (name, Some(rename.to_string())),
+        None => (lib, None),
+    };
+    if name.is_empty() {
+        return Err(ParseInstructionError::InvalidValue {
+            key: "rustc-link-lib".to_string(),
+            value: value.to_string(),
+            expected: "[KIND[:MODIFIERS]=]NAME[:RENAME]",
+        });
+    }
+
+    Ok(LinkLib {
+        kind,
+        modifiers,
+        name: name.to_string(),
+        rename,
+    })
+}
+
+fn parse_link_search(value: &str) -> LinkSearch {
+    let search = value
+        .split_once('=')
+        .and_then(|(kind, path)| Some((SearchKind::from_name(kind)?, path)));
+    match search {
+        Some((kind, path)) => LinkSearch {
+            kind: Some(kind),
+            path: PathBuf::from(path),
+        },
+        None => LinkSearch::new(value),
+    }
+}
+
+fn parse_check_cfg(value: &str) -> Option<Instruction> {
+    let args = split_args(value.strip_prefix("cfg(")?.strip_suffix(')')?)?;
+
+    let (names, values) = match args.split_last() {
+        Some((last, names)) if last.starts_with("values(") => {
+            let list = last.strip_prefix("values(")?.strip_suffix(')')?;
+            let values = split_args(list)?
+                .into_iter()
+                .map(|value| match value {
+                    "none()" | "any()" => Some(None),
+                    value => parse_string(value).map(Some),
+                })
+                .collect::<Option<Vec<_>>>()?;
+            (names, Some(values))
+        }
+        _ => (&args[..], None),
+    };
+    if !names.iter().all(|name| is_identifier(name)) {
+        return None;
+    }
+
+    let values = match values {
+        None => Some(Vec::new()),
+        Some(values) if values.is_empty() => None,
+        Some(values) => values.into_iter().collect::<Option<Vec<_>>>(),
+    };
+    match (names, values) {
+        ([name], Some(values)) => Some(Instruction::RustcCheckCfg {
+            name: name.to_string(),
+            values,
+        }),
+        _ => Some(Instruction::Other {
+            key: "rustc-check-cfg".to_string(),
+            value: value.to_string(),
+        }),
+    }
+}
+
+/// Returns names declared by untyped `rustc-check-cfg` value, e.g. `a` and `b` for `cfg(a, b)`.
+pub(crate) fn check_cfg_names(value: &str) -> Vec<&str> {
+    let args = value
+        .strip_prefix("cfg(")
+        .and_then(|args| args.strip_suffix(')'))
+        .and_then(split_args)
+        .unwrap_or_default();
+    args.into_iter().filter(|arg| is_identifier(arg)).collect()
+}
+
+/// Splits comma-separated arguments, skipping commas inside string literals and parentheses.
+fn split_args(args: &str) -> Option<Vec<&str>> {
+    let mut parts = Vec::new();
+    let (mut start, mut depth) = (0, 0usize);
+    let (mut in_string, mut escaped) = (false, false);
+    for (i, c) in args.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            _ if in_string => {}
+            '(' => depth += 1,
+            ')' => depth = depth.checked_sub(1)?,
+            ',' if depth == 0 => {
+                parts.push(args[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if in_string || depth != 0 {
+        return None;
+    }
+    let last = args[start..].trim();
+    if !last.is_empty() {
+        parts.push(last);
+    }
+    if parts.iter().any(|part| part.is_empty()) {
+        return None;
+    }
+    Some(parts)
+}
+
+/// Parses `"VALUE"` string literal, resolving `\"` and `\\` escapes.
+fn parse_string(s: &str) -> Option<String> {
+    let mut chars = unquote(s)?.chars();
+    let mut string = String::new();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next()? {
+                c @ ('"' | '\\') => string.push(c),
+                'n' => string.push('\n'),
+                't' => string.push('\t'),
+                _ => return None,
+            },
+            '"' => return None,
+            c => string.push(c),
+        }
+    }
+    Some(string)
+}
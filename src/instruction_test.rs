@@ -0,0 +1,334 @@
+use crate::Instruction;
+
+#[test]
+fn instruction_display_test() {
+    let instruction = Instruction::new("rustc-link-lib", "static:+whole-archive=foo");
+
+    assert_eq!(instruction.key(), "rustc-link-lib");
+    assert_eq!(instruction.value(), "static:+whole-archive=foo");
+    assert_eq!(
+        instruction.to_string(),
+        "cargo::rustc-link-lib=static:+whole-archive=foo"
+    );
+}
+
+#[test]
+fn instruction_new_typed_test() {
+    use crate::{LinkKind, LinkLib, LinkSearch, SearchKind};
+
+    assert_eq!(
+        Instruction::new("rerun-if-changed", "src/main.c"),
+        Instruction::RerunIfChanged("src/main.c".into())
+    );
+    assert_eq!(
+        Instruction::new("rustc-link-lib", "static:+whole-archive=foo:bar"),
+        Instruction::RustcLinkLib(
+            LinkLib::new("foo")
+                .kind(LinkKind::Static)
+                .modifier("+whole-archive")
+                .rename("bar")
+        )
+    );
+    assert_eq!(
+        Instruction::new("rustc-link-search", "native=libs"),
+        Instruction::RustcLinkSearch(LinkSearch::new("libs").kind(SearchKind::Native))
+    );
+    assert_eq!(
+        Instruction::new("rustc-check-cfg", "cfg(api, values(\"v1\", \"v2\"))"),
+        Instruction::RustcCheckCfg {
+            name: "api".to_string(),
+            values: vec!["v1".to_string(), "v2".to_string()],
+        }
+    );
+    assert_eq!(
+        Instruction::new("rustc-env", "API_VERSION=3"),
+        Instruction::RustcEnv {
+            var: "API_VERSION".to_string(),
+            value: "3".to_string(),
+        }
+    );
+
+    // Values which don't fit typed variant
+    assert_eq!(
+        Instruction::new("rustc-cfg", "feature=std"),
+        Instruction::Other {
+            key: "rustc-cfg".to_string(),
+            value: "feature=std".to_string(),
+        }
+    );
+    assert_eq!(
+        Instruction::new("rustc-link-lib", "shared=foo").key(),
+        "rustc-link-lib"
+    );
+}
+
+#[test]
+fn instruction_round_trip_test() {
+    for (key, value) in [
+        ("rerun-if-changed", "build.rs"),
+        ("rerun-if-env-changed", "CC"),
+        ("rustc-link-arg-bin", "app=-Wl,-z,now"),
+        ("rustc-link-lib", "framework=CoreFoundation"),
+        ("rustc-link-lib", "dylib:+verbatim,-bundle=foo"),
+        ("rustc-link-search", "libs"),
+        ("rustc-cfg", "has_foo"),
+        ("rustc-cfg", "feature=\"std\""),
+        ("rustc-check-cfg", "cfg(has_foo)"),
+        (
+            "rustc-check-cfg",
+            "cfg(feature, values(\"std\", \"alloc\"))",
+        ),
+        ("metadata", "include=/usr/include"),
+        ("warning", "libfoo not found"),
+        ("new-thing", "value"),
+    ] {
+        let instruction = Instruction::new(key, value);
+
+        assert_eq!(instruction.key(), key);
+        assert_eq!(instruction.value(), value);
+        assert_eq!(instruction.to_string(), format!("cargo::{key}={value}"));
+    }
+}
+
+#[test]
+fn functions_build_instructions_test() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate as cargo_build;
+    use crate::build_out::DirectiveSink;
+
+    struct Recorder(Rc<RefCell<Vec<Instruction>>>);
+
+    impl DirectiveSink for Recorder {
+        fn emit(&mut self, instruction: &Instruction) -> std::io::Result<()> {
+            self.0.borrow_mut().push(instruction.clone());
+            Ok(())
+        }
+    }
+
+    let recorded = Rc::new(RefCell::new(Vec::new()));
+    cargo_build::build_out::set_sink(Recorder(recorded.clone()));
+
+    cargo_build::rerun_if_changed(["build.rs"]);
+    cargo_build::rustc_cfg(("feature", "std"));
+    cargo_build::rustc_link_lib_static(["+whole-archive"], ["foo"]);
+    cargo_build::rustc_check_cfg("api", ["v1"]);
+    cargo_build::warning("careful");
+
+    cargo_build::build_out::reset_sink();
+
+    assert_eq!(
+        *recorded.borrow(),
+        [
+            Instruction::RerunIfChanged("build.rs".into()),
+            Instruction::RustcCfg {
+                name: "feature".to_string(),
+                value: Some("std".to_string()),
+            },
+            Instruction::new("rustc-link-lib", "static:+whole-archive=foo"),
+            Instruction::RustcCheckCfg {
+                name: "api".to_string(),
+                values: vec!["v1".to_string()],
+            },
+            Instruction::Warning("careful".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn instruction_from_str_test() {
+    use crate::{LinkKind, LinkLib};
+
+    assert_eq!(
+        "cargo::rerun-if-changed=build.rs\n".parse::<Instruction>(),
+        Ok(Instruction::RerunIfChanged("build.rs".into()))
+    );
+    assert_eq!(
+        "cargo:rustc-link-lib=framework=CoreFoundation".parse::<Instruction>(),
+        Ok(Instruction::RustcLinkLib(
+            LinkLib::new("CoreFoundation").kind(LinkKind::Framework)
+        ))
+    );
+    assert_eq!(
+        "cargo::rustc-check-cfg=cfg(api, values(\"v1\"))".parse::<Instruction>(),
+        Ok(Instruction::RustcCheckCfg {
+            name: "api".to_string(),
+            values: vec!["v1".to_string()],
+        })
+    );
+
+    // Unknown keys
+    assert_eq!(
+        "cargo::new-thing=value".parse::<Instruction>(),
+        Ok(Instruction::Other {
+            key: "new-thing".to_string(),
+            value: "value".to_string(),
+        })
+    );
+    assert_eq!(
+        "cargo:include=/usr/include".parse::<Instruction>(),
+        Ok(Instruction::Metadata {
+            key: "include".to_string(),
+            value: "/usr/include".to_string(),
+        })
+    );
+}
+
+#[test]
+fn instruction_from_str_check_cfg_test() {
+    assert_eq!(
+        "cargo::rustc-check-cfg=cfg(api, values(\"a,b\", \"c\"))".parse::<Instruction>(),
+        Ok(Instruction::RustcCheckCfg {
+            name: "api".to_string(),
+            values: vec!["a,b".to_string(), "c".to_string()],
+        })
+    );
+
+    // Forms without typed representation
+    for value in [
+        "cfg(foo, values(none(), \"bar\"))",
+        "cfg(foo, values(any()))",
+        "cfg(foo, values())",
+        "cfg(a, b)",
+        "cfg(a, b, values(\"x\"))",
+        "cfg()",
+    ] {
+        let instruction = format!("cargo::rustc-check-cfg={value}")
+            .parse::<Instruction>()
+            .unwrap();
+
+        assert_eq!(instruction, Instruction::new("rustc-check-cfg", value));
+        assert_eq!(instruction.key(), "rustc-check-cfg");
+        assert_eq!(instruction.value(), value);
+        assert_eq!(instruction.min_cargo_version(), Some("1.80"));
+    }
+}
+
+#[test]
+fn check_cfg_round_trip_test() {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate as cargo_build;
+    use crate::build_out::DirectiveSink;
+
+    struct Recorder(Rc<RefCell<Vec<Instruction>>>);
+
+    impl DirectiveSink for Recorder {
+        fn emit(&mut self, instruction: &Instruction) -> std::io::Result<()> {
+            self.0.borrow_mut().push(instruction.clone());
+            Ok(())
+        }
+    }
+
+    let recorded = Rc::new(RefCell::new(Vec::new()));
+    cargo_build::build_out::set_sink(Recorder(recorded.clone()));
+
+    cargo_build::rustc_check_cfg("api", ["a,b", "say \"hi\"", "C:\\"]);
+
+    cargo_build::build_out::reset_sink();
+
+    let instruction = recorded.borrow()[0].clone();
+    assert_eq!(
+        instruction.to_string(),
+        r#"cargo::rustc-check-cfg=cfg(api, values("a,b", "say \"hi\"", "C:\\"))"#
+    );
+    assert_eq!(
+        instruction.to_string().parse::<Instruction>(),
+        Ok(instruction)
+    );
+}
+
+#[test]
+fn cfg_round_trip_test() {
+    let instruction = Instruction::RustcCfg {
+        name: "api".to_string(),
+        value: Some("say \"hi\" C:\\".to_string()),
+    };
+
+    assert_eq!(
+        instruction.to_string(),
+        r#"cargo::rustc-cfg=api="say \"hi\" C:\\""#
+    );
+    assert_eq!(
+        instruction.to_string().parse::<Instruction>(),
+        Ok(instruction)
+    );
+}
+
+#[test]
+fn instruction_from_str_error_test() {
+    use crate::ParseInstructionError;
+
+    assert_eq!(
+        "rustc-cfg=foo".parse::<Instruction>(),
+        Err(ParseInstructionError::MissingPrefix)
+    );
+    assert_eq!(
+        "cargo::rustc-cfg".parse::<Instruction>(),
+        Err(ParseInstructionError::MissingEquals)
+    );
+    assert_eq!(
+        "cargo::rustc cfg=foo".parse::<Instruction>(),
+        Err(ParseInstructionError::InvalidKey("rustc cfg".to_string()))
+    );
+    assert_eq!(
+        "cargo::rustc-link-lib=shared:+verbatim=foo".parse::<Instruction>(),
+        Err(ParseInstructionError::UnknownLinkKind("shared".to_string()))
+    );
+
+    let err = "cargo::rustc-cfg=feature=std"
+        .parse::<Instruction>()
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid value `feature=std` of `rustc-cfg`, expected NAME or NAME=\"VALUE\""
+    );
+
+    let err = "cargo::rustc-check-cfg=cfg(api, values(v1))"
+        .parse::<Instruction>()
+        .unwrap_err();
+    assert_eq!(
+        err,
+        ParseInstructionError::InvalidValue {
+            key: "rustc-check-cfg".to_string(),
+            value: "cfg(api, values(v1))".to_string(),
+            expected: "cfg(NAME) or cfg(NAME, values(\"VALUE\", ...))",
+        }
+    );
+
+    let err = "cargo::rustc-env=API_VERSION"
+        .parse::<Instruction>()
+        .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Invalid value `API_VERSION` of `rustc-env`, expected VAR=VALUE"
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn instruction_serde_test() {
+    use crate::{LinkKind, LinkLib};
+
+    let instructions = vec![
+        Instruction::RerunIfChanged("build.rs".into()),
+        Instruction::RustcLinkLib(LinkLib::new("foo").kind(LinkKind::Static)),
+        Instruction::RustcCfg {
+            name: "feature".to_string(),
+            value: Some("std".to_string()),
+        },
+    ];
+
+    let json = serde_json::to_string(&instructions).unwrap();
+
+    assert_eq!(
+        json,
+        r#"[{"kind":"rerun-if-changed","value":"build.rs"},{"kind":"rustc-link-lib","value":{"kind":"static","modifiers":[],"name":"foo","rename":null}},{"kind":"rustc-cfg","value":{"name":"feature","value":"std"}}]"#
+    );
+    assert_eq!(
+        serde_json::from_str::<Vec<Instruction>>(&json).unwrap(),
+        instructions
+    );
+}
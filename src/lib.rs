@@ -4,14 +4,177 @@
 mod macros;
 // pub use macros::*; no need because #[macro_export] exports them from crate root
 
+mod collector;
+pub use collector::ErrorCollector;
+
+mod diff;
+pub use diff::{diff, DirectiveDiff};
+
+mod error;
+pub use error::Error;
+
 mod functions;
 pub use functions::*;
 
+mod grammar;
+
+mod instruction;
+pub use instruction::{Instruction, ParseInstructionError};
+
+mod link;
+pub use link::*;
+
+mod lint;
+pub use lint::{lint, LintWarning};
+
+mod paths;
+
+mod probe_cache;
+
+mod runner;
+pub use runner::{run, MainResult};
+
+#[cfg(feature = "main")]
+pub use cargo_build_macros::main;
+
+mod session;
+pub use session::{
+    instrument, set_error_policy, set_instrument_threshold, set_rerun_policy, ErrorPolicy,
+    RerunPolicy, Session,
+};
+
+mod sha256;
+
+mod triple;
+pub use triple::Triple;
+
 pub mod build_out;
 
+pub mod env;
+
+pub mod fetch;
+
+pub mod presets;
+
+pub mod macos;
+
+pub mod linux;
+
+pub mod vendor;
+
+pub mod parallel;
+
+pub mod prelude;
+
+pub mod ci;
+
+pub mod config;
+
+pub mod deps;
+
+pub mod toolchain;
+
+pub mod out;
+
+pub mod parse;
+
+pub mod report;
+
+pub mod syntax;
+
+#[cfg(test)]
+mod build_out_test;
+
+#[cfg(test)]
+mod ci_test;
+
+#[cfg(test)]
+mod collector_test;
+
+#[cfg(test)]
+mod config_test;
+
+#[cfg(test)]
+mod deps_test;
+
+#[cfg(test)]
+mod diff_test;
+
+#[cfg(test)]
+mod env_test;
+
+#[cfg(test)]
+mod error_test;
+
+#[cfg(test)]
+mod fetch_test;
+
 #[cfg(test)]
 mod functions_test;
 
+#[cfg(test)]
+mod grammar_test;
+
+#[cfg(test)]
+mod instruction_test;
+
+#[cfg(test)]
+mod link_test;
+
+#[cfg(test)]
+mod lint_test;
+
+#[cfg(test)]
+mod linux_test;
+
+#[cfg(test)]
+mod macos_test;
+
+#[cfg(test)]
+mod out_test;
+
+#[cfg(test)]
+mod parallel_test;
+
+#[cfg(test)]
+mod parse_test;
+
+#[cfg(test)]
+mod probe_cache_test;
+
+#[cfg(test)]
+mod paths_test;
+
+#[cfg(test)]
+mod prelude_test;
+
+#[cfg(test)]
+mod presets_test;
+
+#[cfg(test)]
+mod report_test;
+
+#[cfg(test)]
+mod runner_test;
+
+#[cfg(test)]
+mod session_test;
+
+#[cfg(test)]
+mod sha256_test;
+
+#[cfg(test)]
+mod syntax_test;
+
+#[cfg(test)]
+mod toolchain_test;
+
+#[cfg(test)]
+mod triple_test;
+
+#[cfg(test)]
+mod vendor_test;
+
 #[cfg(test)]
 #[cfg(feature = "macros")]
 mod macros_test;
@@ -0,0 +1,181 @@
+use std::fmt::{self, Display};
+use std::path::PathBuf;
+
+/// Kind of the library passed to `rustc` with `-l KIND=NAME`.
+///
+/// See [`LinkLib`] and [`rustc_link_lib`](crate::rustc_link_lib).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum LinkKind {
+    Dylib,
+    Static,
+    Framework,
+}
+
+impl Display for LinkKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            LinkKind::Dylib => "dylib",
+            LinkKind::Static => "static",
+            LinkKind::Framework => "framework",
+        })
+    }
+}
+
+/// Kind of the library search path passed to `rustc` with `-L KIND=PATH`.
+///
+/// See [`LinkSearch`] and [`rustc_link_search`](crate::rustc_link_search).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum SearchKind {
+    Dependency,
+    Crate,
+    Native,
+    Framework,
+    All,
+}
+
+impl SearchKind {
+    /// Parses `KIND` of `-L KIND=PATH`.
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "dependency" => Some(SearchKind::Dependency),
+            "crate" => Some(SearchKind::Crate),
+            "native" => Some(SearchKind::Native),
+            "framework" => Some(SearchKind::Framework),
+            "all" => Some(SearchKind::All),
+            _ => None,
+        }
+    }
+}
+
+impl Display for SearchKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            SearchKind::Dependency => "dependency",
+            SearchKind::Crate => "crate",
+            SearchKind::Native => "native",
+            SearchKind::Framework => "framework",
+            SearchKind::All => "all",
+        })
+    }
+}
+
+/// Typed library to link. Renders as `[KIND[:MODIFIERS]=]NAME[:RENAME]`.
+///
+/// ```rust
+/// use cargo_build::{LinkKind, LinkLib};
+///
+/// let lib = LinkLib::new("mylib")
+///     .kind(LinkKind::Static)
+///     .modifier("+whole-archive")
+///     .modifier("-bundle")
+///     .rename("renamed_lib");
+///
+/// assert_eq!(lib.to_string(), "static:+whole-archive,-bundle=mylib:renamed_lib");
+/// ```
+///
+/// See [`rustc_flags_from`](crate::rustc_flags_from).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LinkLib {
+    pub kind: Option<LinkKind>,
+    pub modifiers: Vec<String>,
+    pub name: String,
+    pub rename: Option<String>,
+}
+
+impl LinkLib {
+    /// Creates library without `KIND`, modifiers and rename.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            kind: None,
+            modifiers: Vec::new(),
+            name: name.into(),
+            rename: None,
+        }
+    }
+
+    /// Sets `KIND` of the library.
+    pub fn kind(mut self, kind: LinkKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Adds linking modifier such as `+whole-archive`, `-bundle` or `+verbatim`.
+    ///
+    /// Modifiers require `KIND`, [`emit`](crate::emit) and [`rustc_flags_from`](crate::rustc_flags_from)
+    /// reject library with modifiers but without `KIND`.
+    pub fn modifier(mut self, modifier: impl Into<String>) -> Self {
+        self.modifiers.push(modifier.into());
+        self
+    }
+
+    /// Links library under different name.
+    pub fn rename(mut self, rename: impl Into<String>) -> Self {
+        self.rename = Some(rename.into());
+        self
+    }
+}
+
+impl Display for LinkLib {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(kind) = self.kind {
+            write!(f, "{kind}")?;
+            if !self.modifiers.is_empty() {
+                write!(f, ":{}", self.modifiers.join(","))?;
+            }
+            f.write_str("=")?;
+        }
+        f.write_str(&self.name)?;
+        if let Some(rename) = &self.rename {
+            write!(f, ":{rename}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Typed library search path. Renders as `[KIND=]PATH`.
+///
+/// ```rust
+/// use cargo_build::{LinkSearch, SearchKind};
+///
+/// let path = LinkSearch::new("libs").kind(SearchKind::Native);
+///
+/// assert_eq!(path.to_string(), "native=libs");
+/// ```
+///
+/// See [`rustc_flags_from`](crate::rustc_flags_from).
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LinkSearch {
+    pub kind: Option<SearchKind>,
+    pub path: PathBuf,
+}
+
+impl LinkSearch {
+    /// Creates search path without `KIND`.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            kind: None,
+            path: path.into(),
+        }
+    }
+
+    /// Sets `KIND` of the search path.
+    pub fn kind(mut self, kind: SearchKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+}
+
+impl Display for LinkSearch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(kind) = self.kind {
+            write!(f, "{kind}=")?;
+        }
+        write!(f, "{}", self.path.display())
+    }
+}
@@ -0,0 +1,42 @@
+use crate as cargo_build;
+
+use cargo_build::{LinkKind, LinkLib, LinkSearch, SearchKind};
+
+#[test]
+fn link_lib_display_test() {
+    assert_eq!(LinkLib::new("z").to_string(), "z");
+    assert_eq!(LinkLib::new("z").rename("zlib").to_string(), "z:zlib");
+    assert_eq!(
+        LinkLib::new("z").kind(LinkKind::Dylib).to_string(),
+        "dylib=z"
+    );
+    assert_eq!(
+        LinkLib::new("Cocoa").kind(LinkKind::Framework).to_string(),
+        "framework=Cocoa"
+    );
+    assert_eq!(
+        LinkLib::new("z")
+            .kind(LinkKind::Static)
+            .modifier("+whole-archive")
+            .modifier("-bundle")
+            .rename("zlib")
+            .to_string(),
+        "static:+whole-archive,-bundle=z:zlib"
+    );
+}
+
+#[test]
+fn link_search_display_test() {
+    assert_eq!(LinkSearch::new("libs").to_string(), "libs");
+
+    let kinds = [
+        (SearchKind::Dependency, "dependency=libs"),
+        (SearchKind::Crate, "crate=libs"),
+        (SearchKind::Native, "native=libs"),
+        (SearchKind::Framework, "framework=libs"),
+        (SearchKind::All, "all=libs"),
+    ];
+    for (kind, expected) in kinds {
+        assert_eq!(LinkSearch::new("libs").kind(kind).to_string(), expected);
+    }
+}
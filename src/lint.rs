@@ -0,0 +1,219 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display};
+use std::path::PathBuf;
+
+use crate::functions::is_missing_links_key;
+use crate::paths::BuildDirs;
+use crate::{Instruction, LinkKind};
+
+/// Problem found by [`lint`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum LintWarning {
+    /// `rustc-cfg` name without `rustc-check-cfg` declaration, reported by `unexpected_cfgs` lint.
+    UndeclaredCfg { name: String },
+    /// `rustc-cfg` value missing from `values(...)` of its `rustc-check-cfg` declaration.
+    UndeclaredCfgValue { name: String, value: String },
+    /// `rustc-link-lib` without any `rustc-link-search`. Fine for system libraries, but libraries built
+    /// or bundled by the build script usually fail to link.
+    LinkLibWithoutSearch { lib: String },
+    /// `metadata` is ignored because the package doesn't declare `links` key in `Cargo.toml`.
+    MetadataWithoutLinks { key: String },
+    /// `rustc-env` sets the same variable to different values. Only the last one is used.
+    ConflictingEnv { var: String, values: Vec<String> },
+    /// `metadata` sets the same key to different values. Only the last one is used.
+    ConflictingMetadata { key: String, values: Vec<String> },
+    /// `rerun-if-changed` path inside `OUT_DIR`, `CARGO_TARGET_DIR` or `target` directory. It changes on
+    /// every build, so the build script reruns on every build.
+    RerunInsideBuildDir { path: PathBuf, dir: PathBuf },
+}
+
+impl Display for LintWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UndeclaredCfg { name } => write!(
+                f,
+                "cfg `{name}` is set without `rustc-check-cfg` declaration"
+            ),
+            Self::UndeclaredCfgValue { name, value } => write!(
+                f,
+                "cfg `{name}=\"{value}\"` is set, but value `{value}` is not declared with `rustc-check-cfg`"
+            ),
+            Self::LinkLibWithoutSearch { lib } => write!(
+                f,
+                "library `{lib}` is linked without any `rustc-link-search` path"
+            ),
+            Self::MetadataWithoutLinks { key } => write!(
+                f,
+                "metadata `{key}` is ignored because the package doesn't declare `links` key in Cargo.toml"
+            ),
+            Self::ConflictingEnv { var, values } => write!(
+                f,
+                "env variable `{var}` is set to different values: {}",
+                quoted(values)
+            ),
+            Self::ConflictingMetadata { key, values } => write!(
+                f,
+                "metadata `{key}` is set to different values: {}",
+                quoted(values)
+            ),
+            Self::RerunInsideBuildDir { path, dir } => write!(
+                f,
+                "`rerun-if-changed` path `{}` is inside `{}` which changes on every build",
+                path.display(),
+                dir.display()
+            ),
+        }
+    }
+}
+
+fn quoted(values: &[String]) -> String {
+    values
+        .iter()
+        .map(|value| format!("`{value}`"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Checks instructions for problems which only show up across multiple instructions.
+///
+/// Instructions can be recorded with [`build_out::recording`](crate::build_out::recording).
+/// Metadata is checked against `links` key of the package and `rerun-if-changed` paths against `OUT_DIR`
+/// and target directory when called from the build script.
+///
+/// ```rust
+/// use cargo_build::{Instruction, LintWarning};
+///
+/// let instructions = [
+///     Instruction::new("rustc-check-cfg", "cfg(has_foo)"),
+///     Instruction::new("rustc-cfg", "has_foo"),
+///     Instruction::new("rustc-cfg", "has_bar"),
+///     Instruction::new("rustc-env", "API_VERSION=2"),
+///     Instruction::new("rustc-env", "API_VERSION=3"),
+/// ];
+///
+/// assert_eq!(
+///     cargo_build::lint(&instructions),
+///     [
+///         LintWarning::UndeclaredCfg { name: "has_bar".to_string() },
+///         LintWarning::ConflictingEnv {
+///             var: "API_VERSION".to_string(),
+///             values: vec!["2".to_string(), "3".to_string()],
+///         },
+///     ]
+/// );
+/// ```
+pub fn lint(instructions: &[Instruction]) -> Vec<LintWarning> {
+    lint_with(
+        instructions,
+        is_missing_links_key(|var| std::env::var_os(var).is_some()),
+        &BuildDirs::from_env(|var| std::env::var_os(var)),
+    )
+}
+
+pub(crate) fn lint_with(
+    instructions: &[Instruction],
+    missing_links: bool,
+    build_dirs: &BuildDirs,
+) -> Vec<LintWarning> {
+    let mut warnings = Vec::new();
+
+    // `None` values accept any value, e.g. for `cfg(foo, values(any()))`. Repeated declarations of
+    // the same name are merged, like rustc does.
+    let mut check_cfgs: BTreeMap<&str, Option<BTreeSet<&str>>> = BTreeMap::new();
+    let mut has_search = false;
+    for instruction in instructions {
+        match instruction {
+            Instruction::RustcCheckCfg { name, values } => {
+                let declared = check_cfgs
+                    .entry(name)
+                    .or_insert_with(|| Some(BTreeSet::new()));
+                if let Some(declared) = declared {
+                    declared.extend(values.iter().map(String::as_str));
+                }
+            }
+            Instruction::Other { key, value } if key == "rustc-check-cfg" => {
+                for name in crate::instruction::check_cfg_names(value) {
+                    check_cfgs.insert(name, None);
+                }
+            }
+            Instruction::RustcLinkSearch(_) => has_search = true,
+            Instruction::RustcFlags(flags) => has_search |= flags.contains("-L"),
+            _ => {}
+        }
+    }
+
+    let mut linked = BTreeSet::new();
+    let mut env: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    let mut metadata: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+
+    for instruction in instructions {
+        match instruction {
+            Instruction::RustcCfg { name, value } => {
+                let warning = match (check_cfgs.get(name.as_str()), value) {
+                    (None, _) => LintWarning::UndeclaredCfg { name: name.clone() },
+                    (Some(Some(values)), Some(value)) if !values.contains(value.as_str()) => {
+                        LintWarning::UndeclaredCfgValue {
+                            name: name.clone(),
+                            value: value.clone(),
+                        }
+                    }
+                    _ => continue,
+                };
+                if !warnings.contains(&warning) {
+                    warnings.push(warning);
+                }
+            }
+            Instruction::RustcLinkLib(lib)
+                if lib.kind != Some(LinkKind::Framework)
+                    && !has_search
+                    && linked.insert(&lib.name) =>
+            {
+                warnings.push(LintWarning::LinkLibWithoutSearch {
+                    lib: lib.name.clone(),
+                });
+            }
+            Instruction::RustcEnv { var, value } => push_unique(env.entry(var).or_default(), value),
+            Instruction::Metadata { key, value } => {
+                if missing_links && !metadata.contains_key(key.as_str()) {
+                    warnings.push(LintWarning::MetadataWithoutLinks { key: key.clone() });
+                }
+                push_unique(metadata.entry(key).or_default(), value);
+            }
+            Instruction::RerunIfChanged(path) => {
+                if let Some(dir) = build_dirs.find(path) {
+                    warnings.push(LintWarning::RerunInsideBuildDir {
+                        path: path.clone(),
+                        dir: dir.to_path_buf(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for (var, values) in env {
+        if values.len() > 1 {
+            warnings.push(LintWarning::ConflictingEnv {
+                var: var.to_string(),
+                values,
+            });
+        }
+    }
+    for (key, values) in metadata {
+        if values.len() > 1 {
+            warnings.push(LintWarning::ConflictingMetadata {
+                key: key.to_string(),
+                values,
+            });
+        }
+    }
+
+    warnings
+}
+
+fn push_unique(values: &mut Vec<String>, value: &str) {
+    if !values.iter().any(|v| v == value) {
+        values.push(value.to_string());
+    }
+}
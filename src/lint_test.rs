@@ -0,0 +1,149 @@
+use crate::lint::lint_with;
+use crate::paths::BuildDirs;
+use crate::{Instruction, LintWarning};
+
+#[test]
+fn lint_clean_test() {
+    let instructions = [
+        Instruction::new("rustc-check-cfg", "cfg(api, values(\"v1\", \"v2\"))"),
+        Instruction::new("rustc-cfg", "api=\"v2\""),
+        Instruction::new("rustc-check-cfg", "cfg(has_foo, has_bar)"),
+        Instruction::new("rustc-check-cfg", "cfg(os, values(any()))"),
+        Instruction::new("rustc-check-cfg", "cfg(abi, values(\"a\"))"),
+        Instruction::new("rustc-check-cfg", "cfg(abi, values(\"b\"))"),
+        Instruction::new("rustc-check-cfg", "cfg(os, values(\"linux\"))"),
+        Instruction::new("rustc-cfg", "abi=\"a\""),
+        Instruction::new("rustc-cfg", "abi=\"b\""),
+        Instruction::new("rustc-cfg", "has_bar"),
+        Instruction::new("rustc-cfg", "os=\"windows\""),
+        Instruction::new("rustc-link-search", "native=libs"),
+        Instruction::new("rustc-link-lib", "static=foo"),
+        Instruction::new("rustc-env", "API_VERSION=3"),
+        Instruction::new("rustc-env", "API_VERSION=3"),
+        Instruction::new("metadata", "include=/usr/include"),
+    ];
+
+    assert_eq!(lint_with(&instructions, false, &BuildDirs::default()), []);
+}
+
+#[test]
+fn lint_cfg_test() {
+    let instructions = [
+        Instruction::new("rustc-check-cfg", "cfg(api, values(\"v1\"))"),
+        Instruction::new("rustc-cfg", "api=\"v2\""),
+        Instruction::new("rustc-cfg", "has_foo"),
+        Instruction::new("rustc-cfg", "has_foo"),
+    ];
+
+    let warnings = lint_with(&instructions, false, &BuildDirs::default());
+
+    assert_eq!(
+        warnings,
+        [
+            LintWarning::UndeclaredCfgValue {
+                name: "api".to_string(),
+                value: "v2".to_string(),
+            },
+            LintWarning::UndeclaredCfg {
+                name: "has_foo".to_string(),
+            },
+        ]
+    );
+    assert_eq!(
+        warnings[1].to_string(),
+        "cfg `has_foo` is set without `rustc-check-cfg` declaration"
+    );
+}
+
+#[test]
+fn lint_link_test() {
+    let instructions = [
+        Instruction::new("rustc-link-lib", "static=foo"),
+        Instruction::new("rustc-link-lib", "framework=CoreFoundation"),
+        Instruction::new("rustc-link-lib", "static=foo"),
+    ];
+
+    assert_eq!(
+        lint_with(&instructions, false, &BuildDirs::default()),
+        [LintWarning::LinkLibWithoutSearch {
+            lib: "foo".to_string(),
+        }]
+    );
+
+    // `-L` of `rustc-flags` counts as search path
+    let instructions = [
+        Instruction::new("rustc-flags", "-L libs"),
+        Instruction::new("rustc-link-lib", "static=foo"),
+    ];
+    assert_eq!(lint_with(&instructions, false, &BuildDirs::default()), []);
+}
+
+#[test]
+fn lint_metadata_test() {
+    let instructions = [
+        Instruction::new("metadata", "include=/usr/include"),
+        Instruction::new("metadata", "include=/opt/include"),
+        Instruction::new("rustc-env", "API_VERSION=2"),
+        Instruction::new("rustc-env", "API_VERSION=3"),
+    ];
+
+    let warnings = lint_with(&instructions, true, &BuildDirs::default());
+
+    assert_eq!(
+        warnings,
+        [
+            LintWarning::MetadataWithoutLinks {
+                key: "include".to_string(),
+            },
+            LintWarning::ConflictingEnv {
+                var: "API_VERSION".to_string(),
+                values: vec!["2".to_string(), "3".to_string()],
+            },
+            LintWarning::ConflictingMetadata {
+                key: "include".to_string(),
+                values: vec!["/usr/include".to_string(), "/opt/include".to_string()],
+            },
+        ]
+    );
+    assert_eq!(
+        warnings[2].to_string(),
+        "metadata `include` is set to different values: `/usr/include`, `/opt/include`"
+    );
+}
+
+#[test]
+fn lint_rerun_inside_build_dir_test() {
+    let build_dirs = BuildDirs::from_env(|var| match var {
+        "CARGO_MANIFEST_DIR" => Some("/work/foo".into()),
+        "OUT_DIR" => Some("/work/foo/target/debug/build/foo-1234/out".into()),
+        _ => None,
+    });
+    let instructions = [
+        Instruction::new("rerun-if-changed", "build.rs"),
+        Instruction::new(
+            "rerun-if-changed",
+            "target/debug/build/foo-1234/out/bindings.rs",
+        ),
+        Instruction::new("rerun-if-changed", "target/generated.h"),
+    ];
+
+    let warnings = lint_with(&instructions, false, &build_dirs);
+
+    assert_eq!(
+        warnings,
+        [
+            LintWarning::RerunInsideBuildDir {
+                path: "target/debug/build/foo-1234/out/bindings.rs".into(),
+                dir: "/work/foo/target/debug/build/foo-1234/out".into(),
+            },
+            LintWarning::RerunInsideBuildDir {
+                path: "target/generated.h".into(),
+                dir: "/work/foo/target".into(),
+            },
+        ]
+    );
+    assert_eq!(
+        warnings[1].to_string(),
+        "`rerun-if-changed` path `target/generated.h` is inside `/work/foo/target` which changes on every build"
+    );
+}
@@ -0,0 +1,63 @@
+//! Helpers for locating system libraries on Linux distributions.
+
+use std::env;
+use std::path::PathBuf;
+
+use crate::presets::Target;
+
+/// Returns existing system library directories for the target, most specific first. Returns empty
+/// `Vec` if the target OS is not Linux, e.g. when cross-compiling to Windows from Linux host.
+///
+/// Debian-based distributions keep libraries in multiarch directories such as
+/// `/usr/lib/x86_64-linux-gnu`, Fedora and other RPM-based distributions use `/usr/lib64`
+/// for 64-bit targets, and `/usr/lib` is the common fallback.
+///
+/// ```rust
+/// // build.rs
+/// cargo_build::rustc_link_search_native(cargo_build::linux::lib_dirs());
+/// ```
+pub fn lib_dirs() -> Vec<PathBuf> {
+    let target = Target::from_env();
+    let abi = env::var("CARGO_CFG_TARGET_ABI").unwrap_or_default();
+
+    lib_dirs_for(&target.os, &target.arch, &target.env, &abi)
+        .into_iter()
+        .filter(|dir| dir.is_dir())
+        .collect()
+}
+
+/// Candidate library directories, existing or not.
+pub(crate) fn lib_dirs_for(os: &str, arch: &str, env: &str, abi: &str) -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if os != "linux" {
+        return dirs;
+    }
+
+    if let Some(triplet) = multiarch_triplet(arch, env, abi) {
+        dirs.push(PathBuf::from(format!("/usr/lib/{triplet}")));
+        dirs.push(PathBuf::from(format!("/lib/{triplet}")));
+    }
+    if (arch.ends_with("64") || arch == "s390x") && abi != "x32" {
+        dirs.push(PathBuf::from("/usr/lib64"));
+        dirs.push(PathBuf::from("/lib64"));
+    }
+    dirs.push(PathBuf::from("/usr/lib"));
+    dirs.push(PathBuf::from("/lib"));
+    dirs
+}
+
+/// Debian multiarch tuple, e.g. `x86_64-linux-gnu` or `arm-linux-gnueabihf`.
+///
+/// <https://wiki.debian.org/Multiarch/Tuples>
+pub(crate) fn multiarch_triplet(arch: &str, env: &str, abi: &str) -> Option<String> {
+    let arch = match arch {
+        "x86" => "i386",
+        "x86_64" | "aarch64" | "arm" | "riscv64" | "s390x" | "loongarch64" => arch,
+        _ => return None,
+    };
+    let env = match env {
+        "gnu" | "musl" => env,
+        _ => return None,
+    };
+    Some(format!("{arch}-linux-{env}{abi}"))
+}
@@ -0,0 +1,59 @@
+use std::path::PathBuf;
+
+use crate::linux::{lib_dirs_for, multiarch_triplet};
+
+#[test]
+fn multiarch_triplet_test() {
+    assert_eq!(
+        multiarch_triplet("x86_64", "gnu", "").as_deref(),
+        Some("x86_64-linux-gnu")
+    );
+    assert_eq!(
+        multiarch_triplet("x86", "gnu", "").as_deref(),
+        Some("i386-linux-gnu")
+    );
+    assert_eq!(
+        multiarch_triplet("arm", "gnu", "eabihf").as_deref(),
+        Some("arm-linux-gnueabihf")
+    );
+    assert_eq!(
+        multiarch_triplet("x86_64", "gnu", "x32").as_deref(),
+        Some("x86_64-linux-gnux32")
+    );
+    assert_eq!(multiarch_triplet("x86_64", "msvc", ""), None);
+    assert_eq!(multiarch_triplet("mips", "gnu", ""), None);
+}
+
+#[test]
+fn lib_dirs_test() {
+    assert_eq!(
+        lib_dirs_for("linux", "x86_64", "gnu", ""),
+        [
+            "/usr/lib/x86_64-linux-gnu",
+            "/lib/x86_64-linux-gnu",
+            "/usr/lib64",
+            "/lib64",
+            "/usr/lib",
+            "/lib"
+        ]
+        .map(PathBuf::from)
+    );
+    assert_eq!(
+        lib_dirs_for("linux", "arm", "gnu", "eabihf"),
+        [
+            "/usr/lib/arm-linux-gnueabihf",
+            "/lib/arm-linux-gnueabihf",
+            "/usr/lib",
+            "/lib"
+        ]
+        .map(PathBuf::from)
+    );
+    assert_eq!(
+        lib_dirs_for("linux", "mips", "gnu", ""),
+        ["/usr/lib", "/lib"].map(PathBuf::from)
+    );
+
+    // Host directories are not used when cross-compiling to other OS
+    assert!(lib_dirs_for("windows", "x86_64", "gnu", "").is_empty());
+    assert!(lib_dirs_for("macos", "aarch64", "", "").is_empty());
+}
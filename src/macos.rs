@@ -0,0 +1,99 @@
+//! Helpers for linking libraries installed by Homebrew or MacPorts on macOS.
+//!
+//! Package managers are installed on the host, so these helpers describe the host machine
+//! running the build script.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::env::non_empty_var;
+use crate::probe_cache;
+use crate::{metadata, rerun_if_env_changed, rustc_link_search_native};
+
+/// Returns Homebrew installation prefix.
+///
+/// `HOMEBREW_PREFIX` env variable is used if set, then `brew --prefix` output. If `brew` is not
+/// in `PATH` and the host is macOS, `/opt/homebrew` (Apple Silicon) or `/usr/local` (Intel) is
+/// returned if it exists.
+///
+/// ```rust
+/// if let Some(prefix) = cargo_build::macos::homebrew_prefix() {
+///     cargo_build::rustc_link_search_native([prefix.join("lib")]);
+/// }
+/// ```
+pub fn homebrew_prefix() -> Option<PathBuf> {
+    rerun_if_env_changed("HOMEBREW_PREFIX");
+
+    if let Some(prefix) = non_empty_var("HOMEBREW_PREFIX") {
+        return Some(prefix.into());
+    }
+    if let Some(prefix) = brew_prefix(None) {
+        return Some(prefix);
+    }
+    default_homebrew_prefix(std::env::consts::OS, std::env::consts::ARCH)
+        .filter(|prefix| prefix.is_dir())
+}
+
+/// Default Homebrew prefix of macOS host. `/usr/local` on other hosts is not Homebrew.
+pub(crate) fn default_homebrew_prefix(os: &str, arch: &str) -> Option<PathBuf> {
+    match (os, arch) {
+        ("macos", "aarch64") => Some(PathBuf::from("/opt/homebrew")),
+        ("macos", _) => Some(PathBuf::from("/usr/local")),
+        _ => None,
+    }
+}
+
+/// Returns MacPorts installation prefix `/opt/local` if it exists.
+pub fn macports_prefix() -> Option<PathBuf> {
+    Some(PathBuf::from("/opt/local")).filter(|prefix| prefix.is_dir())
+}
+
+/// Emits `rustc-link-search=native` for Homebrew formula and publishes its include directory
+/// as `include` [`metadata`].
+///
+/// Formula is looked up with `brew --prefix FORMULA` and falls back to `PREFIX/opt/FORMULA`,
+/// which is where Homebrew links every installed formula.
+///
+/// Returns prefix of the formula or `None` if it is not installed. Nothing is emitted in this case.
+///
+/// ```rust
+/// // build.rs
+/// if cargo_build::macos::emit_brew_library("zstd").is_some() {
+///     cargo_build::rustc_link_lib("zstd");
+/// }
+/// ```
+pub fn emit_brew_library(formula: &str) -> Option<PathBuf> {
+    let keg = brew_prefix(Some(formula))
+        .or_else(|| Some(homebrew_prefix()?.join("opt").join(formula)))
+        .filter(|keg| keg.is_dir())?;
+
+    emit_keg(&keg);
+    Some(keg)
+}
+
+pub(crate) fn emit_keg(keg: &Path) {
+    rustc_link_search_native([keg.join("lib")]);
+
+    let include = keg.join("include");
+    if include.is_dir() {
+        metadata("include", &include.display().to_string());
+    }
+}
+
+/// Runs `brew --prefix [FORMULA]`.
+fn brew_prefix(formula: Option<&str>) -> Option<PathBuf> {
+    let prefix = probe_cache::cached("brew --prefix", &[formula.unwrap_or("")], || {
+        let output = Command::new("brew")
+            .arg("--prefix")
+            .args(formula)
+            .output()
+            .ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        String::from_utf8(output.stdout).ok()
+    })?;
+    let prefix = prefix.trim();
+
+    (!prefix.is_empty()).then(|| PathBuf::from(prefix))
+}
@@ -0,0 +1,68 @@
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+
+use crate as cargo_build;
+use crate::macos::{default_homebrew_prefix, emit_keg};
+
+#[test]
+fn default_homebrew_prefix_test() {
+    assert_eq!(
+        default_homebrew_prefix("macos", "aarch64"),
+        Some(PathBuf::from("/opt/homebrew"))
+    );
+    assert_eq!(
+        default_homebrew_prefix("macos", "x86_64"),
+        Some(PathBuf::from("/usr/local"))
+    );
+    assert_eq!(default_homebrew_prefix("linux", "x86_64"), None);
+}
+
+#[test]
+fn emit_keg_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    let keg = std::env::temp_dir().join("cargo_build_keg_test");
+    std::fs::create_dir_all(keg.join("include")).unwrap();
+
+    emit_keg(&keg);
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(
+        out,
+        format!(
+            "cargo::rustc-link-search=native={}\ncargo::metadata=include={}\n",
+            keg.join("lib").display(),
+            keg.join("include").display()
+        )
+    );
+}
+
+struct TestWriteVecHandle(Arc<RwLock<Vec<u8>>>);
+
+impl TestWriteVecHandle {
+    fn new() -> Self {
+        Self(Arc::new(RwLock::new(Vec::new())))
+    }
+}
+
+impl Clone for TestWriteVecHandle {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl std::io::Write for TestWriteVecHandle {
+    fn write(&mut self, buf: &[u8]) -> std::result::Result<usize, std::io::Error> {
+        self.0
+            .write()
+            .expect("Unable to aquire Write lock")
+            .write(buf)
+    }
+
+    fn flush(&mut self) -> std::result::Result<(), std::io::Error> {
+        Ok(())
+    }
+}
@@ -744,3 +744,55 @@ macro_rules! metadata {
         $crate::metadata(&format!("{}", $meta_key), &format!("{}", $meta_value));
     }};
 }
+
+/// Emits arbitrary `cargo::KEY=VALUE` instruction.
+///
+/// #### Prefer specific macros such as [`rerun_if_changed!`] or [`rustc_link_lib!`] when they exist.
+///
+/// This macro is an escape hatch for instructions which are not (yet) supported by this crate, e.g.
+/// instructions introduced by newer Cargo versions. `VALUE` follows `format!` macro syntax.
+///
+/// ```rust
+/// cargo_build::directive!("rustc-link-arg", "-Wl,--cref");
+///
+/// let (a, b) = ("first", "second");
+/// cargo_build::directive!("rustc-new-thing", "{}-{}", a, b);
+/// ```
+///
+/// - See [`directive` function](`crate::functions::directive`) if you dont need strings interpolation.
+///
+/// `KEY` may only contain ASCII letters, digits, `-` and `_`. Neither `KEY` nor `VALUE` may contain newlines.
+///
+/// <https://doc.rust-lang.org/cargo/reference/build-scripts.html#outputs-of-the-build-script>
+#[macro_export]
+macro_rules! directive {
+    ( $key:expr, $($fmt_arg:tt),* $(,)? ) => {{
+        $crate::directive_fmt(&format!("{}", $key), format_args!($($fmt_arg),*));
+    }};
+}
+
+/// Measures how long the block takes and returns its value.
+///
+/// Duration is written to `stderr` and recorded for [`Session::report`](crate::Session::report) during
+/// active session. [`cargo::warning`](crate::warning) is emitted if duration exceeds threshold set with
+/// [`set_instrument_threshold`](crate::set_instrument_threshold).
+///
+/// ```rust
+/// cargo_build::set_instrument_threshold(Some(std::time::Duration::from_secs(30)));
+///
+/// let version = 3;
+/// let bindings = cargo_build::instrument!("generate bindings", {
+///     // ...
+///     format!("pub const VERSION: u32 = {version};")
+/// });
+/// ```
+///
+/// Block is executed inside closure, so `return` and `?` apply to the block itself.
+///
+/// - See [`instrument` function](`crate::instrument`).
+#[macro_export]
+macro_rules! instrument {
+    ( $label:expr, $body:block $(,)? ) => {{
+        $crate::instrument(&format!("{}", $label), || $body)
+    }};
+}
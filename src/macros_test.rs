@@ -14,7 +14,7 @@ fn change_output_test() {
 
     {
         let out = vec_out.0.read().expect("Unable to aquire Read lock");
-        let out: &str = str::from_utf8(&out).unwrap();
+        let out: &str = std::str::from_utf8(&out).unwrap();
 
         assert_eq!(
             out,
@@ -50,7 +50,7 @@ fn rerun_if_changed_test() {
     cargo_build::rerun_if_changed!("LICENSE.md"; "README.md"; );
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -97,7 +97,7 @@ fn rerun_if_env_changed_test() {
     cargo_build::rerun_if_env_changed!("VAR1"; "VAR2"; );
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -119,7 +119,7 @@ fn rustc_link_arg_test() {
     cargo_build::rustc_link_arg!("-mlongcalls"; "-ffunction-sections"; "-Wl,--cref"; );
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -196,7 +196,7 @@ fn rustc_link_arg_cdylib_test() {
     cargo_build::rustc_link_arg!(cdylib: "-mlongcalls"; "-ffunction-sections"; "-Wl,--cref");
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -223,7 +223,7 @@ fn rustc_link_arg_bin_test() {
     );
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -244,7 +244,7 @@ fn rustc_link_arg_bins_test() {
     cargo_build::rustc_link_arg!(bins: "-mlongcalls"; "-ffunction-sections"; "-Wl,--cref");
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -264,7 +264,7 @@ fn rustc_link_arg_tests_test() {
     cargo_build::rustc_link_arg!(tests: "-mlongcalls"; "-ffunction-sections"; "-Wl,--cref");
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -284,7 +284,7 @@ fn rustc_link_arg_examples_test() {
     cargo_build::rustc_link_arg!( examples: "-mlongcalls"; "-ffunction-sections"; "-Wl,--cref");
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -304,7 +304,7 @@ fn rustc_link_arg_benches_test() {
     cargo_build::rustc_link_arg!(benches: "-mlongcalls"; "-ffunction-sections"; "-Wl,--cref");
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -337,7 +337,7 @@ fn rustc_link_lib_test_complex() {
     );
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -363,7 +363,7 @@ fn rustc_link_lib_test_types() {
     cargo_build::rustc_link_lib!(framework: "+whole-archive", "-bundle" = "foo:{}", "renamed_foo" );
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -437,7 +437,7 @@ fn rustc_link_search_test() {
     );
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -537,7 +537,7 @@ fn rustc_cfg_test_no_value() {
     cargo_build::rustc_cfg!("api_v1");
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(out, "cargo::rustc-cfg=api_v1\n");
 }
@@ -552,7 +552,7 @@ fn rustc_cfg_test_value() {
     cargo_build::rustc_cfg!("api_version" = value);
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(out, "cargo::rustc-cfg=api_version=\"1\"\n");
 }
@@ -580,7 +580,7 @@ fn rustc_check_cfg_test_no_values() {
     cargo_build::rustc_check_cfg!("api_version");
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(out, "cargo::rustc-check-cfg=cfg(api_version)\n");
 }
@@ -594,7 +594,7 @@ fn rustc_check_cfgs_test() {
     cargo_build::rustc_check_cfg!("api_v1", "api_v2");
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -612,7 +612,7 @@ fn rustc_check_cfg_test_single_value() {
     cargo_build::rustc_check_cfg!("api_version": "1");
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -629,7 +629,7 @@ fn rustc_check_cfg_test_many_values() {
     cargo_build::rustc_check_cfg!("api_version": "1", "2", "3");
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -646,7 +646,7 @@ fn rustc_check_cfg_test_many_values_array() {
     cargo_build::rustc_check_cfg!("api_version": ["1", "2", "3"]);
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(
         out,
@@ -663,7 +663,7 @@ fn rustc_env_test() {
     cargo_build::rustc_env!("GIT_HASH" = "1234");
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(out, "cargo::rustc-env=GIT_HASH=1234\n");
 }
@@ -677,7 +677,7 @@ fn rustc_warning_test() {
     cargo_build::warning!("Warning during build process {}", err);
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(out, "cargo::warning=Warning during build process 10\n");
 }
@@ -691,7 +691,7 @@ fn rustc_error_test() {
     cargo_build::error!("Fatal error during build process {}", err);
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(out, "cargo::error=Fatal error during build process 10\n");
 }
@@ -704,11 +704,47 @@ fn metadata_test() {
     cargo_build::metadata!("META" = "DATA");
 
     let out = vec_out.0.read().expect("Unable to aquire Read lock");
-    let out: &str = str::from_utf8(&out).unwrap();
+    let out: &str = std::str::from_utf8(&out).unwrap();
 
     assert_eq!(out, "cargo::metadata=META=DATA\n");
 }
 
+#[test]
+fn directive_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    let (a, b) = ("first", "second");
+    cargo_build::directive!("rustc-new-thing", "{}-{}", a, b);
+    cargo_build::directive!("rustc-new-thing", "{a}");
+    cargo_build::directive!("rustc-new-thing", "value",);
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(
+        out,
+        "\
+                cargo::rustc-new-thing=first-second\n\
+                cargo::rustc-new-thing=first\n\
+                cargo::rustc-new-thing=value\n"
+    );
+}
+
+#[test]
+fn instrument_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    let step = "bindings";
+    let value = cargo_build::instrument!(format!("generate {step}"), { 2 + 2 });
+
+    assert_eq!(value, 4);
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    assert!(out.is_empty());
+}
+
 struct TestWriteVecHandle(Arc<RwLock<Vec<u8>>>);
 
 impl TestWriteVecHandle {
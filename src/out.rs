@@ -0,0 +1,187 @@
+//! Managed subdirectories of `OUT_DIR` for multi-step generators.
+
+use std::cell::RefCell;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// File inside the workspace listing files produced by the previous run.
+const MANIFEST: &str = ".cargo-build-manifest";
+
+/// Returns workspace managing `OUT_DIR/name` directory, creating it if needed.
+///
+/// Files produced with [`Workspace::write`] or [`Workspace::persist`] are recorded in a manifest when
+/// workspace is finished. Files recorded by the previous run which weren't produced again are removed,
+/// so renamed or removed outputs don't accumulate between builds.
+///
+/// Panics outside of build scripts, if `name` is absolute or contains `..` and if the directory
+/// can't be created.
+///
+/// ```rust,no_run
+/// // build.rs
+/// let codegen = cargo_build::out::workspace("codegen");
+///
+/// for module in ["net", "fs"] {
+///     codegen
+///         .write(format!("{module}.rs"), format!("pub const NAME: &str = {module:?};"))
+///         .unwrap();
+/// }
+///
+/// // Files generated by previous builds, e.g. `ui.rs`, are removed
+/// codegen.finish().unwrap();
+/// ```
+pub fn workspace(name: &str) -> Workspace {
+    workspace_in(&crate::env::out_dir(), name)
+}
+
+pub(crate) fn workspace_in(out_dir: &Path, name: &str) -> Workspace {
+    let name = relative(Path::new(name)).unwrap_or_else(|err| panic!("{err}"));
+    let dir = out_dir.join(name);
+    fs::create_dir_all(&dir)
+        .unwrap_or_else(|err| panic!("Unable to create `{}`: {err}", dir.display()));
+
+    Workspace {
+        dir,
+        produced: RefCell::new(BTreeSet::new()),
+        finished: false,
+    }
+}
+
+/// Subdirectory of `OUT_DIR` returned by [`workspace`].
+///
+/// Workspace is finished when dropped or when [`Workspace::finish`] is called. Nothing is cleaned up
+/// if the thread is panicking.
+pub struct Workspace {
+    dir: PathBuf,
+    /// Paths relative to `dir`.
+    produced: RefCell<BTreeSet<PathBuf>>,
+    finished: bool,
+}
+
+impl Workspace {
+    /// Returns path of the workspace directory.
+    pub fn path(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns path for temporary file which can later be moved into place with [`Workspace::persist`].
+    ///
+    /// Temporary files are never recorded and are removed when workspace is finished.
+    pub fn temp_path(&self, name: impl AsRef<Path>) -> PathBuf {
+        let name = name.as_ref().to_string_lossy().replace(['/', '\\'], "_");
+        self.dir.join(format!(".{name}.tmp"))
+    }
+
+    /// Atomically writes `contents` to `name` inside workspace and returns its path.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if `name` is absolute or contains `..`.
+    ///
+    /// File is left untouched if it already has the same contents, so its modification time is
+    /// preserved for tools which compare it.
+    pub fn write(&self, name: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> io::Result<PathBuf> {
+        let name = relative(name.as_ref())?;
+        let path = self.dir.join(name);
+
+        if fs::read(&path).is_ok_and(|existing| existing == contents.as_ref()) {
+            self.produced.borrow_mut().insert(name.to_path_buf());
+            return Ok(path);
+        }
+
+        let temp = self.temp_path(name);
+        fs::write(&temp, contents)?;
+        self.persist(&temp, name)
+    }
+
+    /// Atomically moves temporary file into place at `name` inside workspace and returns its path.
+    ///
+    /// Fails with [`io::ErrorKind::InvalidInput`] if `name` is absolute or contains `..`.
+    pub fn persist(&self, temp: impl AsRef<Path>, name: impl AsRef<Path>) -> io::Result<PathBuf> {
+        let name = relative(name.as_ref())?;
+        let path = self.dir.join(name);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(temp, &path)?;
+
+        self.produced.borrow_mut().insert(name.to_path_buf());
+        Ok(path)
+    }
+
+    /// Removes stale files and temporary files and records produced files in the manifest.
+    ///
+    /// Same as dropping workspace, but returns error instead of emitting [`warning`](crate::warning).
+    pub fn finish(mut self) -> io::Result<()> {
+        self.finished = true;
+        self.cleanup()
+    }
+
+    fn cleanup(&self) -> io::Result<()> {
+        let manifest = self.dir.join(MANIFEST);
+        let produced = self.produced.borrow();
+
+        let previous = fs::read_to_string(&manifest).unwrap_or_default();
+        // Lines which don't name a path inside workspace are never removed
+        let previous = previous
+            .lines()
+            .filter_map(|line| relative(Path::new(line)).ok());
+        for stale in previous {
+            if !produced.contains(stale) {
+                remove_if_exists(&self.dir.join(stale))?;
+            }
+        }
+
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let name = entry.file_name();
+            let name = name.to_string_lossy();
+            if name.starts_with('.') && name.ends_with(".tmp") {
+                remove_if_exists(&entry.path())?;
+            }
+        }
+
+        let mut contents = String::new();
+        for path in produced.iter() {
+            contents.push_str(&path.to_string_lossy());
+            contents.push('\n');
+        }
+        fs::write(manifest, contents)
+    }
+}
+
+impl Drop for Workspace {
+    fn drop(&mut self) {
+        if self.finished || std::thread::panicking() {
+            return;
+        }
+        if let Err(err) = self.cleanup() {
+            crate::warning(&format!(
+                "Unable to clean up `{}`: {err}",
+                self.dir.display()
+            ));
+        }
+    }
+}
+
+/// Checks that `name` is a relative path which stays inside the directory it is joined to.
+pub(crate) fn relative(name: &Path) -> io::Result<&Path> {
+    let is_normal = |component| matches!(component, Component::Normal(_));
+    if name.components().next().is_some() && name.components().all(is_normal) {
+        return Ok(name);
+    }
+    Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!(
+            "`{}` must be a relative path without `..` components",
+            name.display()
+        ),
+    ))
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
@@ -0,0 +1,67 @@
+use std::fs;
+
+use crate::out::workspace_in;
+
+#[test]
+fn workspace_test() {
+    let out_dir = std::env::temp_dir().join("cargo_build_workspace_test");
+    let _ = fs::remove_dir_all(&out_dir);
+
+    let codegen = workspace_in(&out_dir, "codegen");
+    codegen.write("net.rs", "mod net;").unwrap();
+    codegen.write("ui/mod.rs", "mod ui;").unwrap();
+    fs::write(codegen.temp_path("leftover.rs"), "partial").unwrap();
+    codegen.finish().unwrap();
+
+    let dir = out_dir.join("codegen");
+    assert_eq!(
+        fs::read_to_string(dir.join("ui/mod.rs")).unwrap(),
+        "mod ui;"
+    );
+    assert!(!dir.join(".leftover.rs.tmp").exists());
+
+    // Second run no longer produces `ui/mod.rs`
+    let codegen = workspace_in(&out_dir, "codegen");
+    let temp = codegen.temp_path("fs.rs");
+    fs::write(&temp, "mod fs;").unwrap();
+    codegen.persist(&temp, "fs.rs").unwrap();
+    codegen.write("net.rs", "mod net;").unwrap();
+    drop(codegen);
+
+    assert_eq!(fs::read_to_string(dir.join("fs.rs")).unwrap(), "mod fs;");
+    assert_eq!(fs::read_to_string(dir.join("net.rs")).unwrap(), "mod net;");
+    assert!(!dir.join("ui/mod.rs").exists());
+    assert!(!temp.exists());
+
+    fs::remove_dir_all(&out_dir).unwrap();
+}
+
+#[test]
+fn workspace_outside_paths_test() {
+    let out_dir = std::env::temp_dir().join("cargo_build_workspace_outside_test");
+    let _ = fs::remove_dir_all(&out_dir);
+
+    let outside = out_dir.join("outside.rs");
+    fs::create_dir_all(&out_dir).unwrap();
+    fs::write(&outside, "keep").unwrap();
+
+    let codegen = workspace_in(&out_dir, "codegen");
+    for name in [
+        outside.as_path(),
+        "../outside.rs".as_ref(),
+        "ui/../../outside.rs".as_ref(),
+    ] {
+        let err = codegen.write(name, "overwritten").unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+    }
+    codegen.finish().unwrap();
+
+    // Manifest edited to point outside of workspace
+    let manifest = out_dir.join("codegen/.cargo-build-manifest");
+    fs::write(&manifest, format!("{}\n../outside.rs\n", outside.display())).unwrap();
+    workspace_in(&out_dir, "codegen").finish().unwrap();
+
+    assert_eq!(fs::read_to_string(&outside).unwrap(), "keep");
+
+    fs::remove_dir_all(&out_dir).unwrap();
+}
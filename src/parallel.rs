@@ -0,0 +1,103 @@
+//! Running independent build script steps concurrently.
+
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use crate::build_out::{self, Captured};
+use crate::functions::{emit_instructions_at, ThreadPolicies};
+
+/// Runs tasks on up to `NUM_JOBS` threads and returns their results in the order of tasks.
+///
+/// Instructions emitted by each task are collected into separate buffer and emitted on the calling
+/// thread in the order of tasks, so the output doesn't depend on scheduling. They go through the
+/// [`DirectiveSink`](build_out::DirectiveSink) and [`Session`](crate::Session) of the calling thread.
+/// Policies such as [`set_newline_policy`](crate::set_newline_policy) of the calling thread apply
+/// to tasks too.
+///
+/// `NUM_JOBS` is set by Cargo for build scripts. Number of available CPUs is used otherwise.
+///
+/// If any task panics, panic is propagated after all tasks finish and nothing is written.
+///
+/// ```rust
+/// let modules = ["net", "fs", "ui"];
+///
+/// let generated = cargo_build::parallel::run(modules.map(|module| {
+///     move || {
+///         cargo_build::rerun_if_changed(format!("schema/{module}.json"));
+///         format!("{module}.rs")
+///     }
+/// }));
+///
+/// assert_eq!(generated, ["net.rs", "fs.rs", "ui.rs"]);
+/// ```
+pub fn run<T, R>(tasks: impl IntoIterator<Item = T>) -> Vec<R>
+where
+    T: FnOnce() -> R + Send,
+    R: Send,
+{
+    let slots: Vec<Mutex<Slot<T, R>>> = tasks
+        .into_iter()
+        .map(|task| Mutex::new(Slot::Pending(task)))
+        .collect();
+
+    let workers = jobs(std::env::var("NUM_JOBS").ok().as_deref()).min(slots.len());
+    let next = AtomicUsize::new(0);
+    let policies = ThreadPolicies::current();
+
+    std::thread::scope(|s| {
+        for _ in 0..workers {
+            let policies = policies.clone();
+            s.spawn(|| {
+                policies.set();
+                while let Some(slot) = slots.get(next.fetch_add(1, Ordering::Relaxed)) {
+                    let task = std::mem::replace(
+                        &mut *slot.lock().expect("Unable to aquire Lock"),
+                        Slot::Running,
+                    );
+                    let Slot::Pending(task) = task else {
+                        unreachable!("Each task is taken once");
+                    };
+
+                    let (result, out) = build_out::capture_instructions(task);
+                    *slot.lock().expect("Unable to aquire Lock") = Slot::Done(result, out);
+                }
+            });
+        }
+    });
+
+    slots
+        .into_iter()
+        .map(
+            |slot| match slot.into_inner().expect("Unable to aquire Lock") {
+                Slot::Done(result, out) => {
+                    for captured in out {
+                        match captured {
+                            Captured::Instructions(instructions, caller) => {
+                                emit_instructions_at(instructions, caller)
+                            }
+                            Captured::Raw(bytes) => build_out::write_raw(&bytes),
+                        }
+                    }
+                    result
+                }
+                _ => unreachable!("Each task is finished"),
+            },
+        )
+        .collect()
+}
+
+enum Slot<T, R> {
+    Pending(T),
+    Running,
+    /// Result and instructions emitted by the task.
+    Done(R, Vec<Captured>),
+}
+
+/// Parses `NUM_JOBS`, falling back to the number of available CPUs.
+pub(crate) fn jobs(num_jobs: Option<&str>) -> usize {
+    num_jobs
+        .and_then(|jobs| jobs.parse::<NonZeroUsize>().ok())
+        .or_else(|| std::thread::available_parallelism().ok())
+        .map_or(1, NonZeroUsize::get)
+}
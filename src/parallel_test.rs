@@ -0,0 +1,148 @@
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate as cargo_build;
+use crate::parallel::jobs;
+
+#[test]
+fn run_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    cargo_build::warning("before");
+
+    let results = cargo_build::parallel::run((0..8u64).map(|i| {
+        move || {
+            // Later tasks finish first
+            std::thread::sleep(Duration::from_millis(40 - i * 5));
+            cargo_build::rustc_cfg(format!("task_{i}"));
+            cargo_build::rerun_if_env_changed(format!("TASK_{i}"));
+            i * 10
+        }
+    }));
+
+    cargo_build::warning("after");
+
+    assert_eq!(results, [0, 10, 20, 30, 40, 50, 60, 70]);
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    let mut expected = String::from("cargo::warning=before\n");
+    for i in 0..8 {
+        expected += &format!("cargo::rustc-cfg=task_{i}\ncargo::rerun-if-env-changed=TASK_{i}\n");
+    }
+    expected += "cargo::warning=after\n";
+
+    assert_eq!(out, expected);
+}
+
+#[test]
+fn run_dedup_test() {
+    let vec_out = TestWriteVecHandle::new();
+    let _out = cargo_build::build_out::scoped(vec_out.clone());
+
+    cargo_build::build_out::dedup();
+    cargo_build::rerun_if_changed("build.rs");
+
+    cargo_build::parallel::run((0..4).map(|_| {
+        || {
+            cargo_build::rerun_if_changed("build.rs");
+            cargo_build::rustc_cfg("shared");
+        }
+    }));
+
+    cargo_build::build_out::reset_sink();
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(
+        out,
+        "cargo::rerun-if-changed=build.rs\ncargo::rustc-cfg=shared\n"
+    );
+}
+
+#[test]
+fn run_recording_test() {
+    use crate::Instruction;
+
+    let vec_out = TestWriteVecHandle::new();
+    let _out = cargo_build::build_out::scoped(vec_out.clone());
+
+    let recording = cargo_build::build_out::recording();
+
+    cargo_build::parallel::run((0..2).map(|i| {
+        move || {
+            cargo_build::rustc_cfg(format!("task_{i}"));
+        }
+    }));
+
+    assert_eq!(
+        recording.finish(),
+        [
+            Instruction::new("rustc-cfg", "task_0"),
+            Instruction::new("rustc-cfg", "task_1"),
+        ]
+    );
+    assert!(vec_out.0.read().unwrap().is_empty());
+}
+
+#[test]
+fn run_policies_test() {
+    let vec_out = TestWriteVecHandle::new();
+    let _out = cargo_build::build_out::scoped(vec_out.clone());
+
+    cargo_build::set_newline_policy(cargo_build::NewlinePolicy::Strip);
+
+    cargo_build::parallel::run([|| cargo_build::rustc_env("VERSION", "1.2.3\n")]);
+
+    cargo_build::set_newline_policy(cargo_build::NewlinePolicy::Reject);
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(out, "cargo::rustc-env=VERSION=1.2.3\n");
+}
+
+#[test]
+fn run_empty_test() {
+    let results: Vec<()> = cargo_build::parallel::run(Vec::<fn()>::new());
+
+    assert!(results.is_empty());
+}
+
+#[test]
+fn jobs_test() {
+    assert_eq!(jobs(Some("3")), 3);
+    assert!(jobs(Some("0")) >= 1);
+    assert!(jobs(Some("many")) >= 1);
+    assert!(jobs(None) >= 1);
+}
+
+struct TestWriteVecHandle(Arc<RwLock<Vec<u8>>>);
+
+impl TestWriteVecHandle {
+    fn new() -> Self {
+        Self(Arc::new(RwLock::new(Vec::new())))
+    }
+}
+
+impl Clone for TestWriteVecHandle {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl std::io::Write for TestWriteVecHandle {
+    fn write(&mut self, buf: &[u8]) -> std::result::Result<usize, std::io::Error> {
+        self.0
+            .write()
+            .expect("Unable to aquire Write lock")
+            .write(buf)
+    }
+
+    fn flush(&mut self) -> std::result::Result<(), std::io::Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+//! Parsing of build script output into [`Instruction`]s.
+//!
+//! Accepts `stdout` of any build script, e.g. `target/*/build/*/output`, as well as logs of
+//! `cargo build -vv`, where every line of build script output is prefixed with `[NAME VERSION] `.
+//!
+//! ```rust
+//! use cargo_build::parse::{self, Line};
+//! use cargo_build::Instruction;
+//!
+//! let output = "\
+//! cargo::rerun-if-changed=build.rs
+//! Compiling libfoo with cc
+//! [foo-sys 0.1.0] cargo::rustc-link-lib=static=foo
+//! ";
+//!
+//! let lines: Vec<Line> = parse::lines(output).collect();
+//!
+//! assert_eq!(lines[0], Line::Instruction(Instruction::new("rerun-if-changed", "build.rs")));
+//! assert_eq!(lines[1], Line::Text("Compiling libfoo with cc".to_string()));
+//! assert_eq!(lines[2], Line::Instruction(Instruction::new("rustc-link-lib", "static=foo")));
+//! ```
+
+use std::io::BufRead;
+
+use crate::{Instruction, ParseInstructionError};
+
+/// Single line of build script output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Line {
+    /// Valid `cargo::` or `cargo:` instruction.
+    Instruction(Instruction),
+    /// Line which starts with `cargo:` but isn't a valid instruction.
+    Invalid {
+        text: String,
+        error: ParseInstructionError,
+    },
+    /// Any other output, passed through unchanged.
+    Text(String),
+}
+
+/// Parses single line of build script output. Trailing newline and `[NAME VERSION] ` prefix of
+/// `cargo build -vv` are ignored.
+pub fn line(text: &str) -> Line {
+    let text = text.strip_suffix('\n').unwrap_or(text);
+    let text = text.strip_suffix('\r').unwrap_or(text);
+
+    let instruction = strip_package_prefix(text).unwrap_or(text);
+    if !instruction.starts_with("cargo:") {
+        return Line::Text(text.to_string());
+    }
+
+    match instruction.parse() {
+        Ok(instruction) => Line::Instruction(instruction),
+        Err(error) => Line::Invalid {
+            text: text.to_string(),
+            error,
+        },
+    }
+}
+
+/// Parses every line of build script output.
+pub fn lines(output: &str) -> impl Iterator<Item = Line> + '_ {
+    output.lines().map(line)
+}
+
+/// Parses build script output from `reader` line by line, without reading it into memory first.
+///
+/// ```rust,no_run
+/// let file = std::fs::File::open("target/debug/build/foo-sys-0123456789abcdef/output").unwrap();
+///
+/// for line in cargo_build::parse::read(std::io::BufReader::new(file)) {
+///     if let cargo_build::parse::Line::Instruction(instruction) = line.unwrap() {
+///         println!("{}: {}", instruction.key(), instruction.value());
+///     }
+/// }
+/// ```
+pub fn read<R: BufRead>(reader: R) -> Lines<R> {
+    Lines { reader }
+}
+
+/// Iterator returned by [`read`].
+#[derive(Debug)]
+pub struct Lines<R> {
+    reader: R,
+}
+
+impl<R: BufRead> Iterator for Lines<R> {
+    type Item = std::io::Result<Line>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut text = String::new();
+        match self.reader.read_line(&mut text) {
+            Ok(0) => None,
+            Ok(_) => Some(Ok(line(&text))),
+            Err(err) => Some(Err(err)),
+        }
+    }
+}
+
+/// Strips `[NAME VERSION] ` prefix `cargo build -vv` adds to build script output.
+fn strip_package_prefix(text: &str) -> Option<&str> {
+    let (package, rest) = text.strip_prefix('[')?.split_once("] ")?;
+    match package.split_once(' ') {
+        Some((name, version))
+            if !name.is_empty()
+                && version.starts_with(|c: char| c.is_ascii_digit())
+                && !version.contains(' ') =>
+        {
+            Some(rest)
+        }
+        _ => None,
+    }
+}
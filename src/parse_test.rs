@@ -0,0 +1,103 @@
+use std::io::Cursor;
+
+use crate::parse::{self, Line};
+use crate::{Instruction, ParseInstructionError};
+
+#[test]
+fn line_test() {
+    assert_eq!(
+        parse::line("cargo::rustc-cfg=has_foo\n"),
+        Line::Instruction(Instruction::new("rustc-cfg", "has_foo"))
+    );
+    assert_eq!(
+        parse::line("cargo:warning=legacy syntax\r\n"),
+        Line::Instruction(Instruction::Warning("legacy syntax".to_string()))
+    );
+    assert_eq!(
+        parse::line("running: \"cc\" \"-c\" \"foo.c\""),
+        Line::Text("running: \"cc\" \"-c\" \"foo.c\"".to_string())
+    );
+    assert_eq!(
+        parse::line("cargo::rustc-link-lib=shared=foo"),
+        Line::Invalid {
+            text: "cargo::rustc-link-lib=shared=foo".to_string(),
+            error: ParseInstructionError::UnknownLinkKind("shared".to_string()),
+        }
+    );
+}
+
+#[test]
+fn verbose_log_test() {
+    assert_eq!(
+        parse::line("[foo-sys 0.1.0] cargo::rerun-if-env-changed=FOO_DIR"),
+        Line::Instruction(Instruction::RerunIfEnvChanged("FOO_DIR".to_string()))
+    );
+    // Text keeps the prefix
+    assert_eq!(
+        parse::line("[foo-sys 0.1.0] running: make"),
+        Line::Text("[foo-sys 0.1.0] running: make".to_string())
+    );
+    assert_eq!(
+        parse::line("[not a prefix] cargo::rustc-cfg=foo"),
+        Line::Text("[not a prefix] cargo::rustc-cfg=foo".to_string())
+    );
+}
+
+#[test]
+fn read_test() {
+    let output = "cargo::rerun-if-changed=build.rs\nsome text\ncargo::rustc-cfg=has_foo";
+
+    let lines: Vec<Line> = parse::read(Cursor::new(output))
+        .collect::<std::io::Result<_>>()
+        .unwrap();
+
+    assert_eq!(lines, parse::lines(output).collect::<Vec<_>>());
+    assert_eq!(
+        lines,
+        [
+            Line::Instruction(Instruction::RerunIfChanged("build.rs".into())),
+            Line::Text("some text".to_string()),
+            Line::Instruction(Instruction::new("rustc-cfg", "has_foo")),
+        ]
+    );
+}
+
+#[test]
+fn check_cfg_lines_test() {
+    let output = "\
+cargo::rustc-check-cfg=cfg(api, values(\"v1,v2\"))
+cargo::rustc-check-cfg=cfg(foo, values(none(), \"bar\"))
+cargo::rustc-check-cfg=cfg(os, values(any()))
+cargo::rustc-check-cfg=cfg(has_foo, has_bar)
+cargo::rustc-check-cfg=cfg(api, values(v1))";
+
+    assert_eq!(
+        parse::lines(output).collect::<Vec<_>>(),
+        [
+            Line::Instruction(Instruction::RustcCheckCfg {
+                name: "api".to_string(),
+                values: vec!["v1,v2".to_string()],
+            }),
+            Line::Instruction(Instruction::Other {
+                key: "rustc-check-cfg".to_string(),
+                value: "cfg(foo, values(none(), \"bar\"))".to_string(),
+            }),
+            Line::Instruction(Instruction::Other {
+                key: "rustc-check-cfg".to_string(),
+                value: "cfg(os, values(any()))".to_string(),
+            }),
+            Line::Instruction(Instruction::Other {
+                key: "rustc-check-cfg".to_string(),
+                value: "cfg(has_foo, has_bar)".to_string(),
+            }),
+            Line::Invalid {
+                text: "cargo::rustc-check-cfg=cfg(api, values(v1))".to_string(),
+                error: ParseInstructionError::InvalidValue {
+                    key: "rustc-check-cfg".to_string(),
+                    value: "cfg(api, values(v1))".to_string(),
+                    expected: "cfg(NAME) or cfg(NAME, values(\"VALUE\", ...))",
+                },
+            },
+        ]
+    );
+}
@@ -0,0 +1,176 @@
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+
+/// Maximum length of the path supported by Windows APIs which are not long path aware.
+///
+/// <https://learn.microsoft.com/en-us/windows/win32/fileio/maximum-file-path-limitation>
+const WINDOWS_MAX_PATH: usize = 260;
+
+const KINDS: [&str; 5] = ["dependency=", "crate=", "native=", "framework=", "all="];
+
+thread_local! {
+    /// Disabled with [`Config::normalize_paths`](crate::config::Config::normalize_paths).
+    static NORMALIZE: Cell<bool> = const { Cell::new(true) };
+}
+
+pub(crate) fn set_normalize(normalize: bool) {
+    NORMALIZE.set(normalize);
+}
+
+pub(crate) fn is_normalize() -> bool {
+    NORMALIZE.get()
+}
+
+/// Normalizes path used in `rerun-if-changed` instruction.
+///
+/// - On Windows strips `\\?\` verbatim prefix when it is safe and warns about paths exceeding `MAX_PATH`.
+/// - On macOS converts path to Unicode NFC form if `nfc` feature is enabled.
+pub(crate) fn normalize(path: &Path) -> Cow<'_, Path> {
+    let Some(str) = path.to_str().filter(|_| NORMALIZE.get()) else {
+        return Cow::Borrowed(path);
+    };
+    match normalize_str(Cow::Borrowed(str)) {
+        Cow::Borrowed(_) => Cow::Borrowed(path),
+        Cow::Owned(normalized) => Cow::Owned(normalized.into()),
+    }
+}
+
+/// [`normalize`] alternative for `rustc-link-search` paths which may start with `KIND=`.
+pub(crate) fn normalize_search(path: &Path) -> Cow<'_, Path> {
+    let Some(str) = path.to_str().filter(|_| NORMALIZE.get()) else {
+        return Cow::Borrowed(path);
+    };
+    let kind = KINDS
+        .into_iter()
+        .find(|kind| str.starts_with(kind))
+        .unwrap_or("");
+
+    match normalize_str(Cow::Borrowed(&str[kind.len()..])) {
+        Cow::Borrowed(_) => Cow::Borrowed(path),
+        Cow::Owned(normalized) => Cow::Owned(format!("{kind}{normalized}").into()),
+    }
+}
+
+/// Directories with build artifacts: `OUT_DIR`, `CARGO_TARGET_DIR` and `target` directory of the package.
+///
+/// Build script and Cargo write to them on every build, so `rerun-if-changed` pointing inside makes
+/// Cargo rerun the build script on every build.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct BuildDirs {
+    manifest_dir: Option<PathBuf>,
+    dirs: Vec<PathBuf>,
+}
+
+impl BuildDirs {
+    /// Reads build directories from env variables set by Cargo. Relative paths are resolved against
+    /// `CARGO_MANIFEST_DIR`, like Cargo does.
+    pub(crate) fn from_env(var: impl Fn(&str) -> Option<OsString>) -> Self {
+        let manifest_dir = var("CARGO_MANIFEST_DIR").map(PathBuf::from);
+
+        let mut dirs: Vec<PathBuf> = ["OUT_DIR", "CARGO_TARGET_DIR"]
+            .into_iter()
+            .filter_map(|name| var(name).map(PathBuf::from))
+            .collect();
+        dirs.extend(manifest_dir.is_some().then(|| PathBuf::from("target")));
+
+        let dirs = dirs
+            .iter()
+            .map(|dir| resolve(manifest_dir.as_deref(), dir).into_owned())
+            .collect();
+
+        Self { manifest_dir, dirs }
+    }
+
+    /// Returns build directory which contains `path`.
+    pub(crate) fn find(&self, path: &Path) -> Option<&Path> {
+        let path = resolve(self.manifest_dir.as_deref(), path);
+        self.dirs
+            .iter()
+            .find(|dir| path.starts_with(dir))
+            .map(PathBuf::as_path)
+    }
+}
+
+fn resolve<'a>(manifest_dir: Option<&Path>, path: &'a Path) -> Cow<'a, Path> {
+    match manifest_dir {
+        Some(manifest_dir) => Cow::Owned(manifest_dir.join(path)),
+        None => Cow::Borrowed(path),
+    }
+}
+
+fn normalize_str(mut path: Cow<'_, str>) -> Cow<'_, str> {
+    if cfg!(target_os = "macos") {
+        if let Some(nfc) = to_nfc(&path) {
+            path = Cow::Owned(nfc);
+        }
+    }
+    if cfg!(windows) {
+        path = normalize_windows(path);
+    }
+    path
+}
+
+fn normalize_windows(path: Cow<'_, str>) -> Cow<'_, str> {
+    let path = match strip_verbatim_prefix(&path) {
+        Some(stripped) => Cow::Owned(stripped),
+        None => path,
+    };
+    if path.chars().count() >= WINDOWS_MAX_PATH {
+        crate::warning(&format!(
+            "Path `{path}` exceeds {WINDOWS_MAX_PATH} characters. Cargo fingerprinting and some linkers may fail to use it"
+        ));
+    }
+    path
+}
+
+/// Strips `\\?\` and `\\?\UNC\` verbatim prefixes if the path means the same thing without them.
+///
+/// Returns `None` if path is not verbatim or stripping the prefix is unsafe:
+/// - resulting path would exceed `MAX_PATH`.
+/// - path contains `/`, `.` or `..` components, or components ending with `.` or ` `,
+///   which are interpreted differently by non-verbatim paths.
+pub(crate) fn strip_verbatim_prefix(path: &str) -> Option<String> {
+    let stripped = if let Some(unc) = path.strip_prefix(r"\\?\UNC\") {
+        format!(r"\\{unc}")
+    } else {
+        let disk = path.strip_prefix(r"\\?\")?;
+        let bytes = disk.as_bytes();
+        if bytes.len() < 3 || !bytes[0].is_ascii_alphabetic() || &bytes[1..3] != b":\\" {
+            return None;
+        }
+        disk.to_string()
+    };
+
+    if stripped.chars().count() >= WINDOWS_MAX_PATH || stripped.contains('/') {
+        return None;
+    }
+    let unsafe_component = stripped
+        .trim_start_matches('\\')
+        .split('\\')
+        .any(|c| c == "." || c == ".." || c.ends_with('.') || c.ends_with(' '));
+    if unsafe_component {
+        return None;
+    }
+    Some(stripped)
+}
+
+/// Converts path to Unicode NFC form. Returns `None` if path is already in NFC form.
+///
+/// HFS+ and APFS may report NFD names while `Cargo.toml` and source files usually use NFC.
+#[cfg(feature = "nfc")]
+pub(crate) fn to_nfc(path: &str) -> Option<String> {
+    use unicode_normalization::{is_nfc, UnicodeNormalization};
+
+    if is_nfc(path) {
+        None
+    } else {
+        Some(path.nfc().collect())
+    }
+}
+
+#[cfg(not(feature = "nfc"))]
+pub(crate) fn to_nfc(_path: &str) -> Option<String> {
+    None
+}
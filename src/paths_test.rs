@@ -0,0 +1,73 @@
+use std::path::Path;
+
+use crate::paths::{strip_verbatim_prefix, BuildDirs};
+
+#[test]
+fn strip_verbatim_disk_test() {
+    assert_eq!(
+        strip_verbatim_prefix(r"\\?\C:\libs\foo").as_deref(),
+        Some(r"C:\libs\foo")
+    );
+    assert_eq!(strip_verbatim_prefix(r"\\?\C:\").as_deref(), Some(r"C:\"));
+}
+
+#[test]
+fn strip_verbatim_unc_test() {
+    assert_eq!(
+        strip_verbatim_prefix(r"\\?\UNC\server\share\libs").as_deref(),
+        Some(r"\\server\share\libs")
+    );
+}
+
+#[test]
+fn strip_verbatim_not_verbatim_test() {
+    assert_eq!(strip_verbatim_prefix(r"C:\libs"), None);
+    assert_eq!(strip_verbatim_prefix(r"\\server\share"), None);
+    assert_eq!(strip_verbatim_prefix("libs/foo"), None);
+    assert_eq!(strip_verbatim_prefix(r"\\?\GLOBALROOT\Device"), None);
+}
+
+#[test]
+fn strip_verbatim_unsafe_test() {
+    assert_eq!(strip_verbatim_prefix(r"\\?\C:\libs\..\foo"), None);
+    assert_eq!(strip_verbatim_prefix(r"\\?\C:\libs\.\foo"), None);
+    assert_eq!(strip_verbatim_prefix(r"\\?\C:\libs.\foo"), None);
+    assert_eq!(strip_verbatim_prefix(r"\\?\C:\libs \foo"), None);
+    assert_eq!(strip_verbatim_prefix(r"\\?\C:\libs/foo"), None);
+
+    let long = format!(r"\\?\C:\{}", "a".repeat(300));
+    assert_eq!(strip_verbatim_prefix(&long), None);
+}
+
+#[test]
+#[cfg(feature = "nfc")]
+fn to_nfc_test() {
+    use crate::paths::to_nfc;
+
+    // "é" as `e` followed by combining acute accent
+    let nfd = "caf\u{0065}\u{0301}/menu.txt";
+    assert_eq!(to_nfc(nfd).as_deref(), Some("caf\u{00e9}/menu.txt"));
+
+    assert_eq!(to_nfc("caf\u{00e9}/menu.txt"), None);
+    assert_eq!(to_nfc("src/main.c"), None);
+}
+
+#[test]
+fn build_dirs_test() {
+    let build_dirs = BuildDirs::from_env(|var| match var {
+        "CARGO_MANIFEST_DIR" => Some("/work/foo".into()),
+        "CARGO_TARGET_DIR" => Some("../build".into()),
+        _ => None,
+    });
+
+    assert_eq!(
+        build_dirs.find(Path::new("../build/release/gen.rs")),
+        Some(Path::new("/work/foo/../build"))
+    );
+    assert_eq!(
+        build_dirs.find(Path::new("/work/foo/target/include")),
+        Some(Path::new("/work/foo/target"))
+    );
+    assert_eq!(build_dirs.find(Path::new("src/lib.rs")), None);
+    assert_eq!(build_dirs.find(Path::new("targets.txt")), None);
+}
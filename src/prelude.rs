@@ -0,0 +1,44 @@
+//! Everything build scripts commonly need.
+//!
+//! ```rust
+//! // build.rs
+//! use cargo_build::prelude::*;
+//!
+//! rerun_if_changed(["src/main.c", "src/main.h"]);
+//! rustc_link_search_native(["libs"]);
+//! rustc_link_lib_static([], ["foo"]);
+//!
+//! if !has_atomic(64) {
+//!     rustc_cfg("no_atomic_64");
+//! }
+//! ```
+//!
+//! With `macros` feature enabled, macros with the same names as functions are imported too.
+
+pub use crate::{
+    directive, directive_fmt, emit, emit_cfg_for_feature, emit_target_cfg_table, error,
+    error_and_continue, error_and_exit, feature_cfg, flush, has_errors, metadata, replay,
+    rerun_if_changed, rerun_if_env_changed, rustc_cfg, rustc_check_cfg, rustc_check_cfgs,
+    rustc_env, rustc_flags, rustc_flags_from, rustc_link_arg, rustc_link_arg_benches,
+    rustc_link_arg_bin, rustc_link_arg_bins, rustc_link_arg_cdylib, rustc_link_arg_examples,
+    rustc_link_arg_tests, rustc_link_lib, rustc_link_lib_dylib, rustc_link_lib_framework,
+    rustc_link_lib_static, rustc_link_search, rustc_link_search_all, rustc_link_search_crate,
+    rustc_link_search_dependency, rustc_link_search_framework, rustc_link_search_native,
+    set_missing_path_policy, set_newline_policy, set_unsupported_policy, strict_metadata_keys,
+    strict_rerun_paths, warning, MissingPathPolicy, NewlinePolicy, UnsupportedPolicy,
+};
+
+pub use crate::{
+    instrument, set_error_policy, set_instrument_threshold, set_rerun_policy, ErrorCollector,
+    ErrorPolicy, Instruction, LinkKind, LinkLib, LinkSearch, ParseInstructionError, RerunPolicy,
+    SearchKind, Session,
+};
+
+pub use crate::presets::{Library, LibrarySpec};
+
+pub use crate::env::{
+    cfg_target_features, has_atomic, has_atomic_ptr, has_target_feature, is_cross_compiling,
+    is_host_build, target_features, target_runner,
+};
+
+pub use crate::{build_out, ci, fetch, out, parallel, presets, toolchain, vendor};
@@ -0,0 +1,65 @@
+use std::sync::{Arc, RwLock};
+
+use crate::prelude::*;
+
+#[test]
+fn prelude_test() {
+    let vec_out = TestWriteVecHandle::new();
+    build_out::set(vec_out.clone());
+
+    rerun_if_changed("src/main.c");
+    rustc_flags_from(&[LinkLib::new("foo").kind(LinkKind::Static)], &[]);
+
+    if !is_host_build() {
+        rustc_cfg("cross");
+    }
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert!(
+        out.starts_with("cargo::rerun-if-changed=src/main.c\ncargo::rustc-flags=-l static=foo\n")
+    );
+}
+
+#[test]
+#[cfg(feature = "macros")]
+fn prelude_macros_test() {
+    let vec_out = TestWriteVecHandle::new();
+    build_out::set(vec_out.clone());
+
+    let name = "main";
+    rerun_if_changed!("src/{name}.c");
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(out, "cargo::rerun-if-changed=src/main.c\n");
+}
+
+struct TestWriteVecHandle(Arc<RwLock<Vec<u8>>>);
+
+impl TestWriteVecHandle {
+    fn new() -> Self {
+        Self(Arc::new(RwLock::new(Vec::new())))
+    }
+}
+
+impl Clone for TestWriteVecHandle {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl std::io::Write for TestWriteVecHandle {
+    fn write(&mut self, buf: &[u8]) -> std::result::Result<usize, std::io::Error> {
+        self.0
+            .write()
+            .expect("Unable to aquire Write lock")
+            .write(buf)
+    }
+
+    fn flush(&mut self) -> std::result::Result<(), std::io::Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,590 @@
+//! Ready-made instruction sets for common kinds of native libraries.
+//!
+//! Presets read target of the build from `CARGO_CFG_TARGET_*` env variables set by Cargo
+//! for build scripts and fall back to the host platform otherwise.
+//!
+//! Results of `pkg-config` and `brew` invocations are cached in `OUT_DIR`, so reruns of the build
+//! script don't spawn them again. Set `CARGO_BUILD_REPROBE=1` to force probing.
+
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::env::non_empty_var;
+use crate::probe_cache;
+use crate::{
+    metadata, rerun_if_env_changed, rustc_link_arg_cdylib, rustc_link_lib, rustc_link_search,
+    rustc_link_search_native,
+};
+
+/// Target of the build as seen by presets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Target {
+    pub(crate) os: String,
+    pub(crate) env: String,
+    pub(crate) arch: String,
+}
+
+impl Target {
+    pub(crate) fn from_env() -> Self {
+        Self {
+            os: env::var("CARGO_CFG_TARGET_OS").unwrap_or_else(|_| env::consts::OS.to_string()),
+            env: env::var("CARGO_CFG_TARGET_ENV").unwrap_or_else(|_| host_env().to_string()),
+            arch: env::var("CARGO_CFG_TARGET_ARCH")
+                .unwrap_or_else(|_| env::consts::ARCH.to_string()),
+        }
+    }
+
+    fn is_apple(&self) -> bool {
+        self.os == "macos" || self.os == "ios"
+    }
+}
+
+fn host_env() -> &'static str {
+    if cfg!(target_env = "msvc") {
+        "msvc"
+    } else if cfg!(target_env = "gnu") {
+        "gnu"
+    } else if cfg!(target_env = "musl") {
+        "musl"
+    } else {
+        ""
+    }
+}
+
+/// Name of the env variable specific to the target, e.g. `X86_64_UNKNOWN_LINUX_GNU_OPENSSL_DIR`.
+fn target_env_var_name(triple: &str, name: &str) -> String {
+    format!("{}_{name}", triple.to_uppercase().replace('-', "_"))
+}
+
+/// Native library found by presets.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Library {
+    pub lib_dirs: Vec<PathBuf>,
+    pub include_dirs: Vec<PathBuf>,
+    pub libs: Vec<String>,
+    /// Private dependencies of the library which `pkg-config --static` adds, e.g. `m` or `pthread`.
+    /// Always linked dynamically.
+    pub system_libs: Vec<String>,
+    pub is_static: bool,
+}
+
+/// Emits `rustc-link-search` and `rustc-link-lib` instructions for the library and publishes its
+/// include directories as `include` [`metadata`].
+pub(crate) fn emit_library(library: &Library) {
+    rustc_link_search_native(&library.lib_dirs);
+
+    let kind = if library.is_static { "static" } else { "dylib" };
+    rustc_link_lib(library.libs.iter().map(|lib| format!("{kind}={lib}")));
+    rustc_link_lib(library.system_libs.iter().map(|lib| format!("dylib={lib}")));
+
+    if let Ok(include) = env::join_paths(&library.include_dirs) {
+        if !include.is_empty() {
+            metadata("include", &include.to_string_lossy());
+        }
+    }
+}
+
+/// Env variables which change the result of `pkg-config`.
+const PKG_CONFIG_VARS: [&str; 5] = [
+    "PKG_CONFIG",
+    "PKG_CONFIG_PATH",
+    "PKG_CONFIG_LIBDIR",
+    "PKG_CONFIG_SYSROOT_DIR",
+    "PKG_CONFIG_ALLOW_CROSS",
+];
+
+/// Queries `pkg-config` (or `PKG_CONFIG`) for the package.
+///
+/// When cross-compiling `pkg-config` is only used if `PKG_CONFIG_ALLOW_CROSS` is set.
+pub(crate) fn pkg_config(package: &str, is_static: bool) -> Option<Library> {
+    rerun_if_env_changed(PKG_CONFIG_VARS);
+
+    if non_empty_var("HOST") != non_empty_var("TARGET")
+        && non_empty_var("PKG_CONFIG_ALLOW_CROSS").is_none()
+    {
+        return None;
+    }
+
+    let pkg_config = non_empty_var("PKG_CONFIG").unwrap_or("pkg-config".to_string());
+    let pkg_config_path = non_empty_var("PKG_CONFIG_PATH").unwrap_or_default();
+    let pkg_config_libdir = non_empty_var("PKG_CONFIG_LIBDIR").unwrap_or_default();
+    let pkg_config_sysroot_dir = non_empty_var("PKG_CONFIG_SYSROOT_DIR").unwrap_or_default();
+
+    let query = |is_static: bool| {
+        let static_flag = if is_static { "--static" } else { "" };
+        probe_cache::cached(
+            "pkg-config",
+            &[
+                &pkg_config,
+                &pkg_config_path,
+                &pkg_config_libdir,
+                &pkg_config_sysroot_dir,
+                static_flag,
+                package,
+            ],
+            || {
+                let mut command = Command::new(&pkg_config);
+                command.args(["--libs", "--cflags"]);
+                if is_static {
+                    command.arg("--static");
+                }
+                let output = command.arg(package).output().ok()?;
+                if !output.status.success() {
+                    return None;
+                }
+                String::from_utf8(output.stdout).ok()
+            },
+        )
+    };
+
+    let output = query(false)?;
+    match is_static {
+        true => Some(split_static_libs(&output, &query(true)?)),
+        false => Some(parse_pkg_config(&output)),
+    }
+}
+
+/// Combines `pkg-config --libs` and `pkg-config --static --libs` output of the package.
+///
+/// Only libraries of the package and packages it requires are linked statically. Libraries which
+/// only appear with `--static` are private dependencies, mostly system libraries such as `m`,
+/// `pthread` and `dl`, and are linked dynamically.
+pub(crate) fn split_static_libs(shared_output: &str, static_output: &str) -> Library {
+    let mut library = parse_pkg_config(shared_output);
+    let full = parse_pkg_config(static_output);
+
+    for dir in full.lib_dirs {
+        if !library.lib_dirs.contains(&dir) {
+            library.lib_dirs.push(dir);
+        }
+    }
+    library.system_libs = full
+        .libs
+        .into_iter()
+        .filter(|lib| !library.libs.contains(lib))
+        .collect();
+    library.is_static = true;
+    library
+}
+
+/// Parses `-L`, `-l` and `-I` flags of `pkg-config --libs --cflags` output.
+pub(crate) fn parse_pkg_config(output: &str) -> Library {
+    let mut library = Library::default();
+
+    for flag in output.split_whitespace() {
+        if let Some(dir) = flag.strip_prefix("-L") {
+            library.lib_dirs.push(dir.into());
+        } else if let Some(lib) = flag.strip_prefix("-l") {
+            library.libs.push(lib.to_string());
+        } else if let Some(dir) = flag.strip_prefix("-I") {
+            library.include_dirs.push(dir.into());
+        }
+    }
+    library
+}
+
+/// Looks for the library installed by `vcpkg` under `VCPKG_ROOT`.
+pub(crate) fn vcpkg(target: &Target, libs: &[&str], is_static: bool) -> Option<Library> {
+    rerun_if_env_changed("VCPKG_ROOT");
+
+    let root = PathBuf::from(non_empty_var("VCPKG_ROOT")?);
+    let installed = root
+        .join("installed")
+        .join(vcpkg_triplet(target, is_static)?);
+
+    let lib_dir = installed.join("lib");
+    if !lib_dir.is_dir() {
+        return None;
+    }
+    Some(Library {
+        lib_dirs: vec![lib_dir],
+        include_dirs: vec![installed.join("include")],
+        libs: libs.iter().map(|lib| lib.to_string()).collect(),
+        system_libs: Vec::new(),
+        is_static,
+    })
+}
+
+pub(crate) fn vcpkg_triplet(target: &Target, is_static: bool) -> Option<String> {
+    let arch = match target.arch.as_str() {
+        "x86_64" => "x64",
+        "x86" => "x86",
+        "aarch64" => "arm64",
+        "arm" => "arm",
+        _ => return None,
+    };
+    let os = match (target.os.as_str(), is_static) {
+        ("windows", true) => "windows-static-md",
+        ("windows", false) => "windows",
+        ("linux", _) => "linux",
+        ("macos", _) => "osx",
+        _ => return None,
+    };
+    Some(format!("{arch}-{os}"))
+}
+
+/// Emits link arguments for building Python extension module.
+///
+/// - On macOS and iOS symbols of `libpython` are resolved when the module is loaded by the
+///   interpreter, so `-undefined dynamic_lookup` is passed to the linker.
+/// - On Linux `libpython` is not linked at all, as required by manylinux.
+/// - On Windows the module is linked against `pythonXY.lib` import library. Version is taken from
+///   `PYO3_CROSS_PYTHON_VERSION` (e.g. `3.11`), stable ABI `python3.lib` is used if it is not set.
+///   Directory containing import library is taken from `PYO3_CROSS_LIB_DIR`.
+///
+/// ```rust
+/// // build.rs
+/// cargo_build::presets::pyo3();
+/// ```
+pub fn pyo3() {
+    rerun_if_env_changed(["PYO3_CROSS_LIB_DIR", "PYO3_CROSS_PYTHON_VERSION"]);
+
+    pyo3_for(
+        &Target::from_env(),
+        non_empty_var("PYO3_CROSS_PYTHON_VERSION").as_deref(),
+        non_empty_var("PYO3_CROSS_LIB_DIR").as_deref(),
+    );
+}
+
+pub(crate) fn pyo3_for(target: &Target, version: Option<&str>, lib_dir: Option<&str>) {
+    if target.is_apple() {
+        rustc_link_arg_cdylib(["-undefined", "dynamic_lookup"]);
+    } else if target.os == "windows" {
+        if let Some(lib_dir) = lib_dir {
+            rustc_link_search([format!("native={lib_dir}")]);
+        }
+        let lib = match version {
+            // MinGW import libraries keep the dot: `python3.11`
+            Some(version) if target.env == "gnu" => format!("python{version}"),
+            Some(version) => format!("python{}", version.replace('.', "")),
+            None => "python3".to_string(),
+        };
+        rustc_link_lib([lib]);
+    }
+}
+
+/// Emits link arguments for building Node.js addon.
+///
+/// - On macOS and iOS symbols of `node` are resolved when the addon is loaded, so
+///   `-undefined dynamic_lookup` is passed to the linker.
+/// - On Linux with glibc addon is marked with `-z nodelete` because it can't be safely unloaded
+///   while `node` threads still reference it.
+/// - On Windows nothing is emitted, N-API symbols are resolved from the host process at runtime.
+///
+/// ```rust
+/// // build.rs
+/// cargo_build::presets::napi();
+/// ```
+pub fn napi() {
+    napi_for(&Target::from_env());
+}
+
+pub(crate) fn napi_for(target: &Target) {
+    if target.is_apple() {
+        rustc_link_arg_cdylib(["-undefined", "dynamic_lookup"]);
+    } else if target.os == "linux" && target.env == "gnu" {
+        rustc_link_arg_cdylib("-Wl,-z,nodelete");
+    }
+}
+
+const CUDA_ENV_VARS: [&str; 4] = [
+    "CUDA_PATH",
+    "CUDA_HOME",
+    "CUDA_ROOT",
+    "CUDA_TOOLKIT_ROOT_DIR",
+];
+
+/// Locates CUDA toolkit and links requested CUDA libraries, e.g. `cudart` or `cuda`.
+///
+/// Toolkit is searched in `CUDA_PATH`, `CUDA_HOME`, `CUDA_ROOT` and `CUDA_TOOLKIT_ROOT_DIR`
+/// env variables first, then in standard install directories:
+/// - `C:\Program Files\NVIDIA GPU Computing Toolkit\CUDA\vX.Y` on Windows (newest version wins).
+/// - `/usr/local/cuda`, `/opt/cuda` and `/usr/lib/cuda` elsewhere.
+///
+/// Library directory is chosen for the target architecture. When `cuda` driver library is
+/// requested, `stubs` directory is added too so the crate can be linked on machines without driver.
+///
+/// `root` and `include` directories of the toolkit are published with [`metadata`] for dependent
+/// crates, which requires `links` manifest key.
+///
+/// Returns root of the toolkit or `None` if toolkit was not found. Nothing but env tracking is
+/// emitted in this case.
+///
+/// ```rust
+/// // build.rs
+/// if cargo_build::presets::cuda(&["cudart"]).is_none() {
+///     cargo_build::warning("CUDA toolkit not found, GPU backend is disabled");
+/// }
+/// ```
+pub fn cuda(libs: &[&str]) -> Option<PathBuf> {
+    rerun_if_env_changed(CUDA_ENV_VARS);
+
+    let target = Target::from_env();
+
+    let root = CUDA_ENV_VARS
+        .into_iter()
+        .filter_map(non_empty_var)
+        .map(PathBuf::from)
+        .chain(cuda_default_roots(&target))
+        .find(|root| root.join("include").join("cuda.h").is_file())?;
+
+    cuda_for(&target, &root, libs);
+    Some(root)
+}
+
+pub(crate) fn cuda_for(target: &Target, root: &Path, libs: &[&str]) {
+    let lib_dir = cuda_lib_dirs(target)
+        .into_iter()
+        .map(|dir| root.join(dir))
+        .find(|dir| dir.is_dir());
+
+    if let Some(lib_dir) = lib_dir {
+        rustc_link_search_native([&lib_dir]);
+
+        let stubs = lib_dir.join("stubs");
+        if libs.contains(&"cuda") && stubs.is_dir() {
+            rustc_link_search_native([stubs]);
+        }
+    }
+    rustc_link_lib(libs);
+
+    metadata("root", &root.display().to_string());
+    metadata("include", &root.join("include").display().to_string());
+}
+
+/// Library directories relative to the toolkit root, most specific first.
+fn cuda_lib_dirs(target: &Target) -> Vec<&'static str> {
+    match (target.os.as_str(), target.arch.as_str()) {
+        ("windows", "x86") => vec!["lib/Win32"],
+        ("windows", _) => vec!["lib/x64"],
+        (_, "x86_64") => vec!["targets/x86_64-linux/lib", "lib64", "lib"],
+        (_, "aarch64") => vec![
+            "targets/sbsa-linux/lib",
+            "targets/aarch64-linux/lib",
+            "lib64",
+            "lib",
+        ],
+        _ => vec!["lib64", "lib"],
+    }
+}
+
+fn cuda_default_roots(target: &Target) -> Vec<PathBuf> {
+    if target.os != "windows" {
+        return ["/usr/local/cuda", "/opt/cuda", "/usr/lib/cuda"]
+            .into_iter()
+            .map(PathBuf::from)
+            .collect();
+    }
+
+    let Ok(entries) = std::fs::read_dir(r"C:\Program Files\NVIDIA GPU Computing Toolkit\CUDA")
+    else {
+        return Vec::new();
+    };
+    let mut versions: Vec<((u32, u32), PathBuf)> = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let (major, minor) = path
+                .file_name()?
+                .to_str()?
+                .strip_prefix('v')?
+                .split_once('.')?;
+            Some(((major.parse().ok()?, minor.parse().ok()?), path))
+        })
+        .collect();
+
+    versions.sort_by_key(|(version, _)| std::cmp::Reverse(*version));
+    versions.into_iter().map(|(_, path)| path).collect()
+}
+
+/// Description of the native library for [`probe`].
+///
+/// ```rust
+/// use cargo_build::presets::LibrarySpec;
+///
+/// let zstd = LibrarySpec {
+///     name: "zstd",
+///     pkg_config_name: Some("libzstd"),
+///     env_prefix: "ZSTD",
+///     headers: &["zstd.h"],
+///     libs: &["zstd"],
+/// };
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LibrarySpec<'a> {
+    /// Human-readable name used in warnings.
+    pub name: &'a str,
+    /// Name of the `pkg-config` package. `pkg-config` is not queried if `None`.
+    pub pkg_config_name: Option<&'a str>,
+    /// Prefix of the env variables, e.g. `OPENSSL` for `OPENSSL_DIR`.
+    pub env_prefix: &'a str,
+    /// Headers which must be present in one of include directories.
+    pub headers: &'a [&'a str],
+    /// Libraries to link unless overridden by `{PREFIX}_LIBS` or `pkg-config`.
+    pub libs: &'a [&'a str],
+}
+
+/// Env variable suffixes recognized by [`probe`].
+const LIBRARY_ENV_VARS: [&str; 5] = ["DIR", "LIB_DIR", "INCLUDE_DIR", "STATIC", "LIBS"];
+
+/// Locates native library described by [`LibrarySpec`] and links it.
+///
+/// Each env variable may be prefixed with the target, e.g. `X86_64_UNKNOWN_LINUX_GNU_ZSTD_DIR`,
+/// which takes precedence over the unprefixed variable:
+/// - `{PREFIX}_LIB_DIR` and `{PREFIX}_INCLUDE_DIR` point to library and header directories.
+/// - `{PREFIX}_DIR` is used when they are not set, with `lib` and `include` subdirectories.
+/// - `{PREFIX}_LIBS` overrides `:`-separated list of libraries to link.
+/// - `{PREFIX}_STATIC` links libraries statically. System libraries reported by `pkg-config --static`
+///   are still linked dynamically.
+///
+/// When none of the directories is set, `pkg-config` and then `vcpkg` are queried. Libraries found
+/// this way are only used if they provide all [`LibrarySpec::headers`].
+///
+/// Include directories are published with [`metadata`] as `include` for dependent crates,
+/// which requires `links` manifest key.
+///
+/// Returns `None` and emits nothing but env tracking if the library was not found.
+///
+/// ```rust
+/// // build.rs
+/// use cargo_build::presets::{self, LibrarySpec};
+///
+/// let zstd = presets::probe(LibrarySpec {
+///     name: "zstd",
+///     pkg_config_name: Some("libzstd"),
+///     env_prefix: "ZSTD",
+///     headers: &["zstd.h"],
+///     libs: &["zstd"],
+/// });
+///
+/// if zstd.is_none() {
+///     cargo_build::warning("zstd not found, building bundled copy");
+/// }
+/// ```
+pub fn probe(spec: LibrarySpec<'_>) -> Option<Library> {
+    let target = Target::from_env();
+    let triple = non_empty_var("TARGET");
+
+    for suffix in LIBRARY_ENV_VARS {
+        let name = format!("{}_{suffix}", spec.env_prefix);
+        if let Some(triple) = &triple {
+            rerun_if_env_changed(target_env_var_name(triple, &name));
+        }
+        rerun_if_env_changed(name);
+    }
+
+    let var = |suffix: &str| {
+        let name = format!("{}_{suffix}", spec.env_prefix);
+        triple
+            .as_ref()
+            .and_then(|triple| non_empty_var(&target_env_var_name(triple, &name)))
+            .or_else(|| non_empty_var(&name))
+    };
+
+    let library = probe_for(&spec, &target, var)?;
+    emit_library(&library);
+    Some(library)
+}
+
+/// Resolves the library using `var` to read env variables by suffix, e.g. `DIR`.
+pub(crate) fn probe_for(
+    spec: &LibrarySpec<'_>,
+    target: &Target,
+    var: impl Fn(&str) -> Option<String>,
+) -> Option<Library> {
+    let is_static = var("STATIC").is_some_and(|value| value != "0");
+
+    let mut library = match (var("LIB_DIR"), var("INCLUDE_DIR"), var("DIR")) {
+        (None, None, None) => spec
+            .pkg_config_name
+            .and_then(|package| pkg_config(package, is_static))
+            .filter(|library| has_headers(library, spec.headers))
+            .or_else(|| {
+                vcpkg(target, spec.libs, is_static)
+                    .filter(|library| has_headers(library, spec.headers))
+            })?,
+        (lib_dir, include_dir, dir) => {
+            let dir = dir.map(PathBuf::from);
+
+            let lib_dir = lib_dir.map(PathBuf::from).or_else(|| {
+                let dir = dir.as_ref()?;
+                let lib64 = dir.join("lib64");
+                Some(if lib64.is_dir() {
+                    lib64
+                } else {
+                    dir.join("lib")
+                })
+            });
+            let include_dir = include_dir
+                .map(PathBuf::from)
+                .or_else(|| Some(dir.as_ref()?.join("include")));
+
+            let library = Library {
+                lib_dirs: lib_dir.into_iter().collect(),
+                include_dirs: include_dir.into_iter().collect(),
+                libs: spec.libs.iter().map(|lib| lib.to_string()).collect(),
+                system_libs: Vec::new(),
+                is_static,
+            };
+
+            // Explicit configuration is trusted, but mistakes are worth pointing out
+            if !has_headers(&library, spec.headers) {
+                crate::warning(&format!(
+                    "{}: headers {:?} not found in {:?}",
+                    spec.name, spec.headers, library.include_dirs
+                ));
+            }
+            library
+        }
+    };
+
+    if let Some(libs) = var("LIBS") {
+        library.libs = libs.split(':').map(str::to_string).collect();
+    }
+    Some(library)
+}
+
+/// Checks that every header exists in one of include directories.
+///
+/// Libraries without include directories (e.g. installed into system directories) pass the check.
+fn has_headers(library: &Library, headers: &[&str]) -> bool {
+    library.include_dirs.is_empty()
+        || headers.iter().all(|header| {
+            library
+                .include_dirs
+                .iter()
+                .any(|dir| dir.join(header).is_file())
+        })
+}
+
+/// Locates OpenSSL following the conventions of `openssl-sys` and links it.
+///
+/// This is [`probe`] with `OPENSSL` env prefix and `openssl` `pkg-config` package:
+/// - `OPENSSL_LIB_DIR` and `OPENSSL_INCLUDE_DIR` point to library and header directories.
+/// - `OPENSSL_DIR` is used when they are not set, with `lib` and `include` subdirectories.
+/// - `OPENSSL_LIBS` overrides `:`-separated list of libraries to link.
+/// - `OPENSSL_STATIC` links libraries statically.
+///
+/// ```rust
+/// // build.rs
+/// if cargo_build::presets::openssl().is_none() {
+///     cargo_build::error("OpenSSL not found. Set OPENSSL_DIR or install development package");
+/// }
+/// ```
+pub fn openssl() -> Option<Library> {
+    probe(openssl_spec(&Target::from_env()))
+}
+
+pub(crate) fn openssl_spec(target: &Target) -> LibrarySpec<'static> {
+    LibrarySpec {
+        name: "openssl",
+        pkg_config_name: Some("openssl"),
+        env_prefix: "OPENSSL",
+        headers: &["openssl/ssl.h"],
+        libs: if target.os == "windows" && target.env == "msvc" {
+            &["libssl", "libcrypto"]
+        } else {
+            &["ssl", "crypto"]
+        },
+    }
+}
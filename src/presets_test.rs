@@ -0,0 +1,292 @@
+use std::sync::{Arc, RwLock};
+
+use crate as cargo_build;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::presets::{
+    cuda_for, emit_library, napi_for, openssl_spec, parse_pkg_config, probe_for, pyo3_for,
+    split_static_libs, vcpkg_triplet, Library, LibrarySpec, Target,
+};
+
+fn target(os: &str, env: &str) -> Target {
+    Target {
+        os: os.to_string(),
+        env: env.to_string(),
+        arch: "x86_64".to_string(),
+    }
+}
+
+fn output(f: impl FnOnce()) -> String {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    f();
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    String::from_utf8(out.clone()).unwrap()
+}
+
+#[test]
+fn pyo3_test() {
+    assert_eq!(
+        output(|| pyo3_for(&target("macos", ""), None, None)),
+        "cargo::rustc-link-arg-cdylib=-undefined\ncargo::rustc-link-arg-cdylib=dynamic_lookup\n"
+    );
+
+    assert_eq!(
+        output(|| pyo3_for(&target("linux", "gnu"), Some("3.11"), None)),
+        ""
+    );
+
+    assert_eq!(
+        output(|| pyo3_for(
+            &target("windows", "msvc"),
+            Some("3.11"),
+            Some("C:\\Python311\\libs")
+        )),
+        "cargo::rustc-link-search=native=C:\\Python311\\libs\ncargo::rustc-link-lib=python311\n"
+    );
+
+    assert_eq!(
+        output(|| pyo3_for(&target("windows", "gnu"), Some("3.11"), None)),
+        "cargo::rustc-link-lib=python3.11\n"
+    );
+
+    assert_eq!(
+        output(|| pyo3_for(&target("windows", "msvc"), None, None)),
+        "cargo::rustc-link-lib=python3\n"
+    );
+}
+
+#[test]
+fn napi_test() {
+    assert_eq!(
+        output(|| napi_for(&target("macos", ""))),
+        "cargo::rustc-link-arg-cdylib=-undefined\ncargo::rustc-link-arg-cdylib=dynamic_lookup\n"
+    );
+
+    assert_eq!(
+        output(|| napi_for(&target("linux", "gnu"))),
+        "cargo::rustc-link-arg-cdylib=-Wl,-z,nodelete\n"
+    );
+
+    assert_eq!(output(|| napi_for(&target("linux", "musl"))), "");
+    assert_eq!(output(|| napi_for(&target("windows", "msvc"))), "");
+}
+
+#[test]
+fn cuda_test() {
+    let root = std::env::temp_dir().join("cargo_build_cuda_test");
+    std::fs::create_dir_all(root.join("include")).unwrap();
+    std::fs::create_dir_all(root.join("lib64").join("stubs")).unwrap();
+    std::fs::write(root.join("include").join("cuda.h"), "").unwrap();
+
+    let root_str = root.display();
+    let lib_dir = root.join("lib64");
+    let lib_dir = lib_dir.display();
+    let stubs = root.join("lib64").join("stubs");
+    let stubs = stubs.display();
+    let include = root.join("include");
+    let include = include.display();
+
+    assert_eq!(
+        output(|| cuda_for(&target("linux", "gnu"), &root, &["cudart"])),
+        format!(
+            "cargo::rustc-link-search=native={lib_dir}\n\
+             cargo::rustc-link-lib=cudart\n\
+             cargo::metadata=root={root_str}\n\
+             cargo::metadata=include={include}\n"
+        )
+    );
+
+    assert_eq!(
+        output(|| cuda_for(&target("linux", "gnu"), &root, &["cudart", "cuda"])),
+        format!(
+            "cargo::rustc-link-search=native={lib_dir}\n\
+             cargo::rustc-link-search=native={stubs}\n\
+             cargo::rustc-link-lib=cudart\n\
+             cargo::rustc-link-lib=cuda\n\
+             cargo::metadata=root={root_str}\n\
+             cargo::metadata=include={include}\n"
+        )
+    );
+}
+
+#[test]
+fn openssl_test() {
+    let vars = HashMap::from([
+        ("DIR", "/opt/openssl"),
+        ("INCLUDE_DIR", "/usr/include/openssl3"),
+        ("STATIC", "1"),
+    ]);
+    let var = |name: &str| vars.get(name).map(|value| value.to_string());
+
+    let linux = target("linux", "gnu");
+    let mut library = None;
+    let out = output(|| library = probe_for(&openssl_spec(&linux), &linux, var));
+
+    assert_eq!(
+        out,
+        "cargo::warning=openssl: headers [\"openssl/ssl.h\"] not found in [\"/usr/include/openssl3\"]\n"
+    );
+    assert_eq!(
+        library.unwrap(),
+        Library {
+            lib_dirs: vec![PathBuf::from("/opt/openssl/lib")],
+            include_dirs: vec![PathBuf::from("/usr/include/openssl3")],
+            libs: vec!["ssl".to_string(), "crypto".to_string()],
+            system_libs: Vec::new(),
+            is_static: true,
+        }
+    );
+
+    let vars = HashMap::from([
+        ("LIB_DIR", "C:\\OpenSSL\\lib"),
+        ("LIBS", "libssl_static:libcrypto_static"),
+    ]);
+    let var = |name: &str| vars.get(name).map(|value| value.to_string());
+
+    let windows = target("windows", "msvc");
+    let library = probe_for(&openssl_spec(&windows), &windows, var).unwrap();
+
+    assert_eq!(library.lib_dirs, vec![PathBuf::from("C:\\OpenSSL\\lib")]);
+    assert!(library.include_dirs.is_empty());
+    assert_eq!(library.libs, vec!["libssl_static", "libcrypto_static"]);
+    assert!(!library.is_static);
+}
+
+#[test]
+fn probe_test() {
+    let root = std::env::temp_dir().join("cargo_build_probe_test");
+    std::fs::create_dir_all(root.join("include")).unwrap();
+    std::fs::create_dir_all(root.join("lib")).unwrap();
+    std::fs::write(root.join("include").join("zstd.h"), "").unwrap();
+
+    let spec = LibrarySpec {
+        name: "zstd",
+        pkg_config_name: None,
+        env_prefix: "ZSTD",
+        headers: &["zstd.h"],
+        libs: &["zstd"],
+    };
+    let root_str = root.to_str().unwrap().to_string();
+    let var = |name: &str| (name == "DIR").then(|| root_str.clone());
+
+    let mut library = None;
+    let out = output(|| library = probe_for(&spec, &target("linux", "gnu"), var));
+
+    assert_eq!(out, "");
+    assert_eq!(
+        library.unwrap(),
+        Library {
+            lib_dirs: vec![root.join("lib")],
+            include_dirs: vec![root.join("include")],
+            libs: vec!["zstd".to_string()],
+            system_libs: Vec::new(),
+            is_static: false,
+        }
+    );
+
+    // Nothing configured and no `pkg-config` or `vcpkg` to fall back to
+    let var = |_: &str| None;
+    assert_eq!(probe_for(&spec, &target("linux", "gnu"), var), None);
+}
+
+#[test]
+fn emit_library_test() {
+    let library = Library {
+        lib_dirs: vec![PathBuf::from("/opt/openssl/lib")],
+        include_dirs: vec![PathBuf::from("/opt/openssl/include")],
+        libs: vec!["ssl".to_string(), "crypto".to_string()],
+        system_libs: vec!["dl".to_string()],
+        is_static: true,
+    };
+
+    assert_eq!(
+        output(|| emit_library(&library)),
+        "cargo::rustc-link-search=native=/opt/openssl/lib\n\
+         cargo::rustc-link-lib=static=ssl\n\
+         cargo::rustc-link-lib=static=crypto\n\
+         cargo::rustc-link-lib=dylib=dl\n\
+         cargo::metadata=include=/opt/openssl/include\n"
+    );
+}
+
+#[test]
+fn split_static_libs_test() {
+    let library = split_static_libs(
+        "-I/opt/openssl/include -L/opt/openssl/lib -lssl -lcrypto\n",
+        "-I/opt/openssl/include -L/opt/openssl/lib -lssl -lcrypto -L/opt/zlib/lib -lz -ldl -pthread -lm\n",
+    );
+
+    assert_eq!(
+        library,
+        Library {
+            lib_dirs: vec![
+                PathBuf::from("/opt/openssl/lib"),
+                PathBuf::from("/opt/zlib/lib")
+            ],
+            include_dirs: vec![PathBuf::from("/opt/openssl/include")],
+            libs: vec!["ssl".to_string(), "crypto".to_string()],
+            system_libs: vec!["z".to_string(), "dl".to_string(), "m".to_string()],
+            is_static: true,
+        }
+    );
+}
+
+#[test]
+fn parse_pkg_config_test() {
+    let library = parse_pkg_config("-I/usr/include/openssl -L/usr/lib64 -lssl -lcrypto -pthread\n");
+
+    assert_eq!(library.lib_dirs, vec![PathBuf::from("/usr/lib64")]);
+    assert_eq!(
+        library.include_dirs,
+        vec![PathBuf::from("/usr/include/openssl")]
+    );
+    assert_eq!(library.libs, vec!["ssl", "crypto"]);
+}
+
+#[test]
+fn vcpkg_triplet_test() {
+    assert_eq!(
+        vcpkg_triplet(&target("windows", "msvc"), false).as_deref(),
+        Some("x64-windows")
+    );
+    assert_eq!(
+        vcpkg_triplet(&target("windows", "msvc"), true).as_deref(),
+        Some("x64-windows-static-md")
+    );
+    assert_eq!(
+        vcpkg_triplet(&target("macos", ""), false).as_deref(),
+        Some("x64-osx")
+    );
+    assert_eq!(vcpkg_triplet(&target("freebsd", ""), false), None);
+}
+
+struct TestWriteVecHandle(Arc<RwLock<Vec<u8>>>);
+
+impl TestWriteVecHandle {
+    fn new() -> Self {
+        Self(Arc::new(RwLock::new(Vec::new())))
+    }
+}
+
+impl Clone for TestWriteVecHandle {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl std::io::Write for TestWriteVecHandle {
+    fn write(&mut self, buf: &[u8]) -> std::result::Result<usize, std::io::Error> {
+        self.0
+            .write()
+            .expect("Unable to aquire Write lock")
+            .write(buf)
+    }
+
+    fn flush(&mut self) -> std::result::Result<(), std::io::Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,91 @@
+//! Cache of subprocess probes (`pkg-config`, `brew`) stored in `OUT_DIR`.
+//!
+//! Results are keyed by the toolchain fingerprint (`RUSTC`, `RUSTC_WRAPPER`, `CC`, `HOST` and `TARGET`),
+//! name of the probe and its inputs. `OUT_DIR` is already specific to the compiler version, so
+//! reruns of the build script reuse results while `cargo clean` drops them. Only found results are
+//! cached, a probe which found nothing runs again on the next rerun.
+//!
+//! Setting `CARGO_BUILD_REPROBE` env variable to any value except `0` forces every probe to run again.
+
+use std::fs;
+use std::path::Path;
+
+use crate::env::non_empty_var;
+use crate::sha256;
+
+/// Env variable which forces probes to run again.
+const REPROBE_VAR: &str = "CARGO_BUILD_REPROBE";
+
+/// Env variables identifying the toolchain.
+const FINGERPRINT_VARS: [&str; 5] = ["RUSTC", "RUSTC_WRAPPER", "CC", "HOST", "TARGET"];
+
+/// Returns cached result of `probe` with the same `name` and `inputs`, or runs it and caches the result.
+/// `inputs` must include every env variable which changes the result of `probe`.
+///
+/// Outside of build scripts `probe` always runs.
+pub(crate) fn cached(
+    name: &str,
+    inputs: &[&str],
+    probe: impl FnOnce() -> Option<String>,
+) -> Option<String> {
+    let Some(out_dir) = std::env::var_os("OUT_DIR") else {
+        return probe();
+    };
+    crate::rerun_if_env_changed(REPROBE_VAR);
+
+    let reprobe = non_empty_var(REPROBE_VAR).is_some_and(|value| value != "0");
+    let fingerprint = FINGERPRINT_VARS.map(|var| non_empty_var(var).unwrap_or_default());
+
+    cached_in(
+        &Path::new(&out_dir).join("cargo-build-probes"),
+        reprobe,
+        &key(&fingerprint, name, inputs),
+        probe,
+    )
+}
+
+pub(crate) fn key(fingerprint: &[String], name: &str, inputs: &[&str]) -> String {
+    let mut key = Vec::new();
+    for part in fingerprint
+        .iter()
+        .map(String::as_str)
+        .chain([name])
+        .chain(inputs.iter().copied())
+    {
+        key.extend_from_slice(part.as_bytes());
+        key.push(0);
+    }
+    sha256::hex_digest(&key)
+}
+
+pub(crate) fn cached_in(
+    dir: &Path,
+    reprobe: bool,
+    key: &str,
+    probe: impl FnOnce() -> Option<String>,
+) -> Option<String> {
+    let path = dir.join(key);
+
+    if !reprobe {
+        if let Ok(entry) = fs::read_to_string(&path) {
+            // `+` marks found result
+            if let Some(("+", result)) = entry.split_at_checked(1) {
+                return Some(result.to_string());
+            }
+        }
+    }
+
+    let result = probe();
+    // Failing to cache is not an error
+    match &result {
+        Some(result) => {
+            let _ = fs::create_dir_all(dir).and_then(|()| fs::write(&path, format!("+{result}")));
+        }
+        // Don't keep stale result of a reprobe which found nothing
+        None => {
+            let _ = fs::remove_file(&path);
+        }
+    }
+
+    result
+}
@@ -0,0 +1,72 @@
+use std::cell::Cell;
+
+use crate::probe_cache::{cached_in, key};
+
+#[test]
+fn key_test() {
+    let fingerprint = ["rustc".to_string(), String::new()];
+
+    assert_eq!(
+        key(&fingerprint, "pkg-config", &["zlib"]),
+        key(&fingerprint, "pkg-config", &["zlib"])
+    );
+    assert_ne!(
+        key(&fingerprint, "pkg-config", &["zlib"]),
+        key(&fingerprint, "pkg-config", &["zli", "b"])
+    );
+    assert_ne!(
+        key(&fingerprint, "pkg-config", &["zlib"]),
+        key(&["clippy-driver".to_string()], "pkg-config", &["zlib"])
+    );
+}
+
+#[test]
+fn cached_in_test() {
+    let dir = std::env::temp_dir().join("cargo_build_probe_cache_test");
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let runs = Cell::new(0);
+    let probe = |result: Option<&str>| {
+        runs.set(runs.get() + 1);
+        result.map(str::to_string)
+    };
+
+    assert_eq!(
+        cached_in(&dir, false, "found", || probe(Some("-lz"))),
+        Some("-lz".to_string())
+    );
+    assert_eq!(
+        cached_in(&dir, false, "found", || probe(None)),
+        Some("-lz".to_string())
+    );
+    assert_eq!(runs.get(), 1);
+
+    // Negative results are not cached, library installed later is found
+    assert_eq!(cached_in(&dir, false, "missing", || probe(None)), None);
+    assert_eq!(
+        cached_in(&dir, false, "missing", || probe(Some("-lz"))),
+        Some("-lz".to_string())
+    );
+    assert_eq!(runs.get(), 3);
+
+    // Reprobing replaces cached result
+    assert_eq!(
+        cached_in(&dir, true, "found", || probe(Some(""))),
+        Some(String::new())
+    );
+    assert_eq!(
+        cached_in(&dir, false, "found", || probe(None)),
+        Some(String::new())
+    );
+    assert_eq!(runs.get(), 4);
+
+    // Reprobing which found nothing drops cached result
+    assert_eq!(cached_in(&dir, true, "found", || probe(None)), None);
+    assert_eq!(
+        cached_in(&dir, false, "found", || probe(Some("-lbz2"))),
+        Some("-lbz2".to_string())
+    );
+    assert_eq!(runs.get(), 6);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,196 @@
+//! Human-readable summary of instructions emitted by the build script.
+//!
+//! ```rust
+//! use cargo_build::report::Report;
+//! use cargo_build::Instruction;
+//!
+//! let instructions = [
+//!     Instruction::new("rerun-if-changed", "build.rs"),
+//!     Instruction::new("rerun-if-changed", "src/foo.c"),
+//!     Instruction::new("rustc-link-search", "native=libs"),
+//!     Instruction::new("rustc-link-lib", "static=foo"),
+//!     Instruction::new("rustc-link-lib", "static=bar"),
+//!     Instruction::new("rustc-link-lib", "z"),
+//!     Instruction::new("rustc-env", "API_VERSION=3"),
+//!     Instruction::new("rustc-cfg", "has_foo"),
+//! ];
+//!
+//! assert_eq!(
+//!     Report::new(&instructions).to_string(),
+//!     "\
+//! 2 rerun-if-changed paths
+//!     build.rs
+//!     src/foo.c
+//! 3 link libraries
+//!     static: foo, bar
+//!     unspecified: z
+//! 1 link search path
+//!     native=libs
+//! 1 env variable
+//!     API_VERSION=3
+//! 1 cfg
+//!     has_foo
+//! "
+//! );
+//! ```
+//!
+//! Instructions can be recorded with [`build_out::recording`](crate::build_out::recording), and the
+//! report printed to `stderr` or written next to the build script output:
+//!
+//! ```rust,no_run
+//! let recording = cargo_build::build_out::recording();
+//!
+//! cargo_build::rerun_if_changed(["build.rs"]);
+//! cargo_build::rustc_link_lib(["foo"]);
+//!
+//! let instructions = recording.finish();
+//! let report = cargo_build::report::Report::new(&instructions);
+//!
+//! eprintln!("{report}");
+//! std::fs::write(std::env::var("OUT_DIR").unwrap() + "/report.txt", report.to_string()).unwrap();
+//! ```
+
+use std::fmt::{self, Display};
+
+use crate::{Instruction, LinkKind};
+
+/// Summary of instructions grouped by what they tell Cargo. [`Display`] renders a section per
+/// group, empty groups are omitted.
+#[derive(Debug, Clone, Copy)]
+pub struct Report<'a> {
+    instructions: &'a [Instruction],
+}
+
+impl<'a> Report<'a> {
+    /// Creates report of `instructions`.
+    pub fn new(instructions: &'a [Instruction]) -> Self {
+        Self { instructions }
+    }
+
+    fn section<F>(
+        &self,
+        f: &mut fmt::Formatter<'_>,
+        names: (&str, &str),
+        mut item: F,
+    ) -> fmt::Result
+    where
+        F: FnMut(&Instruction) -> Option<String>,
+    {
+        let items: Vec<String> = self.instructions.iter().filter_map(&mut item).collect();
+        write_section(f, names, items.len(), &items)
+    }
+}
+
+impl Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.section(
+            f,
+            ("rerun-if-changed path", "rerun-if-changed paths"),
+            |i| match i {
+                Instruction::RerunIfChanged(path) => Some(path.display().to_string()),
+                _ => None,
+            },
+        )?;
+        self.section(
+            f,
+            (
+                "rerun-if-env-changed variable",
+                "rerun-if-env-changed variables",
+            ),
+            |i| match i {
+                Instruction::RerunIfEnvChanged(var) => Some(var.clone()),
+                _ => None,
+            },
+        )?;
+
+        let kinds = [
+            (Some(LinkKind::Static), "static"),
+            (Some(LinkKind::Dylib), "dylib"),
+            (Some(LinkKind::Framework), "framework"),
+            (None, "unspecified"),
+        ];
+        let mut count = 0;
+        let mut libs = Vec::new();
+        for (kind, kind_name) in kinds {
+            let names: Vec<String> = self
+                .instructions
+                .iter()
+                .filter_map(|i| match i {
+                    Instruction::RustcLinkLib(lib) if lib.kind == kind => Some(lib.name.clone()),
+                    _ => None,
+                })
+                .collect();
+            if !names.is_empty() {
+                count += names.len();
+                libs.push(format!("{kind_name}: {}", names.join(", ")));
+            }
+        }
+        write_section(f, ("link library", "link libraries"), count, &libs)?;
+
+        self.section(f, ("link search path", "link search paths"), |i| match i {
+            Instruction::RustcLinkSearch(search) => Some(search.to_string()),
+            _ => None,
+        })?;
+        self.section(f, ("link argument", "link arguments"), |i| match i {
+            Instruction::RustcLinkArgBin { bin, flag } => Some(format!("{flag} (bin {bin})")),
+            Instruction::RustcLinkArg(flag) => Some(flag.clone()),
+            Instruction::RustcLinkArgCdylib(flag) => Some(format!("{flag} (cdylib)")),
+            Instruction::RustcLinkArgBins(flag) => Some(format!("{flag} (bins)")),
+            Instruction::RustcLinkArgTests(flag) => Some(format!("{flag} (tests)")),
+            Instruction::RustcLinkArgExamples(flag) => Some(format!("{flag} (examples)")),
+            Instruction::RustcLinkArgBenches(flag) => Some(format!("{flag} (benches)")),
+            Instruction::RustcFlags(flags) => Some(format!("{flags} (rustc-flags)")),
+            _ => None,
+        })?;
+        self.section(f, ("env variable", "env variables"), |i| match i {
+            Instruction::RustcEnv { var, value } => Some(format!("{var}={value}")),
+            _ => None,
+        })?;
+        self.section(f, ("cfg", "cfgs"), |i| match i {
+            Instruction::RustcCfg { .. } => Some(i.value().into_owned()),
+            _ => None,
+        })?;
+        self.section(f, ("check-cfg", "check-cfgs"), |i| match i {
+            Instruction::RustcCheckCfg { .. } => Some(i.value().into_owned()),
+            Instruction::Other { key, value } if key == "rustc-check-cfg" => Some(value.clone()),
+            _ => None,
+        })?;
+        self.section(f, ("metadata entry", "metadata entries"), |i| match i {
+            Instruction::Metadata { key, value } => Some(format!("{key}={value}")),
+            _ => None,
+        })?;
+        self.section(f, ("warning", "warnings"), |i| match i {
+            Instruction::Warning(message) => Some(message.clone()),
+            _ => None,
+        })?;
+        self.section(f, ("error", "errors"), |i| match i {
+            Instruction::Error(message) => Some(message.clone()),
+            _ => None,
+        })?;
+        self.section(
+            f,
+            ("other instruction", "other instructions"),
+            |i| match i {
+                Instruction::Other { key, .. } if key != "rustc-check-cfg" => Some(i.to_string()),
+                _ => None,
+            },
+        )
+    }
+}
+
+fn write_section(
+    f: &mut fmt::Formatter<'_>,
+    (singular, plural): (&str, &str),
+    count: usize,
+    items: &[String],
+) -> fmt::Result {
+    if count == 0 {
+        return Ok(());
+    }
+    let name = if count == 1 { singular } else { plural };
+    writeln!(f, "{count} {name}")?;
+    for item in items {
+        writeln!(f, "    {item}")?;
+    }
+    Ok(())
+}
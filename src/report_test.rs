@@ -0,0 +1,51 @@
+use crate::report::Report;
+use crate::Instruction;
+
+#[test]
+fn report_empty_test() {
+    assert_eq!(Report::new(&[]).to_string(), "");
+}
+
+#[test]
+fn report_sections_test() {
+    let instructions = [
+        Instruction::new("rerun-if-env-changed", "FOO_DIR"),
+        Instruction::new("rustc-link-lib", "framework=CoreFoundation"),
+        Instruction::new("rustc-link-lib", "dylib=ssl"),
+        Instruction::new("rustc-link-arg", "-Wl,--as-needed"),
+        Instruction::new("rustc-link-arg-bin", "server=-Wl,-z,now"),
+        Instruction::new("rustc-cfg", "api=\"v2\""),
+        Instruction::new("rustc-check-cfg", "cfg(api, values(\"v1\", \"v2\"))"),
+        Instruction::new("rustc-check-cfg", "cfg(has_foo, has_bar)"),
+        Instruction::new("metadata", "root=/opt/foo"),
+        Instruction::new("metadata", "include=/opt/foo/include"),
+        Instruction::new("warning", "foo not found, using bundled"),
+        Instruction::new("future-key", "value"),
+    ];
+
+    assert_eq!(
+        Report::new(&instructions).to_string(),
+        "\
+1 rerun-if-env-changed variable
+    FOO_DIR
+2 link libraries
+    dylib: ssl
+    framework: CoreFoundation
+2 link arguments
+    -Wl,--as-needed
+    -Wl,-z,now (bin server)
+1 cfg
+    api=\"v2\"
+2 check-cfgs
+    cfg(api, values(\"v1\", \"v2\"))
+    cfg(has_foo, has_bar)
+2 metadata entries
+    root=/opt/foo
+    include=/opt/foo/include
+1 warning
+    foo not found, using bundled
+1 other instruction
+    cargo::future-key=value
+"
+    );
+}
@@ -0,0 +1,74 @@
+use std::fmt::Display;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Mutex, PoisonError};
+
+use crate::functions::emit_error;
+use crate::session;
+
+/// Return type of the function passed to [`run`]: `()` or `Result<(), E>` where `E: Display`.
+pub trait MainResult {
+    /// Returns error message of `Err`.
+    fn into_error(self) -> Option<String>;
+}
+
+impl MainResult for () {
+    fn into_error(self) -> Option<String> {
+        None
+    }
+}
+
+impl<E: Display> MainResult for Result<(), E> {
+    fn into_error(self) -> Option<String> {
+        self.err().map(|err| err.to_string())
+    }
+}
+
+/// Message of the last panic, recorded by the panic hook installed by [`run`].
+static PANIC_MESSAGE: Mutex<Option<String>> = Mutex::new(None);
+
+/// Runs `main` function of the build script and makes failures visible to Cargo.
+///
+/// - `Err` returned by `main` is emitted as [`error`](crate::error) and the build script exits with
+///   code `1`.
+/// - Panic is emitted as [`error`](crate::error) with panic message and location, then unwinding
+///   continues. Previous panic hook still runs.
+/// - Build script exits with code `1` if any error was emitted, see [`finish`](crate::finish).
+/// - Output stream is flushed in every case.
+///
+/// With `main` feature enabled, `#[cargo_build::main]` attribute does the same for `fn main`.
+///
+/// ```rust,no_run
+/// // build.rs `main`
+/// cargo_build::run(|| -> Result<(), std::env::VarError> {
+///     let sdk = std::env::var("FOO_SDK")?;
+///
+///     cargo_build::rustc_link_search_native(format!("{sdk}/lib"));
+///     Ok(())
+/// });
+/// ```
+pub fn run<R: MainResult>(main: impl FnOnce() -> R) {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        *PANIC_MESSAGE.lock().unwrap_or_else(PoisonError::into_inner) = Some(info.to_string());
+        previous(info);
+    }));
+
+    match panic::catch_unwind(AssertUnwindSafe(main)) {
+        Ok(result) => {
+            if let Some(msg) = result.into_error() {
+                emit_error(&msg);
+                session::exit(1);
+            }
+            crate::finish();
+        }
+        Err(payload) => {
+            let msg = PANIC_MESSAGE
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .take();
+            emit_error(msg.as_deref().unwrap_or("Build script panicked"));
+            crate::flush();
+            panic::resume_unwind(payload);
+        }
+    }
+}
@@ -0,0 +1,55 @@
+use std::io::Write;
+use std::sync::{Arc, RwLock};
+
+use crate as cargo_build;
+
+#[test]
+fn run_panic_test() {
+    let vec_out = TestWriteVecHandle::new();
+    let _out = cargo_build::build_out::scoped(vec_out.clone());
+
+    let result = std::panic::catch_unwind(|| {
+        cargo_build::run(|| -> Result<(), String> {
+            cargo_build::rustc_cfg("has_foo");
+            panic!("libfoo not found");
+        })
+    });
+    assert!(result.is_err());
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out = std::str::from_utf8(&out).unwrap();
+
+    let mut lines = out.lines();
+    assert_eq!(lines.next(), Some("cargo::rustc-cfg=has_foo"));
+    assert!(lines
+        .next()
+        .is_some_and(|line| line.starts_with("cargo::error=panicked at src/runner_test.rs:")));
+    assert_eq!(lines.next(), Some("cargo::error=libfoo not found"));
+}
+
+struct TestWriteVecHandle(Arc<RwLock<Vec<u8>>>);
+
+impl TestWriteVecHandle {
+    fn new() -> Self {
+        Self(Arc::new(RwLock::new(Vec::new())))
+    }
+}
+
+impl Clone for TestWriteVecHandle {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl Write for TestWriteVecHandle {
+    fn write(&mut self, buf: &[u8]) -> std::result::Result<usize, std::io::Error> {
+        self.0
+            .write()
+            .expect("Unable to aquire Write lock")
+            .write(buf)
+    }
+
+    fn flush(&mut self) -> std::result::Result<(), std::io::Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,431 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::io;
+use std::marker::PhantomData;
+use std::panic::Location;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::Instruction;
+
+thread_local! {
+    static SESSION: RefCell<Option<SessionState>> = const { RefCell::new(None) };
+
+    static INSTRUMENT_THRESHOLD: Cell<Option<Duration>> = const { Cell::new(None) };
+}
+
+/// Build script session which enables bookkeeping across `cargo-build` calls on the current thread.
+///
+/// While session is active:
+/// - Repeated calls emitting identical `rustc-link-arg*` instructions are emitted only once, in
+///   first-seen order. Flags of one call are compared as a whole, so `-framework Foo` isn't split.
+/// - Call sites which contributed each sequence of linker flags are recorded for [`Session::report`].
+/// - [`RerunPolicy`] set with [`set_rerun_policy`] is evaluated when session finishes.
+/// - Durations measured with [`instrument`] are recorded for [`Session::report`].
+/// - [`ErrorPolicy`] set with [`set_error_policy`] decides whether [`error`](crate::error) exits.
+///
+/// Session finishes when the guard is dropped or [`Session::finish`] is called. Nothing is emitted
+/// on finish if the thread is panicking.
+///
+/// ```rust
+/// let session = cargo_build::Session::begin();
+///
+/// cargo_build::rustc_link_arg(["-framework", "Security"]);
+/// cargo_build::rustc_link_arg(["-framework", "Security"]); // Not emitted again
+/// cargo_build::rustc_link_arg(["-framework", "CoreFoundation"]);
+///
+/// println!("{}", session.report());
+/// ```
+pub struct Session {
+    /// Session state is thread-local.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Session {
+    /// Starts session on the current thread.
+    ///
+    /// Panics if session is already active on the current thread.
+    pub fn begin() -> Session {
+        SESSION.with_borrow_mut(|session| {
+            assert!(
+                session.is_none(),
+                "Session is already active on the current thread"
+            );
+            *session = Some(SessionState::default());
+        });
+        Session {
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Returns human-readable debug report of the session.
+    ///
+    /// Report lists every sequence of linker flags with call sites which tried to emit it, followed
+    /// by blocks measured with [`instrument`]:
+    /// ```text
+    /// rustc-link-arg=-Wl,--no-undefined
+    ///     build.rs:4:5
+    ///     build.rs:12:5 (duplicate)
+    /// instrument: generate bindings took 1.203s
+    /// ```
+    pub fn report(&self) -> String {
+        SESSION.with_borrow(|session| {
+            let mut report = String::new();
+            if let Some(session) = session {
+                session
+                    .write_report(&mut report)
+                    .expect("Writing to String never fails");
+            }
+            report
+        })
+    }
+
+    /// Writes Make/Ninja-style dependency file listing every path registered with
+    /// [`rerun_if_changed`](crate::rerun_if_changed) during session.
+    ///
+    /// External build systems wrapping Cargo (Meson, CMake, Bazel) can consume the same dependency
+    /// information the build script gave Cargo. Relative `path` is resolved against `OUT_DIR`. Target
+    /// of the rule is `OUT_DIR` and relative dependencies are resolved against `CARGO_MANIFEST_DIR`.
+    /// Returns path of the written file.
+    ///
+    /// ```rust,no_run
+    /// let session = cargo_build::Session::begin();
+    ///
+    /// cargo_build::rerun_if_changed(["proto/api.proto", "build.rs"]);
+    ///
+    /// // OUT_DIR: /path/to/crate/proto/api.proto /path/to/crate/build.rs
+    /// session.write_depfile("build-script.d").unwrap();
+    /// ```
+    pub fn write_depfile(&self, path: impl AsRef<Path>) -> io::Result<PathBuf> {
+        let out_dir = crate::env::out_dir();
+        let manifest_dir = std::env::var_os("CARGO_MANIFEST_DIR")
+            .map(PathBuf::from)
+            .unwrap_or_default();
+
+        let dependencies: Vec<PathBuf> = dependencies()
+            .into_iter()
+            .map(|dep| manifest_dir.join(dep))
+            .collect();
+
+        let path = out_dir.join(path);
+        std::fs::write(&path, render_depfile(&out_dir, &dependencies))?;
+        Ok(path)
+    }
+
+    /// Finishes session, same as dropping it.
+    pub fn finish(self) {}
+}
+
+/// Returns paths of `rerun-if-changed` instructions emitted during active session.
+pub(crate) fn dependencies() -> Vec<PathBuf> {
+    SESSION.with_borrow(|session| {
+        session
+            .as_ref()
+            .map(|session| session.dependencies.clone())
+            .unwrap_or_default()
+    })
+}
+
+/// Renders `target: dependencies` rule, escaping spaces, `#` and `$` as Make and Ninja expect.
+pub(crate) fn render_depfile(target: &Path, dependencies: &[PathBuf]) -> String {
+    let escape = |path: &Path| {
+        let path = path.to_string_lossy();
+        let mut escaped = String::with_capacity(path.len());
+        for c in path.chars() {
+            match c {
+                ' ' | '#' => escaped.push('\\'),
+                '$' => escaped.push('$'),
+                _ => {}
+            }
+            escaped.push(c);
+        }
+        escaped
+    };
+
+    let mut out = format!("{}:", escape(target));
+    for dependency in dependencies {
+        out.push_str(" \\\n    ");
+        out.push_str(&escape(dependency));
+    }
+    out.push('\n');
+    out
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let state = SESSION.take();
+
+        if let Some(state) = state {
+            if !std::thread::panicking() {
+                state.finish();
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct SessionState {
+    link_args: Vec<LinkArg>,
+    /// Index into `link_args` by `KEY=VALUE` lines of the call.
+    link_arg_index: HashMap<String, usize>,
+    rerun_policy: Option<RerunPolicy>,
+    /// `rerun-if-*` instructions emitted during session.
+    rerun_instructions: Vec<String>,
+    timings: Vec<(String, Duration)>,
+    error_policy: Option<ErrorPolicy>,
+    /// Paths of `rerun-if-changed` instructions emitted during session.
+    dependencies: Vec<PathBuf>,
+}
+
+struct LinkArg {
+    /// `KEY=VALUE` lines of linker flags emitted by single call.
+    instruction: String,
+    callers: Vec<&'static Location<'static>>,
+}
+
+impl SessionState {
+    /// Emits instructions deferred until the end of session. Session is no longer active.
+    fn finish(self) {
+        match self.rerun_policy {
+            None | Some(RerunPolicy::Default) => {}
+            Some(RerunPolicy::Never) => {
+                if !self.rerun_instructions.is_empty() {
+                    crate::warning(&format!(
+                        "`RerunPolicy::Never` is set, but build script will also rerun on {}",
+                        self.rerun_instructions.join(", ")
+                    ));
+                }
+                crate::rerun_if_changed("build.rs");
+            }
+            Some(RerunPolicy::OnPaths(paths)) => crate::rerun_if_changed(paths),
+            Some(RerunPolicy::OnEnv(vars)) => crate::rerun_if_env_changed(vars),
+        }
+    }
+
+    fn write_report(&self, out: &mut String) -> fmt::Result {
+        for link_arg in &self.link_args {
+            writeln!(out, "{}", link_arg.instruction)?;
+
+            for (i, caller) in link_arg.callers.iter().enumerate() {
+                let duplicate = if i == 0 { "" } else { " (duplicate)" };
+                writeln!(out, "    {caller}{duplicate}")?;
+            }
+        }
+        for (label, duration) in &self.timings {
+            writeln!(out, "instrument: {label} took {duration:.3?}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Records linker flags emitted by single call of `caller`. Returns `false` if identical sequence
+/// of flags was already emitted during active session and shouldn't be emitted again.
+///
+/// Flags are compared as a whole sequence, since arguments like `-framework Foo` or
+/// `-undefined dynamic_lookup` only make sense together.
+pub(crate) fn record_link_args(
+    instructions: &[Instruction],
+    caller: &'static Location<'static>,
+) -> bool {
+    SESSION.with_borrow_mut(|session| {
+        let Some(session) = session else {
+            return true;
+        };
+        let link_args = instructions
+            .iter()
+            .filter(|instruction| instruction.key().starts_with("rustc-link-arg"));
+
+        let mut instruction = String::new();
+        for link_arg in link_args {
+            if !instruction.is_empty() {
+                instruction.push('\n');
+            }
+            write!(instruction, "{}={}", link_arg.key(), link_arg.value())
+                .expect("Writing to String never fails");
+        }
+        if instruction.is_empty() {
+            return true;
+        }
+
+        match session.link_arg_index.get(&instruction) {
+            Some(&i) => {
+                session.link_args[i].callers.push(caller);
+                false
+            }
+            None => {
+                session
+                    .link_arg_index
+                    .insert(instruction.clone(), session.link_args.len());
+                session.link_args.push(LinkArg {
+                    instruction,
+                    callers: vec![caller],
+                });
+                true
+            }
+        }
+    })
+}
+
+/// Declares when Cargo should rerun the build script. See [`set_rerun_policy`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RerunPolicy {
+    /// Cargo's default: rerun if any file in the package changes. Nothing is emitted.
+    Default,
+    /// Never rerun unless build script itself changes. Emits `rerun-if-changed=build.rs`.
+    Never,
+    /// Rerun if any of the files or directories changes. Emits `rerun-if-changed` for each path.
+    OnPaths(Vec<PathBuf>),
+    /// Rerun if any of the env variables changes. Emits `rerun-if-env-changed` for each variable.
+    OnEnv(Vec<String>),
+}
+
+/// Declares when Cargo should rerun the build script.
+///
+/// During active [`Session`] the policy is evaluated when session finishes, the last call wins. If
+/// [`RerunPolicy::Never`] is combined with [`rerun_if_changed`](crate::rerun_if_changed) or
+/// [`rerun_if_env_changed`](crate::rerun_if_env_changed) calls, [`warning`](crate::warning) is emitted,
+/// since the build script is going to rerun anyway.
+///
+/// Without session the policy is evaluated immediately.
+///
+/// ```rust
+/// use cargo_build::RerunPolicy;
+///
+/// let session = cargo_build::Session::begin();
+///
+/// cargo_build::set_rerun_policy(RerunPolicy::OnPaths(vec!["proto".into()]));
+///
+/// // ...
+///
+/// session.finish(); // cargo::rerun-if-changed=proto
+/// ```
+pub fn set_rerun_policy(policy: RerunPolicy) {
+    let deferred = SESSION.with_borrow_mut(|session| match session {
+        Some(session) => {
+            session.rerun_policy = Some(policy.clone());
+            true
+        }
+        None => false,
+    });
+
+    if !deferred {
+        SessionState {
+            rerun_policy: Some(policy),
+            ..Default::default()
+        }
+        .finish();
+    }
+}
+
+/// Decides what happens after [`error`](crate::error) is emitted. See [`set_error_policy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Remaining steps of the build script run. The build fails once build script has finished. Default.
+    Continue,
+    /// Session is finished, output stream is flushed and build script exits with given exit code.
+    Exit(i32),
+}
+
+/// Sets [`ErrorPolicy`] for the active [`Session`].
+///
+/// Panics if there is no active session on the current thread.
+///
+/// ```rust
+/// use cargo_build::ErrorPolicy;
+///
+/// let session = cargo_build::Session::begin();
+///
+/// cargo_build::set_error_policy(ErrorPolicy::Exit(1));
+///
+/// // Exits right away, remaining steps don't run
+/// // cargo_build::error("libfoo not found");
+/// ```
+///
+/// [`error_and_continue`](crate::error_and_continue) and [`error_and_exit`](crate::error_and_exit)
+/// ignore the policy.
+pub fn set_error_policy(policy: ErrorPolicy) {
+    SESSION.with_borrow_mut(|session| {
+        let session = session
+            .as_mut()
+            .expect("Error policy requires active Session on the current thread");
+        session.error_policy = Some(policy);
+    });
+}
+
+/// Returns exit code if [`ErrorPolicy::Exit`] is set for active session.
+pub(crate) fn error_exit_code() -> Option<i32> {
+    SESSION.with_borrow(|session| match session.as_ref()?.error_policy? {
+        ErrorPolicy::Continue => None,
+        ErrorPolicy::Exit(code) => Some(code),
+    })
+}
+
+/// Finishes active session, flushes output stream and exits the process.
+pub(crate) fn exit(code: i32) -> ! {
+    if let Some(state) = SESSION.take() {
+        state.finish();
+    }
+    crate::build_out::final_flush().expect(crate::build_out::ERR_MSG);
+    std::process::exit(code)
+}
+
+/// Records `rerun-if-*` instruction emitted during active session.
+pub(crate) fn record_rerun(key: &str, value: fmt::Arguments<'_>) {
+    SESSION.with_borrow_mut(|session| {
+        if let Some(session) = session {
+            let value = value.to_string();
+            if key == "rerun-if-changed" {
+                session.dependencies.push(PathBuf::from(&value));
+            }
+            session.rerun_instructions.push(format!("{key}={value}"));
+        }
+    });
+}
+
+/// Runs `f` and measures how long it takes.
+///
+/// Duration is written to `stderr`, which Cargo saves to `target/*/build/*/stderr`, and recorded for
+/// [`Session::report`] during active session. If duration exceeds threshold set with
+/// [`set_instrument_threshold`], [`warning`](crate::warning) is emitted.
+///
+/// ```rust
+/// cargo_build::set_instrument_threshold(Some(std::time::Duration::from_secs(30)));
+///
+/// let bindings = cargo_build::instrument("generate bindings", || {
+///     // ...
+///     "pub const VERSION: u32 = 3;"
+/// });
+/// ```
+///
+/// See also `instrument!` macro.
+pub fn instrument<R>(label: &str, f: impl FnOnce() -> R) -> R {
+    let start = Instant::now();
+    let result = f();
+    record_timing(label, start.elapsed());
+    result
+}
+
+/// Sets duration above which [`instrument`] emits [`warning`](crate::warning) on the current thread.
+///
+/// `None` disables warnings. This is the default.
+pub fn set_instrument_threshold(threshold: Option<Duration>) {
+    INSTRUMENT_THRESHOLD.set(threshold);
+}
+
+fn record_timing(label: &str, duration: Duration) {
+    eprintln!("instrument: {label} took {duration:.3?}");
+
+    if INSTRUMENT_THRESHOLD
+        .get()
+        .is_some_and(|threshold| duration > threshold)
+    {
+        crate::warning(&format!("`{label}` took {duration:.3?}"));
+    }
+
+    SESSION.with_borrow_mut(|session| {
+        if let Some(session) = session {
+            session.timings.push((label.to_string(), duration));
+        }
+    });
+}
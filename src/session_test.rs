@@ -0,0 +1,263 @@
+use std::sync::{Arc, RwLock};
+
+use crate as cargo_build;
+
+#[test]
+fn session_link_arg_dedup_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    let session = cargo_build::Session::begin();
+
+    cargo_build::rustc_link_arg("-Wl,--no-undefined");
+    cargo_build::rustc_link_arg(["-Wl,--as-needed", "-Wl,--no-undefined"]);
+    cargo_build::rustc_link_arg("-Wl,--no-undefined");
+    cargo_build::rustc_link_arg_bin("app", "-Wl,--no-undefined");
+    cargo_build::rustc_link_arg_bin("app", "-Wl,--no-undefined");
+
+    let report = session.report();
+    drop(session);
+
+    // Session has ended
+    cargo_build::rustc_link_arg("-Wl,--no-undefined");
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(
+        out,
+        "\
+cargo::rustc-link-arg=-Wl,--no-undefined
+cargo::rustc-link-arg=-Wl,--as-needed
+cargo::rustc-link-arg=-Wl,--no-undefined
+cargo::rustc-link-arg-bin=app=-Wl,--no-undefined
+cargo::rustc-link-arg=-Wl,--no-undefined
+"
+    );
+
+    let file = file!();
+    assert_eq!(
+        report,
+        format!(
+            "\
+rustc-link-arg=-Wl,--no-undefined
+    {file}:12:5
+    {file}:14:5 (duplicate)
+rustc-link-arg=-Wl,--as-needed
+rustc-link-arg=-Wl,--no-undefined
+    {file}:13:5
+rustc-link-arg-bin=app=-Wl,--no-undefined
+    {file}:15:5
+    {file}:16:5 (duplicate)
+"
+        )
+    );
+}
+
+#[test]
+fn session_link_arg_sequence_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    let session = cargo_build::Session::begin();
+
+    cargo_build::rustc_link_arg(["-framework", "Foo"]);
+    cargo_build::rustc_link_arg(["-framework", "Bar"]);
+    cargo_build::rustc_link_arg(["-framework", "Foo"]);
+    cargo_build::rustc_link_arg(["-undefined", "dynamic_lookup"]);
+    cargo_build::rustc_link_arg(["-undefined", "dynamic_lookup"]);
+
+    drop(session);
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(
+        out,
+        "\
+cargo::rustc-link-arg=-framework
+cargo::rustc-link-arg=Foo
+cargo::rustc-link-arg=-framework
+cargo::rustc-link-arg=Bar
+cargo::rustc-link-arg=-undefined
+cargo::rustc-link-arg=dynamic_lookup
+"
+    );
+}
+
+#[test]
+fn rerun_policy_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    // Evaluated immediately without session
+    cargo_build::set_rerun_policy(cargo_build::RerunPolicy::OnEnv(vec!["PROTOC".to_string()]));
+
+    let session = cargo_build::Session::begin();
+    cargo_build::set_rerun_policy(cargo_build::RerunPolicy::Never);
+    cargo_build::set_rerun_policy(cargo_build::RerunPolicy::OnPaths(vec!["proto".into()]));
+    cargo_build::rustc_cfg("before_finish");
+    session.finish();
+
+    let session = cargo_build::Session::begin();
+    cargo_build::set_rerun_policy(cargo_build::RerunPolicy::Never);
+    cargo_build::rerun_if_changed("src/lib.c");
+    drop(session);
+
+    let session = cargo_build::Session::begin();
+    cargo_build::set_rerun_policy(cargo_build::RerunPolicy::Default);
+    drop(session);
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(
+        out,
+        "\
+cargo::rerun-if-env-changed=PROTOC
+cargo::rustc-cfg=before_finish
+cargo::rerun-if-changed=proto
+cargo::rerun-if-changed=src/lib.c
+cargo::warning=`RerunPolicy::Never` is set, but build script will also rerun on rerun-if-changed=src/lib.c
+cargo::rerun-if-changed=build.rs
+"
+    );
+}
+
+#[test]
+#[should_panic]
+fn session_nested_test() {
+    let _session = cargo_build::Session::begin();
+    let _nested = cargo_build::Session::begin();
+}
+
+#[test]
+fn instrument_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    let session = cargo_build::Session::begin();
+
+    cargo_build::set_instrument_threshold(Some(std::time::Duration::ZERO));
+    let value = cargo_build::instrument("slow step", || {
+        std::thread::sleep(std::time::Duration::from_millis(1));
+        42
+    });
+    cargo_build::set_instrument_threshold(None);
+    cargo_build::instrument("fast step", || {});
+
+    let report = session.report();
+    drop(session);
+
+    assert_eq!(value, 42);
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert!(out.starts_with("cargo::warning=`slow step` took "));
+    assert_eq!(out.lines().count(), 1);
+
+    let lines: Vec<&str> = report.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines[0].starts_with("instrument: slow step took "));
+    assert!(lines[1].starts_with("instrument: fast step took "));
+}
+
+#[test]
+fn error_policy_test() {
+    use crate::session::error_exit_code;
+    use cargo_build::ErrorPolicy;
+
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    let session = cargo_build::Session::begin();
+    assert_eq!(error_exit_code(), None);
+
+    cargo_build::set_error_policy(ErrorPolicy::Continue);
+    cargo_build::error("first");
+    assert_eq!(error_exit_code(), None);
+
+    cargo_build::set_error_policy(ErrorPolicy::Exit(2));
+    assert_eq!(error_exit_code(), Some(2));
+    cargo_build::error_and_continue("second");
+
+    drop(session);
+    assert_eq!(error_exit_code(), None);
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(out, "cargo::error=first\ncargo::error=second\n");
+}
+
+#[test]
+#[should_panic(expected = "Error policy requires active Session")]
+fn error_policy_without_session_test() {
+    cargo_build::set_error_policy(cargo_build::ErrorPolicy::Exit(1));
+}
+
+#[test]
+fn render_depfile_test() {
+    use crate::session::render_depfile;
+    use std::path::{Path, PathBuf};
+
+    assert_eq!(
+        render_depfile(
+            Path::new("/target/out"),
+            &[
+                PathBuf::from("/crate/build.rs"),
+                PathBuf::from("/crate/my protos/$api#1.proto"),
+            ]
+        ),
+        "/target/out: \\\n    /crate/build.rs \\\n    /crate/my\\ protos/$$api\\#1.proto\n"
+    );
+    assert_eq!(render_depfile(Path::new("out"), &[]), "out:\n");
+}
+
+#[test]
+fn depfile_dependencies_test() {
+    let _out = cargo_build::build_out::scoped(std::io::sink());
+
+    let session = cargo_build::Session::begin();
+    cargo_build::rerun_if_changed(["proto/api.proto", "build.rs"]);
+    cargo_build::rerun_if_env_changed("PROTOC");
+
+    let dependencies = crate::session::dependencies();
+    drop(session);
+
+    assert_eq!(
+        dependencies,
+        [
+            std::path::PathBuf::from("proto/api.proto"),
+            std::path::PathBuf::from("build.rs")
+        ]
+    );
+}
+
+struct TestWriteVecHandle(Arc<RwLock<Vec<u8>>>);
+
+impl TestWriteVecHandle {
+    fn new() -> Self {
+        Self(Arc::new(RwLock::new(Vec::new())))
+    }
+}
+
+impl Clone for TestWriteVecHandle {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl std::io::Write for TestWriteVecHandle {
+    fn write(&mut self, buf: &[u8]) -> std::result::Result<usize, std::io::Error> {
+        self.0
+            .write()
+            .expect("Unable to aquire Write lock")
+            .write(buf)
+    }
+
+    fn flush(&mut self) -> std::result::Result<(), std::io::Error> {
+        Ok(())
+    }
+}
@@ -0,0 +1,312 @@
+//! Conversion between `cargo::KEY=VALUE` syntax and legacy `cargo:KEY=VALUE` syntax of Cargo
+//! older than 1.77.
+//!
+//! In legacy syntax unknown keys are metadata, so `cargo::metadata=KEY=VALUE` becomes
+//! `cargo:KEY=VALUE`, and `rustc-link-arg-cdylib` is called `rustc-cdylib-link-arg`.
+//!
+//! ```rust
+//! use cargo_build::syntax::{self, Syntax};
+//!
+//! let output = "\
+//! cargo::rustc-link-lib=static=foo
+//! cargo::metadata=include=/usr/include
+//! Compiling libfoo with cc
+//! ";
+//!
+//! let legacy = syntax::convert(output, Syntax::Legacy);
+//!
+//! assert_eq!(
+//!     legacy,
+//!     "\
+//! cargo:rustc-link-lib=static=foo
+//! cargo:include=/usr/include
+//! Compiling libfoo with cc
+//! "
+//! );
+//! assert_eq!(syntax::convert(&legacy, Syntax::Modern), output);
+//! ```
+//!
+//! Functions and macros of this crate emit instructions in [`Syntax::Modern`] unless syntax is
+//! [`set`] explicitly or chosen by [`detect`] for Cargo running the build script.
+
+use std::fmt;
+use std::io::Write;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::instruction::Value;
+use crate::parse::{self, Line};
+use crate::toolchain::{self, Version};
+use crate::Instruction;
+
+/// Syntax of instructions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Syntax {
+    /// `cargo::KEY=VALUE`, understood by Cargo 1.77 and newer. Required by `error`.
+    #[default]
+    Modern,
+    /// `cargo:KEY=VALUE`, understood by every Cargo version.
+    Legacy,
+}
+
+impl Syntax {
+    /// Renders instruction in this syntax, without trailing newline.
+    ///
+    /// `error` has no legacy equivalent and is rendered as `cargo:error=MESSAGE`, which older Cargo
+    /// treats as metadata.
+    ///
+    /// ```rust
+    /// use cargo_build::syntax::Syntax;
+    /// use cargo_build::Instruction;
+    ///
+    /// let instruction = Instruction::new("rustc-link-arg-cdylib", "-Wl,--as-needed");
+    ///
+    /// assert_eq!(
+    ///     Syntax::Legacy.render(&instruction),
+    ///     "cargo:rustc-cdylib-link-arg=-Wl,--as-needed"
+    /// );
+    /// assert_eq!(Syntax::Modern.render(&instruction), instruction.to_string());
+    /// ```
+    pub fn render(self, instruction: &Instruction) -> String {
+        self.display(instruction).to_string()
+    }
+
+    /// [`render`](Self::render) without allocation. Every instruction written by this crate is
+    /// rendered with it.
+    pub(crate) fn display(self, instruction: &Instruction) -> Rendered<'_> {
+        Rendered {
+            syntax: self,
+            instruction,
+        }
+    }
+}
+
+/// Instruction rendered in [`Syntax`], returned by [`Syntax::display`].
+pub(crate) struct Rendered<'a> {
+    syntax: Syntax,
+    instruction: &'a Instruction,
+}
+
+impl fmt::Display for Rendered<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.syntax, self.instruction) {
+            (Syntax::Modern, instruction) => write!(f, "{instruction}"),
+            (Syntax::Legacy, Instruction::Metadata { key, value }) => {
+                write!(f, "cargo:{key}={value}")
+            }
+            (Syntax::Legacy, Instruction::RustcLinkArgCdylib(flag)) => {
+                write!(f, "cargo:rustc-cdylib-link-arg={flag}")
+            }
+            (Syntax::Legacy, instruction) => {
+                write!(f, "cargo:{}={}", instruction.key(), Value(instruction))
+            }
+        }
+    }
+}
+
+/// First Cargo version which understands `cargo::KEY=VALUE` syntax.
+const MODERN_SINCE: Version = Version::new(1, 77, 0);
+
+const MODERN: u8 = 0;
+const LEGACY: u8 = 1;
+
+/// Set by [`set`] and [`detect`]. Syntax is a property of Cargo running the build script, so it is
+/// shared by all threads.
+static SYNTAX: AtomicU8 = AtomicU8::new(MODERN);
+
+/// Sets syntax of instructions emitted by every function and macro of this crate, on all threads.
+///
+/// [`Syntax::Legacy`] is required for Cargo older than 1.77, which ignores `cargo::` lines.
+///
+/// ```rust
+/// use cargo_build::syntax::{self, Syntax};
+///
+/// let file = std::fs::File::create("target/cargo_build_legacy_log.txt").unwrap();
+/// cargo_build::build_out::set(file);
+///
+/// syntax::set(Syntax::Legacy);
+///
+/// cargo_build::rustc_link_arg_cdylib("-Wl,--as-needed");
+/// cargo_build::metadata("include", "/usr/include");
+///
+/// let out = std::fs::read_to_string("target/cargo_build_legacy_log.txt").unwrap();
+///
+/// assert_eq!(
+///     out,
+///     "cargo:rustc-cdylib-link-arg=-Wl,--as-needed\ncargo:include=/usr/include\n"
+/// );
+/// ```
+///
+/// [`DirectiveSink`](crate::build_out::DirectiveSink)s writing to the output stream use the same syntax:
+///
+/// ```rust
+/// use cargo_build::syntax::{self, Syntax};
+///
+/// let file = std::fs::File::create("target/cargo_build_legacy_sink_log.txt").unwrap();
+/// cargo_build::build_out::set(file);
+///
+/// syntax::set(Syntax::Legacy);
+/// cargo_build::build_out::dedup();
+///
+/// cargo_build::rerun_if_changed(["build.rs", "build.rs"]);
+/// cargo_build::metadata("include", "/usr/include");
+///
+/// let out = std::fs::read_to_string("target/cargo_build_legacy_sink_log.txt").unwrap();
+///
+/// assert_eq!(
+///     out,
+///     "cargo:rerun-if-changed=build.rs\ncargo:include=/usr/include\n"
+/// );
+/// ```
+pub fn set(syntax: Syntax) {
+    let syntax = match syntax {
+        Syntax::Modern => MODERN,
+        Syntax::Legacy => LEGACY,
+    };
+    SYNTAX.store(syntax, Ordering::Relaxed);
+}
+
+/// Returns syntax of instructions emitted by this crate. [`Syntax::Modern`] unless [`set`] or
+/// [`detect`] chose otherwise.
+pub fn get() -> Syntax {
+    match SYNTAX.load(Ordering::Relaxed) {
+        LEGACY => Syntax::Legacy,
+        _ => Syntax::Modern,
+    }
+}
+
+/// Detects syntax understood by Cargo running the build script and [`set`]s it.
+///
+/// For crates published with `rust-version` older than 1.77, which may be built by Cargo that ignores
+/// `cargo::` lines:
+/// - [`Syntax::Modern`] if `rust-version` of the package is 1.77 or newer, so Cargo is at least as
+///   new.
+/// - Otherwise syntax is chosen by [`toolchain::cargo_version`], which runs `$CARGO -V`.
+/// - [`Syntax::Legacy`] if Cargo version is unknown, but `rust-version` is older than 1.77.
+/// - [`Syntax::Modern`] outside of Cargo.
+///
+/// ```rust
+/// use cargo_build::syntax;
+///
+/// syntax::detect();
+///
+/// cargo_build::rustc_cfg("has_foo");
+/// ```
+pub fn detect() -> Syntax {
+    let detected = detect_with(toolchain::rust_version(), toolchain::cargo_version);
+    set(detected);
+    detected
+}
+
+pub(crate) fn detect_with(
+    rust_version: Option<Version>,
+    cargo_version: impl FnOnce() -> Option<Version>,
+) -> Syntax {
+    if rust_version.is_some_and(|version| version >= MODERN_SINCE) {
+        return Syntax::Modern;
+    }
+    match (cargo_version(), rust_version) {
+        (Some(version), _) if version < MODERN_SINCE => Syntax::Legacy,
+        (Some(_), _) | (None, None) => Syntax::Modern,
+        (None, Some(_)) => Syntax::Legacy,
+    }
+}
+
+/// Rewrites every instruction of build script output in `syntax`. Other lines and line endings are
+/// kept unchanged, `[NAME VERSION] ` prefix of `cargo build -vv` is removed from instructions.
+pub fn convert(output: &str, syntax: Syntax) -> String {
+    let mut converted = String::with_capacity(output.len());
+    for text in output.split_inclusive('\n') {
+        match parse::line(text) {
+            Line::Instruction(instruction) => {
+                converted.push_str(&syntax.render(&instruction));
+                let content = text.trim_end_matches(['\r', '\n']);
+                converted.push_str(&text[content.len()..]);
+            }
+            _ => converted.push_str(text),
+        }
+    }
+    converted
+}
+
+/// Wraps `inner` into [`MixedSyntaxGuard`].
+///
+/// ```rust
+/// use std::io::Write;
+///
+/// let mut stdout = cargo_build::syntax::guard(std::io::stdout());
+///
+/// cargo_build::rustc_link_lib("foo");
+/// writeln!(stdout, "cargo:rustc-link-lib=bar").unwrap(); // emits `cargo::warning`
+/// ```
+pub fn guard<W: Write>(inner: W) -> MixedSyntaxGuard<W> {
+    MixedSyntaxGuard {
+        inner,
+        line: Vec::new(),
+        warned: false,
+    }
+}
+
+/// Writer which passes output through to the inner writer and emits a [`warning`](crate::warning) on
+/// the first line with legacy `cargo:KEY=VALUE` syntax, or modern `cargo::KEY=VALUE` syntax if
+/// [`Syntax::Legacy`] is [`set`].
+///
+/// Build scripts which use this crate together with hand-written `println!("cargo:...")` mix
+/// `cargo::` and `cargo:` syntaxes. Cargo older than 1.77 ignores `cargo::` lines, while legacy lines
+/// with misspelled keys silently become metadata, so it is hard to tell which lines take effect. Wrap
+/// `stdout` or output of a child process forwarded by the build script to find such lines.
+pub struct MixedSyntaxGuard<W> {
+    inner: W,
+    line: Vec<u8>,
+    warned: bool,
+}
+
+impl<W> MixedSyntaxGuard<W> {
+    /// Returns the inner writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    fn check(&mut self, written: &[u8]) {
+        for &byte in written {
+            if byte != b'\n' {
+                self.line.push(byte);
+                continue;
+            }
+            let line = String::from_utf8_lossy(&self.line);
+            let line = line.trim_end_matches('\r');
+            if !self.warned {
+                if let Some(msg) = mixed_syntax(line) {
+                    self.warned = true;
+                    crate::warning(&msg);
+                }
+            }
+            self.line.clear();
+        }
+    }
+}
+
+/// Returns warning message if `line` is an instruction in syntax other than the one [`set`].
+fn mixed_syntax(line: &str) -> Option<String> {
+    let modern = line.starts_with("cargo::");
+    match get() {
+        Syntax::Modern if !modern && line.starts_with("cargo:") => Some(format!(
+            "Build script output mixes `cargo::` instructions with legacy `cargo:` line `{line}`"
+        )),
+        Syntax::Legacy if modern => Some(format!(
+            "Build script output mixes legacy `cargo:` instructions with `cargo::` line `{line}`"
+        )),
+        _ => None,
+    }
+}
+
+impl<W: Write> Write for MixedSyntaxGuard<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.check(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
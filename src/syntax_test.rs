@@ -0,0 +1,139 @@
+use std::io::Write;
+use std::sync::{Arc, RwLock};
+
+use crate as cargo_build;
+use crate::syntax::{self, detect_with, Syntax};
+use crate::toolchain::Version;
+use crate::Instruction;
+
+#[test]
+fn render_legacy_test() {
+    let render = |key, value| Syntax::Legacy.render(&Instruction::new(key, value));
+
+    assert_eq!(
+        render("rerun-if-changed", "build.rs"),
+        "cargo:rerun-if-changed=build.rs"
+    );
+    assert_eq!(
+        render("rustc-cfg", "api=\"v2\""),
+        "cargo:rustc-cfg=api=\"v2\""
+    );
+    assert_eq!(
+        render("metadata", "include=/usr/include"),
+        "cargo:include=/usr/include"
+    );
+    assert_eq!(
+        render("rustc-link-arg-cdylib", "-Wl,-z,defs"),
+        "cargo:rustc-cdylib-link-arg=-Wl,-z,defs"
+    );
+}
+
+#[test]
+fn parse_legacy_test() {
+    assert_eq!(
+        "cargo:rustc-cdylib-link-arg=-Wl,-z,defs".parse::<Instruction>(),
+        Ok(Instruction::RustcLinkArgCdylib("-Wl,-z,defs".to_string()))
+    );
+    assert_eq!(
+        "cargo:include=/usr/include".parse::<Instruction>(),
+        Ok(Instruction::new("metadata", "include=/usr/include"))
+    );
+}
+
+#[test]
+fn convert_test() {
+    let modern = "\
+cargo::rerun-if-changed=build.rs\r
+[foo-sys 0.1.0] cargo::metadata=root=/opt/foo
+cargo::rustc-link-arg-cdylib=-Wl,-z,defs
+cargo:not an instruction
+Compiling libfoo with cc";
+
+    let legacy = syntax::convert(modern, Syntax::Legacy);
+
+    assert_eq!(
+        legacy,
+        "\
+cargo:rerun-if-changed=build.rs\r
+cargo:root=/opt/foo
+cargo:rustc-cdylib-link-arg=-Wl,-z,defs
+cargo:not an instruction
+Compiling libfoo with cc"
+    );
+    assert_eq!(
+        syntax::convert(&legacy, Syntax::Modern),
+        "\
+cargo::rerun-if-changed=build.rs\r
+cargo::metadata=root=/opt/foo
+cargo::rustc-link-arg-cdylib=-Wl,-z,defs
+cargo:not an instruction
+Compiling libfoo with cc"
+    );
+}
+
+#[test]
+fn mixed_syntax_guard_test() {
+    let vec_out = TestWriteVecHandle::new();
+    let _out = cargo_build::build_out::scoped(vec_out.clone());
+
+    let mut guard = syntax::guard(Vec::new());
+    write!(
+        guard,
+        "Compiling libfoo\ncargo::rustc-cfg=has_foo\ncargo:rustc-"
+    )
+    .unwrap();
+    writeln!(guard, "link-lib=foo").unwrap();
+    writeln!(guard, "cargo:rustc-link-lib=bar").unwrap();
+
+    assert_eq!(
+        guard.into_inner(),
+        b"Compiling libfoo\ncargo::rustc-cfg=has_foo\ncargo:rustc-link-lib=foo\ncargo:rustc-link-lib=bar\n"
+    );
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    assert_eq!(
+        std::str::from_utf8(&out).unwrap(),
+        "cargo::warning=Build script output mixes `cargo::` instructions with legacy `cargo:` line `cargo:rustc-link-lib=foo`\n"
+    );
+}
+
+#[test]
+fn detect_syntax_test() {
+    let old = Some(Version::new(1, 70, 0));
+    let new = Some(Version::new(1, 80, 0));
+
+    // `rust-version` is enough, Cargo is not asked
+    assert_eq!(detect_with(new, || unreachable!()), Syntax::Modern);
+
+    assert_eq!(detect_with(old, || new), Syntax::Modern);
+    assert_eq!(detect_with(None, || old), Syntax::Legacy);
+    assert_eq!(detect_with(old, || None), Syntax::Legacy);
+    assert_eq!(detect_with(None, || None), Syntax::Modern);
+}
+
+struct TestWriteVecHandle(Arc<RwLock<Vec<u8>>>);
+
+impl TestWriteVecHandle {
+    fn new() -> Self {
+        Self(Arc::new(RwLock::new(Vec::new())))
+    }
+}
+
+impl Clone for TestWriteVecHandle {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl std::io::Write for TestWriteVecHandle {
+    fn write(&mut self, buf: &[u8]) -> std::result::Result<usize, std::io::Error> {
+        self.0
+            .write()
+            .expect("Unable to aquire Write lock")
+            .write(buf)
+    }
+
+    fn flush(&mut self) -> std::result::Result<(), std::io::Error> {
+        Ok(())
+    }
+}
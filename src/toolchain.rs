@@ -0,0 +1,176 @@
+//! Information about compilers and wrappers used for the build.
+
+use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::OnceLock;
+
+use crate::env::non_empty_var;
+use crate::rerun_if_env_changed;
+
+/// Env variables consulted by [`compiler_launcher`], in order of priority.
+const LAUNCHER_VARS: [&str; 4] = ["RUSTC_WRAPPER", "RUSTC_WORKSPACE_WRAPPER", "CC", "CXX"];
+
+/// Compiler cache recognized by [`compiler_launcher`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CompilerCache {
+    Sccache,
+    Ccache,
+}
+
+/// Compiler launcher returned by [`compiler_launcher`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CompilerLauncher {
+    /// Path or name of the launcher executable, e.g. `sccache` or `/usr/bin/ccache`.
+    pub program: PathBuf,
+    /// Env variable the launcher was found in.
+    pub var: &'static str,
+    /// `None` if launcher is not a known compiler cache.
+    pub cache: Option<CompilerCache>,
+}
+
+impl CompilerLauncher {
+    /// Returns `true` if compiler invocations are cached by `sccache` or `ccache`.
+    pub fn is_caching(&self) -> bool {
+        self.cache.is_some()
+    }
+}
+
+/// Detects compiler launcher such as `sccache` or `ccache`.
+///
+/// Launcher is taken from `RUSTC_WRAPPER` or `RUSTC_WORKSPACE_WRAPPER`, or from the first word of
+/// `CC` or `CXX` when it is followed by the compiler, e.g. `CC="ccache gcc"`. Emits
+/// `rerun-if-env-changed` for every consulted variable.
+///
+/// Build scripts can skip their own probe caching or lower parallelism when compilation is cached.
+///
+/// ```rust
+/// let caching = cargo_build::toolchain::compiler_launcher()
+///     .is_some_and(|launcher| launcher.is_caching());
+/// ```
+pub fn compiler_launcher() -> Option<CompilerLauncher> {
+    rerun_if_env_changed(LAUNCHER_VARS);
+
+    compiler_launcher_with(non_empty_var)
+}
+
+pub(crate) fn compiler_launcher_with(
+    var: impl Fn(&str) -> Option<String>,
+) -> Option<CompilerLauncher> {
+    LAUNCHER_VARS.into_iter().find_map(|name| {
+        let value = var(name)?;
+        let program = if name.starts_with("RUSTC_") {
+            value.trim()
+        } else {
+            // `CC=gcc` is a compiler, `CC="ccache gcc"` is a launcher
+            let mut words = value.split_whitespace();
+            let first = words.next()?;
+            words.next()?;
+            first
+        };
+        Some(CompilerLauncher {
+            program: PathBuf::from(program),
+            var: name,
+            cache: compiler_cache(Path::new(program)),
+        })
+    })
+}
+
+fn compiler_cache(program: &Path) -> Option<CompilerCache> {
+    match program.file_stem()?.to_str()? {
+        "sccache" => Some(CompilerCache::Sccache),
+        "ccache" => Some(CompilerCache::Ccache),
+        _ => None,
+    }
+}
+
+/// Version of Cargo or Rust, e.g. `1.77.0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Version {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl Version {
+    pub const fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Parses `MAJOR.MINOR` or `MAJOR.MINOR.PATCH` version. Pre-release suffix such as `-nightly` is
+    /// ignored.
+    ///
+    /// ```rust
+    /// use cargo_build::toolchain::Version;
+    ///
+    /// assert_eq!(Version::parse("1.77"), Some(Version::new(1, 77, 0)));
+    /// assert_eq!(Version::parse("1.80.0-nightly"), Some(Version::new(1, 80, 0)));
+    /// assert_eq!(Version::parse("stable"), None);
+    /// ```
+    pub fn parse(version: &str) -> Option<Self> {
+        let version = version
+            .split_once('-')
+            .map_or(version, |(version, _)| version);
+        let mut parts = version.trim().split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next()?.parse().ok()?;
+        let patch = match parts.next() {
+            Some(patch) => patch.parse().ok()?,
+            None => 0,
+        };
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Self::new(major, minor, patch))
+    }
+}
+
+impl Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// Returns version of Cargo running the build script.
+///
+/// Runs `$CARGO -V` once per process, `CARGO` env variable is set by Cargo for build scripts. Returns
+/// `None` outside of Cargo, if `$CARGO -V` fails or if the output can't be parsed.
+///
+/// ```rust
+/// use cargo_build::toolchain::{self, Version};
+///
+/// if toolchain::cargo_version().is_some_and(|version| version < Version::new(1, 77, 0)) {
+///     // `cargo::` syntax is not supported
+/// }
+/// ```
+pub fn cargo_version() -> Option<Version> {
+    static CARGO_VERSION: OnceLock<Option<Version>> = OnceLock::new();
+
+    *CARGO_VERSION.get_or_init(|| {
+        let cargo = std::env::var_os("CARGO")?;
+        let output = Command::new(cargo).arg("-V").output().ok()?;
+        if !output.status.success() {
+            return None;
+        }
+        parse_cargo_version(std::str::from_utf8(&output.stdout).ok()?)
+    })
+}
+
+/// Parses output of `cargo -V`, e.g. `cargo 1.76.0 (c84b36747 2024-01-18)`.
+pub(crate) fn parse_cargo_version(output: &str) -> Option<Version> {
+    let version = output.trim().strip_prefix("cargo ")?;
+    Version::parse(version.split_whitespace().next()?)
+}
+
+/// Returns minimum supported Rust version of the package, declared with `rust-version` key in
+/// `Cargo.toml`.
+///
+/// Cargo used to build the package is at least this version, unless the check was disabled with
+/// `--ignore-rust-version`.
+pub fn rust_version() -> Option<Version> {
+    Version::parse(&non_empty_var("CARGO_PKG_RUST_VERSION")?)
+}
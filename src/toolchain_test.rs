@@ -0,0 +1,57 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use crate::toolchain::{
+    compiler_launcher_with, parse_cargo_version, CompilerCache, CompilerLauncher, Version,
+};
+
+fn launcher(vars: &[(&str, &str)]) -> Option<CompilerLauncher> {
+    let vars: HashMap<&str, &str> = vars.iter().copied().collect();
+    compiler_launcher_with(|name| vars.get(name).map(|value| value.to_string()))
+}
+
+#[test]
+fn compiler_launcher_test() {
+    assert_eq!(
+        launcher(&[("RUSTC_WRAPPER", "/usr/bin/sccache"), ("CC", "ccache gcc")]),
+        Some(CompilerLauncher {
+            program: PathBuf::from("/usr/bin/sccache"),
+            var: "RUSTC_WRAPPER",
+            cache: Some(CompilerCache::Sccache),
+        })
+    );
+    assert_eq!(
+        launcher(&[("CC", "ccache gcc")]),
+        Some(CompilerLauncher {
+            program: PathBuf::from("ccache"),
+            var: "CC",
+            cache: Some(CompilerCache::Ccache),
+        })
+    );
+
+    let distcc = launcher(&[("CXX", "distcc g++")]).unwrap();
+    assert_eq!(distcc.program, PathBuf::from("distcc"));
+    assert!(!distcc.is_caching());
+
+    // Plain compiler is not a launcher
+    assert_eq!(launcher(&[("CC", "clang")]), None);
+    assert_eq!(launcher(&[]), None);
+}
+
+#[test]
+fn parse_cargo_version_test() {
+    assert_eq!(
+        parse_cargo_version("cargo 1.76.0 (c84b36747 2024-01-18)\n"),
+        Some(Version::new(1, 76, 0))
+    );
+    assert_eq!(
+        parse_cargo_version("cargo 1.84.0-nightly (4a2d8dc63 2024-11-09)"),
+        Some(Version::new(1, 84, 0))
+    );
+    assert_eq!(parse_cargo_version("rustc 1.84.0"), None);
+    assert_eq!(parse_cargo_version(""), None);
+
+    assert!(Version::new(1, 76, 9) < Version::new(1, 77, 0));
+    assert_eq!(Version::parse("1.2.3.4"), None);
+    assert_eq!(Version::new(1, 77, 0).to_string(), "1.77.0");
+}
@@ -0,0 +1,132 @@
+use std::fmt::{self, Display};
+
+/// Vendors recognized in 3-component triples, which are otherwise `ARCH-OS-ENV`, e.g.
+/// `aarch64-linux-android`.
+const VENDORS: [&str; 12] = [
+    "unknown",
+    "pc",
+    "apple",
+    "nvidia",
+    "fortanix",
+    "sun",
+    "wrs",
+    "uwp",
+    "sony",
+    "nintendo",
+    "espressif",
+    "kmc",
+];
+
+/// Target triple such as `x86_64-pc-windows-msvc`, returned by
+/// [`env::target_triple`](crate::env::target_triple).
+///
+/// Components are kept as written in the triple, e.g. OS of `aarch64-apple-darwin` is `darwin`
+/// while `CARGO_CFG_TARGET_OS` is `macos`.
+///
+/// ```rust
+/// use cargo_build::Triple;
+///
+/// let triple = Triple::parse("x86_64-pc-windows-msvc").unwrap();
+///
+/// assert_eq!(triple.arch, "x86_64");
+/// assert_eq!(triple.vendor.as_deref(), Some("pc"));
+/// assert_eq!(triple.os, "windows");
+/// assert_eq!(triple.env.as_deref(), Some("msvc"));
+/// assert!(triple.is_windows_msvc());
+/// assert_eq!(triple.to_string(), "x86_64-pc-windows-msvc");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Triple {
+    pub arch: String,
+    /// `None` for triples without vendor, e.g. `aarch64-linux-android`.
+    pub vendor: Option<String>,
+    pub os: String,
+    /// Environment or ABI, e.g. `gnu`, `musl`, `msvc` or `eabihf`.
+    pub env: Option<String>,
+}
+
+impl Triple {
+    /// Parses `ARCH-VENDOR-OS-ENV`, `ARCH-VENDOR-OS`, `ARCH-OS-ENV` or `ARCH-OS` triple.
+    pub fn parse(triple: &str) -> Option<Self> {
+        let parts: Vec<&str> = triple.split('-').collect();
+        if parts.iter().any(|part| part.is_empty()) {
+            return None;
+        }
+        let (arch, vendor, os, env) = match parts[..] {
+            [arch, vendor, os, env] => (arch, Some(vendor), os, Some(env)),
+            [arch, vendor, os] if VENDORS.contains(&vendor) => (arch, Some(vendor), os, None),
+            [arch, os, env] => (arch, None, os, Some(env)),
+            [arch, os] => (arch, None, os, None),
+            _ => return None,
+        };
+        Some(Self {
+            arch: arch.to_string(),
+            vendor: vendor.map(str::to_string),
+            os: os.to_string(),
+            env: env.map(str::to_string),
+        })
+    }
+
+    pub fn is_windows(&self) -> bool {
+        self.os == "windows"
+    }
+
+    pub fn is_windows_msvc(&self) -> bool {
+        self.is_windows() && self.env.as_deref() == Some("msvc")
+    }
+
+    /// Windows with MinGW toolchain, `gnu` or `gnullvm` env.
+    pub fn is_windows_gnu(&self) -> bool {
+        self.is_windows()
+            && self
+                .env
+                .as_deref()
+                .is_some_and(|env| env.starts_with("gnu"))
+    }
+
+    /// macOS, iOS and other Apple platforms.
+    pub fn is_apple(&self) -> bool {
+        self.vendor.as_deref() == Some("apple")
+    }
+
+    /// Linux, including Android.
+    pub fn is_linux(&self) -> bool {
+        self.os == "linux"
+    }
+
+    pub fn is_android(&self) -> bool {
+        self.env
+            .as_deref()
+            .is_some_and(|env| env.starts_with("android"))
+    }
+
+    pub fn is_musl(&self) -> bool {
+        self.env
+            .as_deref()
+            .is_some_and(|env| env.starts_with("musl"))
+    }
+
+    /// `wasm32` and `wasm64` targets, with or without WASI.
+    pub fn is_wasm(&self) -> bool {
+        self.arch.starts_with("wasm")
+    }
+
+    /// Targets without operating system, e.g. `thumbv7em-none-eabihf`.
+    pub fn is_bare_metal(&self) -> bool {
+        self.os == "none"
+    }
+}
+
+impl Display for Triple {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.arch)?;
+        if let Some(vendor) = &self.vendor {
+            write!(f, "-{vendor}")?;
+        }
+        write!(f, "-{}", self.os)?;
+        if let Some(env) = &self.env {
+            write!(f, "-{env}")?;
+        }
+        Ok(())
+    }
+}
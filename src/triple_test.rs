@@ -0,0 +1,59 @@
+use crate::Triple;
+
+fn parse(triple: &str) -> Triple {
+    Triple::parse(triple).unwrap()
+}
+
+#[test]
+fn triple_parse_test() {
+    let linux = parse("x86_64-unknown-linux-gnu");
+    assert_eq!(linux.arch, "x86_64");
+    assert_eq!(linux.vendor.as_deref(), Some("unknown"));
+    assert_eq!(linux.os, "linux");
+    assert_eq!(linux.env.as_deref(), Some("gnu"));
+
+    let android = parse("aarch64-linux-android");
+    assert_eq!(android.vendor, None);
+    assert_eq!(android.os, "linux");
+    assert_eq!(android.env.as_deref(), Some("android"));
+
+    let macos = parse("aarch64-apple-darwin");
+    assert_eq!(macos.vendor.as_deref(), Some("apple"));
+    assert_eq!(macos.os, "darwin");
+    assert_eq!(macos.env, None);
+
+    let wasi = parse("wasm32-wasip1");
+    assert_eq!(wasi.vendor, None);
+    assert_eq!(wasi.os, "wasip1");
+
+    assert_eq!(Triple::parse("x86_64"), None);
+    assert_eq!(Triple::parse("x86_64--linux-gnu"), None);
+    assert_eq!(Triple::parse("a-b-c-d-e"), None);
+}
+
+#[test]
+fn triple_display_test() {
+    for triple in [
+        "x86_64-pc-windows-msvc",
+        "aarch64-linux-android",
+        "nvptx64-nvidia-cuda",
+        "thumbv7em-none-eabihf",
+        "wasm32-wasip1",
+    ] {
+        assert_eq!(parse(triple).to_string(), triple);
+    }
+}
+
+#[test]
+fn triple_helpers_test() {
+    assert!(parse("x86_64-pc-windows-msvc").is_windows_msvc());
+    assert!(!parse("x86_64-pc-windows-msvc").is_windows_gnu());
+    assert!(parse("x86_64-pc-windows-gnullvm").is_windows_gnu());
+    assert!(parse("aarch64-apple-ios").is_apple());
+    assert!(parse("armv7-linux-androideabi").is_android());
+    assert!(parse("armv7-linux-androideabi").is_linux());
+    assert!(parse("x86_64-unknown-linux-musl").is_musl());
+    assert!(parse("wasm32-unknown-unknown").is_wasm());
+    assert!(parse("thumbv7em-none-eabihf").is_bare_metal());
+    assert!(!parse("x86_64-unknown-linux-gnu").is_bare_metal());
+}
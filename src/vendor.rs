@@ -0,0 +1,307 @@
+//! Preparing vendored sources inside `OUT_DIR`.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::out::relative;
+use crate::{error, rerun_if_changed};
+
+/// Copies vendored sources into `OUT_DIR/vendor/NAME` and applies unified diffs to the copy.
+///
+/// Source tree is never modified. Patches use the format produced by `git diff` and `git format-patch`:
+/// first component of the paths (`a/` and `b/`) is stripped. Creating and deleting files is supported.
+///
+/// `src_dir` and every patch file are tracked with [`rerun_if_changed`].
+///
+/// Emits [`error`] naming the patch, file and hunk and returns `None` if a patch no longer applies.
+/// Returns path to the patched copy otherwise.
+///
+/// ```rust,no_run
+/// // build.rs
+/// let Some(zlib) = cargo_build::vendor::apply_patches(
+///     "vendor/zlib",
+///     ["patches/0001-fix-msvc-warnings.patch"],
+/// ) else {
+///     return;
+/// };
+///
+/// cargo_build::rustc_link_search_native([zlib.join("lib")]);
+/// ```
+pub fn apply_patches<P: AsRef<Path>>(
+    src_dir: impl AsRef<Path>,
+    patches: impl IntoIterator<Item = P>,
+) -> Option<PathBuf> {
+    let out_dir = std::env::var_os("OUT_DIR").expect(
+        "OUT_DIR is not set. `vendor::apply_patches` can only be used inside the build scripts",
+    );
+
+    apply_patches_in(Path::new(&out_dir), src_dir.as_ref(), patches)
+}
+
+pub(crate) fn apply_patches_in<P: AsRef<Path>>(
+    out_dir: &Path,
+    src_dir: &Path,
+    patches: impl IntoIterator<Item = P>,
+) -> Option<PathBuf> {
+    rerun_if_changed([src_dir]);
+
+    let name = src_dir
+        .file_name()
+        .expect("Vendored source directory must have a name");
+    let dst = out_dir.join("vendor").join(name);
+
+    let copied = match fs::remove_dir_all(&dst) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => copy_dir(src_dir, &dst, &mut Vec::new()),
+    };
+    if let Err(err) = copied {
+        error(&format!(
+            "Unable to copy `{}` to `{}`: {err}",
+            src_dir.display(),
+            dst.display()
+        ));
+        return None;
+    }
+
+    for patch in patches {
+        let patch = patch.as_ref();
+        rerun_if_changed([patch]);
+
+        let applied = fs::read_to_string(patch)
+            .map_err(|err| err.to_string())
+            .and_then(|diff| apply_diff(&dst, &diff));
+
+        if let Err(err) = applied {
+            error(&format!(
+                "Patch `{}` no longer applies to `{}`: {err}",
+                patch.display(),
+                src_dir.display()
+            ));
+            return None;
+        }
+    }
+    Some(dst)
+}
+
+/// Copies `src` into `dst`, following symlinks so patches never write into the source tree.
+///
+/// `ancestors` holds canonical paths of directories being copied to detect symlink cycles.
+fn copy_dir(src: &Path, dst: &Path, ancestors: &mut Vec<PathBuf>) -> io::Result<()> {
+    let canonical = fs::canonicalize(src)?;
+    if ancestors.contains(&canonical) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("symlink cycle at `{}`", src.display()),
+        ));
+    }
+    ancestors.push(canonical);
+    fs::create_dir_all(dst)?;
+
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        if entry.file_name() == ".git" {
+            continue;
+        }
+        let path = entry.path();
+        let target = dst.join(entry.file_name());
+
+        let file_type = entry.file_type()?;
+        let is_dir = match file_type.is_symlink() {
+            true => fs::metadata(&path)?.is_dir(),
+            false => file_type.is_dir(),
+        };
+        if is_dir {
+            copy_dir(&path, &target, ancestors)?;
+        } else {
+            fs::copy(&path, &target)?;
+        }
+    }
+    ancestors.pop();
+    Ok(())
+}
+
+/// Changes of one file in unified diff.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct FilePatch {
+    /// `None` for created files.
+    pub(crate) old: Option<String>,
+    /// `None` for deleted files.
+    pub(crate) new: Option<String>,
+    pub(crate) hunks: Vec<Hunk>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct Hunk {
+    /// 1-based line number in the original file.
+    pub(crate) old_start: usize,
+    /// Lines prefixed with ` `, `-` or `+`.
+    pub(crate) lines: Vec<(char, String)>,
+    /// New file doesn't end with newline.
+    pub(crate) no_newline: bool,
+}
+
+/// Applies every file patch of the diff to files inside `dir`.
+pub(crate) fn apply_diff(dir: &Path, diff: &str) -> Result<(), String> {
+    for file in parse_diff(diff)? {
+        let Some(new) = &file.new else {
+            let old = file.old.as_deref().unwrap_or_default();
+            fs::remove_file(dir.join(old)).map_err(|err| format!("`{old}`: {err}"))?;
+            continue;
+        };
+        let path = dir.join(new);
+
+        let original = match &file.old {
+            Some(old) => {
+                fs::read_to_string(dir.join(old)).map_err(|err| format!("`{old}`: {err}"))?
+            }
+            None => String::new(),
+        };
+        let patched =
+            apply_hunks(&original, &file.hunks).map_err(|err| format!("`{new}`: {err}"))?;
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|err| format!("`{new}`: {err}"))?;
+        }
+        fs::write(&path, patched).map_err(|err| format!("`{new}`: {err}"))?;
+    }
+    Ok(())
+}
+
+pub(crate) fn parse_diff(diff: &str) -> Result<Vec<FilePatch>, String> {
+    let mut files: Vec<FilePatch> = Vec::new();
+    let mut lines = diff.lines().peekable();
+
+    while let Some(line) = lines.next() {
+        if let Some(old) = line.strip_prefix("--- ") {
+            let new = lines
+                .next()
+                .and_then(|line| line.strip_prefix("+++ "))
+                .ok_or_else(|| format!("missing `+++` line after `{line}`"))?;
+
+            files.push(FilePatch {
+                old: diff_path(old)?,
+                new: diff_path(new)?,
+                hunks: Vec::new(),
+            });
+        } else if let Some(range) = line.strip_prefix("@@ -") {
+            let file = files
+                .last_mut()
+                .ok_or_else(|| format!("hunk `{line}` without file header"))?;
+
+            let (old_start, mut old_len) =
+                parse_range(range).ok_or_else(|| format!("invalid hunk header `{line}`"))?;
+            let (_, mut new_len) = range
+                .split_once(" +")
+                .and_then(|(_, new)| parse_range(new))
+                .ok_or_else(|| format!("invalid hunk header `{line}`"))?;
+
+            let mut hunk = Hunk {
+                old_start,
+                lines: Vec::new(),
+                no_newline: false,
+            };
+            while old_len > 0 || new_len > 0 {
+                let line = lines
+                    .next()
+                    .ok_or_else(|| format!("hunk #{} is truncated", file.hunks.len() + 1))?;
+                let (kind, text) = match line.chars().next() {
+                    Some(kind @ (' ' | '-' | '+')) => (kind, &line[1..]),
+                    // Some editors strip trailing whitespace of empty context lines
+                    None => (' ', ""),
+                    Some(_) => return Err(format!("invalid line `{line}` in hunk")),
+                };
+                if kind != '+' {
+                    old_len = old_len.saturating_sub(1);
+                }
+                if kind != '-' {
+                    new_len = new_len.saturating_sub(1);
+                }
+                hunk.lines.push((kind, text.to_string()));
+
+                if lines.peek().is_some_and(|line| line.starts_with('\\')) {
+                    lines.next();
+                    if kind != '-' {
+                        hunk.no_newline = true;
+                    }
+                }
+            }
+            file.hunks.push(hunk);
+        }
+    }
+    Ok(files)
+}
+
+/// Strips the first path component like `patch -p1` and timestamp after tab.
+///
+/// Rejects paths which would escape the patched directory.
+fn diff_path(path: &str) -> Result<Option<String>, String> {
+    let path = path.split('\t').next().unwrap_or(path).trim_end();
+    if path == "/dev/null" {
+        return Ok(None);
+    }
+    let path = path.split_once('/').map_or(path, |(_, rest)| rest);
+    relative(Path::new(path)).map_err(|err| err.to_string())?;
+    Ok(Some(path.to_string()))
+}
+
+/// Parses `START[,LEN]`.
+fn parse_range(range: &str) -> Option<(usize, usize)> {
+    let range = range.split(' ').next()?;
+    match range.split_once(',') {
+        Some((start, len)) => Some((start.parse().ok()?, len.parse().ok()?)),
+        None => Some((range.parse().ok()?, 1)),
+    }
+}
+
+/// Applies hunks to the text. Hunks are searched near their line numbers if the file has shifted.
+pub(crate) fn apply_hunks(text: &str, hunks: &[Hunk]) -> Result<String, String> {
+    let mut lines: Vec<&str> = text.split('\n').collect();
+    let mut trailing_newline = text.ends_with('\n') || text.is_empty();
+    if trailing_newline {
+        lines.pop();
+    }
+
+    let mut result: Vec<String> = Vec::with_capacity(lines.len());
+    let mut next = 0;
+
+    for (i, hunk) in hunks.iter().enumerate() {
+        let old: Vec<&str> = hunk
+            .lines
+            .iter()
+            .filter(|(kind, _)| *kind != '+')
+            .map(|(_, line)| line.as_str())
+            .collect();
+
+        let expected = hunk.old_start.saturating_sub(1).max(next);
+        let matches_at =
+            |pos: usize| pos + old.len() <= lines.len() && lines[pos..pos + old.len()] == old[..];
+
+        let pos = (0..=lines.len())
+            .flat_map(|offset| [expected.checked_add(offset), expected.checked_sub(offset)])
+            .flatten()
+            .filter(|pos| *pos >= next)
+            .find(|pos| matches_at(*pos))
+            .ok_or_else(|| format!("hunk #{} at line {} doesn't match", i + 1, hunk.old_start))?;
+
+        result.extend(lines[next..pos].iter().map(|line| line.to_string()));
+        result.extend(
+            hunk.lines
+                .iter()
+                .filter(|(kind, _)| *kind != '-')
+                .map(|(_, line)| line.clone()),
+        );
+        next = pos + old.len();
+
+        if next == lines.len() {
+            trailing_newline = !hunk.no_newline;
+        }
+    }
+    result.extend(lines[next..].iter().map(|line| line.to_string()));
+
+    let mut text = result.join("\n");
+    if trailing_newline && !result.is_empty() {
+        text.push('\n');
+    }
+    Ok(text)
+}
@@ -0,0 +1,248 @@
+use std::sync::{Arc, RwLock};
+
+use crate as cargo_build;
+use crate::vendor::{apply_hunks, apply_patches_in, parse_diff, FilePatch, Hunk};
+
+const DIFF: &str = "\
+diff --git a/src/lib.c b/src/lib.c
+index 3b18e51..a9c2f1e 100644
+--- a/src/lib.c
++++ b/src/lib.c
+@@ -1,3 +1,3 @@
+ int one() { return 1; }
+-int two() { return 3; }
++int two() { return 2; }
+ int three() { return 3; }
+--- /dev/null
++++ b/src/new.h
+@@ -0,0 +1 @@
++int two();
+\\ No newline at end of file
+";
+
+#[test]
+fn parse_diff_test() {
+    assert_eq!(
+        parse_diff(DIFF).unwrap(),
+        vec![
+            FilePatch {
+                old: Some("src/lib.c".to_string()),
+                new: Some("src/lib.c".to_string()),
+                hunks: vec![Hunk {
+                    old_start: 1,
+                    lines: vec![
+                        (' ', "int one() { return 1; }".to_string()),
+                        ('-', "int two() { return 3; }".to_string()),
+                        ('+', "int two() { return 2; }".to_string()),
+                        (' ', "int three() { return 3; }".to_string()),
+                    ],
+                    no_newline: false,
+                }],
+            },
+            FilePatch {
+                old: None,
+                new: Some("src/new.h".to_string()),
+                hunks: vec![Hunk {
+                    old_start: 0,
+                    lines: vec![('+', "int two();".to_string())],
+                    no_newline: true,
+                }],
+            },
+        ]
+    );
+}
+
+#[test]
+fn apply_hunks_test() {
+    let hunks = &parse_diff(DIFF).unwrap()[0].hunks;
+
+    // File has shifted by two lines since the patch was made
+    let original = "// header\n\nint one() { return 1; }\nint two() { return 3; }\nint three() { return 3; }\n";
+
+    assert_eq!(
+        apply_hunks(original, hunks).unwrap(),
+        "// header\n\nint one() { return 1; }\nint two() { return 2; }\nint three() { return 3; }\n"
+    );
+
+    let changed = "int one() { return 1; }\nint two() { return 4; }\nint three() { return 3; }\n";
+
+    assert_eq!(
+        apply_hunks(changed, hunks).unwrap_err(),
+        "hunk #1 at line 1 doesn't match"
+    );
+
+    let hunks = &parse_diff(DIFF).unwrap()[1].hunks;
+
+    assert_eq!(apply_hunks("", hunks).unwrap(), "int two();");
+}
+
+#[test]
+fn apply_patches_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    let root = std::env::temp_dir().join("cargo_build_apply_patches_test");
+    let _ = std::fs::remove_dir_all(&root);
+
+    let src_dir = root.join("vendor").join("mylib");
+    std::fs::create_dir_all(src_dir.join("src")).unwrap();
+    std::fs::write(
+        src_dir.join("src").join("lib.c"),
+        "int one() { return 1; }\nint two() { return 3; }\nint three() { return 3; }\n",
+    )
+    .unwrap();
+
+    let patch = root.join("0001-fix.patch");
+    std::fs::write(&patch, DIFF).unwrap();
+
+    let out_dir = root.join("out");
+    let patched = apply_patches_in(&out_dir, &src_dir, [&patch]).unwrap();
+
+    assert_eq!(patched, out_dir.join("vendor").join("mylib"));
+    assert_eq!(
+        std::fs::read_to_string(patched.join("src").join("lib.c")).unwrap(),
+        "int one() { return 1; }\nint two() { return 2; }\nint three() { return 3; }\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(patched.join("src").join("new.h")).unwrap(),
+        "int two();"
+    );
+
+    // Source tree is untouched, so patches apply again on the next run
+    assert!(!src_dir.join("src").join("new.h").exists());
+    assert!(apply_patches_in(&out_dir, &src_dir, [&patch]).is_some());
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert_eq!(
+        out,
+        format!(
+            "cargo::rerun-if-changed={src}\ncargo::rerun-if-changed={patch}\n\
+             cargo::rerun-if-changed={src}\ncargo::rerun-if-changed={patch}\n",
+            src = src_dir.display(),
+            patch = patch.display(),
+        )
+    );
+}
+
+#[test]
+fn apply_patches_error_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    let root = std::env::temp_dir().join("cargo_build_apply_patches_error_test");
+    let _ = std::fs::remove_dir_all(&root);
+
+    let src_dir = root.join("mylib");
+    std::fs::create_dir_all(src_dir.join("src")).unwrap();
+    std::fs::write(
+        src_dir.join("src").join("lib.c"),
+        "int one() { return 1; }\n",
+    )
+    .unwrap();
+
+    let patch = root.join("0001-fix.patch");
+    std::fs::write(&patch, DIFF).unwrap();
+
+    assert_eq!(
+        apply_patches_in(&root.join("out"), &src_dir, [&patch]),
+        None
+    );
+
+    let out = vec_out.0.read().expect("Unable to aquire Read lock");
+    let out: &str = std::str::from_utf8(&out).unwrap();
+
+    assert!(out.ends_with(&format!(
+        "cargo::error=Patch `{}` no longer applies to `{}`: `src/lib.c`: hunk #1 at line 1 doesn't match\n",
+        patch.display(),
+        src_dir.display()
+    )));
+}
+
+struct TestWriteVecHandle(Arc<RwLock<Vec<u8>>>);
+
+impl TestWriteVecHandle {
+    fn new() -> Self {
+        Self(Arc::new(RwLock::new(Vec::new())))
+    }
+}
+
+impl Clone for TestWriteVecHandle {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl std::io::Write for TestWriteVecHandle {
+    fn write(&mut self, buf: &[u8]) -> std::result::Result<usize, std::io::Error> {
+        self.0
+            .write()
+            .expect("Unable to aquire Write lock")
+            .write(buf)
+    }
+
+    fn flush(&mut self) -> std::result::Result<(), std::io::Error> {
+        Ok(())
+    }
+}
+
+#[test]
+fn parse_diff_escaping_path_test() {
+    let absolute = "--- a//etc/passwd\n+++ b//etc/passwd\n";
+    assert_eq!(
+        parse_diff(absolute),
+        Err("`/etc/passwd` must be a relative path without `..` components".to_string())
+    );
+
+    let parent = "--- /dev/null\n+++ b/../../escape.h\n@@ -0,0 +1 @@\n+int x;\n";
+    assert_eq!(
+        parse_diff(parent),
+        Err("`../../escape.h` must be a relative path without `..` components".to_string())
+    );
+}
+
+#[cfg(unix)]
+#[test]
+fn apply_patches_symlink_test() {
+    let vec_out = TestWriteVecHandle::new();
+    cargo_build::build_out::set(vec_out.clone());
+
+    let root = std::env::temp_dir().join("cargo_build_apply_patches_symlink_test");
+    let _ = std::fs::remove_dir_all(&root);
+
+    let shared = root.join("shared");
+    std::fs::create_dir_all(&shared).unwrap();
+    std::fs::write(
+        shared.join("lib.c"),
+        "int one() { return 1; }\nint two() { return 3; }\nint three() { return 3; }\n",
+    )
+    .unwrap();
+
+    let src_dir = root.join("mylib");
+    std::fs::create_dir_all(&src_dir).unwrap();
+    std::os::unix::fs::symlink(&shared, src_dir.join("src")).unwrap();
+
+    let patch = root.join("0001-fix.patch");
+    std::fs::write(&patch, DIFF).unwrap();
+
+    let patched = apply_patches_in(&root.join("out"), &src_dir, [&patch]).unwrap();
+
+    assert_eq!(
+        std::fs::read_to_string(patched.join("src").join("lib.c")).unwrap(),
+        "int one() { return 1; }\nint two() { return 2; }\nint three() { return 3; }\n"
+    );
+    // Linked directory is copied, not patched in place
+    assert!(!std::fs::symlink_metadata(patched.join("src"))
+        .unwrap()
+        .file_type()
+        .is_symlink());
+    assert!(!shared.join("new.h").exists());
+
+    // Cycles are reported instead of recursing forever
+    std::os::unix::fs::symlink(&src_dir, shared.join("loop")).unwrap();
+    assert_eq!(
+        apply_patches_in(&root.join("out"), &src_dir, [&patch]),
+        None
+    );
+}